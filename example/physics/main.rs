@@ -1,12 +1,10 @@
 use avian3d::prelude::*;
 use bevy::math::*;
 use bevy::prelude::*;
+use bevy_trenchbroom::class::builtin::MoverClassesPlugin;
 use bevy_trenchbroom::config::WriteTrenchBroomConfigOnStartPlugin;
 use bevy_trenchbroom::prelude::*;
 
-#[solid_class]
-pub struct FuncDoor;
-
 fn main() {
 	App::new()
 		.add_plugins(
@@ -29,6 +27,8 @@ fn main() {
 			// I use bsp_loading to write the config.
 			.disable::<WriteTrenchBroomConfigOnStartPlugin>(),
 		)
+		// Gives `func_door` a kinematic collider.
+		.add_plugins(MoverClassesPlugin)
 		.add_plugins((PhysicsPlugins::default(), PhysicsDebugPlugin))
 		.add_systems(PostStartup, setup_scene)
 		.add_systems(FixedUpdate, spawn_cubes)
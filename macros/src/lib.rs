@@ -21,6 +21,8 @@ use syn::*;
 /// - `classname(<string>)` When outputted to fgd, use the specified string instead of a classname with case converted via the previous attribute.
 /// - `group(<string>)` Prefixes `<string>_` to your classname to avoid namespace stuttering.
 /// - `base(<type ...>)` Adds base classes to inherit.
+/// - `inputs(<Name>, <Name>(<FgdType>) ...)` Declares named inputs, generating a `<Type>Input` enum received through `ClassInput`. See `bevy_trenchbroom::class::io`.
+/// - `outputs(<Name> ...)` Declares named outputs, whose connections are read from properties of the same name.
 /// - `hooks(<SceneHooks expression>)` Functions to run inside the spawn function of this class. Use for things like spawning models.
 ///
 /// # Field attributes (`#[class(...)]`)
@@ -41,6 +43,8 @@ pub fn point_class(attr: proc_macro::TokenStream, input: proc_macro::TokenStream
 /// - `classname(<string>)` When outputted to fgd, use the specified string instead of a classname with case converted via the previous attribute.
 /// - `group(<string>)` Prefixes `<string>_` to your classname to avoid namespace stuttering.
 /// - `base(<type ...>)` Adds base classes to inherit.
+/// - `inputs(<Name>, <Name>(<FgdType>) ...)` Declares named inputs, generating a `<Type>Input` enum received through `ClassInput`. See `bevy_trenchbroom::class::io`.
+/// - `outputs(<Name> ...)` Declares named outputs, whose connections are read from properties of the same name.
 /// - `hooks(<SceneHooks expression>)` Functions to run inside the spawn function of this class. Use for things like adding colliders.
 ///
 /// # Field attributes (`#[class(...)]`)
@@ -66,6 +70,8 @@ pub fn solid_class(attr: proc_macro::TokenStream, input: proc_macro::TokenStream
 /// - `classname(<string>)` When outputted to fgd, use the specified string instead of a classname with case converted via the previous attribute.
 /// - `group(<string>)` Prefixes `<string>_` to your classname to avoid namespace stuttering.
 /// - `base(<type ...>)` Adds base classes to inherit.
+/// - `inputs(<Name>, <Name>(<FgdType>) ...)` Declares named inputs, generating a `<Type>Input` enum received through `ClassInput`. See `bevy_trenchbroom::class::io`.
+/// - `outputs(<Name> ...)` Declares named outputs, whose connections are read from properties of the same name.
 /// - `hooks(<SceneHooks expression>)` Functions to run inside the spawn function of this class. Use for things like spawning models.
 ///
 /// # Field attributes (`#[class(...)]`)
@@ -80,6 +80,55 @@ impl ParseMetaItem for BaseType {
 	}
 }
 
+/// A named input, with an optional parameter type, e.g. `SetSpeed(f32)`.
+struct InputDecl {
+	pub attrs: Vec<Attribute>,
+	pub ident: Ident,
+	pub parameter: Option<Type>,
+}
+impl ParseMetaItem for InputDecl {
+	fn parse_meta_item(input: parse::ParseStream, _mode: ParseMode) -> deluxe::Result<Self> {
+		let attrs = Attribute::parse_outer(input)?;
+		let ident = input.parse()?;
+		let parameter = if input.peek(token::Paren) {
+			let content;
+			parenthesized!(content in input);
+			Some(content.parse()?)
+		} else {
+			None
+		};
+
+		Ok(Self { attrs, ident, parameter })
+	}
+}
+
+/// A named output, e.g. `OnOpen`.
+struct OutputDecl {
+	pub attrs: Vec<Attribute>,
+	pub ident: Ident,
+}
+impl ParseMetaItem for OutputDecl {
+	fn parse_meta_item(input: parse::ParseStream, _mode: ParseMode) -> deluxe::Result<Self> {
+		Ok(Self {
+			attrs: Attribute::parse_outer(input)?,
+			ident: input.parse()?,
+		})
+	}
+}
+
+/// Collects the documentation of a list of attributes.
+fn attrs_doc(attrs: &[Attribute]) -> Option<String> {
+	let mut doc = None;
+	for attr in attrs {
+		if let Meta::NameValue(meta) = &attr.meta
+			&& compare_path(&meta.path, "doc")
+		{
+			extract_doc(meta, &mut doc);
+		}
+	}
+	doc
+}
+
 #[derive(Default, ParseMetaItem)]
 #[deluxe(default)]
 struct Opts {
@@ -91,6 +140,8 @@ struct Opts {
 	classname: Option<Tokens>,
 	group: Option<String>,
 	base: Vec<BaseType>,
+	inputs: Vec<InputDecl>,
+	outputs: Vec<OutputDecl>,
 	doc: Option<String>,
 	decal: bool,
 }
@@ -284,6 +335,77 @@ pub(super) fn class_attribute(attr: TokenStream, input: TokenStream, ty: QuakeCl
 	let size = option(opts.size.map(|size| size.to_string()));
 	let decal = opts.decal;
 
+	// Inputs are dispatched as variants of a generated enum.
+	let input_enum_ident = format_ident!("{ident}Input");
+	let mut input_enum = None;
+	let mut inputs = Vec::with_capacity(opts.inputs.len());
+	if !opts.inputs.is_empty() {
+		let vis = &item.vis;
+		let mut variants = Vec::with_capacity(opts.inputs.len());
+
+		for InputDecl {
+			attrs,
+			ident: input_ident,
+			parameter,
+		} in &opts.inputs
+		{
+			let input_name = input_ident.to_string();
+			let description = option(attrs_doc(attrs));
+			let docs = attrs.iter().filter(|attr| compare_path(attr.meta.path(), "doc"));
+
+			let (variant, parameter_type, construct) = match parameter {
+				Some(parameter) => (
+					quote! { #(#docs)* #input_ident(#parameter), },
+					quote! { Some(<#parameter as ::bevy_trenchbroom::fgd::FgdType>::PROPERTY_TYPE) },
+					quote! { #input_enum_ident::#input_ident(<#parameter as ::bevy_trenchbroom::fgd::FgdType>::fgd_parse(parameter)?) },
+				),
+				None => (
+					quote! { #(#docs)* #input_ident, },
+					quote! { None },
+					quote! { #input_enum_ident::#input_ident },
+				),
+			};
+			variants.push(variant);
+
+			inputs.push(quote! {
+				::bevy_trenchbroom::class::QuakeClassInput {
+					name: #input_name,
+					parameter: #parameter_type,
+					description: #description,
+					dispatch: |commands, context, #[allow(unused)] parameter| {
+						commands.trigger(::bevy_trenchbroom::class::io::ClassInput::new(context, #construct));
+						Ok(())
+					},
+				},
+			});
+		}
+
+		let enum_doc = format!("Named inputs of [`{ident}`], received through [`ClassInput`](::bevy_trenchbroom::class::io::ClassInput).");
+		input_enum = Some(quote! {
+			#[doc = #enum_doc]
+			#[derive(Debug, Clone)]
+			#vis enum #input_enum_ident {
+				#(#variants)*
+			}
+		});
+	}
+
+	let outputs = opts.outputs.iter().map(|OutputDecl { attrs, ident: output_ident }| {
+		let output_name = output_ident.to_string();
+		let description = option(attrs_doc(attrs));
+		quote! {
+			::bevy_trenchbroom::class::QuakeClassOutput {
+				name: #output_name,
+				description: #description,
+			},
+		}
+	});
+	let insert_outputs = (!opts.outputs.is_empty()).then(|| {
+		quote! {
+			::bevy_trenchbroom::class::io::insert_outputs(view, <Self as ::bevy_trenchbroom::class::QuakeClass>::CLASS_INFO.outputs)?;
+		}
+	});
+
 	let scene_hooks = match opts.hooks {
 		None => match ty {
 			QuakeClassType::Base => quote! { (view.tb_config.default_base_scene_hooks)() },
@@ -296,6 +418,8 @@ pub(super) fn class_attribute(attr: TokenStream, input: TokenStream, ty: QuakeCl
 	quote! {
 		#item
 
+		#input_enum
+
 		#[automatically_derived]
 		impl ::bevy_trenchbroom::class::QuakeClass for #ident {
 			const CLASS_INFO: ::bevy_trenchbroom::class::QuakeClassInfo = ::bevy_trenchbroom::class::QuakeClassInfo {
@@ -311,12 +435,15 @@ pub(super) fn class_attribute(attr: TokenStream, input: TokenStream, ty: QuakeCl
 				decal: #decal,
 
 				properties: &[#(#properties)*],
+				inputs: &[#(#inputs)*],
+				outputs: &[#(#outputs)*],
 			};
 
 			fn class_spawn(view: &mut ::bevy_trenchbroom::class::QuakeClassSpawnView) -> ::bevy_trenchbroom::anyhow::Result<()> {
 				use ::bevy_trenchbroom::qmap::QuakeEntityErrorResultExt;
 				#spawn_constructor_default_value
 				view.world.entity_mut(view.entity).insert(#spawn_constructor);
+				#insert_outputs
 				let hooks: ::bevy_trenchbroom::class::scene_hooks::SceneHooks = #scene_hooks;
 				hooks.apply(view)?;
 				Ok(())
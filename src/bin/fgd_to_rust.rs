@@ -0,0 +1,50 @@
+//! Generates Rust quake class definitions from an FGD file.
+//!
+//! Usage: `fgd_to_rust <input.fgd> [output.rs]`
+//!
+//! `@include`d files are read relative to the input file. If no output path is specified, the generated code is written to stdout.
+use std::{fs, path::Path, process::ExitCode};
+
+use bevy_trenchbroom::fgd::{FgdClassDefinition, fgd_to_rust, parse_fgd};
+
+fn main() -> ExitCode {
+	let mut args = std::env::args().skip(1);
+	let (Some(input), output) = (args.next(), args.next()) else {
+		eprintln!("usage: fgd_to_rust <input.fgd> [output.rs]");
+		return ExitCode::FAILURE;
+	};
+
+	let mut classes = Vec::new();
+	if let Err(err) = read_classes(Path::new(&input), &mut classes) {
+		eprintln!("{err}");
+		return ExitCode::FAILURE;
+	}
+
+	let source = fgd_to_rust(&classes);
+
+	match output {
+		Some(output) => {
+			if let Err(err) = fs::write(&output, source) {
+				eprintln!("writing {output}: {err}");
+				return ExitCode::FAILURE;
+			}
+		}
+		None => print!("{source}"),
+	}
+
+	ExitCode::SUCCESS
+}
+
+/// Reads classes from the FGD file at `path`, with the classes of included files coming first.
+fn read_classes(path: &Path, classes: &mut Vec<FgdClassDefinition>) -> Result<(), String> {
+	let source = fs::read_to_string(path).map_err(|err| format!("reading {}: {err}", path.display()))?;
+	let file = parse_fgd(&source).map_err(|err| format!("parsing {}: {err}", path.display()))?;
+
+	let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+	for include in &file.includes {
+		read_classes(&dir.join(include), classes)?;
+	}
+
+	classes.extend(file.classes);
+	Ok(())
+}
@@ -0,0 +1,369 @@
+//! Quake 2 area and area portal support.
+//!
+//! Quake 2 BSPs split the world into areas, connected by area portals (`func_areaportal` brushes, usually placed inside doors).
+//! When a portal is closed, everything on the other side of it can be culled, regardless of what the PVS says.
+
+#[cfg(feature = "client")]
+use bevy::{camera::visibility::VisibilitySystems, transform::TransformSystems};
+use bsp::split_mesh_by_face;
+use class::{
+	builtin::{FuncAreaportal, FuncDoor},
+	io::Use,
+};
+use qbsp::{
+	BspFormat,
+	data::{BspNodeRef, LumpDirectory},
+	read_lump,
+	reader::BspByteReader,
+};
+use smart_default::SmartDefault;
+
+use crate::{util::BevyTrenchbroomCoordinateConversions, *};
+
+pub struct BspAreasPlugin;
+impl Plugin for BspAreasPlugin {
+	fn build(&self, app: &mut App) {
+		#[rustfmt::skip]
+		app
+			.init_asset::<BspAreas>()
+			.add_observer(Self::set_area_portal_state)
+			.add_observer(Self::use_area_portal)
+		;
+
+		#[cfg(feature = "client")]
+		app.add_systems(
+			PostUpdate,
+			Self::cull_areas
+				.after(TransformSystems::Propagate)
+				.before(VisibilitySystems::VisibilityPropagate),
+		);
+	}
+}
+impl BspAreasPlugin {
+	/// Opens or closes every [`FuncAreaportal`] targeted by a [`SetAreaPortalState`] event.
+	pub fn set_area_portal_state(trigger: On<SetAreaPortalState>, mut query: Query<(&mut FuncAreaportal, &Targetable)>) {
+		for (mut portal, targetable) in &mut query {
+			if targetable.targetname.0 == trigger.target {
+				portal.open = trigger.open;
+			}
+		}
+	}
+
+	/// Toggles a [`FuncAreaportal`] when it's [used](Use), like Quake 2.
+	///
+	/// Doors set the state of the portals they target directly with [`SetAreaPortalState`] instead, so uses from doors are ignored.
+	pub fn use_area_portal(trigger: On<Use>, mut query: Query<&mut FuncAreaportal>, door_query: Query<(), With<FuncDoor>>) {
+		if door_query.contains(trigger.source) {
+			return;
+		}
+		let Ok(mut portal) = query.get_mut(trigger.entity) else { return };
+		portal.open = !portal.open;
+	}
+
+	/// Hides [`BspArea`] entities that aren't connected to any camera's area through open area portals.
+	#[cfg(feature = "client")]
+	#[allow(clippy::type_complexity)]
+	pub fn cull_areas(
+		camera_query: Query<&GlobalTransform, With<Camera3d>>,
+		graph_query: Query<(Entity, &BspAreaGraph, &GlobalTransform)>,
+		portal_query: Query<(Entity, &FuncAreaportal)>,
+		mut area_query: Query<(&BspArea, &ChildOf, &mut Visibility)>,
+		parent_query: Query<&ChildOf>,
+		areas_assets: Res<Assets<BspAreas>>,
+	) {
+		let mut visible_areas_per_graph: HashMap<Entity, Option<Vec<bool>>> = HashMap::new();
+
+		for (graph_entity, BspAreaGraph(handle), graph_transform) in &graph_query {
+			let Some(areas) = areas_assets.get(handle) else { continue };
+			let scene_root = parent_query.root_ancestor(graph_entity);
+
+			let open_portals: Vec<u32> = portal_query
+				.iter()
+				.filter(|(portal_entity, portal)| portal.open && parent_query.root_ancestor(*portal_entity) == scene_root)
+				.map(|(_, portal)| portal.style)
+				.collect();
+
+			let to_local = graph_transform.affine().inverse();
+			let mut visible: Option<Vec<bool>> = None;
+
+			for camera_transform in &camera_query {
+				let Some(area) = areas.area_at_point(to_local.transform_point3(camera_transform.translation())) else {
+					// A camera outside the world sees everything, like in Quake 2.
+					visible = None;
+					break;
+				};
+
+				let connected = areas.connected_areas(area, |portal| open_portals.contains(&portal));
+
+				match &mut visible {
+					Some(visible) => visible.iter_mut().zip(connected).for_each(|(visible, connected)| *visible |= connected),
+					None => visible = Some(connected),
+				}
+			}
+
+			visible_areas_per_graph.insert(graph_entity, visible);
+		}
+
+		for (BspArea(area), child_of, mut visibility) in &mut area_query {
+			let Some(visible_areas) = visible_areas_per_graph.get(&child_of.parent()) else { continue };
+			let visible = match visible_areas {
+				Some(visible_areas) => visible_areas.get(*area as usize).copied().unwrap_or(true),
+				None => true,
+			};
+
+			visibility.set_if_neq(if visible { Visibility::Inherited } else { Visibility::Hidden });
+		}
+	}
+}
+
+/// Triggers [`FuncAreaportal`]s with the targetname `target` to open or close.
+///
+/// `func_door`s from [`MoverClassesPlugin`](crate::class::builtin::MoverClassesPlugin) trigger this with their `target` when they start opening, and again after they've finished closing.
+#[derive(Event, Debug, Clone)]
+pub struct SetAreaPortalState {
+	pub target: String,
+	pub open: bool,
+}
+
+/// Area connectivity of a Quake 2 BSP, along with the world's BSP tree to find which area a point is in.
+#[derive(Asset, Reflect, Debug, Clone, SmartDefault)]
+pub struct BspAreas {
+	/// Portals leading out of each area, indexed by area. Area 0 is reserved for solid space and is never connected.
+	pub areas: Vec<Vec<BspAreaPortalLink>>,
+	/// The area of each leaf, 0 if the leaf isn't in an area.
+	pub leaf_areas: Vec<u32>,
+	/// The world model's BSP tree, in Bevy space.
+	pub nodes: Vec<BspAreaNode>,
+	#[default(BspNodeRef::Leaf(0))]
+	pub root: BspNodeRef,
+}
+
+/// A connection from one area to another, only passable if the portal is open.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BspAreaPortalLink {
+	/// The number of the portal, matching [`FuncAreaportal::style`].
+	pub portal: u32,
+	pub other_area: u32,
+}
+
+/// Splitting plane of a BSP node.
+#[derive(Reflect, Debug, Clone, Copy)]
+pub struct BspAreaNode {
+	pub normal: Vec3,
+	pub distance: f32,
+	pub front: BspNodeRef,
+	pub back: BspNodeRef,
+}
+
+impl BspAreas {
+	/// Reads the area and area portal lumps from `bsp`. Returns [`None`] if the format doesn't support them, or they're empty.
+	pub fn parse(bsp: &[u8], data: &BspData, config: &TrenchBroomConfig) -> anyhow::Result<Option<Self>> {
+		if !data.parse_ctx.format.is_quake2() {
+			return Ok(None);
+		}
+
+		let mut reader = BspByteReader::new(bsp, &data.parse_ctx);
+		let _: BspFormat = reader.read()?;
+		let lump_dir: LumpDirectory = reader.read()?;
+
+		let (Some(areas_entry), Some(area_portals_entry)) = (*lump_dir.areas, *lump_dir.area_portals) else {
+			return Ok(None);
+		};
+
+		// [num_portals, first_portal]
+		let raw_areas: Vec<[u32; 2]> = read_lump(bsp, areas_entry, "areas", &data.parse_ctx)?;
+		// [portal_num, other_area]
+		let raw_portals: Vec<[u32; 2]> = read_lump(bsp, area_portals_entry, "area portals", &data.parse_ctx)?;
+
+		if raw_areas.is_empty() {
+			return Ok(None);
+		}
+
+		let areas = read_area_links(&raw_areas, &raw_portals)?;
+
+		Ok(Some(Self {
+			areas,
+			leaf_areas: data.leaves.iter().map(|leaf| leaf.area.0.unwrap_or(0)).collect(),
+			nodes: data
+				.nodes
+				.iter()
+				.map(|node| {
+					let plane = &data.planes[node.plane_idx as usize];
+					BspAreaNode {
+						normal: plane.normal.trenchbroom_to_bevy(),
+						distance: plane.dist / config.scale,
+						front: *node.front,
+						back: *node.back,
+					}
+				})
+				.collect(),
+			root: data.models.first().map(|model| model.hulls.root).unwrap_or(BspNodeRef::Leaf(0)),
+		}))
+	}
+
+	/// Returns the area `point` (in the world's local Bevy space) is in, or [`None`] if it's in solid space or outside the world.
+	pub fn area_at_point(&self, point: Vec3) -> Option<u32> {
+		let mut node_ref = self.root;
+		loop {
+			match node_ref {
+				BspNodeRef::Leaf(leaf_idx) => {
+					return self.leaf_areas.get(leaf_idx as usize).copied().filter(|area| *area != 0);
+				}
+				BspNodeRef::Node(node_idx) => {
+					let node = self.nodes.get(node_idx as usize)?;
+					node_ref = if node.normal.dot(point) - node.distance >= 0. {
+						node.front
+					} else {
+						node.back
+					};
+				}
+			}
+		}
+	}
+
+	/// Flood-fills from `start` through every portal `portal_open` returns `true` for.
+	/// Returns whether each area is connected, indexed by area.
+	pub fn connected_areas(&self, start: u32, portal_open: impl Fn(u32) -> bool) -> Vec<bool> {
+		let mut connected = vec![false; self.areas.len()];
+		if start as usize >= connected.len() {
+			return connected;
+		}
+
+		let mut stack = vec![start];
+		connected[start as usize] = true;
+
+		while let Some(area) = stack.pop() {
+			for link in &self.areas[area as usize] {
+				let Some(other_connected) = connected.get_mut(link.other_area as usize) else { continue };
+				if *other_connected || !portal_open(link.portal) {
+					continue;
+				}
+
+				*other_connected = true;
+				stack.push(link.other_area);
+			}
+		}
+
+		connected
+	}
+
+	/// Maps each face of the world model to the area of the first leaf that references it.
+	pub(crate) fn face_areas(&self, data: &BspData) -> HashMap<u32, u32> {
+		let mut face_areas = HashMap::new();
+
+		for (leaf, area) in data.leaves.iter().zip(&self.leaf_areas) {
+			if *area == 0 {
+				continue;
+			}
+
+			for mark_surface_idx in leaf.face_idx.0..leaf.face_idx.0.saturating_add(leaf.face_num.0) {
+				let Some(face_idx) = data.mark_surfaces.get(mark_surface_idx as usize) else { continue };
+				face_areas.entry(face_idx.0).or_insert(*area);
+			}
+		}
+
+		face_areas
+	}
+}
+
+/// Collects the portals leading out of each area from the raw `[num_portals, first_portal]` areas and `[portal_num, other_area]` area portals lumps.
+fn read_area_links(raw_areas: &[[u32; 2]], raw_portals: &[[u32; 2]]) -> anyhow::Result<Vec<Vec<BspAreaPortalLink>>> {
+	raw_areas
+		.iter()
+		.map(|&[num_portals, first_portal]| {
+			let portals = first_portal
+				.checked_add(num_portals)
+				.and_then(|end| raw_portals.get(first_portal as usize..end as usize))
+				.ok_or_else(|| anyhow!("area portals {first_portal}..{first_portal}+{num_portals} out of bounds"))?;
+
+			Ok(portals
+				.iter()
+				.map(|&[portal, other_area]| BspAreaPortalLink { portal, other_area })
+				.collect())
+		})
+		.collect()
+}
+
+/// Splits an exported mesh into one mesh per area, using the area map from [`BspAreas::face_areas`].
+pub(crate) fn split_mesh_by_area(mesh: ExportedMesh, face_areas: &HashMap<u32, u32>, data: &BspData) -> Vec<(Option<u32>, ExportedMesh)> {
+	split_mesh_by_face(mesh, data, |face_idx| face_areas.get(&face_idx).copied())
+}
+
+/// The area of the world a mesh entity is in. Hidden by [`BspAreasPlugin`] when its area isn't connected to a camera's area.
+///
+/// Added to world geometry meshes automatically, must be a child of an entity with [`BspAreaGraph`].
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct BspArea(pub u32);
+
+/// Added to the `worldspawn` entity of Quake 2 BSPs to allow culling child [`BspArea`]s.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct BspAreaGraph(pub Handle<BspAreas>);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		class::io::{EntityIoPlugin, Fire},
+		util::{target, targetable},
+	};
+
+	#[test]
+	fn area_connectivity() {
+		let link = |portal, other_area| BspAreaPortalLink { portal, other_area };
+
+		// 1 <-(0)-> 2 <-(1)-> 3
+		let areas = BspAreas {
+			areas: vec![vec![], vec![link(0, 2)], vec![link(0, 1), link(1, 3)], vec![link(1, 2)]],
+			..default()
+		};
+
+		assert_eq!(areas.connected_areas(1, |_| false), [false, true, false, false]);
+		assert_eq!(areas.connected_areas(1, |portal| portal == 0), [false, true, true, false]);
+		assert_eq!(areas.connected_areas(1, |_| true), [false, true, true, true]);
+		assert_eq!(areas.connected_areas(3, |portal| portal == 1), [false, false, true, true]);
+	}
+
+	#[test]
+	fn use_area_portals() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin))
+			.add_observer(BspAreasPlugin::set_area_portal_state)
+			.add_observer(BspAreasPlugin::use_area_portal);
+
+		let world = app.world_mut();
+		let portal = world.spawn((FuncAreaportal::default(), targetable("portal"))).id();
+		let button = world.spawn(target("portal")).id();
+		let is_open = |app: &App| app.world().get::<FuncAreaportal>(portal).unwrap().open;
+
+		app.world_mut().trigger(Fire::new(button));
+		app.world_mut().flush();
+		assert!(is_open(&app));
+		app.world_mut().trigger(Fire::new(button));
+		app.world_mut().flush();
+		assert!(!is_open(&app));
+
+		app.world_mut().trigger(SetAreaPortalState {
+			target: "portal".into(),
+			open: true,
+		});
+		assert!(is_open(&app));
+	}
+
+	#[test]
+	fn area_links() {
+		let links = read_area_links(&[[0, 0], [1, 0], [2, 1]], &[[0, 2], [0, 1], [1, 3]]).unwrap();
+		assert_eq!(
+			links[2],
+			[
+				BspAreaPortalLink { portal: 0, other_area: 1 },
+				BspAreaPortalLink { portal: 1, other_area: 3 }
+			]
+		);
+
+		// Out of bounds or overflowing portal ranges are errors rather than panics.
+		assert!(read_area_links(&[[2, 2]], &[[0, 1], [0, 2]]).is_err());
+		assert!(read_area_links(&[[2, u32::MAX]], &[[0, 1], [0, 2]]).is_err());
+	}
+}
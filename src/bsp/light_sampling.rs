@@ -0,0 +1,314 @@
+//! CPU-side sampling of a BSP's baked lighting, for lighting things that aren't covered by the irradiance volume, gameplay (stealth, etc.), or servers without any rendering.
+
+use qbsp::{
+	data::{
+		BspNodeRef,
+		bspx::{LightGridCell, LightGridNode, LightGridOctree},
+		lighting::BspLighting,
+		texture::BspSurfaceFlags,
+	},
+	mesh::FaceExtents,
+};
+
+use crate::*;
+use bsp::Bsp;
+
+/// How far down (in map units) [`Bsp::sample_light`] traces to find a lightmapped face when the BSP has no light grid. Same as Quake's `R_LightPoint`.
+pub const LIGHT_POINT_TRACE_DISTANCE: f32 = 2048.;
+
+/// The baked light at a point, as returned by [`Bsp::sample_light`].
+///
+/// Colors are in linear RGB, before any animation or exposure is applied.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub struct BakedLightSample {
+	/// Up to 4 styles and their light color, terminated by [`LightmapStyle::NONE`], the same as [`BspFace::lightmap_styles`](qbsp::data::models::BspFace::lightmap_styles).
+	pub styles: [(LightmapStyle, Vec3); 4],
+}
+impl Default for BakedLightSample {
+	fn default() -> Self {
+		Self {
+			styles: [(LightmapStyle::NONE, Vec3::ZERO); 4],
+		}
+	}
+}
+impl BakedLightSample {
+	/// Iterates through each style this sample contains, and its light color.
+	pub fn iter(&self) -> impl Iterator<Item = (LightmapStyle, Vec3)> + '_ {
+		self.styles.iter().copied().take_while(|(style, _)| *style != LightmapStyle::NONE)
+	}
+
+	/// Adds `color` to `style`'s slot, or to a new slot if it doesn't have one. Styles past the 4th are thrown away.
+	fn add(&mut self, style: LightmapStyle, color: Vec3) {
+		for slot in &mut self.styles {
+			if slot.0 == style {
+				slot.1 += color;
+				return;
+			} else if slot.0 == LightmapStyle::NONE {
+				*slot = (style, color);
+				return;
+			}
+		}
+	}
+
+	/// Combines every style into a single color, multiplying each by the result of `multiplier`.
+	pub fn color(&self, multiplier: impl Fn(LightmapStyle) -> Vec3) -> Vec3 {
+		self.iter().map(|(style, color)| color * multiplier(style)).sum()
+	}
+
+	/// Combines every style into a single color, animated by `animators` at `seconds` the same way lightmaps are on the GPU.
+	///
+	/// # Examples
+	/// ```
+	/// # use bevy::prelude::*;
+	/// # use bevy_trenchbroom::prelude::*;
+	/// # use bevy_trenchbroom::bsp::{Bsp, light_sampling::BakedLightSample};
+	/// fn light_level(
+	///     bsp: &Bsp,
+	///     position: Vec3,
+	///     server: &TrenchBroomServer,
+	///     animators: &LightingAnimators,
+	///     time: &Time,
+	/// ) -> f32 {
+	///     bsp.sample_light(position, &server.config)
+	///         .map(|sample| sample.animated(animators, time.elapsed_secs()).max_element())
+	///         .unwrap_or(0.)
+	/// }
+	/// ```
+	#[cfg(feature = "client")]
+	pub fn animated(&self, animators: &LightingAnimators, seconds: f32) -> Vec3 {
+		self.color(|style| animators.values.get(&style).copied().unwrap_or_default().sample(seconds))
+	}
+}
+
+impl Bsp {
+	/// Samples the baked lighting at `position` (in the world's local Bevy space).
+	///
+	/// Uses the BSPX light grid if the BSP has one, otherwise traces down to the nearest lightmapped face below `position` like Quake's `R_LightPoint`.
+	///
+	/// Returns [`None`] if the BSP has no lighting, or there's no light data at this point (such as outside the world, or with nothing below it).
+	pub fn sample_light(&self, position: Vec3, config: &TrenchBroomConfig) -> Option<BakedLightSample> {
+		sample_baked_light(&self.data, config.from_bevy_space(position))
+	}
+}
+
+/// [`Bsp::sample_light`], but with `position` in Quake space.
+pub fn sample_baked_light(data: &BspData, position: Vec3) -> Option<BakedLightSample> {
+	if let Some(light_grid) = &data.bspx.light_grid_octree {
+		return sample_light_grid(light_grid, position);
+	}
+
+	let lighting = data.lighting.as_ref()?;
+	let root = data.models.first()?.hulls.root;
+	light_point(data, lighting, root, position, position - Vec3::Z * LIGHT_POINT_TRACE_DISTANCE)
+}
+
+/// Trilinearly interpolates the 8 light grid cells around `position` (in Quake space), ignoring occluded and missing cells.
+fn sample_light_grid(light_grid: &LightGridOctree, position: Vec3) -> Option<BakedLightSample> {
+	let cell_position = (position - light_grid.mins) / light_grid.step;
+	let base = cell_position.floor();
+	let fraction = cell_position - base;
+
+	let mut sample = BakedLightSample::default();
+	let mut total_weight = 0.;
+
+	for corner in 0..8 {
+		let offset = uvec3(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+		let cell = base + offset.as_vec3();
+		if cell.cmplt(Vec3::ZERO).any() {
+			continue;
+		}
+		let cell = cell.as_uvec3();
+		if cell.cmpge(light_grid.size).any() {
+			continue;
+		}
+
+		let Some(LightGridCell::Filled(cell_samples)) = light_grid_cell(light_grid, cell) else { continue };
+
+		let weight = Vec3::select(offset.cmpeq(UVec3::ONE), fraction, 1. - fraction).element_product();
+		if weight <= 0. {
+			continue;
+		}
+		total_weight += weight;
+
+		for cell_sample in cell_samples {
+			sample.add(cell_sample.style, decode_color(cell_sample.color) * weight);
+		}
+	}
+
+	if total_weight <= 0. {
+		return None;
+	}
+
+	for (_, color) in &mut sample.styles {
+		*color /= total_weight;
+	}
+
+	Some(sample)
+}
+
+/// Walks the light grid's octree to find the cell at `cell`, in Quake axis order.
+fn light_grid_cell(light_grid: &LightGridOctree, cell: UVec3) -> Option<&LightGridCell> {
+	let mut idx = light_grid.root_idx;
+
+	loop {
+		if idx & LightGridNode::LEAF != 0 {
+			let leaf = light_grid.leafs.get((idx & !LightGridNode::LEAF) as usize)?;
+			let local = cell.checked_sub(leaf.mins)?;
+			if local.cmpge(leaf.size()).any() {
+				return None;
+			}
+			return Some(leaf.get_cell(local.x, local.y, local.z));
+		}
+		if idx & LightGridNode::MISSING != 0 {
+			return None;
+		}
+
+		idx = light_grid.nodes.get(idx as usize)?.get_child_index_towards(cell.as_vec3());
+	}
+}
+
+/// Recursively traces from `start` to `end` (in Quake space) through the BSP tree, returning the lighting of the first lightmapped face hit, or [`None`] if nothing is hit.
+///
+/// Ported from Quake's `RecursiveLightPoint`.
+fn light_point(data: &BspData, lighting: &BspLighting, node_ref: BspNodeRef, start: Vec3, end: Vec3) -> Option<BakedLightSample> {
+	let BspNodeRef::Node(node_idx) = node_ref else { return None };
+	let node = data.nodes.get(node_idx as usize)?;
+	let plane = data.planes.get(node.plane_idx as usize)?;
+
+	let front = plane.point_side(start);
+	let back = plane.point_side(end);
+	let side_is_back = front < 0.;
+	let (near, far) = if side_is_back {
+		(*node.back, *node.front)
+	} else {
+		(*node.front, *node.back)
+	};
+
+	if (back < 0.) == side_is_back {
+		return light_point(data, lighting, near, start, end);
+	}
+
+	let mid = start.lerp(end, front / (front - back));
+
+	if let Some(sample) = light_point(data, lighting, near, start, mid) {
+		return Some(sample);
+	}
+
+	for face_idx in node.face_idx.0..node.face_idx.0 + node.face_num.0 {
+		let Some(face) = data.faces.get(face_idx as usize) else { continue };
+		let Some(tex_info) = data.tex_info.get(face.texture_info_idx.0 as usize) else { continue };
+
+		if tex_info.flags.texture_flags.unwrap_or_default() != BspTexFlags::Normal
+			|| tex_info.flags.surface_flags.intersects(BspSurfaceFlags::SKY | BspSurfaceFlags::WARP)
+		{
+			continue;
+		}
+
+		let (uv, extents, lightmap_offset) = match data.bspx.decoupled_lm.as_ref().and_then(|lm_infos| lm_infos.get(face_idx as usize)) {
+			Some(lm_info) => (
+				lm_info.projection.project(mid),
+				FaceExtents::new_decoupled(face.vertices(data).map(|pos| lm_info.projection.project(pos)), lm_info),
+				lm_info.offset.pixels,
+			),
+			None => (
+				tex_info.projection.project(mid),
+				FaceExtents::new(face.vertices(data).map(|pos| tex_info.projection.project(pos))),
+				face.lightmap_offset.pixels,
+			),
+		};
+
+		let face_rect = extents.face_rect();
+		if uv.cmplt(face_rect.min).any() || uv.cmpgt(face_rect.max).any() {
+			continue;
+		}
+
+		// Hit a face without a lightmap, Quake treats this as complete darkness.
+		if lightmap_offset < 0 || extents.lightmap_size() == UVec2::ZERO {
+			return Some(BakedLightSample::default());
+		}
+
+		// Move from pixel corners to pixel centers.
+		let luxel = extents.compute_lightmap_uvs([uv], Vec2::ZERO).next()? - 0.5;
+		return Some(sample_lightmap(lighting, face.lightmap_styles, lightmap_offset as usize, &extents, luxel));
+	}
+
+	light_point(data, lighting, far, mid, end)
+}
+
+/// Bilinearly samples each style of a face's lightmap at `luxel`.
+fn sample_lightmap(
+	lighting: &BspLighting,
+	styles: [LightmapStyle; 4],
+	lightmap_offset: usize,
+	extents: &FaceExtents,
+	luxel: Vec2,
+) -> BakedLightSample {
+	let size = extents.lightmap_size();
+	let luxel = luxel.clamp(Vec2::ZERO, (size - 1).as_vec2());
+	let min = luxel.floor().as_uvec2();
+	let max = (min + 1).min(size - 1);
+	let fraction = luxel - min.as_vec2();
+
+	let mut sample = BakedLightSample::default();
+
+	for (style_idx, style) in styles.into_iter().enumerate() {
+		if style == LightmapStyle::NONE {
+			break;
+		}
+
+		let get = |x: u32, y: u32| {
+			let idx = lightmap_offset + extents.lightmap_pixels() as usize * style_idx + (y * size.x + x) as usize;
+			lighting.get(idx).map(decode_color).unwrap_or_default()
+		};
+
+		let top = get(min.x, min.y).lerp(get(max.x, min.y), fraction.x);
+		let bottom = get(min.x, max.y).lerp(get(max.x, max.y), fraction.x);
+		sample.add(style, top.lerp(bottom, fraction.y));
+	}
+
+	sample
+}
+
+/// Converts sRGB lighting data into linear RGB, the same as the GPU does with lightmap and irradiance volume textures.
+fn decode_color([r, g, b]: [u8; 3]) -> Vec3 {
+	LinearRgba::from(Srgba::rgb_u8(r, g, b)).to_vec3()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn sample_above_floor() {
+		let bytes = fs::read("assets/maps/example.bsp").unwrap();
+		let mut data = BspData::parse(BspParseInput {
+			bsp: &bytes,
+			lit: None,
+			settings: default(),
+		})
+		.unwrap();
+		let model = &data.models[0];
+		let floor = (model.first_face..model.first_face + model.num_faces)
+			.map(|face_idx| &data.faces[face_idx as usize])
+			.find(|face| {
+				let plane = &data.planes[face.plane_idx.0 as usize];
+				face.plane_side.0 == 0 && plane.normal.z > 0.99 && face.lightmap_offset.pixels >= 0
+			})
+			.unwrap();
+		let center = floor.vertices(&data).sum::<Vec3>() / floor.num_edges.0 as f32;
+
+		assert!(data.bspx.light_grid_octree.is_some());
+		assert!(sample_baked_light(&data, center + Vec3::Z * 8.).is_some());
+
+		// Force the trace path.
+		data.bspx.light_grid_octree = None;
+		let sample = sample_baked_light(&data, center + Vec3::Z * 8.).unwrap();
+		assert!(sample.iter().count() > 0);
+		assert!(sample.color(|_| Vec3::ONE).max_element() > 0.);
+		assert_eq!(sample.color(|_| Vec3::ZERO), Vec3::ZERO);
+
+		// Nothing below the world.
+		assert_eq!(sample_baked_light(&data, Vec3::splat(-100_000.)), None);
+	}
+}
@@ -0,0 +1,263 @@
+//! Data-driven [`LightingAnimators`], loaded from files so they can be tuned without recompiling.
+use bevy::{
+	asset::{AssetLoader, AssetPath, LoadContext},
+	platform::collections::HashSet,
+	tasks::ConditionalSendFuture,
+};
+use bevy_materialize::load::deserializer::MaterialDeserializer;
+use qmap::QuakeMapEntities;
+
+use crate::*;
+
+use super::{MAX_LIGHTMAP_FRAMES, QUAKE_LIGHT_STYLE_FPS};
+
+/// Worldspawn key pointing to a [`LightingAnimatorsAsset`] that overrides the global one while the map is loaded. E.g. `"lighting/spooky.animators.toml"`.
+pub const LIGHTING_ANIMATORS_WORLDSPAWN_KEY: &str = "_lighting_animators";
+
+/// A set of [`LightingAnimator`]s loaded from a `.animators.toml` file.
+///
+/// Each table in the file is a named style, with either a Quake style `pattern`, or a `sequence` of frames that are either brightness numbers or RGB arrays.
+/// ```toml
+/// [flicker]
+/// style = 1
+/// pattern = "mmnmmommommnonmmonqnmmo"
+///
+/// [alarm]
+/// style = 2
+/// speed = 2
+/// interpolate = 1
+/// sequence = [[1, 0, 0], 0.2]
+/// ```
+///
+/// These are applied to the [`LightingAnimators`] resource when loaded or changed, see [`TrenchBroomConfig::lighting_animators`] and [`LIGHTING_ANIMATORS_WORLDSPAWN_KEY`].
+#[derive(Asset, TypePath, Debug, Clone, Default)]
+pub struct LightingAnimatorsAsset {
+	pub animators: LightingAnimators,
+	/// The style each name in the file refers to. Light entities can use these names as their `style` instead of a number.
+	pub names: HashMap<String, LightmapStyle>,
+}
+
+/// A single named style in a [`LightingAnimatorsAsset`] file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LightingAnimatorDefinition {
+	pub style: LightmapStyle,
+	/// A Quake light style string, see [`LightingAnimator::from_style_string`]. Mutually exclusive with `sequence`.
+	#[serde(default)]
+	pub pattern: Option<String>,
+	#[serde(default)]
+	pub sequence: Vec<LightingAnimatorFrame>,
+	/// Frames per second. (Default: [`QUAKE_LIGHT_STYLE_FPS`])
+	#[serde(default = "LightingAnimatorDefinition::default_speed")]
+	pub speed: f32,
+	/// See [`LightingAnimator::interpolate`]. (Default: 0)
+	#[serde(default)]
+	pub interpolate: f32,
+}
+impl LightingAnimatorDefinition {
+	fn default_speed() -> f32 {
+		QUAKE_LIGHT_STYLE_FPS
+	}
+
+	pub fn to_animator(&self) -> anyhow::Result<LightingAnimator> {
+		let mut animator = match &self.pattern {
+			Some(_) if !self.sequence.is_empty() => return Err(anyhow!("can't have both a `pattern` and a `sequence`")),
+			Some(pattern) => LightingAnimator::from_style_string(pattern)?,
+			None if self.sequence.is_empty() => return Err(anyhow!("needs either a `pattern` or a `sequence`")),
+			None if self.sequence.len() > MAX_LIGHTMAP_FRAMES => {
+				return Err(anyhow!(
+					"sequence has {} frames, but the max is {MAX_LIGHTMAP_FRAMES}",
+					self.sequence.len()
+				));
+			}
+			None => {
+				let mut animator = LightingAnimator::unanimated(Vec3::ZERO);
+				for (frame, value) in animator.sequence.iter_mut().zip(&self.sequence) {
+					*frame = value.rgb();
+				}
+				animator.sequence_len = self.sequence.len() as u32;
+				animator
+			}
+		};
+
+		animator.speed = self.speed;
+		animator.interpolate = self.interpolate;
+
+		Ok(animator)
+	}
+}
+
+/// A frame in a [`LightingAnimatorDefinition`]'s sequence.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(untagged)]
+pub enum LightingAnimatorFrame {
+	Brightness(f32),
+	Rgb(Vec3),
+}
+impl LightingAnimatorFrame {
+	pub fn rgb(self) -> Vec3 {
+		match self {
+			Self::Brightness(brightness) => Vec3::splat(brightness),
+			Self::Rgb(rgb) => rgb,
+		}
+	}
+}
+
+#[derive(TypePath, Default)]
+pub struct LightingAnimatorsLoader;
+impl AssetLoader for LightingAnimatorsLoader {
+	type Asset = LightingAnimatorsAsset;
+	type Settings = ();
+	type Error = anyhow::Error;
+
+	fn load(
+		&self,
+		reader: &mut dyn bevy::asset::io::Reader,
+		_settings: &Self::Settings,
+		_load_context: &mut LoadContext,
+	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+
+			let definitions: HashMap<String, LightingAnimatorDefinition> = TomlMaterialDeserializer.deserialize(&bytes)?;
+
+			let mut asset = LightingAnimatorsAsset::default();
+			for (name, definition) in definitions {
+				let animator = definition.to_animator().map_err(|err| anyhow!("Style `{name}`: {err}"))?;
+				asset.animators.values.insert(definition.style, animator);
+				asset.names.insert(name, definition.style);
+			}
+
+			Ok(asset)
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["animators.toml"]
+	}
+}
+
+/// Spawned into a map's scene if its worldspawn has [`LIGHTING_ANIMATORS_WORLDSPAWN_KEY`] set. While it exists, its animators override the global ones.
+#[derive(Component, Debug, Clone)]
+pub struct MapLightingAnimators(pub Handle<LightingAnimatorsAsset>);
+
+/// Keeps track of [`LightingAnimatorsAsset`]s applied to the [`LightingAnimators`] resource.
+#[derive(Resource, Debug, Default)]
+pub struct LightingAnimatorFiles {
+	/// Loaded from [`TrenchBroomConfig::lighting_animators`].
+	pub global: Option<Handle<LightingAnimatorsAsset>>,
+	/// Styles set from files, removed before files are re-applied so that styles deleted from a file (or from an unloaded map's override) don't stick around.
+	applied: HashSet<LightmapStyle>,
+}
+
+/// Re-applies the global and per-map [`LightingAnimatorsAsset`]s whenever one loads, changes (e.g. from hot reloading), or a map is spawned or despawned.
+///
+/// Styles from files take priority over ones set in code, and per-map overrides take priority over the global file.
+pub fn apply_lighting_animator_files(
+	mut files: ResMut<LightingAnimatorFiles>,
+	mut asset_events: MessageReader<AssetEvent<LightingAnimatorsAsset>>,
+	added_map_files: Query<(), Added<MapLightingAnimators>>,
+	mut removed_map_files: RemovedComponents<MapLightingAnimators>,
+	map_files: Query<&MapLightingAnimators>,
+	assets: Res<Assets<LightingAnimatorsAsset>>,
+	mut animators: ResMut<LightingAnimators>,
+) {
+	// Make sure all of these are read.
+	let changed = asset_events.read().count() > 0;
+	let removed = removed_map_files.read().count() > 0;
+	if !changed && !removed && added_map_files.is_empty() {
+		return;
+	}
+
+	let files = &mut *files;
+	for style in files.applied.drain() {
+		animators.values.remove(&style);
+	}
+
+	for asset in files
+		.global
+		.iter()
+		.chain(map_files.iter().map(|map_file| &map_file.0))
+		.filter_map(|handle| assets.get(handle))
+	{
+		for (style, animator) in &asset.animators.values {
+			animators.values.insert(*style, *animator);
+			files.applied.insert(*style);
+		}
+	}
+}
+
+/// Resolves named light styles in `entities` from the global and map's [`LightingAnimatorsAsset`]s,
+/// returning the component to spawn into the map's scene if the map overrides the global animators.
+pub(crate) async fn prepare_map_lighting_animators(
+	load_context: &mut LoadContext<'_>,
+	asset_server: &AssetServer,
+	config: &TrenchBroomConfig,
+	entities: &mut QuakeMapEntities,
+) -> anyhow::Result<Option<MapLightingAnimators>> {
+	if config.no_bsp_lighting {
+		return Ok(None);
+	}
+
+	let map_path = entities
+		.worldspawn()
+		.and_then(|worldspawn| worldspawn.properties.get(LIGHTING_ANIMATORS_WORLDSPAWN_KEY))
+		.map(|path| AssetPath::from(path.clone()));
+
+	if entities.has_named_light_styles() {
+		let mut names = HashMap::default();
+		for path in config.lighting_animators.iter().chain(&map_path) {
+			let asset = load_context
+				.load_builder()
+				.load_value::<LightingAnimatorsAsset>(path.clone())
+				.await
+				.map_err(|err| anyhow!("Loading lighting animators {path}: {err}"))?;
+			names.extend(asset.take().names);
+		}
+
+		entities.resolve_named_light_styles(&names);
+	}
+
+	Ok(map_path.map(|path| MapLightingAnimators(asset_server.load(path))))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_definitions() {
+		let definitions: HashMap<String, LightingAnimatorDefinition> = TomlMaterialDeserializer
+			.deserialize(
+				br#"
+				[flicker]
+				style = 1
+				pattern = "az"
+
+				[alarm]
+				style = 2
+				speed = 2
+				interpolate = 1
+				sequence = [[1, 0, 0], 0.5]
+				"#,
+			)
+			.unwrap();
+
+		let flicker = definitions["flicker"].to_animator().unwrap();
+		assert_eq!(definitions["flicker"].style, LightmapStyle(1));
+		assert_eq!(flicker.sample(0.), Vec3::ZERO);
+		assert_eq!(flicker.speed, QUAKE_LIGHT_STYLE_FPS);
+
+		let alarm = definitions["alarm"].to_animator().unwrap();
+		assert_eq!(alarm.sequence_len, 2);
+		assert_eq!(alarm.sample(0.), Vec3::X);
+		assert_eq!(alarm.sample(0.5), Vec3::splat(0.5));
+
+		let both = LightingAnimatorDefinition {
+			sequence: vec![LightingAnimatorFrame::Brightness(1.)],
+			..definitions["flicker"].clone()
+		};
+		assert!(both.to_animator().is_err());
+	}
+}
@@ -0,0 +1,325 @@
+//! Compositing animated lighting on the CPU, for backends without compute shaders.
+use bevy::{
+	asset::RenderAssetUsages,
+	image::TextureAccessError,
+	render::render_resource::{TextureDimension, TextureFormat},
+};
+use smart_default::SmartDefault;
+
+use crate::*;
+
+use super::AnimatedLighting;
+
+/// Settings for compositing [`AnimatedLighting`] on the CPU instead of with compute shaders, see [`TrenchBroomConfig::cpu_lighting_compositing`].
+#[derive(Debug, Clone, Copy, SmartDefault)]
+pub struct CpuLightingCompositing {
+	/// How many times a second animated styles are re-composited. Lower values use less CPU time, but make animations choppier. (Default: 20)
+	#[default(20.)]
+	pub updates_per_second: f32,
+}
+
+/// The asset usages [`AnimatedLighting`] images need, based on `usages`. When compositing on the CPU, their data has to stay in the main world.
+pub(crate) fn animated_lighting_usages(config: &TrenchBroomConfig, usages: RenderAssetUsages) -> RenderAssetUsages {
+	if config.cpu_lighting_compositing.is_some() {
+		usages | RenderAssetUsages::MAIN_WORLD
+	} else {
+		usages
+	}
+}
+
+/// Caches the data needed to composite each [`AnimatedLighting`] on the CPU.
+#[derive(Resource, Default)]
+pub struct CpuLightingCompositor {
+	composites: HashMap<AssetId<AnimatedLighting>, CpuComposite>,
+	/// [`Time::elapsed_secs_wrapped`] of the next time animated styles should be updated.
+	next_update: f32,
+}
+
+/// The decoded inputs of an [`AnimatedLighting`].
+struct CpuComposite {
+	size: UVec3,
+	/// Linear colors of each input image, and its size.
+	inputs: [(Vec<Vec3>, UVec3); 4],
+	styles: Vec<[u8; 4]>,
+	styles_size: UVec3,
+	/// Which output pixels use each style, so only pixels of styles that changed have to be re-composited.
+	style_pixels: HashMap<LightmapStyle, Vec<UVec3>>,
+	/// The multiplier each style was last composited with, [`None`] if this hasn't been composited yet.
+	composited: Option<HashMap<LightmapStyle, Vec3>>,
+}
+impl CpuComposite {
+	/// Decodes the images of `animated_lighting`, returns [`None`] if any aren't loaded yet.
+	fn new(animated_lighting: &AnimatedLighting, images: &Assets<Image>) -> Option<Self> {
+		let output = images.get(&animated_lighting.output)?;
+		let size = extent_size(output);
+
+		let mut inputs: [(Vec<Vec3>, UVec3); 4] = default();
+		for (input, handle) in inputs.iter_mut().zip(&animated_lighting.input) {
+			let image = images.get(handle)?;
+			*input = (decode_image(image)?, extent_size(image));
+		}
+
+		let styles_image = images.get(&animated_lighting.styles)?;
+		let styles = styles_image
+			.data
+			.as_ref()?
+			.chunks_exact(4)
+			.map(|styles| [styles[0], styles[1], styles[2], styles[3]])
+			.collect_vec();
+		let styles_size = extent_size(styles_image);
+
+		let mut style_pixels: HashMap<LightmapStyle, Vec<UVec3>> = default();
+		for z in 0..size.z {
+			for y in 0..size.y {
+				for x in 0..size.x {
+					let pos = uvec3(x, y, z);
+					for style in styles[texel_index(pos, styles_size)] {
+						if style != LightmapStyle::NONE.0 {
+							style_pixels.entry(LightmapStyle(style)).or_default().push(pos);
+						}
+					}
+				}
+			}
+		}
+
+		for pixels in style_pixels.values_mut() {
+			pixels.dedup();
+		}
+
+		Some(Self {
+			size,
+			inputs,
+			styles,
+			styles_size,
+			style_pixels,
+			composited: None,
+		})
+	}
+
+	/// Composites a single pixel the same way `composite_lightmaps.wgsl` does.
+	fn composite(&self, pos: UVec3, multipliers: &HashMap<LightmapStyle, Vec3>) -> Vec3 {
+		self.styles[texel_index(pos, self.styles_size)]
+			.iter()
+			.zip(&self.inputs)
+			.filter(|(style, _)| **style != LightmapStyle::NONE.0)
+			.map(|(style, (input, input_size))| input[texel_index(pos, *input_size)] * multipliers[&LightmapStyle(*style)])
+			.sum()
+	}
+}
+
+fn extent_size(image: &Image) -> UVec3 {
+	let extent = image.texture_descriptor.size;
+	uvec3(extent.width, extent.height, extent.depth_or_array_layers)
+}
+
+/// Index of the texel at `pos` in an image of `size`, repeating like the compositing shaders do.
+fn texel_index(pos: UVec3, size: UVec3) -> usize {
+	let pos = pos % size.max(UVec3::ONE);
+	((pos.z * size.y + pos.y) * size.x + pos.x) as usize
+}
+
+/// [`Image::get_color_at_3d`] only accepts 3D images or 2D arrays, this works with both those and plain 2D images.
+fn get_color(image: &Image, pos: UVec3) -> Result<Color, TextureAccessError> {
+	match image.texture_descriptor.dimension {
+		TextureDimension::D3 => image.get_color_at_3d(pos.x, pos.y, pos.z),
+		_ => image.get_color_at(pos.x, pos.y),
+	}
+}
+fn set_color(image: &mut Image, pos: UVec3, color: Color) -> Result<(), TextureAccessError> {
+	match image.texture_descriptor.dimension {
+		TextureDimension::D3 => image.set_color_at_3d(pos.x, pos.y, pos.z, color),
+		_ => image.set_color_at(pos.x, pos.y, color),
+	}
+}
+
+/// Reads the linear RGB of each texel in `image`.
+fn decode_image(image: &Image) -> Option<Vec<Vec3>> {
+	// Used by deluxemaps, but not supported by `Image::get_color_at`.
+	if image.texture_descriptor.format == TextureFormat::Rgba8Snorm {
+		return Some(
+			image
+				.data
+				.as_ref()?
+				.chunks_exact(4)
+				.map(|texel| Vec3::from_array([texel[0], texel[1], texel[2]].map(|v| (v as i8 as f32 / 127.).max(-1.))))
+				.collect(),
+		);
+	}
+
+	let size = extent_size(image);
+	let mut texels = Vec::with_capacity(size.element_product() as usize);
+	for z in 0..size.z {
+		for y in 0..size.y {
+			for x in 0..size.x {
+				texels.push(get_color(image, uvec3(x, y, z)).ok()?.to_linear().to_vec3());
+			}
+		}
+	}
+
+	Some(texels)
+}
+
+impl CpuLightingCompositor {
+	/// Composites every loaded [`AnimatedLighting`] into its output image at `seconds`.
+	///
+	/// Every asset is fully composited once after it loads, then only pixels using styles whose multiplier changed are updated, at most [`CpuLightingCompositing::updates_per_second`] times a second.
+	/// This means maps with only unanimated lighting are only composited once.
+	pub fn composite(
+		&mut self,
+		settings: CpuLightingCompositing,
+		seconds: f32,
+		animated_lighting_assets: &Assets<AnimatedLighting>,
+		images: &mut Assets<Image>,
+		animators: &LightingAnimators,
+	) {
+		// Time wrapping around also counts as an update.
+		let update_animated = seconds >= self.next_update || seconds + settings.updates_per_second.recip() < self.next_update;
+		if update_animated {
+			self.next_update = seconds + settings.updates_per_second.recip();
+		}
+
+		for (id, animated_lighting) in animated_lighting_assets.iter() {
+			if !self.composites.contains_key(&id) {
+				let Some(composite) = CpuComposite::new(animated_lighting, images) else { continue };
+				self.composites.insert(id, composite);
+			}
+			let composite = self.composites.get_mut(&id).expect("inserted above");
+
+			let multipliers: HashMap<LightmapStyle, Vec3> = composite
+				.style_pixels
+				.keys()
+				.map(|style| {
+					(
+						*style,
+						animators.values.get(style).map(|animator| animator.sample(seconds)).unwrap_or(Vec3::ONE),
+					)
+				})
+				.collect();
+
+			// Collects the pixels to update, or `None` for all of them.
+			let dirty: Option<Vec<UVec3>> = match &composite.composited {
+				None => None,
+				Some(_) if !update_animated => continue,
+				Some(composited) => {
+					let dirty = multipliers
+						.iter()
+						.filter(|(style, multiplier)| composited.get(*style) != Some(multiplier))
+						.flat_map(|(style, _)| composite.style_pixels[style].iter().copied())
+						.collect_vec();
+
+					if dirty.is_empty() {
+						continue;
+					}
+					Some(dirty)
+				}
+			};
+
+			let Some(mut output) = images.get_mut(&animated_lighting.output) else { continue };
+
+			let mut write = |pos: UVec3| {
+				let color = composite.composite(pos, &multipliers);
+				set_color(&mut output, pos, LinearRgba::from_vec3(color).into()).expect("output image has a supported format");
+			};
+
+			match dirty {
+				Some(dirty) => dirty.into_iter().for_each(&mut write),
+				None => {
+					for z in 0..composite.size.z {
+						for y in 0..composite.size.y {
+							for x in 0..composite.size.x {
+								write(uvec3(x, y, z));
+							}
+						}
+					}
+				}
+			}
+
+			composite.composited = Some(multipliers);
+		}
+	}
+}
+
+/// Composites [`AnimatedLighting`] into their output images on the CPU with [`CpuLightingCompositor`].
+pub fn composite_lighting_on_cpu(
+	mut compositor: ResMut<CpuLightingCompositor>,
+	mut asset_events: MessageReader<AssetEvent<AnimatedLighting>>,
+	animated_lighting_assets: Res<Assets<AnimatedLighting>>,
+	mut images: ResMut<Assets<Image>>,
+	animators: Res<LightingAnimators>,
+	time: Res<Time>,
+	tb_server: Res<TrenchBroomServer>,
+) {
+	let Some(settings) = tb_server.config.cpu_lighting_compositing else { return };
+
+	for event in asset_events.read() {
+		if let AssetEvent::Modified { id } | AssetEvent::Removed { id } | AssetEvent::Unused { id } = event {
+			compositor.composites.remove(id);
+		}
+	}
+
+	compositor.composite(settings, time.elapsed_secs_wrapped(), &animated_lighting_assets, &mut images, &animators);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::bsp::lighting::{AnimatedLightingType, new_animated_lighting_output_image};
+	use bevy::render::render_resource::Extent3d;
+
+	#[test]
+	fn cpu_composite_only_updates_animated_styles() {
+		let settings = CpuLightingCompositing::default();
+		let config = TrenchBroomConfig::default().cpu_lighting_compositing(Some(settings));
+		let mut images = Assets::<Image>::default();
+		let mut animated_lighting_assets = Assets::<AnimatedLighting>::default();
+		let mut animators = LightingAnimators::new([(LightmapStyle(1), LightingAnimator::unanimated(Vec3::splat(2.)))]);
+		let mut compositor = CpuLightingCompositor::default();
+
+		let extent = Extent3d {
+			width: 2,
+			height: 1,
+			depth_or_array_layers: 1,
+		};
+		let mut input = |value: u8| {
+			images.add(Image::new_fill(
+				extent,
+				TextureDimension::D2,
+				&[value, value, value, 255],
+				TextureFormat::Rgba8Unorm,
+				RenderAssetUsages::all(),
+			))
+		};
+		let input = [input(255), input(51), input(0), input(0)];
+		let output = images.add(new_animated_lighting_output_image(extent, TextureDimension::D2, &config));
+		// The first pixel only uses style 0, the second also uses style 1.
+		let styles = images.add(Image::new(
+			extent,
+			TextureDimension::D2,
+			vec![0, 255, 255, 255, 0, 1, 255, 255],
+			TextureFormat::Rgba8Uint,
+			RenderAssetUsages::all(),
+		));
+		animated_lighting_assets.add(AnimatedLighting {
+			ty: AnimatedLightingType::Lightmap,
+			output: output.clone(),
+			input,
+			styles,
+		});
+
+		let pixel = |images: &Assets<Image>, x: u32| images.get(&output).unwrap().get_color_at(x, 0).unwrap().to_linear().to_vec3();
+
+		compositor.composite(settings, 0., &animated_lighting_assets, &mut images, &animators);
+		assert!(pixel(&images, 0).abs_diff_eq(Vec3::ONE, 0.01));
+		assert!(pixel(&images, 1).abs_diff_eq(Vec3::splat(1.4), 0.01));
+
+		animators.values.insert(LightmapStyle(1), LightingAnimator::unanimated(Vec3::ZERO));
+
+		// Throttled.
+		compositor.composite(settings, 0.01, &animated_lighting_assets, &mut images, &animators);
+		assert!(pixel(&images, 1).abs_diff_eq(Vec3::splat(1.4), 0.01));
+
+		compositor.composite(settings, 1., &animated_lighting_assets, &mut images, &animators);
+		assert!(pixel(&images, 0).abs_diff_eq(Vec3::ONE, 0.01));
+		assert!(pixel(&images, 1).abs_diff_eq(Vec3::ONE, 0.01));
+	}
+}
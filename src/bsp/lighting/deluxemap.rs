@@ -0,0 +1,145 @@
+//! Applying normal maps against baked light directions.
+use bevy::{
+	pbr::{ExtendedMaterial, MaterialExtension},
+	render::render_resource::AsBindGroup,
+};
+use smart_default::SmartDefault;
+
+use crate::*;
+
+use super::AnimatedLighting;
+
+/// Holds the [`AnimatedLighting`] of a deluxemap (composited baked light directions) parallel to an entity's [`AnimatedLightingHandle`](super::AnimatedLightingHandle).
+///
+/// If the entity's [`StandardMaterial`] has a normal map, it's replaced with a [`DeluxemapMaterial`] using it.
+#[derive(Component, Reflect, Debug, Clone, Default, PartialEq, Eq, Deref, DerefMut)]
+#[reflect(Component, Default)]
+pub struct DeluxemapHandle(pub Handle<AnimatedLighting>);
+
+/// Material extension to [`StandardMaterial`] which shades normal maps against the dominant direction of baked lighting.
+///
+/// Without this, lightmaps are applied uniformly regardless of the surface's normal, making normal maps look flat.
+pub type DeluxemapMaterial = ExtendedMaterial<StandardMaterial, DeluxemapMaterialExt>;
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone, SmartDefault)]
+pub struct DeluxemapMaterialExt {
+	/// The output image of a deluxemap's [`AnimatedLighting`]. Holds light directions in tangent space, weighted by intensity.
+	#[texture(100, sample_type = "float", filterable = false)]
+	pub deluxemap: Handle<Image>,
+	/// How much the normal map affects the lightmap, 0 being not at all. (Default: 1)
+	#[uniform(101)]
+	#[default(1.)]
+	pub strength: f32,
+}
+impl MaterialExtension for DeluxemapMaterialExt {
+	fn fragment_shader() -> bevy::shader::ShaderRef {
+		"embedded://bevy_trenchbroom/bsp/lighting/deluxemap.wgsl".into()
+	}
+}
+
+/// Replaces the [`StandardMaterial`] of entities with a [`DeluxemapHandle`] with a [`DeluxemapMaterial`] if it has a normal map.
+///
+/// Converted materials are shared between entities with the same material and deluxemap, and freed once no entity uses them.
+///
+/// NOTE: Changes to the original material after this won't be reflected, including image animations.
+pub fn apply_deluxemap_materials(
+	mut commands: Commands,
+	query: Query<(Entity, &DeluxemapHandle, &MeshMaterial3d<StandardMaterial>)>,
+	animated_lighting_assets: Res<Assets<AnimatedLighting>>,
+	standard_materials: Res<Assets<StandardMaterial>>,
+	mut deluxemap_materials: ResMut<Assets<DeluxemapMaterial>>,
+	mut asset_events: MessageReader<AssetEvent<DeluxemapMaterial>>,
+	mut cache: Local<HashMap<(AssetId<StandardMaterial>, AssetId<AnimatedLighting>), AssetId<DeluxemapMaterial>>>,
+) {
+	// Only IDs are cached so that materials are freed along with the maps using them.
+	for event in asset_events.read() {
+		if let AssetEvent::Removed { id } = event {
+			cache.retain(|_, cached| cached != id);
+		}
+	}
+
+	for (entity, deluxemap_handle, material_handle) in &query {
+		let Some(deluxemap) = animated_lighting_assets.get(&deluxemap_handle.0) else { continue };
+		let Some(material) = standard_materials.get(&material_handle.0) else { continue };
+
+		if material.normal_map_texture.is_none() {
+			// Nothing to apply the deluxemap to.
+			commands.entity(entity).remove::<DeluxemapHandle>();
+			continue;
+		}
+
+		let key = (material_handle.id(), deluxemap_handle.id());
+		let handle = match cache.get(&key).and_then(|id| deluxemap_materials.get_strong_handle(*id)) {
+			Some(handle) => handle,
+			None => {
+				let handle = deluxemap_materials.add(DeluxemapMaterial {
+					base: material.clone(),
+					extension: DeluxemapMaterialExt {
+						deluxemap: deluxemap.output.clone(),
+						..default()
+					},
+				});
+				cache.insert(key, handle.id());
+				handle
+			}
+		};
+
+		commands
+			.entity(entity)
+			.remove::<MeshMaterial3d<StandardMaterial>>()
+			.insert(MeshMaterial3d(handle));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bevy::asset::AssetPlugin;
+
+	use super::*;
+	use crate::bsp::lighting::AnimatedLightingType;
+
+	#[test]
+	fn free_unused_deluxemap_materials() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+			.init_asset::<Image>()
+			.init_asset::<StandardMaterial>()
+			.init_asset::<DeluxemapMaterial>()
+			.init_asset::<AnimatedLighting>()
+			.add_systems(Update, apply_deluxemap_materials);
+
+		let world = app.world_mut();
+		let material = world.resource_mut::<Assets<StandardMaterial>>().add(StandardMaterial {
+			normal_map_texture: Some(default()),
+			..default()
+		});
+		let deluxemap = world.resource_mut::<Assets<AnimatedLighting>>().add(AnimatedLighting {
+			ty: AnimatedLightingType::Lightmap,
+			output: default(),
+			input: default(),
+			styles: default(),
+		});
+		let spawn = |world: &mut World| world.spawn((DeluxemapHandle(deluxemap.clone()), MeshMaterial3d(material.clone()))).id();
+		let entities = [spawn(world), spawn(world)];
+		app.update();
+
+		// Entities with the same material and deluxemap share the converted material.
+		let deluxemap_material = |app: &App, entity| app.world().get::<MeshMaterial3d<DeluxemapMaterial>>(entity).unwrap().id();
+		assert_eq!(deluxemap_material(&app, entities[0]), deluxemap_material(&app, entities[1]));
+		assert_eq!(app.world().resource::<Assets<DeluxemapMaterial>>().len(), 1);
+
+		// Once the map is gone, so is the material.
+		for entity in entities {
+			app.world_mut().despawn(entity);
+		}
+		app.update();
+		app.update();
+		assert_eq!(app.world().resource::<Assets<DeluxemapMaterial>>().len(), 0);
+
+		// And it's recreated if needed again.
+		let entity = spawn(app.world_mut());
+		app.update();
+		assert!(app.world().get::<MeshMaterial3d<DeluxemapMaterial>>(entity).is_some());
+		assert_eq!(app.world().resource::<Assets<DeluxemapMaterial>>().len(), 1);
+	}
+}
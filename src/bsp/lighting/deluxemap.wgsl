@@ -0,0 +1,78 @@
+#import bevy_pbr::{
+	pbr_fragment::pbr_input_from_standard_material,
+	pbr_functions::alpha_discard,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+	prepass_io::{VertexOutput, FragmentOutput},
+	pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+	forward_io::{VertexOutput, FragmentOutput},
+	pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+// Keeps grazing light from blowing up the lightmap.
+const MIN_GEOMETRIC_FACTOR: f32 = 0.1;
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(100) var deluxemap: texture_2d<f32>;
+@group(#{MATERIAL_BIND_GROUP}) @binding(101) var<uniform> strength: f32;
+
+// Rgba32Float can't be filtered on every platform, so we do it ourselves.
+fn sample_deluxemap(uv: vec2f) -> vec3f {
+	let size = vec2i(textureDimensions(deluxemap));
+	let coords = uv * vec2f(size) - 0.5;
+	let base = vec2i(floor(coords));
+	let t = fract(coords);
+
+	let a = textureLoad(deluxemap, clamp(base, vec2i(0), size - 1), 0).xyz;
+	let b = textureLoad(deluxemap, clamp(base + vec2i(1, 0), vec2i(0), size - 1), 0).xyz;
+	let c = textureLoad(deluxemap, clamp(base + vec2i(0, 1), vec2i(0), size - 1), 0).xyz;
+	let d = textureLoad(deluxemap, clamp(base + vec2i(1, 1), vec2i(0), size - 1), 0).xyz;
+
+	return mix(mix(a, b, t.x), mix(c, d, t.x), t.y);
+}
+
+@fragment
+fn fragment(
+	in: VertexOutput,
+	@builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+	var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+	pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifndef PREPASS_PIPELINE
+#ifdef LIGHTMAP
+#ifdef VERTEX_UVS_B
+#ifdef VERTEX_TANGENTS
+	let direction = sample_deluxemap(in.uv_b);
+
+	if dot(direction, direction) > 0.0001 {
+		let N = pbr_input.world_normal;
+		let T = normalize(in.world_tangent.xyz - N * dot(in.world_tangent.xyz, N));
+		let B = in.world_tangent.w * cross(N, T);
+		let L = normalize(direction.x * T + direction.y * B + direction.z * N);
+
+		let geometric = max(dot(N, L), MIN_GEOMETRIC_FACTOR);
+		let mapped = max(dot(pbr_input.N, L), 0.0);
+		pbr_input.lightmap_light *= mix(1.0, mapped / geometric, strength);
+	}
+#endif
+#endif
+#endif
+#endif
+
+#ifdef PREPASS_PIPELINE
+	let out = deferred_output(in, pbr_input);
+#else
+	var out: FragmentOutput;
+	out.color = apply_pbr_lighting(pbr_input);
+	out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+	return out;
+}
@@ -1,4 +1,12 @@
+mod animators_asset;
+mod cpu;
+mod deluxemap;
+mod switchable;
 mod types;
+pub use animators_asset::*;
+pub use cpu::*;
+pub use deluxemap::*;
+pub use switchable::*;
 pub use types::*;
 
 use bevy::{
@@ -29,16 +37,30 @@ const LIGHTMAP_WORKGROUP_SIZE: u32 = 8;
 
 /// The format used for the out channel in lighting animation. I've found that [`TextureFormat::Rgba8UnormSrgb`] and the like show noticeable banding on slower animations.
 pub(crate) const ANIMATED_LIGHTING_OUTPUT_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
-pub(crate) fn new_animated_lighting_output_image(extent: Extent3d, dimension: TextureDimension) -> Image {
-	let mut image = Image::new_fill(
-		extent,
-		dimension,
-		// Bright color -- easy to spot errors
-		[0.0_f32, 1., 0., 1.].map(|f| f.to_ne_bytes()).as_flattened(),
-		ANIMATED_LIGHTING_OUTPUT_TEXTURE_FORMAT,
-		RenderAssetUsages::RENDER_WORLD,
-	);
-	image.texture_descriptor.usage |= TextureUsages::STORAGE_BINDING;
+/// Output format when compositing on the CPU, as WebGL2 can't linearly filter [`TextureFormat::Rgba32Float`].
+pub(crate) const CPU_ANIMATED_LIGHTING_OUTPUT_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+pub(crate) fn new_animated_lighting_output_image(extent: Extent3d, dimension: TextureDimension, config: &TrenchBroomConfig) -> Image {
+	let mut image = if config.cpu_lighting_compositing.is_some() {
+		Image::new_fill(
+			extent,
+			dimension,
+			// Bright color -- easy to spot errors, as little-endian half floats
+			&[0, 0, 0, 0x3C, 0, 0, 0, 0x3C],
+			CPU_ANIMATED_LIGHTING_OUTPUT_TEXTURE_FORMAT,
+			animated_lighting_usages(config, RenderAssetUsages::RENDER_WORLD),
+		)
+	} else {
+		let mut image = Image::new_fill(
+			extent,
+			dimension,
+			// Bright color -- easy to spot errors
+			[0.0_f32, 1., 0., 1.].map(|f| f.to_ne_bytes()).as_flattened(),
+			ANIMATED_LIGHTING_OUTPUT_TEXTURE_FORMAT,
+			RenderAssetUsages::RENDER_WORLD,
+		);
+		image.texture_descriptor.usage |= TextureUsages::STORAGE_BINDING;
+		image
+	};
 	image.sampler = ImageSampler::linear();
 	image
 }
@@ -48,19 +70,44 @@ impl Plugin for BspLightingPlugin {
 	fn build(&self, app: &mut App) {
 		embedded_asset!(app, "composite_lightmaps.wgsl");
 		embedded_asset!(app, "composite_irradiance_volumes.wgsl");
+		embedded_asset!(app, "deluxemap.wgsl");
 
 		#[rustfmt::skip]
 		app
-			.add_plugins(RenderAssetPlugin::<AnimatedLighting>::default())
-
-			.add_plugins(ExtractResourcePlugin::<LightingAnimators>::default())
 			.init_resource::<LightingAnimators>()
 
+			.init_asset::<LightingAnimatorsAsset>()
+			.init_asset_loader::<LightingAnimatorsLoader>()
+			.init_resource::<LightingAnimatorFiles>()
+			.add_systems(PostUpdate, apply_lighting_animator_files.before(update_switchable_light_animators))
+
 			.init_asset::<AnimatedLighting>()
 
-			.add_systems(PreUpdate, Self::insert_animated_lightmaps)
+			.add_plugins(MaterialPlugin::<DeluxemapMaterial>::default())
+
+			.add_systems(PreUpdate, (Self::insert_animated_lightmaps, apply_deluxemap_materials))
+
+			.register_type::<SwitchableLight>()
+			.add_observer(insert_switchable_lights)
+			.add_observer(switch_lights)
+			.add_observer(use_switchable_lights)
+			.add_systems(PostUpdate, update_switchable_light_animators)
 		;
 
+		if let Some(path) = app.world().resource::<TrenchBroomServer>().config.lighting_animators.clone() {
+			let handle = app.world().resource::<AssetServer>().load(path);
+			app.world_mut().resource_mut::<LightingAnimatorFiles>().global = Some(handle);
+		}
+
+		if app.world().resource::<TrenchBroomServer>().config.cpu_lighting_compositing.is_some() {
+			app.init_resource::<CpuLightingCompositor>()
+				.add_systems(PostUpdate, composite_lighting_on_cpu.after(update_switchable_light_animators));
+			return;
+		}
+
+		app.add_plugins(RenderAssetPlugin::<AnimatedLighting>::default())
+			.add_plugins(ExtractResourcePlugin::<LightingAnimators>::default());
+
 		let render_app = app.sub_app_mut(RenderApp);
 
 		render_app.init_resource::<AnimatedLightingBindGroups>();
@@ -73,6 +120,9 @@ impl Plugin for BspLightingPlugin {
 	}
 
 	fn finish(&self, app: &mut App) {
+		if app.world().resource::<TrenchBroomServer>().config.cpu_lighting_compositing.is_some() {
+			return;
+		}
 		app.sub_app_mut(RenderApp).init_resource::<AnimatedLightingPipeline>();
 	}
 }
@@ -0,0 +1,151 @@
+//! Switchable lights, which `ericw-tools` compiles into their own light styles.
+use crate::*;
+use class::{
+	builtin::{BspLight, Targetable},
+	io::Use,
+};
+
+/// Added to [`BspLight`]s compiled into their own style (see [`BspLight::is_switchable`]). Changing [`on`](Self::on) updates the [`LightingAnimators`] for its style.
+///
+/// Lights are toggled when [used](Use) by entity IO, such as a button targeting them, and can be switched by their `targetname` with [`SwitchLights`].
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct SwitchableLight {
+	pub style: LightmapStyle,
+	pub on: bool,
+}
+
+/// Switches all [`SwitchableLight`]s with a [`Targetable::targetname`] of `targetname`.
+///
+/// # Examples
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_trenchbroom::prelude::*;
+/// # use bevy_trenchbroom::bsp::lighting::SwitchLights;
+/// fn toggle_lights(mut commands: Commands) {
+///     commands.trigger(SwitchLights::toggle("hallway_lights"));
+/// }
+/// ```
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct SwitchLights {
+	pub targetname: String,
+	/// If [`None`], toggles the lights.
+	pub on: Option<bool>,
+}
+impl SwitchLights {
+	pub fn toggle(targetname: impl Into<String>) -> Self {
+		Self {
+			targetname: targetname.into(),
+			on: None,
+		}
+	}
+
+	pub fn set(targetname: impl Into<String>, on: bool) -> Self {
+		Self {
+			targetname: targetname.into(),
+			on: Some(on),
+		}
+	}
+}
+
+/// Inserts [`SwitchableLight`] onto switchable [`BspLight`]s with a targetname.
+pub fn insert_switchable_lights(trigger: On<Add, BspLight>, mut commands: Commands, query: Query<(&BspLight, Option<&Targetable>)>) {
+	let Ok((light, targetable)) = query.get(trigger.event_target()) else { return };
+	if !light.is_switchable() || targetable.is_none_or(|targetable| targetable.targetname.is_empty()) {
+		return;
+	}
+
+	commands.entity(trigger.event_target()).insert(SwitchableLight {
+		style: light.style,
+		on: light.spawnflags & BspLight::START_OFF == 0,
+	});
+}
+
+pub fn switch_lights(trigger: On<SwitchLights>, mut query: Query<(&Targetable, &mut SwitchableLight)>) {
+	for (targetable, mut light) in &mut query {
+		if *targetable.targetname != trigger.targetname {
+			continue;
+		}
+
+		light.on = trigger.on.unwrap_or(!light.on);
+	}
+}
+
+/// Toggles [`SwitchableLight`]s when they're [used](Use), like Quake's switchable lights.
+pub fn use_switchable_lights(trigger: On<Use>, mut query: Query<&mut SwitchableLight>) {
+	let Ok(mut light) = query.get_mut(trigger.entity) else { return };
+	light.on = !light.on;
+}
+
+/// Sets the animator of each changed [`SwitchableLight`]'s style to full brightness (`m`) or off (`a`).
+pub fn update_switchable_light_animators(query: Query<&SwitchableLight, Changed<SwitchableLight>>, mut animators: ResMut<LightingAnimators>) {
+	for light in &query {
+		animators
+			.values
+			.insert(light.style, LightingAnimator::unanimated(Vec3::splat(if light.on { 1. } else { 0. })));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		class::{
+			builtin::FIRST_SWITCHABLE_LIGHT_STYLE,
+			io::{EntityIoPlugin, Fire},
+		},
+		util::{target, targetable},
+	};
+
+	#[test]
+	fn switch_targeted_lights() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin))
+			.init_resource::<LightingAnimators>()
+			.add_observer(insert_switchable_lights)
+			.add_observer(switch_lights)
+			.add_observer(use_switchable_lights)
+			.add_systems(PostUpdate, update_switchable_light_animators);
+
+		let style = LightmapStyle(FIRST_SWITCHABLE_LIGHT_STYLE);
+		let world = app.world_mut();
+		let lamp = world
+			.spawn((
+				BspLight {
+					style,
+					spawnflags: BspLight::START_OFF,
+					..default()
+				},
+				targetable("lamp"),
+			))
+			.id();
+		// Without a targetname, the light isn't switchable.
+		let unnamed = world
+			.spawn(BspLight {
+				style: LightmapStyle(FIRST_SWITCHABLE_LIGHT_STYLE + 1),
+				..default()
+			})
+			.id();
+		let button = world.spawn(target("lamp")).id();
+		world.flush();
+		assert!(!app.world().get::<SwitchableLight>(lamp).unwrap().on);
+		assert!(app.world().get::<SwitchableLight>(unnamed).is_none());
+
+		let brightness = |app: &App| app.world().resource::<LightingAnimators>().values[&style].sequence[0];
+
+		// Buttons toggle the lights they target.
+		app.world_mut().trigger(Fire::new(button));
+		app.update();
+		assert!(app.world().get::<SwitchableLight>(lamp).unwrap().on);
+		assert_eq!(brightness(&app), Vec3::ONE);
+
+		app.world_mut().trigger(SwitchLights::set("lamp", false));
+		app.update();
+		assert!(!app.world().get::<SwitchableLight>(lamp).unwrap().on);
+		assert_eq!(brightness(&app), Vec3::ZERO);
+
+		app.world_mut().trigger(SwitchLights::toggle("lamp"));
+		app.update();
+		assert!(app.world().get::<SwitchableLight>(lamp).unwrap().on);
+	}
+}
@@ -41,6 +41,42 @@ impl LightingAnimator {
 		}
 	}
 
+	/// Parses a Quake light style string like `"mmnmmommommnonmmonqnmmo"`, where each letter is a frame of brightness from `a` (0) to `z` (2.08), `m` being 1.
+	///
+	/// Frames advance at [`QUAKE_LIGHT_STYLE_FPS`] without interpolation, like in Quake. An empty string is unanimated full brightness.
+	///
+	/// # Examples
+	/// ```
+	/// # use bevy::prelude::*;
+	/// # use bevy_trenchbroom::prelude::*;
+	/// let animator = LightingAnimator::from_style_string("az").unwrap();
+	/// assert_eq!(animator.sample(0.), Vec3::ZERO);
+	/// assert_eq!(animator.sample(0.1), Vec3::splat(25. / 12.));
+	/// ```
+	pub fn from_style_string(style: &str) -> Result<Self, LightStyleParseError> {
+		if style.is_empty() {
+			return Ok(Self::unanimated(Vec3::ONE));
+		}
+		if style.len() > MAX_LIGHTMAP_FRAMES {
+			return Err(LightStyleParseError::TooLong { len: style.len() });
+		}
+
+		let mut sequence = [Vec3::ZERO; MAX_LIGHTMAP_FRAMES];
+		for (i, character) in style.chars().enumerate() {
+			if !character.is_ascii_lowercase() {
+				return Err(LightStyleParseError::InvalidCharacter { character, position: i });
+			}
+			sequence[i] = Vec3::splat((character as u8 - b'a') as f32 / (b'm' - b'a') as f32);
+		}
+
+		Ok(Self {
+			sequence,
+			sequence_len: style.len() as u32,
+			speed: QUAKE_LIGHT_STYLE_FPS,
+			interpolate: 0.,
+		})
+	}
+
 	#[inline]
 	pub fn unanimated(rgb: Vec3) -> Self {
 		Self {
@@ -246,6 +282,68 @@ impl LightingAnimators {
 	pub fn new(animators: impl Into<HashMap<LightmapStyle, LightingAnimator>>) -> Self {
 		Self { values: animators.into() }
 	}
+
+	/// The stock Quake light styles 0 through 11 from [`QUAKE_LIGHT_STYLES`].
+	///
+	/// # Examples
+	/// ```
+	/// # use bevy::prelude::*;
+	/// # use bevy_trenchbroom::prelude::*;
+	/// App::new().insert_resource(LightingAnimators::quake());
+	/// ```
+	pub fn quake() -> Self {
+		Self {
+			values: QUAKE_LIGHT_STYLES
+				.iter()
+				.enumerate()
+				.map(|(style, pattern)| {
+					(
+						LightmapStyle(style as u8),
+						LightingAnimator::from_style_string(pattern).expect("builtin light styles are valid"),
+					)
+				})
+				.collect(),
+		}
+	}
+}
+
+/// How many frames of a Quake light style string play per second.
+pub const QUAKE_LIGHT_STYLE_FPS: f32 = 10.;
+
+/// The light styles Quake's `world.qc` sets up, indexed by [`LightmapStyle`]. See [`LightingAnimators::quake`].
+pub const QUAKE_LIGHT_STYLES: [&str; 12] = [
+	// 0 normal
+	"m",
+	// 1 flicker (first variety)
+	"mmnmmommommnonmmonqnmmo",
+	// 2 slow strong pulse
+	"abcdefghijklmnopqrstuvwxyzyxwvutsrqponmlkjihgfedcba",
+	// 3 candle (first variety)
+	"mmmmmaaaaammmmmaaaaaabcdefgabcdefg",
+	// 4 fast strobe
+	"mamamamamama",
+	// 5 gentle pulse 1
+	"jklmnopqrstuvwxyzyxwvutsrqponmlkj",
+	// 6 flicker (second variety)
+	"nmonqnmomnmomomno",
+	// 7 candle (second variety)
+	"mmmaaaabcdefgmmmmaaaammmaamm",
+	// 8 candle (third variety)
+	"mmmaaammmaaammmabcdefaaaammmmabcdefmmmaaaa",
+	// 9 slow strobe (fourth variety)
+	"aaaaaaaazzzzzzzz",
+	// 10 fluorescent flicker
+	"mmamammmmammamamaaamammma",
+	// 11 slow pulse not fading to black
+	"abcdefghijklmnopqrrqponmlkjihgfedcba",
+];
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LightStyleParseError {
+	#[error("invalid character {character:?} at position {position} in light style, expected `a` through `z`")]
+	InvalidCharacter { character: char, position: usize },
+	#[error("light style has {len} frames, but the max is {MAX_LIGHTMAP_FRAMES}")]
+	TooLong { len: usize },
 }
 
 /// Contains multiple images that are composited together in `output` using the current [`TrenchBroomConfig`]'s lightmap animators. Used for both lightmaps an irradiance volumes.
@@ -290,3 +388,40 @@ pub enum AnimatedLightingType {
 	/// 3D textures, if texture differ in size they will repeat, this allows for non-directional volumes to store a 6th of the required data.
 	IrradianceVolume,
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn style_strings() {
+		// An empty style is full brightness.
+		let animator = LightingAnimator::from_style_string("").unwrap();
+		assert_eq!((animator.sequence_len, animator.sample(0.)), (1, Vec3::ONE));
+
+		let animator = LightingAnimator::from_style_string("amz").unwrap();
+		assert_eq!(animator.sequence_len, 3);
+		assert_eq!(animator.sequence[1], Vec3::ONE);
+
+		assert_eq!(
+			LightingAnimator::from_style_string("maM").map(|_| ()),
+			Err(LightStyleParseError::InvalidCharacter { character: 'M', position: 2 })
+		);
+		assert_eq!(
+			LightingAnimator::from_style_string("m5").map(|_| ()),
+			Err(LightStyleParseError::InvalidCharacter { character: '5', position: 1 })
+		);
+		assert_eq!(
+			LightingAnimator::from_style_string("mé").map(|_| ()),
+			Err(LightStyleParseError::InvalidCharacter {
+				character: 'é', position: 1
+			})
+		);
+		assert_eq!(
+			LightingAnimator::from_style_string(&"m".repeat(MAX_LIGHTMAP_FRAMES + 1)).map(|_| ()),
+			Err(LightStyleParseError::TooLong {
+				len: MAX_LIGHTMAP_FRAMES + 1
+			})
+		);
+	}
+}
@@ -6,136 +6,197 @@ use bevy::{
 	render::render_resource::{Extent3d, TextureDimension, TextureFormat},
 };
 use bsp::*;
-use lighting::{AnimatedLightingHandle, AnimatedLightingType, new_animated_lighting_output_image};
+use lighting::{AnimatedLightingHandle, AnimatedLightingType, animated_lighting_usages, new_animated_lighting_output_image};
 use ndshape::{RuntimeShape, Shape};
-use qbsp::data::bspx::LightGridCell;
+use qbsp::data::bspx::{LightGridCell, LightGridOctree};
 
-pub fn load_irradiance_volume(ctx: &mut BspLoadCtx, world: &mut World) -> anyhow::Result<Option<Handle<AnimatedLighting>>> {
+/// Max size of an irradiance volume tile on any axis. The z axis of the volume's texture is 3x its size, and textures can be at most 2048 in size.
+pub const MAX_IRRADIANCE_VOLUME_TILE_SIZE: u32 = 2048 / 3;
+
+pub fn load_irradiance_volumes(ctx: &mut BspLoadCtx, world: &mut World) -> anyhow::Result<Vec<Handle<AnimatedLighting>>> {
 	let config = &ctx.loader.tb_server.config;
 
 	if config.no_bsp_lighting {
-		return Ok(None);
+		return Ok(Vec::new());
 	}
 
-	// Calculate irradiance volumes for light grids.
-	// Right now we just have one big irradiance volume for the entire map, this means the volume has to be less than 682 (2048/3 (z axis is 3x)) cells in size.
-	Ok(if let Some(light_grid) = &ctx.data.bspx.light_grid_octree {
-		let grid_mins = config.to_bevy_space(light_grid.mins);
-		// We add 1 to the size because the volume has to be offset by half a step to line up, and as such sometimes doesn't fill the full space
-		let grid_size = light_grid.size.yzx() + 1;
-		let grid_step = config.to_bevy_space(light_grid.step);
+	// Calculate irradiance volumes for light grids, split into tiles so that large maps don't go over the max texture size.
+	let data = ctx.data;
+	let Some(light_grid) = &data.bspx.light_grid_octree else { return Ok(Vec::new()) };
 
-		let mut input_builders: [Option<IrradianceVolumeBuilder>; 4] = [(); 4].map(|_| None);
+	let tile_size = config.irradiance_volume_tile_size;
 
-		let new_builder = || IrradianceVolumeBuilder::new(grid_size, [0, 0, 0, 255], config.irradiance_volume_multipliers);
+	Ok(light_grid_tiles(light_grid, tile_size)
+		.into_iter()
+		.enumerate()
+		.map(|(tile_idx, (tile_min, tile_max))| load_irradiance_volume_tile(ctx, world, light_grid, tile_idx, tile_min, tile_max))
+		.collect())
+}
 
-		let mut style_map_builder = IrradianceVolumeBuilder::new(grid_size, [255; 4], IrradianceVolumeMultipliers::IDENTITY);
+/// Splits the light grid into tiles of `tile_size` cells (clamped so it fits in [`MAX_IRRADIANCE_VOLUME_TILE_SIZE`]), returning the min (inclusive) and max (exclusive) cell of each in Quake axis order.
+///
+/// Tiles overlap by a cell on each side so that interpolation lines up at the seams, and tiles without any light grid leafs are skipped.
+fn light_grid_tiles(light_grid: &LightGridOctree, tile_size: u32) -> Vec<(UVec3, UVec3)> {
+	// We add 1 to the size because the volume has to be offset by half a step to line up, and as such sometimes doesn't fill the full space.
+	let grid_size = light_grid.size + 1;
+	let tile_size = tile_size.clamp(1, MAX_IRRADIANCE_VOLUME_TILE_SIZE - 2);
+	let tile_counts = (grid_size + tile_size - 1) / tile_size;
+
+	let mut tiles = Vec::new();
+
+	for tile_x in 0..tile_counts.x {
+		for tile_y in 0..tile_counts.y {
+			for tile_z in 0..tile_counts.z {
+				let core_min = uvec3(tile_x, tile_y, tile_z) * tile_size;
+				let core_max = (core_min + tile_size).min(grid_size);
+
+				if !light_grid
+					.leafs
+					.iter()
+					.any(|leaf| leaf.mins.cmplt(core_max).all() && (leaf.mins + leaf.size()).cmpgt(core_min).all())
+				{
+					continue;
+				}
 
-		for leaf in &light_grid.leafs {
-			let leaf_mins = leaf.mins.yzx();
-			let leaf_size = leaf.size().yzx();
-
-			for x in 0..leaf_size.x {
-				for y in 0..leaf_size.y {
-					for z in 0..leaf_size.z {
-						let LightGridCell::Filled(samples) = leaf.get_cell(z, x, y) else { continue };
-						let dest = uvec3(x + leaf_mins.x, y + leaf_mins.y, z + leaf_mins.z);
-						let mut style_map: [u8; 4] = [255; 4];
-
-						for (slot_idx, sample) in samples.into_iter().enumerate() {
-							if slot_idx >= 4 {
-								error!(
-									"Light grid cell at {} has more than 4 samples! Data past sample 4 will be thrown away!",
-									leaf_mins + uvec3(x, y, z)
-								);
-								break;
-							}
+				tiles.push((core_min.saturating_sub(UVec3::ONE), (core_max + 1).min(grid_size)));
+			}
+		}
+	}
 
-							let [r, g, b] = sample.color;
+	tiles
+}
+
+/// Creates the irradiance volume for the light grid cells from `tile_min` (inclusive) to `tile_max` (exclusive) in Quake axis order.
+fn load_irradiance_volume_tile(
+	ctx: &mut BspLoadCtx,
+	world: &mut World,
+	light_grid: &LightGridOctree,
+	tile_idx: usize,
+	tile_min: UVec3,
+	tile_max: UVec3,
+) -> Handle<AnimatedLighting> {
+	let config = &ctx.loader.tb_server.config;
+
+	let grid_mins = config.to_bevy_space(light_grid.mins + tile_min.as_vec3() * light_grid.step);
+	let grid_size = (tile_max - tile_min).yzx();
+	let grid_step = config.to_bevy_space(light_grid.step);
+
+	let mut input_builders: [Option<IrradianceVolumeBuilder>; 4] = [(); 4].map(|_| None);
+
+	let new_builder = || IrradianceVolumeBuilder::new(grid_size, [0, 0, 0, 255], config.irradiance_volume_multipliers);
+
+	let mut style_map_builder = IrradianceVolumeBuilder::new(grid_size, [255; 4], IrradianceVolumeMultipliers::IDENTITY);
+
+	for leaf in &light_grid.leafs {
+		// The part of the leaf inside this tile.
+		let min = leaf.mins.max(tile_min);
+		let max = (leaf.mins + leaf.size()).min(tile_max);
+		if min.cmpge(max).any() {
+			continue;
+		}
 
-							input_builders[slot_idx].get_or_insert_with(new_builder).put_all(dest, [r, g, b, 255]);
-							style_map[slot_idx] = sample.style.0;
+		for x in min.x..max.x {
+			for y in min.y..max.y {
+				for z in min.z..max.z {
+					let LightGridCell::Filled(samples) = leaf.get_cell(x - leaf.mins.x, y - leaf.mins.y, z - leaf.mins.z) else { continue };
+					let dest = (uvec3(x, y, z) - tile_min).yzx();
+					let mut style_map: [u8; 4] = [255; 4];
+
+					for (slot_idx, sample) in samples.into_iter().enumerate() {
+						if slot_idx >= 4 {
+							error!(
+								"Light grid cell at {} has more than 4 samples! Data past sample 4 will be thrown away!",
+								uvec3(x, y, z)
+							);
+							break;
 						}
 
-						style_map_builder.put_all(dest, style_map);
+						let [r, g, b] = sample.color;
+
+						input_builders[slot_idx].get_or_insert_with(new_builder).put_all(dest, [r, g, b, 255]);
+						style_map[slot_idx] = sample.style.0;
 					}
+
+					style_map_builder.put_all(dest, style_map);
 				}
 			}
 		}
+	}
 
-		// This is pretty much instructed by FTE docs
-		flood_non_filled(&mut input_builders, &mut style_map_builder, &new_builder);
-
-		let full_size = IrradianceVolumeBuilder::full_size(grid_size);
-
-		let mut slot_idx = 0;
-		let input = input_builders.map(|builder| {
-			let mut image = builder.map(IrradianceVolumeBuilder::build).unwrap_or_else(|| {
-				Image::new_fill(
-					Extent3d {
-						width: 1,
-						height: 1,
-						depth_or_array_layers: 1,
-					},
-					TextureDimension::D3,
-					&[0; 4],
-					TextureFormat::Rgba8UnormSrgb,
-					RenderAssetUsages::RENDER_WORLD,
-				)
-			});
-			image.sampler = ImageSampler::linear();
-
-			let handle = ctx.load_context.add_labeled_asset(format!("IrradianceVolumeSlot{slot_idx}"), image);
-			slot_idx += 1;
-			handle
-		});
+	// This is pretty much instructed by FTE docs
+	flood_non_filled(&mut input_builders, &mut style_map_builder, &new_builder);
+
+	let full_size = IrradianceVolumeBuilder::full_size(grid_size);
 
-		let output = ctx.load_context.add_labeled_asset(
-			"IrradianceVolume",
-			new_animated_lighting_output_image(
+	let mut slot_idx = 0;
+	let input = input_builders.map(|builder| {
+		let mut image = builder.map(IrradianceVolumeBuilder::build).unwrap_or_else(|| {
+			Image::new_fill(
 				Extent3d {
-					width: full_size.x,
-					height: full_size.y,
-					depth_or_array_layers: full_size.z,
+					width: 1,
+					height: 1,
+					depth_or_array_layers: 1,
 				},
 				TextureDimension::D3,
-			),
-		);
-
-		let mut style_map_image = style_map_builder.build();
-		style_map_image.texture_descriptor.format = TextureFormat::Rgba8Uint;
+				&[0; 4],
+				TextureFormat::Rgba8UnormSrgb,
+				RenderAssetUsages::RENDER_WORLD,
+			)
+		});
+		image.sampler = ImageSampler::linear();
+		image.asset_usage = animated_lighting_usages(config, image.asset_usage);
 
-		let styles = ctx
+		let handle = ctx
 			.load_context
-			.add_labeled_asset("IrradianceVolumeStyleMap".to_string(), style_map_image);
-
-		let animated_lighting_handle = ctx.load_context.add_labeled_asset(
-			"IrradianceVolumeAnimator",
-			AnimatedLighting {
-				ty: AnimatedLightingType::IrradianceVolume,
-				output,
-				input,
-				styles,
-			},
-		);
-
-		let scale: Vec3 = grid_size.as_vec3() * grid_step;
-
-		world.spawn((
-			Name::new("Light Grid Irradiance Volume"),
-			// LightProbe::new(), // TODO: remove?
-			AnimatedLightingHandle(animated_lighting_handle.clone()),
-			Transform {
-				translation: grid_mins + scale / 2. - Vec3::from_array(grid_step.to_array()) / 2.,
-				scale,
-				..default()
+			.add_labeled_asset(format!("IrradianceVolume{tile_idx}Slot{slot_idx}"), image);
+		slot_idx += 1;
+		handle
+	});
+
+	let output = ctx.load_context.add_labeled_asset(
+		format!("IrradianceVolume{tile_idx}"),
+		new_animated_lighting_output_image(
+			Extent3d {
+				width: full_size.x,
+				height: full_size.y,
+				depth_or_array_layers: full_size.z,
 			},
-		));
-
-		Some(animated_lighting_handle)
-	} else {
-		None
-	})
+			TextureDimension::D3,
+			config,
+		),
+	);
+
+	let mut style_map_image = style_map_builder.build();
+	style_map_image.texture_descriptor.format = TextureFormat::Rgba8Uint;
+	style_map_image.asset_usage = animated_lighting_usages(config, style_map_image.asset_usage);
+
+	let styles = ctx
+		.load_context
+		.add_labeled_asset(format!("IrradianceVolume{tile_idx}StyleMap"), style_map_image);
+
+	let animated_lighting_handle = ctx.load_context.add_labeled_asset(
+		format!("IrradianceVolume{tile_idx}Animator"),
+		AnimatedLighting {
+			ty: AnimatedLightingType::IrradianceVolume,
+			output,
+			input,
+			styles,
+		},
+	);
+
+	let scale: Vec3 = grid_size.as_vec3() * grid_step;
+
+	world.spawn((
+		Name::new(format!("Light Grid Irradiance Volume {tile_idx}")),
+		AnimatedLightingHandle(animated_lighting_handle.clone()),
+		Transform {
+			translation: grid_mins + scale / 2. - Vec3::from_array(grid_step.to_array()) / 2.,
+			scale,
+			..default()
+		},
+	));
+
+	animated_lighting_handle
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -379,3 +440,36 @@ impl IrradianceVolumeDirection {
 	pub const NEG_Y: Self = Self(uvec3(0, 1, 1));
 	pub const NEG_Z: Self = Self(uvec3(0, 1, 2));
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn light_grid_tiles_cover_leafs() {
+		let bytes = fs::read("assets/maps/example.bsp").unwrap();
+		let data = BspData::parse(BspParseInput {
+			bsp: &bytes,
+			lit: None,
+			settings: default(),
+		})
+		.unwrap();
+		let light_grid = data.bspx.light_grid_octree.as_ref().unwrap();
+
+		let tiles = light_grid_tiles(light_grid, 4);
+		assert!(tiles.len() > 1);
+
+		for (min, max) in &tiles {
+			assert!(min.cmplt(*max).all());
+			assert!((*max - *min).cmple(UVec3::splat(4 + 2)).all());
+			assert!(max.cmple(light_grid.size + 1).all());
+		}
+
+		for leaf in &light_grid.leafs {
+			for corner in [leaf.mins, leaf.mins + leaf.size() - 1] {
+				assert!(tiles.iter().any(|(min, max)| corner.cmpge(*min).all() && corner.cmplt(*max).all()));
+			}
+		}
+	}
+}
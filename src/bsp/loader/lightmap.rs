@@ -7,110 +7,482 @@ use bevy::{
 	render::render_resource::{Extent3d, TextureDimension, TextureFormat},
 };
 use bsp::*;
-use lighting::{AnimatedLightingType, new_animated_lighting_output_image};
-use qbsp::mesh::lightmap::ComputeLightmapAtlasError;
+use lighting::{AnimatedLightingType, animated_lighting_usages, new_animated_lighting_output_image};
+use qbsp::{
+	data::lighting::{BspLighting, RgbLighting, read_lit},
+	data::texture::BspTexFlags,
+	mesh::lightmap::{ComputeLightmapAtlasError, LightmapPacker, LightmapPackerFaceView, PerSlotLightmapData},
+	reader::BspParseContext,
+	util::Rect,
+};
 
 /// Writes lightmaps to target/lightmaps folder
 const WRITE_DEBUG_FILES: bool = true;
 
+/// Lightmap atlas pages of a BSP, along with where each face's lightmap is.
 pub struct BspLightmap {
-	pub animated_lighting: Handle<AnimatedLighting>,
+	/// One for each atlas page, faces are packed into as many pages as needed to stay within the max size of [`TrenchBroomConfig::compute_lightmap_settings`].
+	pub pages: Vec<BspLightmapPage>,
+	/// Face UVs, normalized to the size of the page each face is on.
 	pub uv_map: LightmapUvMap,
+	/// Maps face indexes to the index of the page in [`pages`](Self::pages) their lightmap is on.
+	pub face_pages: HashMap<u32, usize>,
+}
+
+pub struct BspLightmapPage {
+	pub animated_lighting: Handle<AnimatedLighting>,
+	/// Composites light directions in parallel to [`animated_lighting`](Self::animated_lighting) if the BSP has deluxemaps.
+	pub deluxemap: Option<Handle<AnimatedLighting>>,
 }
 
 impl BspLightmap {
-	pub fn compute(ctx: &mut BspLoadCtx) -> anyhow::Result<Option<Self>> {
+	/// Packs deluxemap data (baked light directions) into atlas pages with the exact same layout [`compute`](Self::compute) will produce.
+	///
+	/// Deluxemaps are read from `lux` (the `.lux` file next to the BSP) if present, otherwise from the `LIGHTINGDIR` BSPX lump.
+	pub fn compute_deluxe_atlas(
+		data: &mut BspData,
+		lux: Option<&[u8]>,
+		config: &TrenchBroomConfig,
+	) -> anyhow::Result<Option<Vec<PerSlotLightmapData>>> {
+		if config.no_bsp_lighting || !config.load_deluxemaps {
+			return Ok(None);
+		}
+		let Some(lighting) = &data.lighting else { return Ok(None) };
+		let Some(directions) = read_deluxemap_directions(lux, data.bspx.get_unparsed("LIGHTINGDIR"), lighting, &data.parse_ctx)? else {
+			return Ok(None);
+		};
+
+		// Pointing straight out of the surface.
+		const NEUTRAL_DIRECTION: [u8; 3] = [128, 128, 255];
+
+		// Deluxemaps are laid out exactly the same as colored lighting, so we temporarily swap them in to pack them identically.
+		let lighting = data.lighting.replace(BspLighting::Colored(directions));
+		let atlas = PagedLightmapAtlas::compute(
+			data,
+			ComputeLightmapSettings {
+				no_lighting_color: NEUTRAL_DIRECTION,
+				special_lighting_color: NEUTRAL_DIRECTION,
+				..config.compute_lightmap_settings
+			},
+		);
+		data.lighting = lighting;
+
+		match atlas {
+			Ok(atlas) => Ok(Some(atlas.pages)),
+			Err(ComputeLightmapAtlasError::NoLightmaps) => Ok(None),
+			Err(err) => Err(anyhow::anyhow!(err)),
+		}
+	}
+
+	pub fn compute(ctx: &mut BspLoadCtx, deluxe_atlas: Option<Vec<PerSlotLightmapData>>) -> anyhow::Result<Option<Self>> {
 		let config = &ctx.loader.tb_server.config;
 
 		if config.no_bsp_lighting {
 			return Ok(None);
 		}
 
-		match ctx
-			.data
-			.compute_lightmap_atlas(PerSlotLightmapPacker::new(config.compute_lightmap_settings))
+		let atlas = match PagedLightmapAtlas::compute(ctx.data, config.compute_lightmap_settings) {
+			Ok(atlas) => atlas,
+			Err(ComputeLightmapAtlasError::NoLightmaps) => return Ok(None),
+			Err(err) => return Err(anyhow::anyhow!(err)),
+		};
+
+		let deluxe_pages = match deluxe_atlas {
+			Some(deluxe_pages)
+				if deluxe_pages.len() == atlas.pages.len()
+					&& deluxe_pages.iter().zip(&atlas.pages).all(|(deluxe, page)| deluxe.size() == page.size()) =>
+			{
+				deluxe_pages.into_iter().map(Some).collect()
+			}
+			Some(_) => {
+				error!("Deluxemap atlas is laid out differently than the lightmap atlas, ignoring deluxemaps");
+				Vec::new()
+			}
+			None => Vec::new(),
+		};
+
+		if WRITE_DEBUG_FILES {
+			fs::create_dir("target/lightmaps").ok();
+		}
+
+		let mut pages = Vec::with_capacity(atlas.pages.len());
+
+		for (page_idx, (page, deluxe_page)) in atlas
+			.pages
+			.into_iter()
+			.zip(deluxe_pages.into_iter().chain(std::iter::repeat_with(|| None)))
+			.enumerate()
 		{
-			Ok(atlas) => {
-				let size = atlas.data.size();
-
-				if WRITE_DEBUG_FILES {
-					fs::create_dir("target/lightmaps").ok();
-					for (i, image) in atlas.data.slots.iter().enumerate() {
-						image.save_with_format(format!("target/lightmaps/{i}.png"), image::ImageFormat::Png).ok();
-					}
-					atlas
-						.data
-						.styles
-						.save_with_format("target/lightmaps/styles.png", image::ImageFormat::Png)
+			let size = page.size();
+
+			if WRITE_DEBUG_FILES {
+				for (i, image) in page.slots.iter().enumerate() {
+					image
+						.save_with_format(format!("target/lightmaps/{page_idx}_{i}.png"), image::ImageFormat::Png)
 						.ok();
 				}
+				page.styles
+					.save_with_format(format!("target/lightmaps/{page_idx}_styles.png"), image::ImageFormat::Png)
+					.ok();
+			}
 
-				let output = ctx.load_context.add_labeled_asset(
-					"LightmapOutput".to_string(),
-					new_animated_lighting_output_image(
-						Extent3d {
-							width: size.x,
-							height: size.y,
-							..default()
-						},
-						TextureDimension::D2,
-					),
-				);
+			let output = ctx.load_context.add_labeled_asset(
+				format!("Lightmap{page_idx}Output"),
+				new_animated_lighting_output_image(
+					Extent3d {
+						width: size.x,
+						height: size.y,
+						..default()
+					},
+					TextureDimension::D2,
+					config,
+				),
+			);
 
-				let mut i = 0;
-				let input = atlas.data.slots.map(|image| {
-					let handle = ctx.load_context.add_labeled_asset(
-						format!("LightmapInput{i}"),
-						Image::new(
-							Extent3d {
-								width: image.width(),
-								height: image.height(),
-								..default()
-							},
-							TextureDimension::D2,
-							image.pixels().flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]).collect(),
-							// Without Srgb all the colors are washed out, so i'm guessing ericw-tools outputs sRGB, though i can't find it documented anywhere.
-							TextureFormat::Rgba8UnormSrgb,
-							config.bsp_textures_asset_usages,
-						),
-					);
-
-					i += 1;
-					handle
-				});
-
-				let styles = ctx.load_context.add_labeled_asset(
-					"LightmapStyles",
+			let mut i = 0;
+			let input = page.slots.each_ref().map(|image| {
+				let handle = ctx.load_context.add_labeled_asset(
+					format!("Lightmap{page_idx}Input{i}"),
 					Image::new(
 						Extent3d {
-							width: size.x,
-							height: size.y,
-							depth_or_array_layers: 1,
+							width: image.width(),
+							height: image.height(),
+							..default()
 						},
 						TextureDimension::D2,
-						atlas.data.styles.into_vec(),
-						TextureFormat::Rgba8Uint,
-						RenderAssetUsages::RENDER_WORLD,
+						image.pixels().flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]).collect(),
+						// Without Srgb all the colors are washed out, so i'm guessing ericw-tools outputs sRGB, though i can't find it documented anywhere.
+						TextureFormat::Rgba8UnormSrgb,
+						animated_lighting_usages(config, config.bsp_textures_asset_usages),
 					),
 				);
 
-				let handle = ctx.load_context.add_labeled_asset(
-					"LightmapAnimator",
-					AnimatedLighting {
-						ty: AnimatedLightingType::Lightmap,
-						output,
-						input,
-						styles,
+				i += 1;
+				handle
+			});
+
+			let styles = ctx.load_context.add_labeled_asset(
+				format!("Lightmap{page_idx}Styles"),
+				Image::new(
+					Extent3d {
+						width: size.x,
+						height: size.y,
+						depth_or_array_layers: 1,
 					},
-				);
+					TextureDimension::D2,
+					page.styles.into_vec(),
+					TextureFormat::Rgba8Uint,
+					animated_lighting_usages(config, RenderAssetUsages::RENDER_WORLD),
+				),
+			);
+
+			let deluxemap = deluxe_page.map(|deluxe_page| Self::composite_deluxemap(ctx, page_idx, deluxe_page, &page.slots, styles.clone()));
+
+			let animated_lighting = ctx.load_context.add_labeled_asset(
+				format!("Lightmap{page_idx}Animator"),
+				AnimatedLighting {
+					ty: AnimatedLightingType::Lightmap,
+					output,
+					input,
+					styles,
+				},
+			);
+
+			pages.push(BspLightmapPage {
+				animated_lighting,
+				deluxemap,
+			});
+		}
+
+		Ok(Some(Self {
+			pages,
+			uv_map: atlas.uvs,
+			face_pages: atlas.face_pages,
+		}))
+	}
+
+	/// The page of the face at `face_idx`, if it has one.
+	pub fn face_page(&self, face_idx: u32) -> Option<&BspLightmapPage> {
+		self.face_pages.get(&face_idx).and_then(|page_idx| self.pages.get(*page_idx))
+	}
+
+	/// Creates the [`AnimatedLighting`] for a deluxemap atlas, which composites light directions weighted by the light intensity of each style.
+	/// The output's RGB holds the dominant light direction in the surface's tangent space, which isn't normalized.
+	fn composite_deluxemap(
+		ctx: &mut BspLoadCtx,
+		page_idx: usize,
+		deluxe_atlas: PerSlotLightmapData,
+		lightmap_slots: &[image::RgbImage; 4],
+		styles: Handle<Image>,
+	) -> Handle<AnimatedLighting> {
+		let config = &ctx.loader.tb_server.config;
+		let size = deluxe_atlas.size();
 
-				Ok(Some(Self {
-					animated_lighting: handle,
-					uv_map: atlas.uvs,
-				}))
+		let output = ctx.load_context.add_labeled_asset(
+			format!("Deluxemap{page_idx}Output"),
+			new_animated_lighting_output_image(
+				Extent3d {
+					width: size.x,
+					height: size.y,
+					..default()
+				},
+				TextureDimension::D2,
+				config,
+			),
+		);
+
+		let input = std::array::from_fn(|i| {
+			let data = deluxe_atlas.slots[i]
+				.pixels()
+				.zip(lightmap_slots[i].pixels())
+				.flat_map(|(direction, light)| {
+					let direction = Vec3::from_array(direction.0.map(|v| v as f32 / 128. - 1.));
+					let intensity = Vec3::from_array(light.0.map(|v| v as f32 / 255.)).dot(vec3(0.2126, 0.7152, 0.0722));
+					let [x, y, z] = (direction * intensity * 127.).round().to_array().map(|v| v as i8 as u8);
+					[x, y, z, 0]
+				})
+				.collect();
+
+			ctx.load_context.add_labeled_asset(
+				format!("Deluxemap{page_idx}Input{i}"),
+				Image::new(
+					Extent3d {
+						width: size.x,
+						height: size.y,
+						..default()
+					},
+					TextureDimension::D2,
+					data,
+					TextureFormat::Rgba8Snorm,
+					animated_lighting_usages(config, config.bsp_textures_asset_usages),
+				),
+			)
+		});
+
+		ctx.load_context.add_labeled_asset(
+			format!("Deluxemap{page_idx}Animator"),
+			AnimatedLighting {
+				ty: AnimatedLightingType::Lightmap,
+				output,
+				input,
+				styles,
+			},
+		)
+	}
+}
+
+/// Lightmap atlas split into as many pages as needed to fit within the max size of a [`ComputeLightmapSettings`].
+pub(crate) struct PagedLightmapAtlas {
+	pub pages: Vec<PerSlotLightmapData>,
+	/// Face UVs, normalized to the size of the page each face is on.
+	pub uvs: LightmapUvMap,
+	/// Maps face indexes to the page their lightmap is on.
+	pub face_pages: HashMap<u32, usize>,
+}
+impl PagedLightmapAtlas {
+	pub fn compute(data: &BspData, settings: ComputeLightmapSettings) -> Result<Self, ComputeLightmapAtlasError> {
+		let mut atlas = data.compute_lightmap_atlas(PagedLightmapPacker {
+			settings,
+			pages: Vec::new(),
+			face_pages: HashMap::default(),
+			reserved_pages: [None; 2],
+		})?;
+		let PagedLightmapData {
+			pages,
+			mut face_pages,
+			reserved_pages,
+		} = atlas.data;
+		let atlas_size = pages.iter().map(PerSlotLightmapData::size).fold(UVec2::ONE, UVec2::max).as_vec2();
+
+		for (face_idx, uvs) in &mut atlas.uvs {
+			// Faces without lightmaps share a single reserved pixel, which is only packed once.
+			let page_idx = *face_pages.entry(*face_idx).or_insert_with(|| {
+				let face = &data.faces[*face_idx as usize];
+				reserved_pages[is_special_face(data, face) as usize].unwrap_or_default()
+			});
+
+			// `compute_lightmap_atlas` normalizes to the largest page's size, rather than the face's page.
+			let page_size = pages[page_idx].size().max(UVec2::ONE).as_vec2();
+			for uv in uvs {
+				*uv = *uv * atlas_size / page_size;
 			}
-			Err(ComputeLightmapAtlasError::NoLightmaps) => Ok(None),
-			Err(err) => Err(anyhow::anyhow!(err)),
+		}
+
+		Ok(Self {
+			pages,
+			uvs: atlas.uvs,
+			face_pages,
+		})
+	}
+}
+
+fn is_special_face(data: &BspData, face: &qbsp::data::models::BspFace) -> bool {
+	data.tex_info[face.texture_info_idx.0 as usize].flags.texture_flags.unwrap_or_default() != BspTexFlags::Normal
+}
+
+/// Packs lightmaps with [`PerSlotLightmapPacker`]s, starting a new page whenever one fills up.
+struct PagedLightmapPacker {
+	settings: ComputeLightmapSettings,
+	pages: Vec<PerSlotLightmapPacker>,
+	face_pages: HashMap<u32, usize>,
+	/// The pages of the reserved pixels for faces without lightmaps, indexed by whether the face is special.
+	reserved_pages: [Option<usize>; 2],
+}
+
+struct PagedLightmapData {
+	pages: Vec<PerSlotLightmapData>,
+	face_pages: HashMap<u32, usize>,
+	reserved_pages: [Option<usize>; 2],
+}
+impl LightmapAtlas for PagedLightmapData {
+	fn size(&self) -> UVec2 {
+		self.pages.iter().map(PerSlotLightmapData::size).fold(UVec2::ONE, UVec2::max)
+	}
+}
+
+impl LightmapPacker for PagedLightmapPacker {
+	type Input = <PerSlotLightmapPacker as LightmapPacker>::Input;
+	type Output = PagedLightmapData;
+
+	fn create_single_color_input(size: impl Into<UVec2>, color: [u8; 3]) -> Self::Input {
+		PerSlotLightmapPacker::create_single_color_input(size, color)
+	}
+
+	fn read_from_face(&self, view: LightmapPackerFaceView) -> Self::Input {
+		// Reading doesn't depend on the packer's state, so a fresh one works if we don't have a page yet.
+		match self.pages.last() {
+			Some(page) => page.read_from_face(view),
+			None => PerSlotLightmapPacker::new(self.settings).read_from_face(view),
+		}
+	}
+
+	fn settings(&self) -> ComputeLightmapSettings {
+		self.settings
+	}
+
+	fn pack(&mut self, view: LightmapPackerFaceView, images: Self::Input) -> Result<Rect<UVec2>, ComputeLightmapAtlasError> {
+		let fitted = match self.pages.last_mut() {
+			Some(page) => page.pack(view, images.clone()).ok(),
+			None => None,
+		};
+
+		let rect = match fitted {
+			Some(rect) => rect,
+			None => {
+				let mut page = PerSlotLightmapPacker::new(self.settings);
+				// If it doesn't fit on an empty page, it never will.
+				let rect = page.pack(view, images)?;
+				self.pages.push(page);
+				rect
+			}
+		};
+
+		let page_idx = self.pages.len() - 1;
+		self.face_pages.insert(view.face_idx as u32, page_idx);
+
+		if view.lm_info.lightmap_offset.is_negative() || view.lm_info.extents.lightmap_size() == UVec2::ZERO {
+			self.reserved_pages[is_special_face(view.bsp, view.face) as usize] = Some(page_idx);
+		}
+
+		Ok(rect)
+	}
+
+	fn export(&self) -> Self::Output {
+		PagedLightmapData {
+			pages: self.pages.iter().map(LightmapPacker::export).collect(),
+			face_pages: self.face_pages.clone(),
+			reserved_pages: self.reserved_pages,
+		}
+	}
+}
+
+/// Reads deluxemap directions from `lux` (a `.lux` file) if present, otherwise from `lightingdir` (the `LIGHTINGDIR` BSPX lump).
+///
+/// Returns [`None`] if neither is present, and an error if the directions don't line up with each pixel of `lighting`.
+fn read_deluxemap_directions(
+	lux: Option<&[u8]>,
+	lightingdir: Option<&[u8]>,
+	lighting: &BspLighting,
+	ctx: &BspParseContext,
+) -> anyhow::Result<Option<RgbLighting>> {
+	let (directions, source) = match (lux, lightingdir) {
+		(Some(lux), _) => (read_lit(lux, ctx, false), ".lux file"),
+		(None, Some(lump)) => (read_lit(lump, ctx, true), "LIGHTINGDIR BSPX lump"),
+		(None, None) => return Ok(None),
+	};
+	let directions = directions.map_err(|err| anyhow!("Parsing {source}: {err}"))?;
+
+	let pixels = match lighting {
+		BspLighting::Grayscale(lighting) => lighting.len(),
+		BspLighting::Colored(lighting) => lighting.len(),
+	};
+	if directions.len() != pixels {
+		return Err(anyhow!(
+			"{source} has {} light directions, but the BSP has {pixels} lightmap pixels",
+			directions.len()
+		));
+	}
+
+	Ok(Some(directions))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use qbsp::BspFormat;
+
+	#[test]
+	fn deluxemap_directions() {
+		let ctx = BspParseContext { format: BspFormat::BSP29 };
+		let lighting = BspLighting::Grayscale(vec![0; 2]);
+		let directions = [128, 128, 255, 255, 128, 128];
+		let lux = |directions: &[u8]| [b"QLIT".as_slice(), &1_i32.to_le_bytes(), directions].concat();
+
+		assert!(read_deluxemap_directions(None, None, &lighting, &ctx).unwrap().is_none());
+		assert_eq!(
+			read_deluxemap_directions(None, Some(&directions), &lighting, &ctx).unwrap().unwrap(),
+			[[128, 128, 255], [255, 128, 128]]
+		);
+		// The `.lux` file takes priority over the BSPX lump.
+		assert_eq!(
+			read_deluxemap_directions(Some(&lux(&[0; 6])), Some(&directions), &lighting, &ctx)
+				.unwrap()
+				.unwrap(),
+			[[0; 3]; 2]
+		);
+
+		// Wrong sizes and headers.
+		assert!(read_deluxemap_directions(None, Some(&directions[..3]), &lighting, &ctx).is_err());
+		assert!(read_deluxemap_directions(None, Some(&directions[..4]), &lighting, &ctx).is_err());
+		assert!(read_deluxemap_directions(Some(&lux(&[0; 9])), None, &lighting, &ctx).is_err());
+		assert!(read_deluxemap_directions(Some(&directions), None, &lighting, &ctx).is_err());
+		assert!(read_deluxemap_directions(Some(b"QLIT"), None, &lighting, &ctx).is_err());
+	}
+
+	#[test]
+	fn lightmap_pages() {
+		let bytes = fs::read("assets/maps/example.bsp").unwrap();
+		let data = BspData::parse(BspParseInput {
+			bsp: &bytes,
+			lit: None,
+			settings: default(),
+		})
+		.unwrap();
+
+		let settings = ComputeLightmapSettings {
+			max_width: 64,
+			max_height: 64,
+			..TrenchBroomConfig::default_compute_lightmap_settings()
+		};
+		let atlas = PagedLightmapAtlas::compute(&data, settings).unwrap();
+
+		assert!(atlas.pages.len() > 1);
+		for page in &atlas.pages {
+			assert!(page.size().cmple(uvec2(64, 64)).all());
+		}
+		for (face_idx, uvs) in &atlas.uvs {
+			assert!(atlas.face_pages[face_idx] < atlas.pages.len());
+			assert!(uvs.iter().all(|uv| uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all()));
 		}
 	}
 }
@@ -3,20 +3,21 @@ mod irradiance_volume;
 use std::io;
 
 #[cfg(feature = "client")]
-pub use irradiance_volume::IrradianceVolumeMultipliers;
+pub use irradiance_volume::{IrradianceVolumeMultipliers, MAX_IRRADIANCE_VOLUME_TILE_SIZE};
 #[cfg(feature = "client")]
 mod lightmap;
 mod models;
 mod scene;
-mod textures;
+pub(crate) mod textures;
 
 use bevy::{
 	asset::{AssetLoader, LoadContext},
 	tasks::ConditionalSendFuture,
 };
-use bsp::*;
+use bsp::{areas::BspAreas, *};
+use config::MapLoadSettings;
 #[cfg(feature = "client")]
-use irradiance_volume::load_irradiance_volume;
+use irradiance_volume::load_irradiance_volumes;
 #[cfg(feature = "client")]
 use lightmap::BspLightmap;
 use models::{compute_models, finalize_models};
@@ -33,6 +34,9 @@ pub(crate) struct BspLoadCtx<'a, 'lc: 'a> {
 	pub type_registry: &'a AppTypeRegistry,
 	pub data: &'a BspData,
 	pub entities: &'a QuakeMapEntities,
+	pub settings: &'a MapLoadSettings,
+	/// Quake 2 area data, and the handle to its labeled asset.
+	pub areas: Option<(&'a BspAreas, Handle<BspAreas>)>,
 }
 
 #[derive(TypePath)]
@@ -54,12 +58,12 @@ impl FromWorld for BspLoader {
 impl AssetLoader for BspLoader {
 	type Asset = Bsp;
 	type Error = anyhow::Error;
-	type Settings = ();
+	type Settings = MapLoadSettings;
 
 	fn load(
 		&self,
 		reader: &mut dyn bevy::asset::io::Reader,
-		_settings: &Self::Settings,
+		settings: &Self::Settings,
 		load_context: &mut LoadContext,
 	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
 		Box::pin(async move {
@@ -67,8 +71,11 @@ impl AssetLoader for BspLoader {
 			reader.read_to_end(&mut bytes).await?;
 
 			let lit = load_context.read_asset_bytes(load_context.path().path().with_extension("lit")).await.ok();
+			#[cfg(feature = "client")]
+			let lux = load_context.read_asset_bytes(load_context.path().path().with_extension("lux")).await.ok();
 
-			let data = BspData::parse(BspParseInput {
+			#[allow(unused_mut)]
+			let mut data = BspData::parse(BspParseInput {
 				bsp: &bytes,
 				lit: lit.as_deref(),
 				settings: self.tb_server.config.bsp_parse_settings.clone(),
@@ -77,7 +84,17 @@ impl AssetLoader for BspLoader {
 			let fixed_entities_lump = qbsp::util::quake_string_to_utf8(&data.entities, "\\<b>", "\\</b>");
 
 			let quake_map = quake_map::parse(&mut io::Cursor::new(fixed_entities_lump)).map_err(|err| anyhow!("Parsing entities: {err}"))?;
-			let entities = QuakeMapEntities::from_quake_map(quake_map, &self.tb_server.config);
+			#[allow(unused_mut)]
+			let mut entities = QuakeMapEntities::from_quake_map(quake_map, &self.tb_server.config);
+
+			#[cfg(feature = "client")]
+			let map_animators = lighting::prepare_map_lighting_animators(load_context, &self.asset_server, &self.tb_server.config, &mut entities).await?;
+
+			#[cfg(feature = "client")]
+			let deluxe_atlas = BspLightmap::compute_deluxe_atlas(&mut data, lux.as_deref(), &self.tb_server.config)?;
+
+			let areas = BspAreas::parse(&bytes, &data, &self.tb_server.config).map_err(|err| anyhow!("Parsing areas: {err}"))?;
+			let areas_handle = areas.clone().map(|areas| load_context.add_labeled_asset("Areas".to_string(), areas));
 
 			let mut ctx = BspLoadCtx {
 				loader: self,
@@ -86,35 +103,53 @@ impl AssetLoader for BspLoader {
 				type_registry: &self.type_registry,
 				data: &data,
 				entities: &entities,
+				settings,
+				areas: areas.as_ref().zip(areas_handle.clone()),
 			};
 
 			let embedded_textures = EmbeddedTextures::setup(&mut ctx).await?;
 
 			#[cfg(feature = "client")]
-			let lightmap = BspLightmap::compute(&mut ctx)?;
+			let lightmap = BspLightmap::compute(&mut ctx, deluxe_atlas)?;
 			#[cfg(not(feature = "client"))]
 			let lightmap = None;
 
 			let mut models = compute_models(&mut ctx, &lightmap, &embedded_textures).await;
 
-			let embedded_textures = embedded_textures.finalize(&mut ctx);
+			let embedded_textures = embedded_textures.finalize(ctx.load_context);
 
 			let mut world = initialize_scene(&mut ctx, &mut models)?;
 
+			#[cfg(feature = "client")]
+			if let Some(map_animators) = map_animators {
+				world.spawn((Name::new("Lighting Animators"), map_animators));
+			}
+
 			let bsp_models = finalize_models(&mut ctx, models, &mut world)?;
 
 			// TODO: Lightmaps + irradiance volume currently doesn't work until 0.19.1 (https://github.com/bevyengine/bevy/pull/24714)
 			#[cfg(feature = "client")]
-			let irradiance_volume = load_irradiance_volume(&mut ctx, &mut world)?;
+			let irradiance_volumes = load_irradiance_volumes(&mut ctx, &mut world)?;
 
 			Ok(Bsp {
 				world: load_context.add_labeled_asset("Scene".to_string(), WorldAsset::new(world)),
 				embedded_textures,
 				#[cfg(feature = "client")]
-				lightmap: lightmap.map(|lm| lm.animated_lighting),
+				lightmaps: lightmap
+					.iter()
+					.flat_map(|lm| &lm.pages)
+					.map(|page| page.animated_lighting.clone())
+					.collect(),
+				#[cfg(feature = "client")]
+				deluxemaps: lightmap
+					.iter()
+					.flat_map(|lm| &lm.pages)
+					.filter_map(|page| page.deluxemap.clone())
+					.collect(),
 				#[cfg(feature = "client")]
-				irradiance_volume,
+				irradiance_volumes,
 				models: bsp_models,
+				areas: areas_handle,
 
 				data,
 				entities,
@@ -160,6 +195,7 @@ mod tests {
 			.init_asset::<BrushHullsAsset>()
 			.init_asset::<BrushesAsset>()
 			.init_asset::<WorldAsset>()
+			.init_asset::<BspAreas>()
 			.init_asset::<Bsp>()
 			.init_asset_loader::<BspLoader>()
 		;
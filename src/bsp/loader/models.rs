@@ -6,7 +6,7 @@ use crate::{
 	*,
 };
 use bevy_mesh::{Indices, PrimitiveTopology};
-use bsp::*;
+use bsp::{areas::split_mesh_by_area, *};
 use qbsp::data::{
 	BspLeaf, BspNodeRef, ModelBrushes,
 	texture::{EmbeddedTextureName, TextureName},
@@ -25,6 +25,8 @@ pub struct InternalModelMesh {
 	pub mesh: Mesh,
 	/// Entity to apply [`Mesh3d`] to. Should probably only be one of these.
 	pub entity: Option<Entity>,
+	/// The Quake 2 area this mesh is in, if the model was split by area.
+	pub area: Option<u32>,
 }
 
 #[cfg(feature = "client")]
@@ -47,12 +49,39 @@ pub async fn compute_models<'a, 'lc: 'a>(
 
 	let mut texture_size_cache: TextureSizeCache<TextureName> = default();
 
+	let face_areas = ctx.areas.as_ref().map(|(areas, _)| areas.face_areas(ctx.data));
+
 	for model_idx in 0..ctx.data.models.len() {
 		let model_output = ctx.data.mesh_model(model_idx, lightmap_uvs);
+
+		// Only world geometry is split by area, brush entities can move between them.
+		let exported_meshes = match &face_areas {
+			Some(face_areas) if model_idx == 0 => model_output
+				.meshes
+				.into_iter()
+				.flat_map(|mesh| split_mesh_by_area(mesh, face_areas, ctx.data))
+				.collect_vec(),
+			_ => model_output.meshes.into_iter().map(|mesh| (None, mesh)).collect_vec(),
+		};
+
+		// Each lightmap page has its own animated lighting, so meshes can't span multiple.
+		#[cfg(feature = "client")]
+		let exported_meshes = match lightmap {
+			Some(lightmap) if lightmap.pages.len() > 1 => exported_meshes
+				.into_iter()
+				.flat_map(|(area, mesh)| {
+					split_mesh_by_face(mesh, ctx.data, |face_idx| lightmap.face_pages.get(&face_idx).copied())
+						.into_iter()
+						.map(move |(_, mesh)| (area, mesh))
+				})
+				.collect_vec(),
+			_ => exported_meshes,
+		};
+
 		let mut model = InternalModel::default();
-		model.meshes.reserve(model_output.meshes.len());
+		model.meshes.reserve(exported_meshes.len());
 
-		for mut exported_mesh in model_output.meshes {
+		for (area, mut exported_mesh) in exported_meshes {
 			// Check if we have to scale the UVs ourselves.
 			if let Some(texture_name) = exported_mesh.texture
 				&& !exported_mesh.prescaled_uvs
@@ -124,16 +153,26 @@ pub async fn compute_models<'a, 'lc: 'a>(
 				ctx.loader.tb_server.missing_material.read().clone()
 			};
 
+			// Meshes are split by page above, so all faces share the first one's page.
+			#[cfg(feature = "client")]
+			let lightmap_page = lightmap
+				.as_ref()
+				.zip(exported_mesh.faces.first())
+				.and_then(|(lightmap, face_idx)| lightmap.face_page(*face_idx));
+
 			model.meshes.push(InternalModelMesh {
 				texture: MapGeometryTexture {
 					material,
 					#[cfg(feature = "client")]
-					lightmap: lightmap.as_ref().map(|lm| lm.animated_lighting.clone()),
+					lightmap: lightmap_page.map(|page| page.animated_lighting.clone()),
+					#[cfg(feature = "client")]
+					deluxemap: lightmap_page.and_then(|page| page.deluxemap.clone()),
 					name: exported_mesh.texture.as_ref().map(ToString::to_string),
 					flags: exported_mesh.tex_flags,
 				},
 				mesh,
 				entity: None,
+				area,
 			});
 		}
 
@@ -2,14 +2,18 @@ use std::borrow::Cow;
 
 use super::*;
 #[cfg(feature = "client")]
-use crate::bsp::lighting::AnimatedLightingHandle;
+use crate::bsp::lighting::{AnimatedLightingHandle, DeluxemapHandle};
 use crate::{
 	class::{QuakeClassMeshView, QuakeClassSpawnView, generate_class_map, spawn_quake_entity_into_scene},
+	config::SpawnFilterView,
 	geometry::BrushGeometry,
 	util::MapFileType,
 	*,
 };
-use bsp::*;
+use bsp::{
+	areas::{BspArea, BspAreaGraph},
+	*,
+};
 use models::InternalModel;
 
 pub fn initialize_scene(ctx: &mut BspLoadCtx, models: &mut [InternalModel]) -> anyhow::Result<World> {
@@ -30,6 +34,17 @@ pub fn initialize_scene(ctx: &mut BspLoadCtx, models: &mut [InternalModel]) -> a
 			continue;
 		};
 
+		let spawn_filter_view = SpawnFilterView {
+			src_entity: map_entity,
+			src_entity_idx: map_entity_idx,
+			class,
+			settings: ctx.settings,
+			tb_config: config,
+		};
+		if !(config.spawn_filter)(spawn_filter_view) {
+			continue;
+		}
+
 		let entity = world.spawn_empty().id();
 
 		let mut meshes = Vec::new();
@@ -48,6 +63,12 @@ pub fn initialize_scene(ctx: &mut BspLoadCtx, models: &mut [InternalModel]) -> a
 			}
 			model.entity = Some(entity);
 
+			if model_idx == 0
+				&& let Some((_, areas_handle)) = &ctx.areas
+			{
+				world.entity_mut(entity).insert(BspAreaGraph(areas_handle.clone()));
+			}
+
 			meshes.reserve(model.meshes.len());
 
 			for model_mesh in &mut model.meshes {
@@ -66,6 +87,10 @@ pub fn initialize_scene(ctx: &mut BspLoadCtx, models: &mut [InternalModel]) -> a
 				#[cfg(not(feature = "client"))]
 				let mesh_entity = world.spawn((Name::new(name), Transform::default())).id();
 
+				if let Some(area) = model_mesh.area {
+					world.entity_mut(mesh_entity).insert(BspArea(area));
+				}
+
 				meshes.push(QuakeClassMeshView {
 					entity: mesh_entity,
 					mesh: &mut model_mesh.mesh,
@@ -98,6 +123,10 @@ pub fn initialize_scene(ctx: &mut BspLoadCtx, models: &mut [InternalModel]) -> a
 			view.world
 				.entity_mut(mesh_view.entity)
 				.insert(AnimatedLightingHandle(animated_lighting_handle.clone()));
+
+			if let Some(deluxemap_handle) = &mesh_view.texture.deluxemap {
+				view.world.entity_mut(mesh_view.entity).insert(DeluxemapHandle(deluxemap_handle.clone()));
+			}
 		}
 
 		spawn_quake_entity_into_scene(&mut view).map_err(|err| anyhow!("spawning entity {map_entity_idx} ({classname}): {err}"))?;
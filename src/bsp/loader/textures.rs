@@ -1,6 +1,11 @@
+use bevy::asset::{LoadContext, RenderAssetUsages};
 use bsp::*;
 use loader::BspLoadCtx;
-use qbsp::data::texture::EmbeddedTextureName;
+use qbsp::{
+	BspFormat,
+	data::texture::{BspMipTexture, EmbeddedTextureName},
+};
+use qmap::QuakeMapEntities;
 use wgpu_types::{Extent3d, TextureDimension, TextureFormat};
 
 use crate::*;
@@ -14,53 +19,87 @@ fn is_cutout_texture(name: &EmbeddedTextureName) -> bool {
 	name.as_bytes()[0] == b'{'
 }
 
+/// Converts palette indices into an [`Image`]. If `cutout` is `true`, pixels with the index `255` will be transparent.
+pub fn paletted_image(size: UVec2, data: &[u8], palette: &Palette, cutout: bool, asset_usages: RenderAssetUsages) -> Image {
+	Image::new(
+		Extent3d {
+			width: size.x,
+			height: size.y,
+			..default()
+		},
+		TextureDimension::D2,
+		data.iter()
+			.copied()
+			.flat_map(|pixel| {
+				if cutout && pixel == 255 {
+					[0; 4]
+				} else {
+					let [r, g, b] = palette.colors[pixel as usize];
+					[r, g, b, 255]
+				}
+			})
+			.collect(),
+		TextureFormat::Rgba8UnormSrgb,
+		asset_usages,
+	)
+}
+
+/// Reads the palette at [`TrenchBroomConfig::texture_pallette`], falling back to [`QUAKE_PALETTE`] if it doesn't exist.
+pub async fn load_palette(load_context: &mut LoadContext<'_>, config: &TrenchBroomConfig) -> anyhow::Result<Palette> {
+	// Have to clone `texture_pallette` for the borrow checker. Can't figure out why.
+	match load_context.read_asset_bytes(config.texture_pallette.clone()).await.ok() {
+		Some(bytes) => Palette::parse(&bytes).map_err(|err| anyhow!("Parsing palette file {:?}: {err}", config.texture_pallette)),
+		None => Ok(QUAKE_PALETTE.clone()),
+	}
+}
+
 impl EmbeddedTextures {
 	pub async fn setup<'a, 'lc>(ctx: &mut BspLoadCtx<'a, 'lc>) -> anyhow::Result<Self> {
-		let config = &ctx.loader.tb_server.config;
-
-		// Have to clone `texture_pallette` for the borrow checker. Can't figure out why.
-		let palette = match ctx.load_context.read_asset_bytes(config.texture_pallette.clone()).await.ok() {
-			Some(bytes) => Palette::parse(&bytes).map_err(|err| anyhow!("Parsing palette file {:?}: {err}", config.texture_pallette))?,
-			None => QUAKE_PALETTE.clone(),
-		};
-
-		let images: HashMap<EmbeddedTextureName, (Image, Handle<Image>)> = ctx
-			.data
-			.textures
-			.iter()
-			.flatten()
-			.filter(|texture| texture.data.full.is_some())
-			.map(|texture| {
+		let palette = load_palette(ctx.load_context, &ctx.loader.tb_server.config).await?;
+		let format = ctx.data.parse_ctx.format;
+
+		Ok(Self::load(
+			ctx.data.textures.iter().flatten().map(|texture| (texture, format)),
+			&palette,
+			&ctx.loader.tb_server,
+			ctx.load_context,
+			ctx.asset_server,
+			ctx.entities,
+		)
+		.await)
+	}
+
+	/// Converts paletted mip textures (from a BSP or WAD) into images, and loads their materials via [`TrenchBroomConfig::load_embedded_texture`].
+	///
+	/// Each texture is paired with the format of the BSP it's stored the same way as, which decides things like its liquid prefix.
+	pub async fn load<'t>(
+		textures: impl IntoIterator<Item = (&'t BspMipTexture, BspFormat)>,
+		palette: &Palette,
+		tb_server: &TrenchBroomServer,
+		load_context: &mut LoadContext<'_>,
+		asset_server: &AssetServer,
+		entities: &QuakeMapEntities,
+	) -> Self {
+		let config = &tb_server.config;
+		let mut formats: HashMap<EmbeddedTextureName, BspFormat> = default();
+
+		let images: HashMap<EmbeddedTextureName, (Image, Handle<Image>)> = textures
+			.into_iter()
+			.filter(|(texture, _)| texture.data.full.is_some())
+			.map(|(texture, format)| {
 				let Some(data) = &texture.data.full else { unreachable!() };
 				let name = texture.header.name;
+				formats.insert(name, format);
 
-				let is_cutout_texture = is_cutout_texture(&name);
-
-				let palette = texture.data.palette.as_ref().unwrap_or(&palette);
-
-				let image = Image::new(
-					Extent3d {
-						width: texture.header.width,
-						height: texture.header.height,
-						..default()
-					},
-					TextureDimension::D2,
-					data.iter()
-						.copied()
-						.flat_map(|pixel| {
-							if config.embedded_texture_cutouts && is_cutout_texture && pixel == 255 {
-								[0; 4]
-							} else {
-								let [r, g, b] = palette.colors[pixel as usize];
-								[r, g, b, 255]
-							}
-						})
-						.collect(),
-					TextureFormat::Rgba8UnormSrgb,
+				let image = paletted_image(
+					uvec2(texture.header.width, texture.header.height),
+					data,
+					texture.data.palette.as_ref().unwrap_or(palette),
+					config.embedded_texture_cutouts && is_cutout_texture(&name),
 					config.bsp_textures_asset_usages,
 				);
 
-				let image_handle = ctx.load_context.get_label_handle(format!("{TEXTURE_PREFIX}{name}"));
+				let image_handle = load_context.get_label_handle(format!("{TEXTURE_PREFIX}{name}"));
 
 				(name, (image, image_handle))
 			})
@@ -75,16 +114,16 @@ impl EmbeddedTextures {
 			let material = (config.load_embedded_texture)(EmbeddedTextureLoadView {
 				parent_view: TextureLoadView {
 					name: name.as_str(),
-					tb_server: &ctx.loader.tb_server,
-					load_context: ctx.load_context,
-					asset_server: ctx.asset_server,
-					entities: ctx.entities,
+					tb_server,
+					load_context,
+					asset_server,
+					entities,
 					#[cfg(feature = "client")]
 					alpha_mode: is_cutout_texture.then_some(AlphaMode::Mask(0.5)),
 					embedded_textures: Some(&images),
 				},
 
-				bsp_format: ctx.data.parse_ctx.format,
+				bsp_format: formats[name],
 				image_handle,
 				image,
 			})
@@ -99,13 +138,20 @@ impl EmbeddedTextures {
 			);
 		}
 
-		Ok(Self { images, textures })
+		Self { images, textures }
+	}
+
+	/// Finds a texture and its image by name, ignoring case like Quake does.
+	pub fn get_ignore_case(&self, name: &str) -> Option<(&Image, &BspEmbeddedTexture)> {
+		let (key, texture) = self.textures.iter().find(|(key, _)| key.as_str().eq_ignore_ascii_case(name))?;
+		let (image, _) = self.images.get(key)?;
+		Some((image, texture))
 	}
 
 	/// Loads the placeholder images, and returns the embedded textures.
-	pub fn finalize(self, ctx: &mut BspLoadCtx) -> HashMap<EmbeddedTextureName, BspEmbeddedTexture> {
+	pub fn finalize(self, load_context: &mut LoadContext) -> HashMap<EmbeddedTextureName, BspEmbeddedTexture> {
 		for (name, (image, _)) in self.images {
-			ctx.load_context.add_labeled_asset(format!("{TEXTURE_PREFIX}{name}"), image);
+			load_context.add_labeled_asset(format!("{TEXTURE_PREFIX}{name}"), image);
 		}
 
 		self.textures
@@ -1,7 +1,12 @@
+pub mod areas;
+pub mod light_sampling;
 #[cfg(feature = "client")]
 pub mod lighting;
 pub mod loader;
+pub mod wad;
+pub mod wal;
 
+use areas::BspAreas;
 use brush::{BrushPlane, ConvexHull};
 use class::ErasedQuakeClass;
 use config::{EmbeddedTextureLoadView, TextureLoadView};
@@ -22,6 +27,9 @@ impl Plugin for BspPlugin {
 			.init_asset::<BrushHullsAsset>()
 			.init_asset::<Bsp>()
 			.init_asset_loader::<BspLoader>()
+			.add_plugins(areas::BspAreasPlugin)
+			.add_plugins(wad::WadPlugin)
+			.add_plugins(wal::WalPlugin)
 		;
 
 		#[cfg(feature = "client")]
@@ -40,12 +48,19 @@ pub struct Bsp {
 	/// The main scene of everything put together.
 	pub world: Handle<WorldAsset>,
 	pub embedded_textures: HashMap<EmbeddedTextureName, BspEmbeddedTexture>,
+	/// One for each lightmap atlas page, large maps are split into multiple pages to stay within [`TrenchBroomConfig::compute_lightmap_settings`]'s max size.
 	#[cfg(feature = "client")]
-	pub lightmap: Option<Handle<AnimatedLighting>>,
+	pub lightmaps: Vec<Handle<AnimatedLighting>>,
+	/// Composited baked light directions, one for each page in [`lightmaps`](Self::lightmaps). Empty if the BSP doesn't have deluxemaps.
 	#[cfg(feature = "client")]
-	pub irradiance_volume: Option<Handle<AnimatedLighting>>,
+	pub deluxemaps: Vec<Handle<AnimatedLighting>>,
+	/// One for each tile of the BSPX light grid, large grids are split into tiles of [`TrenchBroomConfig::irradiance_volume_tile_size`]. Empty if the BSP doesn't have a light grid.
+	#[cfg(feature = "client")]
+	pub irradiance_volumes: Vec<Handle<AnimatedLighting>>,
 	/// Models for brush entities (world geometry).
 	pub models: Vec<BspModel>,
+	/// Area connectivity, only present in Quake 2 BSPs.
+	pub areas: Option<Handle<BspAreas>>,
 	/// The source data this BSP's assets was created from.
 	pub data: BspData,
 	/// The entities parsed from the map that was used to construct the scene.
@@ -91,7 +106,7 @@ impl ConvexHull for BrushHull {
 }
 
 /// A reference to a texture loaded from a BSP file. Stores the handle to the [`Image`], and to the [`GenericMaterial`] that will be applied to mesh entities.
-#[derive(Reflect, Debug)]
+#[derive(Reflect, Debug, Clone)]
 pub struct BspEmbeddedTexture {
 	pub image: Handle<Image>,
 	pub material: Handle<GenericMaterial>,
@@ -111,3 +126,57 @@ fn get_model_idx(map_entity: &QuakeMapEntity, class: &ErasedQuakeClass) -> Optio
 	}
 	model_property_trimmed.parse::<usize>().ok()
 }
+
+/// Splits an exported mesh into one mesh for each distinct key `key_of` returns for its faces.
+pub(crate) fn split_mesh_by_face<K: PartialEq>(mesh: ExportedMesh, data: &BspData, key_of: impl Fn(u32) -> K) -> Vec<(K, ExportedMesh)> {
+	let mut split: Vec<(K, ExportedMesh)> = Vec::new();
+
+	let mut first_vertex = 0;
+	for face_idx in &mesh.faces {
+		let vertex_count = data.faces[*face_idx as usize].num_edges.0 as usize;
+		let key = key_of(*face_idx);
+
+		let (_, out) = match split.iter().position(|(split_key, _)| *split_key == key) {
+			Some(i) => &mut split[i],
+			None => {
+				split.push((
+					key,
+					ExportedMesh {
+						tex_flags: mesh.tex_flags,
+						texture: mesh.texture,
+						prescaled_uvs: mesh.prescaled_uvs,
+						..default()
+					},
+				));
+				split.last_mut().unwrap()
+			}
+		};
+
+		let vertices = first_vertex..first_vertex + vertex_count;
+		let out_first_vertex = out.positions.len() as u32;
+
+		out.faces.push(*face_idx);
+		out.positions.extend_from_slice(&mesh.positions[vertices.clone()]);
+		out.normals.extend_from_slice(&mesh.normals[vertices.clone()]);
+		out.uvs.extend_from_slice(&mesh.uvs[vertices.clone()]);
+		if let Some(tangents) = &mesh.tangents {
+			out.tangents.get_or_insert_default().extend_from_slice(&tangents[vertices.clone()]);
+		}
+		if let Some(lightmap_uvs) = &mesh.lightmap_uvs
+			&& lightmap_uvs.len() == mesh.positions.len()
+		{
+			out.lightmap_uvs
+				.get_or_insert_default()
+				.extend_from_slice(&lightmap_uvs[vertices.clone()]);
+		}
+
+		// Faces are triangle fans, this matches the indices `mesh_model` creates.
+		for i in 1..vertex_count as u32 - 1 {
+			out.indices.push([0, i + 1, i].map(|x| out_first_vertex + x));
+		}
+
+		first_vertex += vertex_count;
+	}
+
+	split
+}
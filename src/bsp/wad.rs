@@ -0,0 +1,291 @@
+//! WAD2 (Quake) and WAD3 (GoldSrc) texture archive support.
+//!
+//! TrenchBroom reads textures for `.map` files from the WADs listed in the worldspawn's `wad` property. [`QuakeMapLoader`](crate::qmap::loader::QuakeMapLoader) does the same,
+//! treating the textures inside as embedded textures.
+
+use std::path::{Path, PathBuf};
+
+use bevy::{
+	asset::{AssetLoader, LoadContext},
+	tasks::ConditionalSendFuture,
+};
+use bsp::loader::textures::{EmbeddedTextures, load_palette};
+use qbsp::{
+	BspFormat,
+	data::texture::{BspMipTexture, EmbeddedTextureName},
+	reader::{BspByteReader, BspParseContext},
+};
+
+use qmap::QuakeMapEntities;
+
+use crate::*;
+
+pub struct WadPlugin;
+impl Plugin for WadPlugin {
+	fn build(&self, app: &mut App) {
+		#[rustfmt::skip]
+		app
+			.init_asset::<Wad>()
+			.init_asset_loader::<WadLoader>()
+		;
+	}
+}
+
+/// Directory entry type of mip textures in WAD2 files.
+const WAD2_MIP_TEXTURE: u8 = b'D';
+/// Directory entry type of mip textures (with embedded palettes) in WAD3 files.
+const WAD3_MIP_TEXTURE: u8 = b'C';
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WadFormat {
+	/// Quake WADs, textures use the palette at [`TrenchBroomConfig::texture_pallette`].
+	#[default]
+	Wad2,
+	/// GoldSrc WADs, each texture embeds its own palette.
+	Wad3,
+}
+impl WadFormat {
+	/// The BSP format that stores textures the same way as this WAD format.
+	pub fn bsp_format(self) -> BspFormat {
+		match self {
+			Self::Wad2 => BspFormat::BSP29,
+			Self::Wad3 => BspFormat::BSP30,
+		}
+	}
+}
+
+/// A texture archive loaded from a `.wad` file. Only mip textures are read, other lumps are skipped.
+#[derive(Asset, Reflect, Debug, Clone, Default)]
+pub struct Wad {
+	pub format: WadFormat,
+	pub textures: Vec<BspMipTexture>,
+}
+impl Wad {
+	pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+		let format = match bytes.get(0..4) {
+			Some(b"WAD2") => WadFormat::Wad2,
+			Some(b"WAD3") => WadFormat::Wad3,
+			magic => return Err(anyhow!("invalid WAD magic number {magic:?}")),
+		};
+
+		let ctx = BspParseContext { format: format.bsp_format() };
+		let mut reader = BspByteReader::new(bytes, &ctx).with_pos(4);
+
+		let num_entries: u32 = reader.read()?;
+		let directory_offset: u32 = reader.read()?;
+
+		let mut reader = reader.with_pos(directory_offset as usize);
+		let mut textures = Vec::new();
+
+		for entry_idx in 0..num_entries {
+			let offset: u32 = reader.read()?;
+			let _disk_size: u32 = reader.read()?;
+			let _size: u32 = reader.read()?;
+			let [ty, compression]: [u8; 2] = reader.read()?;
+			let _padding: u16 = reader.read()?;
+			let name: EmbeddedTextureName = reader.read()?;
+
+			let expected_ty = match format {
+				WadFormat::Wad2 => WAD2_MIP_TEXTURE,
+				WadFormat::Wad3 => WAD3_MIP_TEXTURE,
+			};
+			if ty != expected_ty {
+				continue;
+			}
+			if compression != 0 {
+				warn!("Skipping compressed WAD texture \"{name}\"");
+				continue;
+			}
+
+			let texture: BspMipTexture = reader
+				.with_pos(offset as usize)
+				.read()
+				.map_err(|err| anyhow!("reading texture {entry_idx} (\"{name}\"): {err}"))?;
+
+			textures.push(texture);
+		}
+
+		Ok(Self { format, textures })
+	}
+
+	/// Finds a texture by name, ignoring case like Quake does.
+	pub fn get(&self, name: &str) -> Option<&BspMipTexture> {
+		self.textures
+			.iter()
+			.find(|texture| texture.header.name.as_str().eq_ignore_ascii_case(name))
+	}
+}
+
+/// Returns the asset paths to try for an entry of a worldspawn's `wad` property, in order of priority.
+///
+/// TrenchBroom writes these relative to the map file, relative to the game directory (the parent of [`assets_path`](TrenchBroomConfig::assets_path)), or as absolute paths.
+pub fn wad_path_candidates(entry: &str, map_path: &Path, config: &TrenchBroomConfig) -> Vec<PathBuf> {
+	let entry = PathBuf::from(entry.replace('\\', "/"));
+	let mut candidates = Vec::with_capacity(3);
+
+	if entry.is_relative() {
+		if let Some(map_dir) = map_path.parent() {
+			candidates.push(map_dir.join(&entry));
+		}
+		if let Ok(stripped) = entry.strip_prefix(&config.assets_path) {
+			candidates.push(stripped.to_path_buf());
+		}
+		candidates.push(entry);
+	} else if let Some(assets_dir_name) = config.assets_path.file_name() {
+		// Take everything after the last occurrence of the assets directory.
+		let components = entry.components().collect_vec();
+		if let Some(idx) = components.iter().rposition(|component| component.as_os_str() == assets_dir_name) {
+			candidates.push(components[idx + 1..].iter().collect());
+		}
+	}
+
+	candidates
+}
+
+/// Loads the textures in `used_textures` from the WADs listed in the worldspawn's `wad` property, as if they were embedded textures.
+///
+/// Returns [`None`] if the map doesn't list any WADs.
+pub(crate) async fn load_map_wad_textures(
+	used_textures: impl IntoIterator<Item = &str>,
+	tb_server: &TrenchBroomServer,
+	load_context: &mut LoadContext<'_>,
+	asset_server: &AssetServer,
+	entities: &QuakeMapEntities,
+) -> anyhow::Result<Option<EmbeddedTextures>> {
+	let Some(wad_list) = entities.worldspawn().and_then(|worldspawn| worldspawn.properties.get("wad")) else { return Ok(None) };
+	let config = &tb_server.config;
+	let map_path = load_context.path().path().to_path_buf();
+
+	let mut wads = Vec::new();
+
+	for entry in wad_list.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+		let mut found = false;
+
+		for path in wad_path_candidates(entry, &map_path, config) {
+			let Ok(bytes) = load_context.read_asset_bytes(path.clone()).await else { continue };
+			wads.push(Wad::parse(&bytes).map_err(|err| anyhow!("Parsing WAD {path:?}: {err}"))?);
+			found = true;
+			break;
+		}
+
+		if !found {
+			error!("Couldn't find WAD \"{entry}\" listed in worldspawn of {map_path:?}");
+		}
+	}
+
+	if wads.is_empty() {
+		return Ok(None);
+	}
+
+	let mut textures: Vec<(&BspMipTexture, BspFormat)> = Vec::new();
+
+	for name in used_textures {
+		for wad in &wads {
+			let Some(texture) = wad.get(name) else { continue };
+			textures.push((texture, wad.format.bsp_format()));
+
+			// Animated textures need the rest of their frames too.
+			if let Some(name_content) = name.strip_prefix('+').and_then(|name| name.get(1..)) {
+				textures.extend(
+					wad.textures
+						.iter()
+						.filter(|frame| {
+							frame.header.name.as_str().starts_with('+')
+								&& frame
+									.header
+									.name
+									.as_str()
+									.get(2..)
+									.is_some_and(|frame_content| frame_content.eq_ignore_ascii_case(name_content))
+						})
+						.map(|frame| (frame, wad.format.bsp_format())),
+				);
+			}
+
+			break;
+		}
+	}
+
+	let palette = load_palette(load_context, config).await?;
+
+	Ok(Some(
+		EmbeddedTextures::load(textures, &palette, tb_server, load_context, asset_server, entities).await,
+	))
+}
+
+#[derive(TypePath, Default)]
+pub struct WadLoader;
+impl AssetLoader for WadLoader {
+	type Asset = Wad;
+	type Settings = ();
+	type Error = anyhow::Error;
+
+	fn load(
+		&self,
+		reader: &mut dyn bevy::asset::io::Reader,
+		_settings: &Self::Settings,
+		_load_context: &mut bevy::asset::LoadContext,
+	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+
+			Wad::parse(&bytes)
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["wad"]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Builds a WAD with a single 2x2 texture.
+	fn test_wad(magic: &[u8; 4], ty: u8) -> Vec<u8> {
+		let mut texture = Vec::new();
+		let mut name = [0; 16];
+		name[..6].copy_from_slice(b"{GRATE");
+		texture.extend(name);
+		texture.extend([2_u32, 2, 40, 44, 45, 45].iter().flat_map(|v| v.to_le_bytes()));
+		texture.extend([0, 1, 2, 255, 3]);
+		if ty == WAD3_MIP_TEXTURE {
+			texture.extend(256_i16.to_le_bytes());
+			texture.extend((0..=255_u8).flat_map(|i| [i, i, i]));
+		}
+
+		let mut bytes = Vec::new();
+		bytes.extend(magic);
+		bytes.extend(1_u32.to_le_bytes());
+		bytes.extend((12 + texture.len() as u32).to_le_bytes());
+		bytes.extend(&texture);
+
+		bytes.extend(12_u32.to_le_bytes());
+		bytes.extend((texture.len() as u32).to_le_bytes());
+		bytes.extend((texture.len() as u32).to_le_bytes());
+		bytes.extend([ty, 0, 0, 0]);
+		bytes.extend(name);
+
+		bytes
+	}
+
+	#[test]
+	fn wad_parsing() {
+		let wad = Wad::parse(&test_wad(b"WAD2", WAD2_MIP_TEXTURE)).unwrap();
+		assert_eq!(wad.format, WadFormat::Wad2);
+		let texture = wad.get("{grate").unwrap();
+		assert_eq!(texture.data.full.as_deref(), Some(&[0, 1, 2, 255][..]));
+		assert!(texture.data.palette.is_none());
+
+		let wad = Wad::parse(&test_wad(b"WAD3", WAD3_MIP_TEXTURE)).unwrap();
+		assert_eq!(wad.format, WadFormat::Wad3);
+		let texture = wad.get("{GRATE").unwrap();
+		assert_eq!(texture.data.palette.as_ref().unwrap().colors[7], [7, 7, 7]);
+
+		// Entries of the wrong type are skipped.
+		assert!(Wad::parse(&test_wad(b"WAD2", WAD3_MIP_TEXTURE)).unwrap().textures.is_empty());
+		assert!(Wad::parse(b"PACK").is_err());
+	}
+}
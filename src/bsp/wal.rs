@@ -0,0 +1,209 @@
+//! Quake 2 `.wal` texture support.
+//!
+//! Quake 2 doesn't embed textures in BSPs, instead referencing loose `.wal` files. Add `"wal"` to [`TrenchBroomConfig::texture_extensions`] to use them.
+
+#[cfg(feature = "client")]
+use std::path::Path;
+
+#[cfg(feature = "client")]
+use bevy::asset::{AssetPath, io::AssetSourceId};
+use bevy::{
+	asset::{AssetLoader, LoadContext, RenderAssetUsages},
+	tasks::ConditionalSendFuture,
+};
+#[cfg(feature = "client")]
+use bevy_materialize::animation::{GenericMaterialAnimationState, ImagesAnimation, MaterialAnimations};
+use bsp::loader::textures::{load_palette, paletted_image};
+use qbsp::{
+	data::{nodes::BspLeafContentFlags, texture::BspSurfaceFlags, util::FixedStr},
+	reader::{BspByteReader, BspParseContext},
+};
+
+use crate::*;
+
+pub struct WalPlugin;
+impl Plugin for WalPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_asset_loader::<WalLoader>();
+	}
+}
+
+/// A Quake 2 texture parsed from a `.wal` file.
+#[derive(Reflect, Debug, Clone)]
+pub struct Wal {
+	/// The name of the texture. Usually its path from the texture root, without extension.
+	pub name: String,
+	pub width: u32,
+	pub height: u32,
+	/// The name of the next texture in this texture's animation chain, if it's animated.
+	pub next: Option<String>,
+	/// Surface flags TrenchBroom applies to faces using this texture by default.
+	pub flags: BspSurfaceFlags,
+	/// Content flags TrenchBroom applies to faces using this texture by default.
+	pub contents: BspLeafContentFlags,
+	/// Default light value, used for emissive surfaces.
+	pub value: u32,
+	/// Full-resolution palette indices.
+	pub data: Vec<u8>,
+}
+impl Wal {
+	pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+		let ctx = BspParseContext::default();
+		let mut reader = BspByteReader::new(bytes, &ctx);
+
+		let name: FixedStr<32> = reader.read()?;
+		let width: u32 = reader.read()?;
+		let height: u32 = reader.read()?;
+		let [offset_full, _offset_half, _offset_quarter, _offset_eighth]: [u32; 4] = reader.read()?;
+		let next: FixedStr<32> = reader.read()?;
+		let flags: BspSurfaceFlags = reader.read()?;
+		let contents: u32 = reader.read()?;
+		let value: u32 = reader.read()?;
+
+		let data = reader
+			.with_pos(offset_full as usize)
+			.read_bytes(width as usize * height as usize)
+			.map_err(|err| anyhow!("reading {width}x{height} pixels of \"{name}\": {err}"))?
+			.to_vec();
+
+		Ok(Self {
+			name: name.as_str().to_string(),
+			width,
+			height,
+			next: (!next.is_empty()).then(|| next.as_str().to_string()),
+			flags,
+			contents: BspLeafContentFlags::from_bits_truncate(contents),
+			value,
+			data,
+		})
+	}
+
+	pub fn to_image(&self, palette: &Palette, asset_usages: RenderAssetUsages) -> Image {
+		paletted_image(uvec2(self.width, self.height), &self.data, palette, false, asset_usages)
+	}
+}
+
+/// Loads `.wal` files as [`Image`]s, using the palette at [`TrenchBroomConfig::texture_pallette`].
+#[derive(TypePath)]
+pub struct WalLoader {
+	pub tb_server: TrenchBroomServer,
+}
+impl FromWorld for WalLoader {
+	fn from_world(world: &mut World) -> Self {
+		Self {
+			tb_server: world.resource::<TrenchBroomServer>().clone(),
+		}
+	}
+}
+impl AssetLoader for WalLoader {
+	type Asset = Image;
+	type Settings = ();
+	type Error = anyhow::Error;
+
+	fn load(
+		&self,
+		reader: &mut dyn bevy::asset::io::Reader,
+		_settings: &Self::Settings,
+		load_context: &mut LoadContext,
+	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+
+			let wal = Wal::parse(&bytes)?;
+			let palette = load_palette(load_context, &self.tb_server.config).await?;
+
+			Ok(wal.to_image(&palette, RenderAssetUsages::default()))
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["wal"]
+	}
+}
+
+/// Follows the animation chain of the `.wal` texture at `path`, returning an animation cycling through its frames.
+///
+/// Returns [`None`] if the texture isn't animated.
+#[cfg(feature = "client")]
+pub(crate) async fn load_wal_animation(
+	load_context: &mut LoadContext<'_>,
+	source: &AssetSourceId<'static>,
+	path: &Path,
+	config: &TrenchBroomConfig,
+) -> Option<MaterialAnimations> {
+	let fps = config.embedded_texture_animation_fps?;
+
+	let mut frames = vec![load_context.load::<Image>(AssetPath::from_path(path).with_source(source.clone()))];
+	let mut visited = vec![path.to_path_buf()];
+	let mut current = path.to_path_buf();
+
+	loop {
+		let bytes = load_context
+			.read_asset_bytes(AssetPath::from_path(&current).with_source(source.clone()))
+			.await
+			.ok()?;
+		let wal = Wal::parse(&bytes).ok()?;
+		let Some(next) = wal.next else { break };
+
+		let next = config.material_root.join(format!("{next}.wal"));
+		// Chains loop back around to the first frame.
+		if visited.contains(&next) {
+			break;
+		}
+
+		frames.push(load_context.load::<Image>(AssetPath::from_path(&next).with_source(source.clone())));
+		visited.push(next.clone());
+		current = next;
+	}
+
+	if frames.len() < 2 {
+		return None;
+	}
+
+	Some(MaterialAnimations {
+		next: None,
+		images: Some(ImagesAnimation {
+			fps,
+			fields: [("base_color_texture".to_string(), frames)].into_iter().collect(),
+			state: GenericMaterialAnimationState {
+				current_frame: 0,
+				next_frame_time: default(),
+			},
+		}),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn wal_parsing() {
+		let mut bytes = Vec::new();
+		let mut name = [0; 32];
+		name[..10].copy_from_slice(b"e1u1/water");
+		bytes.extend(name);
+		bytes.extend([2_u32, 1, 100, 0, 0, 0].iter().flat_map(|v| v.to_le_bytes()));
+		let mut next = [0; 32];
+		next[..11].copy_from_slice(b"e1u1/water2");
+		bytes.extend(next);
+		bytes.extend(
+			[BspSurfaceFlags::WARP.bits(), BspLeafContentFlags::WATER.bits(), 50]
+				.iter()
+				.flat_map(|v| v.to_le_bytes()),
+		);
+		bytes.extend([3, 7]);
+
+		let wal = Wal::parse(&bytes).unwrap();
+		assert_eq!(wal.name, "e1u1/water");
+		assert_eq!(wal.next.as_deref(), Some("e1u1/water2"));
+		assert!(wal.flags.contains(BspSurfaceFlags::WARP));
+		assert!(wal.contents.contains(BspLeafContentFlags::WATER));
+		assert_eq!(wal.value, 50);
+
+		let image = wal.to_image(&QUAKE_PALETTE, RenderAssetUsages::default());
+		let [r, g, b] = QUAKE_PALETTE.colors[7];
+		assert_eq!(image.pixel_bytes(uvec3(1, 0, 0)).unwrap(), [r, g, b, 255]);
+	}
+}
@@ -1,4 +1,6 @@
-use crate::fgd::{TargetDestination, TargetSource};
+use enumflags2::bitflags;
+
+use crate::fgd::{FgdFlags, ModelPath, TargetDestination, TargetSource};
 
 use super::*;
 
@@ -21,6 +23,7 @@ impl Plugin for BaseClassesPlugin {
 		app
 			.register_type_data::<Transform, ReflectQuakeClass>()
 			.register_type_data::<Name, ReflectQuakeClass>()
+			.register_type::<ModelProp>()
 		;
 
 		#[cfg(feature = "client")]
@@ -67,6 +70,8 @@ impl QuakeClass for Transform {
 				default_value: Some(|| Vec3::ONE.fgd_to_string()),
 			},
 		],
+		inputs: &[],
+		outputs: &[],
 	};
 
 	fn class_spawn(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
@@ -103,6 +108,8 @@ impl QuakeClass for Name {
 			description: None,
 			default_value: Some(String::new),
 		}],
+		inputs: &[],
+		outputs: &[],
 	};
 
 	fn class_spawn(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
@@ -139,6 +146,8 @@ impl QuakeClass for Visibility {
 			description: None,
 			default_value: Some(|| "\"Inherited\"".to_string()),
 		}],
+		inputs: &[],
+		outputs: &[],
 	};
 
 	fn class_spawn(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
@@ -163,9 +172,7 @@ impl QuakeClass for Visibility {
 
 // TODO: It would be nice to have these be tuple structs.
 
-/// Quake entity IO - Able to target entities with the [`Targetable`] component.
-///
-/// TODO: this is currently just a skeleton struct, first-class entity IO hasn't been added yet.
+/// Quake entity IO - Able to target entities with the [`Targetable`] component. See [`class::io`](crate::class::io).
 #[base_class(classname("__target"))]
 #[derive(Debug, Clone, SmartDefault, Serialize, Deserialize)]
 #[reflect(Default, Serialize, Deserialize)]
@@ -182,9 +189,7 @@ pub struct KillTarget {
 	pub killtarget: TargetDestination,
 }
 
-/// Quake entity IO - Able to be targeted from a [`Target`] component.
-///
-/// TODO: this is currently just a skeleton struct, first-class entity IO hasn't been added yet.
+/// Quake entity IO - Able to be targeted from a [`Target`] component. See [`class::io`](crate::class::io).
 #[base_class(classname("__targetable"))]
 #[derive(Debug, Clone, SmartDefault, Serialize, Deserialize)]
 #[reflect(Default, Serialize, Deserialize)]
@@ -192,3 +197,91 @@ pub struct Targetable {
 	/// The name for entities with [`Target`] components to point to.
 	pub targetname: TargetSource,
 }
+
+/// Quake entity IO - Postpones firing this entity's targets. See [`class::io`](crate::class::io).
+#[base_class(classname("__target_delay"))]
+#[derive(Debug, Clone, SmartDefault, Serialize, Deserialize)]
+#[reflect(Default, Serialize, Deserialize)]
+pub struct TargetDelay {
+	/// How many seconds to wait after being fired before firing targets.
+	pub delay: f32,
+}
+
+/// Quake entity IO - Shows a message when this entity's targets fire. See [`class::io`](crate::class::io).
+#[base_class(classname("__target_message"))]
+#[derive(Debug, Clone, SmartDefault, Serialize, Deserialize)]
+#[reflect(Default, Serialize, Deserialize)]
+pub struct TargetMessage {
+	/// If not empty, triggers a [`CenterPrint`](crate::class::io::CenterPrint) event with this message when this entity's targets fire.
+	pub message: String,
+}
+
+/// Quake's difficulty and game mode spawnflags, which leave the entity out of maps loaded with some [`MapLoadSettings`](crate::config::MapLoadSettings).
+///
+/// These are read by [`TrenchBroomConfig::default_spawn_filter`] from every entity, this just shows them in TrenchBroom.
+#[base_class(classname("__spawn_filter"))]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(Default)]
+pub struct SpawnFilter {
+	pub spawnflags: FgdFlags<SpawnFilterFlags>,
+}
+
+#[bitflags]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SpawnFilterFlags {
+	/// Not in Easy
+	NotInEasy = 1 << 8,
+	/// Not in Normal
+	NotInNormal = 1 << 9,
+	/// Not in Hard
+	NotInHard = 1 << 10,
+	/// Not in Deathmatch
+	NotInDeathmatch = 1 << 11,
+	/// Not in Coop
+	NotInCoop = 1 << 12,
+	/// Not in Singleplayer
+	NotInSingleplayer = 1 << 13,
+}
+
+/// Generic prop whose model is chosen per entity through the `model` property, previewed in TrenchBroom.
+///
+/// Supports glTF (`.gltf`/`.glb`) files, as well as Quake models and sprites (`.mdl`, `.md2`, `.spr`, `.sp2`) with the `bsp` and `client` features.
+/// Models are preloaded with the map, and can optionally have collision with the `physics-integration` feature.
+///
+/// # Examples
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_trenchbroom::prelude::*;
+/// #[point_class(base(ModelProp, Transform, Visibility))]
+/// pub struct PropStatic;
+/// ```
+#[base_class(
+	classname("__model_prop"),
+	model({ "path": model, "skin": skin, "frame": frame }),
+	hooks(SceneHooks::new().preload_model::<Self>().spawn_class_model::<Self>()),
+)]
+#[derive(Debug, Clone, SmartDefault, Serialize, Deserialize)]
+#[reflect(Default, Serialize, Deserialize)]
+pub struct ModelProp {
+	/// Path of the model to spawn, relative to the assets folder.
+	pub model: ModelPath,
+	/// Skin index for Quake models.
+	pub skin: u32,
+	/// Frame index for Quake models and sprites.
+	pub frame: u32,
+	/// Collision to create from the model's meshes. Requires the `physics-integration` feature.
+	pub collision: ModelCollision,
+}
+
+#[derive(FgdType, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[number_key]
+pub enum ModelCollision {
+	/// No collision.
+	#[default]
+	None = 0,
+	/// A trimesh collider for each mesh. This means the model will be hollow.
+	Trimesh = 1,
+	/// A convex hull collider wrapping each mesh.
+	Convex = 2,
+}
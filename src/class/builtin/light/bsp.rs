@@ -9,7 +9,7 @@ use super::*;
 /// Contains properties used by the `ericw-tools` compiler for any entity with a classname starting with the first five letters "light". E.g. "light", "light_spot", "light_flame_small_yellow", etc.
 ///
 /// This is a combined class instead of split into point, spot, and directional lights because `ericw-tools` makes no distinction based on entity type. You have to specify per-entity what kind of light it is.
-#[base_class(classname("__bsp_combined_light"))]
+#[base_class(base(Targetable), classname("__bsp_combined_light"))]
 #[derive(Debug, Clone, SmartDefault, Serialize, Deserialize)]
 #[reflect(Debug, Default, Serialize, Deserialize)]
 pub struct BspLight {
@@ -35,13 +35,13 @@ pub struct BspLight {
 	#[default(Srgb::WHITE_255)]
 	pub _color: Srgb,
 
-	/// Turns the light into a switchable light, toggled by another entity targeting it’s name.
-	pub targetname: Option<String>,
-
 	/// Set the animated light style. Default 0.
 	#[default(LightmapStyle::NORMAL)]
 	pub style: LightmapStyle,
 
+	/// Set to 1 ([`BspLight::START_OFF`]) to have a switchable light start off.
+	pub spawnflags: u32,
+
 	/// Sets a scaling factor for how much influence the angle of incidence of light on a surface has on the brightness of the surface
 	/// Value must be between 0.0 and 1.0. Smaller values mean less attenuation, with zero meaning that angle of incidence has no effect at all on the brightness.
 	/// Default 0.5.
@@ -176,6 +176,14 @@ pub struct BspLight {
 	pub _shadow_channel_mask: Option<u32>,
 }
 impl BspLight {
+	/// [`spawnflags`](Self::spawnflags) bit that makes switchable lights start off.
+	pub const START_OFF: u32 = 1;
+
+	/// Returns whether this light was compiled into its own switchable style, which `ericw-tools` does for lights with a [`Targetable::targetname`].
+	pub fn is_switchable(&self) -> bool {
+		self.style.0 >= FIRST_SWITCHABLE_LIGHT_STYLE
+	}
+
 	/// Returns whether this light represents a directional/sun light.
 	/// This also returns `true` if this entity represents the upper/lower hemisphere lighting (`_sunlight2`, and `_sunlight3`)
 	pub fn is_sun(&self) -> bool {
@@ -194,6 +202,9 @@ impl BspLight {
 	}
 }
 
+/// The first style `ericw-tools` assigns to switchable lights, lower styles are animated styles set by the mapper.
+pub const FIRST_SWITCHABLE_LIGHT_STYLE: u8 = 32;
+
 /// How light fades over distance. Used in the `delay` property of light entities.
 #[derive(FgdType, Reflect, Debug, Clone, Default, Serialize, Deserialize)]
 #[number_key]
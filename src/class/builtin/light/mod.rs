@@ -194,6 +194,8 @@ impl QuakeClass for PointLight {
 				default_value: Some(|| PointLight::DEFAULT_SHADOW_MAP_NEAR_Z.fgd_to_string()),
 			},
 		],
+		inputs: &[],
+		outputs: &[],
 	};
 
 	fn class_spawn(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
@@ -339,6 +341,8 @@ impl QuakeClass for SpotLight {
 				default_value: Some(|| "\"0\"".to_string()),
 			},
 		],
+		inputs: &[],
+		outputs: &[],
 	};
 
 	fn class_spawn(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
@@ -454,6 +458,8 @@ impl QuakeClass for DirectionalLight {
 				default_value: Some(|| DirectionalLight::DEFAULT_SHADOW_NORMAL_BIAS.fgd_to_string()),
 			},
 		],
+		inputs: &[],
+		outputs: &[],
 	};
 
 	fn class_spawn(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
@@ -6,10 +6,16 @@ mod base;
 pub use base::*;
 mod light;
 pub use light::*;
+mod mover;
+pub use mover::*;
+mod path;
+pub use path::*;
 mod point;
 pub use point::*;
 mod solid;
 pub use solid::*;
+mod trigger;
+pub use trigger::*;
 
 use bevy::app::plugin_group;
 use fgd::FgdType;
@@ -23,8 +29,10 @@ plugin_group! {
 	pub struct BasicClassesPlugins {
 		:BaseClassesPlugin,
 		:LightingClassesPlugin,
+		:PathClassesPlugin,
 		:PointClassesPlugin,
 		:SolidClassesPlugin,
+		:TriggerClassesPlugin,
 	}
 }
 
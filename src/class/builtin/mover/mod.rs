@@ -0,0 +1,877 @@
+use enumflags2::{BitFlags, bitflags};
+use fgd::FgdFlags;
+use smart_default::SmartDefault;
+use util::BevyTrenchbroomCoordinateConversions;
+
+use super::*;
+#[cfg(feature = "bsp")]
+use crate::bsp::areas::SetAreaPortalState;
+use crate::class::io::{ClassInput, EntityIo, Use};
+
+/// How long a `func_plat` waits at the top before lowering, in seconds.
+pub const PLAT_WAIT: f32 = 3.;
+
+/// Adds Quake's moving brush entities: `func_door`, `func_button`, `func_plat`, `func_rotating`, and `func_train`.
+///
+/// This isn't part of [`BasicClassesPlugins`], add it yourself if you want to use these. Trains follow `path_corner`s from [`PathClassesPlugin`], which is.
+///
+/// Movers move by setting their [`Transform`]. With the `physics-integration` feature they get kinematic colliders, and doors, buttons, and plats can be activated by touching them.
+/// Like trigger volumes, only entities with [`TriggerActivator`] can touch movers under [`TriggerWorkflow::TriggerActivatorOnly`].
+#[derive(Default)]
+pub struct MoverClassesPlugin;
+impl Plugin for MoverClassesPlugin {
+	fn build(&self, app: &mut App) {
+		#[rustfmt::skip]
+		app
+			.register_type::<Mover>()
+			.register_type::<FuncDoor>()
+			.register_type::<FuncButton>()
+			.register_type::<FuncPlat>()
+			.register_type::<FuncRotating>()
+			.register_type::<FuncTrain>()
+			.register_type::<TwoPositionMover>()
+			.register_type::<Rotator>()
+			.register_type::<MoverLocked>()
+			.add_observer(Self::use_mover)
+			.add_observer(Self::door_input)
+			.add_observer(Self::button_input)
+			.add_observer(Self::press_button)
+			.add_observer(Self::train_departed)
+			.add_observer(Self::train_arrived)
+			.add_systems(Update, (
+				(Self::place_movers, Self::move_two_position_movers).chain(),
+				Self::rotate,
+			))
+		;
+
+		#[cfg(feature = "physics-integration")]
+		app.add_observer(Self::touch);
+
+		#[cfg(feature = "bsp")]
+		app.add_observer(Self::open_door_area_portals).add_observer(Self::close_door_area_portals);
+	}
+}
+impl MoverClassesPlugin {
+	/// Activates doors, buttons, and plats when a valid activator touches them, according to the [`TriggerWorkflow`]. Doors with a `targetname` can only be opened by being used.
+	#[cfg(feature = "physics-integration")]
+	pub fn touch(
+		trigger: On<crate::physics::KinematicTouched>,
+		mut commands: Commands,
+		workflow: Option<Res<TriggerWorkflow>>,
+		activator_query: Query<(), With<TriggerActivator>>,
+		mut query: Query<(Option<&FuncDoor>, Option<&Targetable>, Has<MoverLocked>, &mut TwoPositionMover)>,
+		mut io: EntityIo,
+	) {
+		if workflow.is_some_and(|workflow| *workflow == TriggerWorkflow::TriggerActivatorOnly) && !activator_query.contains(trigger.entity) {
+			return;
+		}
+		let Ok((door, targetable, locked, mut mover)) = query.get_mut(trigger.kinematic) else { return };
+		if door.is_some() && targetable.is_some_and(|targetable| !targetable.targetname.is_empty()) {
+			return;
+		}
+
+		activate(&mut commands, &mut io, trigger.kinematic, Some(trigger.entity), door, locked, &mut mover);
+	}
+
+	pub fn use_mover(
+		trigger: On<Use>,
+		mut commands: Commands,
+		mut two_position_query: Query<(Option<&FuncDoor>, Has<MoverLocked>, &mut TwoPositionMover)>,
+		mut rotator_query: Query<&mut Rotator>,
+		mut train_query: Query<&mut FollowPath, With<FuncTrain>>,
+		mut io: EntityIo,
+	) {
+		let entity = trigger.entity;
+
+		if let Ok((door, locked, mut mover)) = two_position_query.get_mut(entity) {
+			activate(&mut commands, &mut io, entity, trigger.activator, door, locked, &mut mover);
+		} else if let Ok(mut rotator) = rotator_query.get_mut(entity) {
+			rotator.enabled = !rotator.enabled;
+			if rotator.enabled {
+				commands.trigger(MoverStarted { entity });
+			} else {
+				commands.trigger(MoverStopped { entity });
+			}
+		} else if let Ok(mut train) = train_query.get_mut(entity) {
+			// Only start trains that are stopped at a corner.
+			if !train.moving {
+				train.paused = false;
+			}
+		}
+	}
+
+	pub fn door_input(trigger: On<ClassInput<FuncDoorInput>>, mut commands: Commands, mut query: Query<&mut TwoPositionMover>, mut io: EntityIo) {
+		let entity = trigger.entity;
+		match trigger.input {
+			FuncDoorInput::Lock => {
+				commands.entity(entity).insert(MoverLocked);
+			}
+			FuncDoorInput::Unlock => {
+				commands.entity(entity).remove::<MoverLocked>();
+			}
+			FuncDoorInput::Open => {
+				let Ok(mut mover) = query.get_mut(entity) else { return };
+				mover.activator = trigger.activator;
+				if mover.open() {
+					commands.trigger(MoverStarted { entity });
+					io.fire(entity, trigger.activator);
+				}
+			}
+			FuncDoorInput::Close => {
+				let Ok(mut mover) = query.get_mut(entity) else { return };
+				if mover.close() {
+					commands.trigger(MoverStarted { entity });
+				}
+			}
+		}
+	}
+
+	pub fn button_input(trigger: On<ClassInput<FuncButtonInput>>, mut commands: Commands, mut query: Query<&mut TwoPositionMover>) {
+		let entity = trigger.entity;
+		match trigger.input {
+			FuncButtonInput::Lock => {
+				commands.entity(entity).insert(MoverLocked);
+			}
+			FuncButtonInput::Unlock => {
+				commands.entity(entity).remove::<MoverLocked>();
+			}
+			FuncButtonInput::Press => {
+				let Ok(mut mover) = query.get_mut(entity) else { return };
+				mover.activator = trigger.activator;
+				if mover.open() {
+					commands.trigger(MoverStarted { entity });
+				}
+			}
+		}
+	}
+
+	/// Buttons fire their targets once they've been fully pressed in.
+	pub fn press_button(trigger: On<MoverStopped>, query: Query<&TwoPositionMover, With<FuncButton>>, mut io: EntityIo) {
+		let Ok(mover) = query.get(trigger.entity) else { return };
+		if mover.state == MoverState::Open {
+			io.fire(trigger.entity, mover.activator);
+		}
+	}
+
+	/// Opens the [`FuncAreaportal`]s a door targets when it starts opening, like Quake 2.
+	#[cfg(feature = "bsp")]
+	pub fn open_door_area_portals(trigger: On<MoverStarted>, mut commands: Commands, query: Query<(&TwoPositionMover, &Target), With<FuncDoor>>) {
+		let Ok((mover, target)) = query.get(trigger.entity) else { return };
+		if mover.state == MoverState::Opening && !target.target.is_empty() {
+			commands.trigger(SetAreaPortalState {
+				target: target.target.0.clone(),
+				open: true,
+			});
+		}
+	}
+
+	/// Closes the [`FuncAreaportal`]s a door targets once it's fully closed.
+	#[cfg(feature = "bsp")]
+	pub fn close_door_area_portals(trigger: On<MoverStopped>, mut commands: Commands, query: Query<(&TwoPositionMover, &Target), With<FuncDoor>>) {
+		let Ok((mover, target)) = query.get(trigger.entity) else { return };
+		if mover.state == MoverState::Closed && !target.target.is_empty() {
+			commands.trigger(SetAreaPortalState {
+				target: target.target.0.clone(),
+				open: false,
+			});
+		}
+	}
+
+	/// Moves newly spawned movers to their resting position.
+	///
+	/// This happens after spawning so that colliders are built from where the brushes are in the map.
+	pub fn place_movers(mut query: Query<(&TwoPositionMover, &mut Transform), Added<TwoPositionMover>>) {
+		for (mover, mut transform) in &mut query {
+			transform.translation = match mover.state {
+				MoverState::Open | MoverState::Closing => mover.open,
+				MoverState::Closed | MoverState::Opening => mover.closed,
+			};
+		}
+	}
+
+	pub fn move_two_position_movers(mut commands: Commands, mut query: Query<(Entity, &mut TwoPositionMover, &mut Transform)>, time: Res<Time>) {
+		for (entity, mut mover, mut transform) in &mut query {
+			let state = mover.state;
+			match state {
+				MoverState::Opening | MoverState::Closing => {
+					let opening = state == MoverState::Opening;
+					let destination = if opening { mover.open } else { mover.closed };
+
+					if move_towards(&mut transform.translation, destination, mover.speed * time.delta_secs()) {
+						mover.state = if opening { MoverState::Open } else { MoverState::Closed };
+						if opening {
+							mover.wait_timer = Timer::from_seconds(mover.wait.max(0.), TimerMode::Once);
+						}
+						commands.trigger(MoverStopped { entity });
+					}
+				}
+				MoverState::Open if mover.wait >= 0. && mover.wait_timer.tick(time.delta()).is_finished() => {
+					mover.state = MoverState::Closing;
+					commands.trigger(MoverStarted { entity });
+				}
+				_ => {}
+			}
+		}
+	}
+
+	pub fn rotate(mut query: Query<(&Rotator, &mut Transform)>, time: Res<Time>) {
+		for (rotator, mut transform) in &mut query {
+			if rotator.enabled {
+				transform.rotate_local(Quat::from_axis_angle(rotator.axis, rotator.speed * time.delta_secs()));
+			}
+		}
+	}
+
+	pub fn train_departed(trigger: On<PathNodeDeparted>, mut commands: Commands, query: Query<(), With<FuncTrain>>) {
+		if query.contains(trigger.entity) {
+			commands.trigger(MoverStarted { entity: trigger.entity });
+		}
+	}
+
+	pub fn train_arrived(trigger: On<PathNodeArrived>, mut commands: Commands, query: Query<(), With<FuncTrain>>) {
+		if query.contains(trigger.entity) {
+			commands.trigger(MoverStopped { entity: trigger.entity });
+		}
+	}
+}
+
+/// Activates a door, button, or plat, either from being used or touched.
+fn activate(
+	commands: &mut Commands,
+	io: &mut EntityIo,
+	entity: Entity,
+	activator: Option<Entity>,
+	door: Option<&FuncDoor>,
+	locked: bool,
+	mover: &mut TwoPositionMover,
+) {
+	if locked {
+		commands.trigger(LockedMoverUsed { entity, activator });
+		return;
+	}
+	mover.activator = activator;
+
+	if let Some(door) = door
+		&& BitFlags::from(door.spawnflags).contains(FuncDoorFlags::Toggle)
+		&& matches!(mover.state, MoverState::Open | MoverState::Opening)
+	{
+		if mover.close() {
+			commands.trigger(MoverStarted { entity });
+		}
+		return;
+	}
+
+	if mover.open() {
+		commands.trigger(MoverStarted { entity });
+		// Doors fire their targets when they start opening, buttons do once they're pressed in.
+		if door.is_some() {
+			io.fire(entity, activator);
+		}
+	} else if mover.state == MoverState::Open {
+		// Keep it open while it's being used.
+		mover.wait_timer.reset();
+	}
+}
+
+/// Moves `translation` towards `destination` by at most `distance`, returning `true` if it arrived.
+fn move_towards(translation: &mut Vec3, destination: Vec3, distance: f32) -> bool {
+	let delta = destination - *translation;
+	if delta.length() <= distance {
+		*translation = destination;
+		true
+	} else {
+		*translation += delta.normalize() * distance;
+		false
+	}
+}
+
+/// The bounds of an entity's brush meshes, relative to the entity.
+fn mesh_bounds(view: &QuakeClassSpawnView) -> (Vec3, Vec3) {
+	let mut positions = view
+		.meshes
+		.iter()
+		.filter_map(|mesh_view| mesh_view.mesh.attribute(Mesh::ATTRIBUTE_POSITION)?.as_float3())
+		.flatten()
+		.map(|position| Vec3::from_array(*position))
+		.peekable();
+
+	if positions.peek().is_none() {
+		return (Vec3::ZERO, Vec3::ZERO);
+	}
+	positions.fold((Vec3::MAX, Vec3::MIN), |(min, max), position| (min.min(position), max.max(position)))
+}
+
+/// Calculates the closed and open positions of a door or button, moving in the direction of `angle` by its size minus `lip`.
+fn calculate_travel(view: &QuakeClassSpawnView, angle: f32, lip: f32) -> (Vec3, Vec3) {
+	let closed = view
+		.world
+		.get::<Transform>(view.entity)
+		.map(|transform| transform.translation)
+		.unwrap_or_default();
+	let direction = angle_to_quat(angle) * Vec3::NEG_Z;
+	let (min, max) = mesh_bounds(view);
+	let distance = (direction.abs().dot(max - min) - lip / view.tb_config.scale).max(0.);
+
+	(closed, closed + direction * distance)
+}
+
+/// Triggered on a mover when it starts moving, such as for playing its moving sound.
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct MoverStarted {
+	#[event_target]
+	pub entity: Entity,
+}
+
+/// Triggered on a mover when it stops moving, such as for playing its stopping sound.
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct MoverStopped {
+	#[event_target]
+	pub entity: Entity,
+}
+
+/// Triggered on a [locked](MoverLocked) mover when something tries to activate it, such as for playing a locked sound.
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct LockedMoverUsed {
+	#[event_target]
+	pub entity: Entity,
+	pub activator: Option<Entity>,
+}
+
+/// Prevents a door or button from being used or touched. Inserted and removed by the `Lock` and `Unlock` inputs.
+#[derive(Component, Reflect, Debug, Clone, Copy, Default)]
+#[reflect(Component, Default)]
+pub struct MoverLocked;
+
+/// Where a [`TwoPositionMover`] is, or is going.
+#[derive(Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MoverState {
+	#[default]
+	Closed,
+	Opening,
+	Open,
+	Closing,
+}
+
+/// Runtime state of a mover moving between two positions, like Quake's doors, buttons, and plats.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+#[require(Transform)]
+pub struct TwoPositionMover {
+	/// Resting position when closed, relative to the entity's parent. For plats, this is the lowered position, and for buttons it's unpressed.
+	pub closed: Vec3,
+	/// Resting position when open.
+	pub open: Vec3,
+	/// Units per second.
+	pub speed: f32,
+	/// Seconds to stay open before closing again. If negative, stays open until closed by something else.
+	pub wait: f32,
+	pub state: MoverState,
+	/// Counts down [`wait`](Self::wait) while open.
+	pub wait_timer: Timer,
+	/// The entity that last activated the mover, passed on when it fires its targets.
+	pub activator: Option<Entity>,
+}
+impl TwoPositionMover {
+	pub fn new(closed: Vec3, open: Vec3, speed: f32, wait: f32) -> Self {
+		Self {
+			closed,
+			open,
+			speed,
+			wait,
+			state: MoverState::Closed,
+			wait_timer: Timer::default(),
+			activator: None,
+		}
+	}
+
+	/// Starts moving towards the open position. Returns `false` if it's already open or opening.
+	pub fn open(&mut self) -> bool {
+		if matches!(self.state, MoverState::Open | MoverState::Opening) {
+			return false;
+		}
+		self.state = MoverState::Opening;
+		true
+	}
+
+	/// Starts moving towards the closed position. Returns `false` if it's already closed or closing.
+	pub fn close(&mut self) -> bool {
+		if matches!(self.state, MoverState::Closed | MoverState::Closing) {
+			return false;
+		}
+		self.state = MoverState::Closing;
+		true
+	}
+}
+
+/// Runtime state of a continuously rotating mover.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+#[require(Transform)]
+pub struct Rotator {
+	/// Local axis to rotate around.
+	pub axis: Vec3,
+	/// Radians per second.
+	pub speed: f32,
+	/// Whether it's currently rotating. Toggled when used.
+	pub enabled: bool,
+}
+
+fn mover_hooks() -> SceneHooks {
+	let hooks = SceneHooks::new().push(|view| {
+		let translation = read_translation_from_entity(view.src_entity, view.tb_config)?;
+		view.world.entity_mut(view.entity).insert(Transform::from_translation(translation));
+		Ok(())
+	});
+	#[cfg(feature = "physics-integration")]
+	let hooks = hooks.kinematic_collider();
+	hooks
+}
+
+/// Brush entity that moves. Positioned at its `origin`, which can be set with an [origin brush](crate::config::TrenchBroomConfig::origin_textures) to choose the point it rotates around.
+///
+/// Doesn't read `angle`, as movers use it as the direction to move in.
+#[base_class(
+	classname("__mover"),
+	base(
+		Targetable,
+		#[cfg(feature = "client")] Visibility,
+	),
+	hooks(mover_hooks()),
+)]
+#[derive(Debug, Clone)]
+#[reflect(no_auto_register)]
+pub struct Mover;
+
+#[bitflags]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FuncDoorFlags {
+	/// Start open
+	/// Starts in its open position, and moves to where it's placed in the map when used.
+	StartOpen = 1,
+	/// Toggle
+	/// Using it while open closes it, and it doesn't close by itself.
+	Toggle = 1 << 5,
+	/// Start locked
+	/// Can't be used or touched until it receives the `Unlock` input.
+	StartLocked = 1 << 6,
+}
+
+/// Slides in the direction of `angle` when used or touched, firing its targets as it starts opening.
+/// Doors with a `targetname` can only be opened by being used.
+///
+/// With the `bsp` feature, targeted `func_areaportal`s are opened as the door starts opening, and closed once it's fully closed.
+#[solid_class(
+	base(Mover, Target, TargetDelay, TargetMessage, KillTarget),
+	hooks(SceneHooks::new().push(Self::spawn_mover)),
+	inputs(
+		/// Opens the door, even if locked.
+		Open,
+		/// Closes the door, even if locked.
+		Close,
+		/// Prevents the door from being used or touched.
+		Lock,
+		Unlock,
+	),
+)]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct FuncDoor {
+	/// Direction to open in, in degrees around the up axis. -1 is up, and -2 is down.
+	pub angle: f32,
+	/// Units per second.
+	#[default(100.)]
+	pub speed: f32,
+	/// Units of the door left sticking out when open.
+	#[default(8.)]
+	pub lip: f32,
+	/// Seconds to stay open before closing. If -1, stays open.
+	#[default(3.)]
+	pub wait: f32,
+	/// Which set of sounds to play, up to the game to interpret with [`MoverStarted`] and [`MoverStopped`].
+	pub sounds: u32,
+	pub spawnflags: FgdFlags<FuncDoorFlags>,
+}
+impl FuncDoor {
+	fn spawn_mover(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
+		let Some(door) = view.world.get::<Self>(view.entity).cloned() else { return Ok(()) };
+		let flags = BitFlags::from(door.spawnflags);
+
+		let (mut closed, mut open) = calculate_travel(view, door.angle, door.lip);
+		if flags.contains(FuncDoorFlags::StartOpen) {
+			std::mem::swap(&mut closed, &mut open);
+		}
+		let wait = if flags.contains(FuncDoorFlags::Toggle) { -1. } else { door.wait };
+
+		let mut entity = view.world.entity_mut(view.entity);
+		entity.insert(TwoPositionMover::new(closed, open, door.speed / view.tb_config.scale, wait));
+		if flags.contains(FuncDoorFlags::StartLocked) {
+			entity.insert(MoverLocked);
+		}
+		Ok(())
+	}
+}
+
+#[bitflags]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FuncButtonFlags {
+	/// Start locked
+	/// Can't be used or touched until it receives the `Unlock` input.
+	StartLocked = 1,
+}
+
+/// Slides in the direction of `angle` when used or touched, firing its targets once pressed in, then returns after `wait` seconds.
+#[solid_class(
+	base(Mover, Target, TargetDelay, TargetMessage, KillTarget),
+	hooks(SceneHooks::new().push(Self::spawn_mover)),
+	inputs(
+		/// Presses the button, even if locked.
+		Press,
+		/// Prevents the button from being used or touched.
+		Lock,
+		Unlock,
+	),
+)]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct FuncButton {
+	/// Direction to move in when pressed, in degrees around the up axis. -1 is up, and -2 is down.
+	pub angle: f32,
+	/// Units per second.
+	#[default(40.)]
+	pub speed: f32,
+	/// Units of the button left sticking out when pressed.
+	#[default(4.)]
+	pub lip: f32,
+	/// Seconds to stay pressed before returning. If -1, stays pressed.
+	#[default(1.)]
+	pub wait: f32,
+	/// Which set of sounds to play, up to the game to interpret with [`MoverStarted`] and [`MoverStopped`].
+	pub sounds: u32,
+	pub spawnflags: FgdFlags<FuncButtonFlags>,
+}
+impl FuncButton {
+	fn spawn_mover(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
+		let Some(button) = view.world.get::<Self>(view.entity).cloned() else { return Ok(()) };
+		let (closed, open) = calculate_travel(view, button.angle, button.lip);
+
+		let mut entity = view.world.entity_mut(view.entity);
+		entity.insert(TwoPositionMover::new(closed, open, button.speed / view.tb_config.scale, button.wait));
+		if BitFlags::from(button.spawnflags).contains(FuncButtonFlags::StartLocked) {
+			entity.insert(MoverLocked);
+		}
+		Ok(())
+	}
+}
+
+/// Lift that starts lowered, rising when used or touched, and lowering again after [`PLAT_WAIT`] seconds.
+#[solid_class(base(Mover), hooks(SceneHooks::new().push(Self::spawn_mover)))]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct FuncPlat {
+	/// Units per second.
+	#[default(150.)]
+	pub speed: f32,
+	/// Units to lower by. If 0, lowers by its height minus 8.
+	pub height: f32,
+	/// Which set of sounds to play, up to the game to interpret with [`MoverStarted`] and [`MoverStopped`].
+	pub sounds: u32,
+}
+impl FuncPlat {
+	fn spawn_mover(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
+		let Some(plat) = view.world.get::<Self>(view.entity).cloned() else { return Ok(()) };
+		let open = view
+			.world
+			.get::<Transform>(view.entity)
+			.map(|transform| transform.translation)
+			.unwrap_or_default();
+		let (min, max) = mesh_bounds(view);
+		let height = if plat.height > 0. {
+			plat.height / view.tb_config.scale
+		} else {
+			(max.y - min.y - 8. / view.tb_config.scale).max(0.)
+		};
+
+		view.world.entity_mut(view.entity).insert(TwoPositionMover::new(
+			open - Vec3::Y * height,
+			open,
+			plat.speed / view.tb_config.scale,
+			PLAT_WAIT,
+		));
+		Ok(())
+	}
+}
+
+#[bitflags]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FuncRotatingFlags {
+	/// Start on
+	StartOn = 1,
+	/// Reverse
+	Reverse = 1 << 1,
+	/// X axis
+	/// Rotate around the forward axis instead of the up axis.
+	XAxis = 1 << 2,
+	/// Y axis
+	/// Rotate around the left axis instead of the up axis.
+	YAxis = 1 << 3,
+}
+
+/// Continuously rotates around its origin, toggled when used. Use an origin brush to choose the point it rotates around.
+#[solid_class(base(Mover), hooks(SceneHooks::new().push(Self::spawn_mover)))]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct FuncRotating {
+	/// Degrees per second.
+	#[default(100.)]
+	pub speed: f32,
+	pub spawnflags: FgdFlags<FuncRotatingFlags>,
+}
+impl FuncRotating {
+	fn spawn_mover(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
+		let Some(rotating) = view.world.get::<Self>(view.entity).cloned() else { return Ok(()) };
+		let flags = BitFlags::from(rotating.spawnflags);
+
+		let axis = if flags.contains(FuncRotatingFlags::XAxis) {
+			Vec3::X
+		} else if flags.contains(FuncRotatingFlags::YAxis) {
+			Vec3::Y
+		} else {
+			Vec3::Z
+		};
+		let direction = if flags.contains(FuncRotatingFlags::Reverse) { -1. } else { 1. };
+
+		view.world.entity_mut(view.entity).insert(Rotator {
+			axis: axis.trenchbroom_to_bevy(),
+			speed: rotating.speed.to_radians() * direction,
+			enabled: flags.contains(FuncRotatingFlags::StartOn),
+		});
+		Ok(())
+	}
+}
+
+/// Moves along a chain of `path_corner`s, starting at the one it targets.
+/// If it has a `targetname`, it waits to be used before starting, and when it stops at a corner with a negative `wait`.
+///
+/// Without an origin brush, the train's lowest corner is placed on path corners, like in Quake.
+#[solid_class(base(Mover, Target), hooks(SceneHooks::new().push(Self::spawn_mover)))]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct FuncTrain {
+	/// Units per second.
+	#[default(100.)]
+	pub speed: f32,
+	/// Which set of sounds to play, up to the game to interpret with [`MoverStarted`] and [`MoverStopped`].
+	pub sounds: u32,
+}
+impl FuncTrain {
+	fn spawn_mover(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
+		let Some(train) = view.world.get::<Self>(view.entity).cloned() else { return Ok(()) };
+		let offset = if view.src_entity.properties.contains_key("origin") {
+			Vec3::ZERO
+		} else {
+			mesh_bounds(view).0
+		};
+
+		// Trains with a targetname wait to be used before starting.
+		let paused = view
+			.world
+			.get::<Targetable>(view.entity)
+			.is_some_and(|targetable| !targetable.targetname.is_empty());
+
+		view.world.entity_mut(view.entity).insert(FollowPath {
+			speed: train.speed / view.tb_config.scale,
+			offset,
+			paused,
+			..default()
+		});
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use bevy::time::TimeUpdateStrategy;
+
+	use bevy::reflect::TypeRegistry;
+
+	use super::*;
+	use crate::{
+		class::io::{EntityIoPlugin, InputContext},
+		util::{spawn_test_entity, target, targetable},
+	};
+
+	#[derive(Resource, Default)]
+	struct LockedUses(usize);
+
+	fn app() -> App {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin, PathClassesPlugin, MoverClassesPlugin))
+			.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+			.init_resource::<LockedUses>()
+			.add_observer(|_: On<LockedMoverUsed>, mut uses: ResMut<LockedUses>| uses.0 += 1);
+		app
+	}
+
+	fn update(app: &mut App, seconds: f32) {
+		for _ in 0..(seconds * 10.).round() as usize {
+			app.update();
+		}
+	}
+
+	#[test]
+	fn button_opens_door() {
+		let mut app = app();
+
+		let world = app.world_mut();
+		let door = world
+			.spawn((
+				FuncDoor::default(),
+				TwoPositionMover::new(Vec3::ZERO, Vec3::Y * 2., 10., -1.),
+				targetable("door"),
+			))
+			.id();
+		let button = world
+			.spawn((FuncButton::default(), TwoPositionMover::new(Vec3::ZERO, Vec3::Z, 10., 1.), target("door")))
+			.id();
+
+		world.trigger(Use {
+			entity: button,
+			source: button,
+			activator: None,
+		});
+		update(&mut app, 1.);
+
+		let translation = |app: &App, entity| app.world().get::<Transform>(entity).unwrap().translation;
+		assert_eq!(translation(&app, door), Vec3::Y * 2.);
+		assert_eq!(app.world().get::<TwoPositionMover>(button).unwrap().state, MoverState::Open);
+
+		// The button returns after waiting, but the door stays open.
+		update(&mut app, 1.5);
+		assert_eq!(translation(&app, button), Vec3::ZERO);
+		assert_eq!(translation(&app, door), Vec3::Y * 2.);
+
+		let context = InputContext {
+			entity: door,
+			source: button,
+			activator: None,
+		};
+		app.world_mut().trigger(ClassInput::new(context, FuncDoorInput::Lock));
+		app.world_mut().trigger(ClassInput::new(context, FuncDoorInput::Close));
+		update(&mut app, 1.);
+		assert_eq!(translation(&app, door), Vec3::ZERO);
+
+		// Locked doors can't be used.
+		app.world_mut().trigger(Use {
+			entity: door,
+			source: button,
+			activator: None,
+		});
+		update(&mut app, 1.);
+		assert_eq!(translation(&app, door), Vec3::ZERO);
+		assert_eq!(app.world().resource::<LockedUses>().0, 1);
+	}
+
+	#[test]
+	fn spawnflags_with_skill_bits() {
+		let mut type_registry = TypeRegistry::new();
+		type_registry.register::<FuncDoor>();
+		type_registry.register::<FuncButton>();
+		type_registry.register::<FuncRotating>();
+
+		// 256 is Quake's "Not in Easy", which these classes don't define, but still have to parse.
+		let spawn = |classname| spawn_test_entity(&type_registry, &default(), &[("classname", classname), ("spawnflags", "257")]).unwrap();
+
+		let (world, entity) = spawn("func_door");
+		assert_eq!(
+			BitFlags::from(world.get::<FuncDoor>(entity).unwrap().spawnflags),
+			FuncDoorFlags::StartOpen
+		);
+		let (world, entity) = spawn("func_button");
+		assert_eq!(
+			BitFlags::from(world.get::<FuncButton>(entity).unwrap().spawnflags),
+			FuncButtonFlags::StartLocked
+		);
+		let (world, entity) = spawn("func_rotating");
+		assert_eq!(
+			BitFlags::from(world.get::<FuncRotating>(entity).unwrap().spawnflags),
+			FuncRotatingFlags::StartOn
+		);
+	}
+
+	#[cfg(feature = "bsp")]
+	#[test]
+	fn door_opens_area_portal() {
+		use crate::bsp::areas::BspAreasPlugin;
+
+		let mut app = app();
+		app.add_observer(BspAreasPlugin::set_area_portal_state)
+			.add_observer(BspAreasPlugin::use_area_portal);
+
+		let world = app.world_mut();
+		let portal = world.spawn((FuncAreaportal::default(), targetable("portal"))).id();
+		let door = world
+			.spawn((
+				FuncDoor::default(),
+				TwoPositionMover::new(Vec3::ZERO, Vec3::Y * 2., 2., 1.),
+				target("portal"),
+			))
+			.id();
+		let is_open = |app: &App| app.world().get::<FuncAreaportal>(portal).unwrap().open;
+
+		// The door's use of the portal doesn't toggle it back closed.
+		app.world_mut().trigger(Use {
+			entity: door,
+			source: door,
+			activator: None,
+		});
+		update(&mut app, 0.1);
+		assert!(is_open(&app));
+
+		// It stays open until the door has waited and fully closed.
+		update(&mut app, 2.5);
+		assert_eq!(app.world().get::<TwoPositionMover>(door).unwrap().state, MoverState::Closing);
+		assert!(is_open(&app));
+		update(&mut app, 1.);
+		assert_eq!(app.world().get::<TwoPositionMover>(door).unwrap().state, MoverState::Closed);
+		assert!(!is_open(&app));
+	}
+
+	#[test]
+	fn train_follows_corners() {
+		let mut app = app();
+
+		let world = app.world_mut();
+		let a = spawn_test_path_node(world, "a", "b", Vec3::ZERO, 0.);
+		let b = spawn_test_path_node(world, "b", "a", Vec3::X * 2., -1.);
+		let train = world
+			.spawn((
+				FuncTrain::default(),
+				FollowPath {
+					speed: 10.,
+					offset: Vec3::NEG_Y,
+					..default()
+				},
+				target("a"),
+			))
+			.id();
+
+		// The train's offset is kept from each corner, and it stops at `b` because of its negative wait.
+		update(&mut app, 1.);
+		assert_eq!(app.world().get::<Transform>(train).unwrap().translation, vec3(2., 1., 0.));
+		let state = app.world().get::<FollowPath>(train).unwrap();
+		assert_eq!((state.node, state.paused), (Some(b), true));
+
+		app.world_mut().trigger(Use {
+			entity: train,
+			source: train,
+			activator: None,
+		});
+		app.update();
+		assert_eq!(app.world().get::<FollowPath>(train).unwrap().next, Some(a));
+	}
+}
@@ -0,0 +1,381 @@
+use smart_default::SmartDefault;
+
+use super::*;
+use crate::class::io::Targetnames;
+
+/// Adds `path_corner`, and links [`PathNode`]s together by their `target`s for [`FollowPath`] to move entities along.
+#[derive(Default)]
+pub struct PathClassesPlugin;
+impl Plugin for PathClassesPlugin {
+	fn build(&self, app: &mut App) {
+		#[rustfmt::skip]
+		app
+			.register_type::<PathCorner>()
+			.register_type::<PathNode>()
+			.register_type::<FollowPath>()
+			.add_systems(Update, (Self::resolve_paths, Self::start_following, Self::follow_paths).chain())
+		;
+	}
+}
+impl PathClassesPlugin {
+	/// Rebuilds the [`next`](PathNode::next) and [`previous`](PathNode::previous) links of every [`PathNode`] when nodes are added or removed, or targets or targetnames change.
+	pub fn resolve_paths(
+		mut query: Query<(Entity, &mut PathNode, Option<Ref<Target>>)>,
+		mut removed: RemovedComponents<PathNode>,
+		mut removed_targets: RemovedComponents<Target>,
+		targetnames: Res<Targetnames>,
+	) {
+		let removed = removed.read().count() > 0 || removed_targets.read().count() > 0;
+		let changed = query
+			.iter_mut()
+			.any(|(_, node, target)| node.is_added() || target.is_some_and(|target| target.is_changed()));
+		if !changed && !removed && !targetnames.is_changed() {
+			return;
+		}
+
+		let links = query
+			.iter()
+			.map(|(entity, _, target)| {
+				let next = target
+					.map(|target| {
+						targetnames
+							.get(&target.target)
+							.iter()
+							.copied()
+							.filter(|next| query.contains(*next))
+							.collect_vec()
+					})
+					.unwrap_or_default();
+				(entity, next)
+			})
+			.collect_vec();
+
+		for (_, mut node, _) in &mut query {
+			node.next.clear();
+			node.previous.clear();
+		}
+		for (entity, next) in links {
+			for &next_entity in &next {
+				if let Ok((_, mut next_node, _)) = query.get_mut(next_entity) {
+					next_node.previous.push(entity);
+				}
+			}
+			if let Ok((_, mut node, _)) = query.get_mut(entity) {
+				node.next = next;
+			}
+		}
+	}
+
+	/// Places new followers at their starting node, using the node their `target` points at if [`FollowPath::node`] isn't set.
+	///
+	/// Changing the `target` of an existing follower moves it back onto the path from the new target's node.
+	pub fn start_following(
+		mut query: Query<(Entity, &mut FollowPath, &mut Transform, Option<&Target>), Or<(Added<FollowPath>, Changed<Target>)>>,
+		node_query: NodeQuery,
+		targetnames: Res<Targetnames>,
+	) {
+		for (entity, mut follow_path, mut transform, target) in &mut query {
+			if !follow_path.is_added() {
+				follow_path.node = None;
+				follow_path.next = None;
+				follow_path.previous = None;
+				follow_path.moving = false;
+				follow_path.progress = 0.;
+				follow_path.wait_timer = None;
+			}
+			if follow_path.node.is_none() {
+				follow_path.node = target.and_then(|target| targetnames.get(&target.target).iter().copied().find(|node| node_query.contains(*node)));
+			}
+			let Some((_, node_transform)) = follow_path.node.and_then(|node| node_query.get(node).ok()) else {
+				warn!("{entity} is following a path, but doesn't have a path node to start at");
+				continue;
+			};
+
+			transform.translation = node_transform.translation - follow_path.offset;
+		}
+	}
+
+	pub fn follow_paths(mut commands: Commands, mut query: Query<(Entity, &mut FollowPath, &mut Transform)>, node_query: NodeQuery, time: Res<Time>) {
+		for (entity, mut follow_path, mut transform) in &mut query {
+			let Some(node) = follow_path.node else { continue };
+			let Ok((node_data, node_transform)) = node_query.get(node) else { continue };
+
+			if !follow_path.moving {
+				if follow_path.paused {
+					continue;
+				}
+				if let Some(timer) = &mut follow_path.wait_timer {
+					if !timer.tick(time.delta()).is_finished() {
+						continue;
+					}
+					follow_path.wait_timer = None;
+				}
+
+				if follow_path.next.is_none() {
+					follow_path.next = node_data.next.first().copied();
+				}
+				// The end of the path.
+				let Some(next) = follow_path.next else { continue };
+
+				follow_path.moving = true;
+				follow_path.progress = 0.;
+				commands.trigger(PathNodeDeparted { entity, node, next });
+			}
+
+			let Some(next) = follow_path.next else { continue };
+			let Ok((next_data, next_transform)) = node_query.get(next) else {
+				// The node was despawned, stop where we are.
+				follow_path.moving = false;
+				follow_path.next = None;
+				follow_path.paused = true;
+				continue;
+			};
+
+			let start = node_transform.translation;
+			let end = next_transform.translation;
+			let speed = node_data.speed.unwrap_or(follow_path.speed);
+			let length = start.distance(end);
+			follow_path.progress = if length > 0. {
+				(follow_path.progress + speed * time.delta_secs() / length).min(1.)
+			} else {
+				1.
+			};
+
+			let position = match follow_path.interpolation {
+				PathInterpolation::Linear => start.lerp(end, follow_path.progress),
+				PathInterpolation::CatmullRom => {
+					let translation = |node: Option<Entity>| {
+						node.and_then(|node| node_query.get(node).ok())
+							.map(|(_, transform)| transform.translation)
+					};
+					let before = translation(follow_path.previous).unwrap_or(start);
+					let after = translation(next_data.next.first().copied()).unwrap_or(end);
+					catmull_rom(before, start, end, after, follow_path.progress)
+				}
+			};
+			transform.translation = position - follow_path.offset;
+
+			if follow_path.progress < 1. {
+				continue;
+			}
+
+			follow_path.previous = Some(node);
+			follow_path.node = Some(next);
+			follow_path.next = None;
+			follow_path.moving = false;
+			if next_data.wait > 0. {
+				follow_path.wait_timer = Some(Timer::from_seconds(next_data.wait, TimerMode::Once));
+			} else if next_data.wait < 0. {
+				follow_path.paused = true;
+			}
+			commands.trigger(PathNodeArrived { entity, node: next });
+		}
+	}
+}
+
+type NodeQuery<'w, 's> = Query<'w, 's, (&'static PathNode, &'static Transform), Without<FollowPath>>;
+
+/// Interpolates between `p1` and `p2` along a uniform Catmull-Rom spline, where `p0` and `p3` are the points before and after them.
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+	let t2 = t * t;
+	let t3 = t2 * t;
+	0.5 * (2. * p1 + (p2 - p0) * t + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2 + (3. * p1 - p0 - 3. * p2 + p3) * t3)
+}
+
+/// Triggered on a [`FollowPath`] entity when it arrives at a [`PathNode`].
+///
+/// Observers can set [`FollowPath::next`] to choose which branch to take when the node targets more than one node.
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct PathNodeArrived {
+	#[event_target]
+	pub entity: Entity,
+	pub node: Entity,
+}
+
+/// Triggered on a [`FollowPath`] entity when it leaves a [`PathNode`] for the next.
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct PathNodeDeparted {
+	#[event_target]
+	pub entity: Entity,
+	pub node: Entity,
+	pub next: Entity,
+}
+
+/// A point along a path, linked to the nodes whose `targetname` matches its [`Target`].
+///
+/// `path_corner` inserts this, but it can be added to any entity with a [`Transform`] to make it part of a path.
+#[derive(Component, Reflect, Debug, Clone, Default)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct PathNode {
+	/// Units per second followers travel from this node to the next. If [`None`], they use their own [`FollowPath::speed`].
+	pub speed: Option<f32>,
+	/// Seconds followers wait at this node before continuing. If negative, they pause until [`FollowPath::paused`] is unset.
+	pub wait: f32,
+	/// Nodes this node targets, in the order they were spawned. More than one means the path branches here.
+	pub next: Vec<Entity>,
+	/// Nodes that target this node.
+	pub previous: Vec<Entity>,
+}
+
+/// How a [`FollowPath`] entity moves between nodes.
+#[derive(Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathInterpolation {
+	/// Moves in a straight line from node to node.
+	#[default]
+	Linear,
+	/// Moves along a smooth curve through every node, using the nodes before and after the current segment.
+	/// Speed is only approximate, as progress along each segment is based on the straight distance between nodes.
+	CatmullRom,
+}
+
+/// Moves an entity along a chain of [`PathNode`]s, stopping at each for its [`wait`](PathNode::wait).
+///
+/// If [`node`](Self::node) isn't set when this is added, the entity starts at the node its [`Target`] points at.
+#[derive(Component, Reflect, Debug, Clone, SmartDefault)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct FollowPath {
+	/// Units per second, unless overridden by [`PathNode::speed`].
+	#[default(100.)]
+	pub speed: f32,
+	pub interpolation: PathInterpolation,
+	/// Offset from the point on the entity that sits on path nodes to the entity's origin.
+	pub offset: Vec3,
+	/// If `true`, the entity stays at the node it's at. Set when arriving at a node with a negative [`wait`](PathNode::wait).
+	pub paused: bool,
+	/// The node the entity is at, or last left from.
+	pub node: Option<Entity>,
+	/// The node the entity is moving towards. When stopped at a node, this is where it will go next, defaulting to the first of the node's [`next`](PathNode::next) nodes.
+	pub next: Option<Entity>,
+	/// The node before [`node`](Self::node), used for [`PathInterpolation::CatmullRom`].
+	pub previous: Option<Entity>,
+	/// Whether the entity is currently travelling between [`node`](Self::node) and [`next`](Self::next).
+	pub moving: bool,
+	/// How far along the current segment the entity is, from 0 to 1.
+	pub progress: f32,
+	/// Counts down the [`wait`](PathNode::wait) of the node the entity is at.
+	pub wait_timer: Option<Timer>,
+}
+
+/// A point along a path, for `func_train`s and anything else with [`FollowPath`] to move along. Targets the next corner along the path.
+///
+/// Paths can loop, and branch by targeting more than one corner.
+#[point_class(base(Transform, Targetable, Target), hooks(SceneHooks::new().push(Self::spawn_node)))]
+#[derive(Debug, Clone, Default)]
+#[reflect(no_auto_register, Default)]
+pub struct PathCorner {
+	/// Units per second to travel to the next corner. If 0, followers keep their own speed.
+	pub speed: f32,
+	/// Seconds to wait at this corner before continuing. If negative, followers stop until started again, such as a `func_train` being used.
+	pub wait: f32,
+}
+impl PathCorner {
+	fn spawn_node(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
+		let Some(corner) = view.world.get::<Self>(view.entity).cloned() else { return Ok(()) };
+
+		view.world.entity_mut(view.entity).insert(PathNode {
+			speed: (corner.speed > 0.).then(|| corner.speed / view.tb_config.scale),
+			wait: corner.wait,
+			..default()
+		});
+		Ok(())
+	}
+}
+
+/// Spawns a [`PathNode`] at `translation` named `name` that leads to `target`, for tests.
+#[cfg(test)]
+pub(crate) fn spawn_test_path_node(world: &mut World, name: &str, target: &str, translation: Vec3, wait: f32) -> Entity {
+	world
+		.spawn((
+			PathNode { wait, ..default() },
+			Transform::from_translation(translation),
+			util::targetable(name),
+			util::target(target),
+		))
+		.id()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use bevy::time::TimeUpdateStrategy;
+
+	use super::*;
+	use crate::{class::io::EntityIoPlugin, util::target};
+
+	#[derive(Resource, Default)]
+	struct Arrivals(Vec<Entity>);
+
+	#[test]
+	fn follow_branching_loop() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin, PathClassesPlugin))
+			.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+			.init_resource::<Arrivals>()
+			.add_observer(|trigger: On<PathNodeArrived>, mut arrivals: ResMut<Arrivals>| arrivals.0.push(trigger.node));
+
+		// `a` -> `b` -> `a`, with `b` also branching off to `c` as it shares `a`'s targetname.
+		let world = app.world_mut();
+		let a = spawn_test_path_node(world, "a", "b", Vec3::ZERO, 0.);
+		let b = spawn_test_path_node(world, "b", "a", Vec3::X, 0.5);
+		let c = spawn_test_path_node(world, "a", "", Vec3::Z, -1.);
+		let follower = world.spawn((FollowPath { speed: 10., ..default() }, target("a"))).id();
+
+		app.update();
+		let path_node = |app: &App, entity| app.world().get::<PathNode>(entity).unwrap().clone();
+		assert_eq!(path_node(&app, b).next, [a, c]);
+		assert_eq!(path_node(&app, a).previous, [b]);
+		assert_eq!(app.world().get::<FollowPath>(follower).unwrap().node, Some(a));
+
+		while app.world().resource::<Arrivals>().0.len() < 3 {
+			app.update();
+		}
+		assert_eq!(app.world().resource::<Arrivals>().0, [b, a, b]);
+
+		// Take the branch to `c` when at `b`, where it pauses.
+		app.world_mut().get_mut::<FollowPath>(follower).unwrap().next = Some(c);
+		for _ in 0..20 {
+			app.update();
+		}
+		let follow_path = app.world().get::<FollowPath>(follower).unwrap();
+		assert_eq!((follow_path.node, follow_path.paused), (Some(c), true));
+		assert_eq!(app.world().get::<Transform>(follower).unwrap().translation, Vec3::Z);
+	}
+
+	#[test]
+	fn retarget_paths() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin, PathClassesPlugin))
+			.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)));
+
+		let world = app.world_mut();
+		let a = spawn_test_path_node(world, "a", "b", Vec3::ZERO, -1.);
+		let b = spawn_test_path_node(world, "b", "", Vec3::X, -1.);
+		let c = spawn_test_path_node(world, "c", "", Vec3::Y, -1.);
+		let follower = world.spawn((FollowPath { speed: 10., ..default() }, target("a"))).id();
+		app.update();
+		assert_eq!(app.world().get::<PathNode>(a).unwrap().next, [b]);
+
+		// Retargeting a node relinks the path.
+		app.world_mut().entity_mut(a).insert(target("c"));
+		app.update();
+		assert_eq!(app.world().get::<PathNode>(a).unwrap().next, [c]);
+		assert_eq!(app.world().get::<PathNode>(b).unwrap().previous, []);
+		assert_eq!(app.world().get::<PathNode>(c).unwrap().previous, [a]);
+
+		// Retargeting a follower moves it to its new starting node.
+		app.world_mut().entity_mut(follower).insert(target("b"));
+		app.update();
+		assert_eq!(app.world().get::<FollowPath>(follower).unwrap().node, Some(b));
+		assert_eq!(app.world().get::<Transform>(follower).unwrap().translation, Vec3::X);
+	}
+
+	#[test]
+	fn catmull_rom_passes_through_nodes() {
+		let points = [Vec3::ZERO, Vec3::X, vec3(2., 1., 0.), Vec3::Y * 3.];
+		assert_eq!(catmull_rom(points[0], points[1], points[2], points[3], 0.), points[1]);
+		assert_eq!(catmull_rom(points[0], points[1], points[2], points[3], 1.), points[2]);
+	}
+}
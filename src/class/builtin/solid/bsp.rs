@@ -7,3 +7,18 @@ use super::*;
 #[derive(Debug, Clone)]
 #[reflect(no_auto_register)]
 pub struct FuncDetail;
+
+/// Quake 2 area portal. Separates the world into areas, and while closed, hides everything on the other side of it.
+///
+/// Usually placed inside a door that targets it, which opens and closes it with [`SetAreaPortalState`](crate::bsp::areas::SetAreaPortalState).
+/// Other entities targeting it, such as buttons, toggle it when [used](crate::class::io::Use).
+#[solid_class(base(Targetable))]
+#[derive(Debug, Clone, Default)]
+#[reflect(no_auto_register)]
+pub struct FuncAreaportal {
+	/// The area portal's number. Set automatically by the compiler, you shouldn't need to touch this.
+	pub style: u32,
+	/// Whether the area portal lets areas behind it be seen. Area portals always start closed.
+	#[class(ignore)]
+	pub open: bool,
+}
@@ -17,7 +17,11 @@ impl Plugin for SolidClassesPlugin {
 		;
 
 		#[cfg(feature = "bsp")]
-		app.register_type::<FuncDetail>();
+		#[rustfmt::skip]
+		app
+			.register_type::<FuncDetail>()
+			.register_type::<FuncAreaportal>()
+		;
 	}
 }
 
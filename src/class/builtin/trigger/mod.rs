@@ -0,0 +1,315 @@
+use enumflags2::{BitFlags, bitflags};
+use fgd::FgdFlags;
+use smart_default::SmartDefault;
+
+use super::*;
+use crate::class::io::{CenterPrint, EntityIo, Use};
+
+/// Commonly used trigger setups. Used with [`TriggerClassesPlugin`] to choose which entities can activate trigger volumes.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TriggerWorkflow {
+	/// Any entity entering a trigger volume activates it.
+	AnyActivator,
+	/// Only entities with the [`TriggerActivator`] component activate trigger volumes, like how only players can touch triggers in Quake.
+	#[default]
+	TriggerActivatorOnly,
+	/// Don't register any ready-to-go trigger types, you're on your own.
+	Custom,
+}
+
+/// Adds `trigger_once`, `trigger_multiple`, `trigger_relay`, and `trigger_counter`, which fire their targets through [entity IO](crate::class::io).
+///
+/// `trigger_once` and `trigger_multiple` are activated by entering their brushes, which requires the `physics-integration` feature. They can always be activated by being used.
+#[derive(Default)]
+pub struct TriggerClassesPlugin(pub TriggerWorkflow);
+impl Plugin for TriggerClassesPlugin {
+	fn build(&self, app: &mut App) {
+		if self.0 == TriggerWorkflow::Custom {
+			return;
+		}
+
+		#[rustfmt::skip]
+		app
+			.insert_resource(self.0)
+			.register_type::<TouchTrigger>()
+			.register_type::<TriggerOnce>()
+			.register_type::<TriggerMultiple>()
+			.register_type::<TriggerRelay>()
+			.register_type::<TriggerCounter>()
+			.register_type::<TriggerActivator>()
+			.register_type::<TriggerCooldown>()
+			.add_observer(Self::use_touch_trigger)
+			.add_observer(Self::use_relay)
+			.add_observer(Self::use_counter)
+			.add_systems(Update, Self::tick_cooldowns)
+		;
+
+		#[cfg(feature = "physics-integration")]
+		app.add_observer(Self::touch);
+	}
+}
+impl TriggerClassesPlugin {
+	/// Activates touch triggers when a valid activator enters them, according to the [`TriggerWorkflow`].
+	#[cfg(feature = "physics-integration")]
+	pub fn touch(
+		trigger: On<crate::physics::SensorEntered>,
+		mut commands: Commands,
+		workflow: Res<TriggerWorkflow>,
+		activator_query: Query<(), With<TriggerActivator>>,
+		query: Query<(&TouchTrigger, Option<&TriggerMultiple>, Has<TriggerCooldown>)>,
+		mut io: EntityIo,
+	) {
+		let Ok((touch_trigger, multiple, cooling_down)) = query.get(trigger.sensor) else { return };
+		if BitFlags::from(touch_trigger.spawnflags).contains(TriggerFlags::NoTouch) {
+			return;
+		}
+		if *workflow == TriggerWorkflow::TriggerActivatorOnly && !activator_query.contains(trigger.entity) {
+			return;
+		}
+
+		activate_touch_trigger(&mut commands, &mut io, trigger.sensor, Some(trigger.entity), multiple, cooling_down);
+	}
+
+	pub fn use_touch_trigger(
+		trigger: On<Use>,
+		mut commands: Commands,
+		query: Query<(Option<&TriggerMultiple>, Has<TriggerCooldown>), With<TouchTrigger>>,
+		mut io: EntityIo,
+	) {
+		let Ok((multiple, cooling_down)) = query.get(trigger.entity) else { return };
+
+		activate_touch_trigger(&mut commands, &mut io, trigger.entity, trigger.activator, multiple, cooling_down);
+	}
+
+	pub fn use_relay(trigger: On<Use>, query: Query<(), With<TriggerRelay>>, mut io: EntityIo) {
+		if query.contains(trigger.entity) {
+			io.fire(trigger.entity, trigger.activator);
+		}
+	}
+
+	pub fn use_counter(trigger: On<Use>, mut commands: Commands, mut query: Query<&mut TriggerCounter>, mut io: EntityIo) {
+		let Ok(mut counter) = query.get_mut(trigger.entity) else { return };
+		if counter.activations >= counter.count {
+			return;
+		}
+		counter.activations += 1;
+
+		let message = if counter.activations < counter.count {
+			format!("Only {} more to go...", counter.count - counter.activations)
+		} else {
+			io.fire(trigger.entity, trigger.activator);
+			"Sequence completed!".to_string()
+		};
+
+		if !BitFlags::from(counter.spawnflags).contains(TriggerCounterFlags::NoMessage) {
+			commands.trigger(CenterPrint {
+				message,
+				source: trigger.entity,
+				activator: trigger.activator,
+			});
+		}
+	}
+
+	pub fn tick_cooldowns(mut commands: Commands, mut query: Query<(Entity, &mut TriggerCooldown)>, time: Res<Time>) {
+		for (entity, mut cooldown) in &mut query {
+			let Some(timer) = &mut cooldown.timer else { continue };
+			if timer.tick(time.delta()).is_finished() {
+				commands.entity(entity).remove::<TriggerCooldown>();
+			}
+		}
+	}
+}
+
+/// Fires a touch trigger's targets, then puts it on cooldown according to its `wait`.
+fn activate_touch_trigger(
+	commands: &mut Commands,
+	io: &mut EntityIo,
+	entity: Entity,
+	activator: Option<Entity>,
+	multiple: Option<&TriggerMultiple>,
+	cooling_down: bool,
+) {
+	if cooling_down {
+		return;
+	}
+
+	io.fire(entity, activator);
+
+	let timer = match multiple {
+		Some(multiple) if multiple.wait == 0. => return,
+		Some(multiple) if multiple.wait > 0. => Some(Timer::from_seconds(multiple.wait, TimerMode::Once)),
+		_ => None,
+	};
+	commands.entity(entity).insert(TriggerCooldown { timer });
+}
+
+/// Marks an entity as able to activate trigger volumes under [`TriggerWorkflow::TriggerActivatorOnly`], such as the player.
+#[derive(Component, Reflect, Debug, Clone, Copy, Default)]
+#[reflect(Component, Default)]
+pub struct TriggerActivator;
+
+/// Inserted on [`TouchTrigger`]s after activating, preventing them from activating again until it's removed.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct TriggerCooldown {
+	/// The component is removed when this finishes. If [`None`], the trigger never activates again, such as a `trigger_once` that has been activated.
+	pub timer: Option<Timer>,
+}
+
+#[bitflags]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TriggerFlags {
+	/// Not touchable
+	/// Can only be activated by being used by another entity.
+	NoTouch = 1,
+}
+
+#[bitflags]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TriggerCounterFlags {
+	/// No message
+	/// Don't trigger a `CenterPrint` event counting down the remaining activations.
+	NoMessage = 1,
+}
+
+fn touch_trigger_hooks() -> SceneHooks {
+	let hooks = SceneHooks::new();
+	#[cfg(feature = "client")]
+	let hooks = hooks.with(Visibility::Hidden);
+	#[cfg(feature = "physics-integration")]
+	let hooks = hooks.sensor_collider();
+	hooks
+}
+
+/// Invisible brush volume that fires its targets when an activator enters it. See [`TriggerWorkflow`].
+#[base_class(
+	classname("__touch_trigger"),
+	base(
+		Target,
+		Targetable,
+		TargetDelay,
+		TargetMessage,
+		KillTarget,
+		#[cfg(feature = "client")] Visibility,
+	),
+	hooks(touch_trigger_hooks()),
+)]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct TouchTrigger {
+	pub spawnflags: FgdFlags<TriggerFlags>,
+}
+
+/// Fires its targets the first time it's activated, then never again.
+#[solid_class(base(TouchTrigger))]
+#[derive(Debug, Clone)]
+#[reflect(no_auto_register)]
+pub struct TriggerOnce;
+
+/// Fires its targets every time it's activated, as long as it isn't waiting from the last activation.
+#[solid_class(base(TouchTrigger))]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct TriggerMultiple {
+	/// Seconds to wait after activating before it can be activated again. If 0, it can activate every time it's entered. If negative, it only activates once.
+	#[default(0.2)]
+	pub wait: f32,
+}
+
+/// Fires its targets when used. Useful for adding a delay, message, or killtarget to a chain of entities.
+#[point_class(base(Target, Targetable, TargetDelay, TargetMessage, KillTarget))]
+#[derive(Debug, Clone)]
+#[reflect(no_auto_register)]
+pub struct TriggerRelay;
+
+/// Fires its targets after being used `count` times.
+#[point_class(base(Target, Targetable, TargetDelay, TargetMessage, KillTarget))]
+#[derive(Debug, Clone, SmartDefault)]
+#[reflect(no_auto_register, Default)]
+pub struct TriggerCounter {
+	/// How many times it has to be used before firing.
+	#[default(2)]
+	pub count: u32,
+	pub spawnflags: FgdFlags<TriggerCounterFlags>,
+	/// How many times it has been used so far.
+	#[class(ignore)]
+	pub activations: u32,
+}
+
+#[cfg(test)]
+mod tests {
+	use bevy::reflect::TypeRegistry;
+
+	use super::*;
+	use crate::{
+		class::io::{EntityIoPlugin, Fire},
+		util::{spawn_test_entity, target, targetable},
+	};
+
+	#[derive(Resource, Default)]
+	struct Used(Vec<Entity>);
+
+	#[derive(Resource, Default)]
+	struct Messages(Vec<String>);
+
+	#[test]
+	fn trigger_chain() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin, TriggerClassesPlugin::default()))
+			.init_resource::<Used>()
+			.init_resource::<Messages>()
+			.add_observer(|trigger: On<Use>, mut used: ResMut<Used>| used.0.push(trigger.entity))
+			.add_observer(|trigger: On<CenterPrint>, mut messages: ResMut<Messages>| messages.0.push(trigger.message.clone()));
+
+		let world = app.world_mut();
+		let door = world.spawn(targetable("door")).id();
+		let counter = world.spawn((TriggerCounter::default(), targetable("counter"), target("door"))).id();
+		let once = world
+			.spawn((TriggerOnce, TouchTrigger::default(), targetable("once"), target("counter")))
+			.id();
+		let relay = world.spawn((TriggerRelay, target("once"))).id();
+		let button = world.spawn(target("counter")).id();
+
+		// The relay uses the `trigger_once`, which uses the counter. Using the relay again shouldn't do anything, as the `trigger_once` has been spent.
+		world.trigger(Fire::new(relay));
+		world.flush();
+		world.trigger(Fire::new(relay));
+		world.flush();
+		assert_eq!(app.world().resource::<Used>().0, [once, counter, once]);
+		assert!(app.world().get::<TriggerCooldown>(once).unwrap().timer.is_none());
+
+		let world = app.world_mut();
+		world.trigger(Fire::new(button));
+		world.flush();
+		world.trigger(Fire::new(button));
+		world.flush();
+		assert_eq!(app.world().resource::<Used>().0, [once, counter, once, counter, door, counter]);
+		assert_eq!(app.world().get::<TriggerCounter>(counter).unwrap().activations, 2);
+		assert_eq!(app.world().resource::<Messages>().0, ["Only 1 more to go...", "Sequence completed!"]);
+	}
+
+	#[test]
+	fn spawnflags_with_skill_bits() {
+		let mut type_registry = TypeRegistry::new();
+		type_registry.register::<TriggerOnce>();
+		type_registry.register::<TriggerCounter>();
+
+		// 256 is Quake's "Not in Easy", which these classes don't define, but still have to parse, as do bits used by other games and tools.
+		for spawnflags in ["257", "65537"] {
+			let spawn = |classname| spawn_test_entity(&type_registry, &default(), &[("classname", classname), ("spawnflags", spawnflags)]).unwrap();
+
+			let (world, entity) = spawn("trigger_once");
+			assert_eq!(
+				BitFlags::from(world.get::<TouchTrigger>(entity).unwrap().spawnflags),
+				TriggerFlags::NoTouch
+			);
+			let (world, entity) = spawn("trigger_counter");
+			assert_eq!(
+				BitFlags::from(world.get::<TriggerCounter>(entity).unwrap().spawnflags),
+				TriggerCounterFlags::NoMessage
+			);
+		}
+	}
+}
@@ -0,0 +1,548 @@
+//! Classes defined at runtime rather than by Rust types, registered via [`QuakeClassAppExt::register_dynamic_classes`],
+//! or imported from existing FGD files via [`QuakeClassAppExt::import_fgd`].
+//!
+//! Entities of these classes spawn with a [`DynamicClassProperties`] component and any [reflected components](DynamicClassComponent) their class specifies.
+//! Point and solid classes spawn with a [`Transform`] (and [`Visibility`] on clients) as well.
+use std::{fs, path::Path};
+
+use bevy::reflect::GetPath;
+use fgd::{FgdClassDefinition, FgdType, ReflectFgdType, parse_fgd};
+use qmap::QuakeEntityError;
+
+use super::*;
+
+/// Marker type that all dynamic classes share the [`TypeId`] of. Its type registration holds the [`DynamicQuakeClasses`] type data.
+#[derive(Reflect, Debug, Clone, Copy, Default)]
+pub struct DynamicQuakeClass;
+
+/// Type data of [`DynamicQuakeClass`] containing all dynamic classes. Use [`DynamicQuakeClasses::get`] to retrieve it from a type registry.
+#[derive(Clone, Default)]
+pub struct DynamicQuakeClasses {
+	classes: Vec<DynamicQuakeClassEntry>,
+	/// Every class that has been leaked for this type registry, reused when a class is registered with the same info again.
+	interned: Vec<&'static ErasedQuakeClass>,
+	next_revision: u64,
+}
+#[derive(Clone)]
+struct DynamicQuakeClassEntry {
+	erased_class: &'static ErasedQuakeClass,
+	class: DynamicClass,
+	/// Unquoted default property values of this class and all its base classes.
+	defaults: HashMap<String, String>,
+	/// Components of this class and all its dynamic base classes.
+	components: Vec<DynamicClassComponent>,
+	/// Unique for every time an entry is built, so derived classes can tell when a base class has changed.
+	revision: u64,
+	/// Names and revisions of the dynamic base classes this entry was built from.
+	base_revisions: Vec<(&'static str, u64)>,
+}
+impl DynamicQuakeClasses {
+	pub fn get(type_registry: &TypeRegistry) -> Option<&Self> {
+		type_registry.get_type_data::<Self>(TypeId::of::<DynamicQuakeClass>())
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &'static ErasedQuakeClass> + '_ {
+		self.classes.iter().map(|entry| entry.erased_class)
+	}
+
+	/// Gets a dynamic class by classname.
+	pub fn get_class(&self, classname: &str) -> Option<&'static ErasedQuakeClass> {
+		self.iter().find(|class| class.info.name == classname)
+	}
+
+	/// Returns the [`DynamicClass`] `class` was created from, or [`None`] if it isn't a dynamic class.
+	pub fn dynamic_class(&self, class: &ErasedQuakeClass) -> Option<&DynamicClass> {
+		self.entry(class).map(|entry| &entry.class)
+	}
+
+	/// Returns the definition `class` was created from, or [`None`] if it isn't a dynamic class.
+	pub fn definition(&self, class: &ErasedQuakeClass) -> Option<&FgdClassDefinition> {
+		self.dynamic_class(class).map(|class| &class.definition)
+	}
+
+	fn entry(&self, class: &ErasedQuakeClass) -> Option<&DynamicQuakeClassEntry> {
+		self.classes.iter().find(|entry| entry.erased_class.is(class))
+	}
+}
+
+/// A class defined at runtime. Can be deserialized from data files, for example in TOML:
+/// ```toml
+/// name = "item_crate"
+/// ty = "point"
+/// base = ["breakable"]
+/// size = "-8 -8 -8, 8 8 8"
+///
+/// [[properties]]
+/// name = "health"
+/// ty = { value = "integer" }
+/// default_value = "10"
+///
+/// [[components]]
+/// component = "my_game::Health"
+/// fields = { max = "health" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DynamicClass {
+	#[serde(flatten)]
+	pub definition: FgdClassDefinition,
+	/// Reflected components inserted into entities of this class, or classes deriving from it.
+	#[serde(default)]
+	pub components: Vec<DynamicClassComponent>,
+}
+impl From<FgdClassDefinition> for DynamicClass {
+	fn from(definition: FgdClassDefinition) -> Self {
+		Self {
+			definition,
+			components: Vec::new(),
+		}
+	}
+}
+
+/// A reflected component inserted when spawning an entity of a [`DynamicClass`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DynamicClassComponent {
+	/// Type path of the component, such as `my_game::Health`. It must be registered, and reflect `Component` and `Default`.
+	pub component: String,
+	/// Maps paths of fields in the component (such as `max` or `color.red`) to the properties they're parsed from.
+	/// The types of these fields must have [`ReflectFgdType`] registered.
+	///
+	/// Fields not in this map, or whose property isn't set and has no default, keep their default value.
+	#[serde(default)]
+	pub fields: HashMap<String, String>,
+}
+
+/// Registers dynamic classes, replacing existing dynamic classes with the same classname.
+///
+/// Base classes are looked up by classname, first in dynamic classes (including ones earlier in `classes`), then in Rust classes.
+/// Classes with base classes that can't be found are skipped with an error.
+///
+/// Re-registering an identical class reuses the existing one. When a class is replaced, dynamic classes deriving from it are rebuilt to use the new version.
+///
+/// NOTE: Classes have to be `'static`, so they're leaked and never freed. Leaked classes are reused whenever a class is registered with the same name, description, base classes,
+/// and editor attributes as before, so changing properties or components (such as when hot-reloading) doesn't leak, but each new combination of those does.
+pub fn register_dynamic_classes(type_registry: &mut TypeRegistry, classes: impl IntoIterator<Item = DynamicClass>) {
+	if !type_registry.contains(TypeId::of::<DynamicQuakeClass>()) {
+		type_registry.register::<DynamicQuakeClass>();
+	}
+
+	let rust_classes: HashMap<&str, &'static ErasedQuakeClass> = type_registry
+		.iter_with_data::<ReflectQuakeClass>()
+		.filter(|(_, class)| class.enabled)
+		.map(|(_, class)| (class.erased_class.info.name, class.erased_class))
+		.collect();
+
+	let mut dynamic_classes = DynamicQuakeClasses::get(type_registry).cloned().unwrap_or_default();
+
+	for class in classes {
+		let existing = dynamic_classes
+			.classes
+			.iter()
+			.position(|entry| entry.erased_class.info.name == class.definition.name);
+		if existing.is_some_and(|idx| dynamic_classes.classes[idx].class == class) {
+			continue;
+		}
+
+		let Some(entry) = build_dynamic_class(class, &mut dynamic_classes, &rust_classes) else { continue };
+		match existing {
+			Some(idx) => dynamic_classes.classes[idx] = entry,
+			None => dynamic_classes.classes.push(entry),
+		}
+	}
+
+	// Rebuild classes whose dynamic base classes have been replaced, until every class is built from the latest version of its bases.
+	while let Some(idx) = dynamic_classes.classes.iter().position(|entry| {
+		entry.base_revisions.iter().any(|(name, revision)| {
+			dynamic_classes
+				.classes
+				.iter()
+				.any(|base| base.erased_class.info.name == *name && base.revision != *revision)
+		})
+	}) {
+		let class = dynamic_classes.classes[idx].class.clone();
+		match build_dynamic_class(class, &mut dynamic_classes, &rust_classes) {
+			Some(entry) => dynamic_classes.classes[idx] = entry,
+			None => {
+				dynamic_classes.classes.remove(idx);
+			}
+		}
+	}
+
+	type_registry
+		.get_mut(TypeId::of::<DynamicQuakeClass>())
+		.expect("registered above")
+		.insert(dynamic_classes);
+}
+
+fn build_dynamic_class(
+	class: DynamicClass,
+	dynamic_classes: &mut DynamicQuakeClasses,
+	rust_classes: &HashMap<&str, &'static ErasedQuakeClass>,
+) -> Option<DynamicQuakeClassEntry> {
+	let definition = &class.definition;
+	let mut base = Vec::with_capacity(definition.base.len());
+	let mut defaults = HashMap::default();
+	let mut components = Vec::new();
+	let mut base_revisions = Vec::new();
+
+	for base_name in &definition.base {
+		let Some(base_class) = dynamic_classes
+			.get_class(base_name)
+			.or_else(|| rust_classes.get(base_name.as_str()).copied())
+		else {
+			error!("`{}`'s base class `{base_name}` isn't registered, skipping", definition.name);
+			return None;
+		};
+
+		match dynamic_classes.entry(base_class) {
+			Some(entry) => {
+				defaults.extend(entry.defaults.clone());
+				components.extend(entry.components.iter().cloned());
+				base_revisions.push((base_class.info.name, entry.revision));
+			}
+			None => collect_rust_class_defaults(&base_class.info, &mut defaults),
+		}
+		base.push(base_class);
+	}
+
+	for property in &definition.properties {
+		if let Some(value) = property.default_value_unquoted() {
+			defaults.insert(property.name.clone(), value);
+		}
+	}
+	components.extend(class.components.iter().cloned());
+
+	let erased_class = match dynamic_classes
+		.interned
+		.iter()
+		.copied()
+		.find(|interned| interned_class_matches(&interned.info, definition, &base))
+	{
+		Some(interned) => interned,
+		None => {
+			let erased_class = leak_dynamic_class(definition, base);
+			dynamic_classes.interned.push(erased_class);
+			erased_class
+		}
+	};
+
+	let revision = dynamic_classes.next_revision;
+	dynamic_classes.next_revision += 1;
+
+	Some(DynamicQuakeClassEntry {
+		erased_class,
+		class,
+		defaults,
+		components,
+		revision,
+		base_revisions,
+	})
+}
+
+/// Whether `info` was leaked from a class with the same `definition` and `base` classes, so it can be reused.
+fn interned_class_matches(info: &QuakeClassInfo, definition: &FgdClassDefinition, base: &[&'static ErasedQuakeClass]) -> bool {
+	info.ty == definition.ty
+		&& info.name == definition.name
+		&& info.description == definition.description.as_deref()
+		&& info.base.len() == base.len()
+		&& info.base.iter().zip(base).all(|(a, b)| std::ptr::eq(*a, *b))
+		&& info.model == definition.model.as_deref()
+		&& info.color == definition.color.as_deref()
+		&& info.iconsprite == definition.iconsprite.as_deref()
+		&& info.size == definition.size.as_deref()
+		&& info.decal == definition.decal
+}
+
+fn leak_dynamic_class(definition: &FgdClassDefinition, base: Vec<&'static ErasedQuakeClass>) -> &'static ErasedQuakeClass {
+	fn leak(s: &str) -> &'static str {
+		Box::leak(s.into())
+	}
+
+	Box::leak(Box::new(ErasedQuakeClass {
+		type_id: TypeId::of::<DynamicQuakeClass>(),
+		info: QuakeClassInfo {
+			ty: definition.ty,
+			name: leak(&definition.name),
+			description: definition.description.as_deref().map(leak),
+			base: Box::leak(base.into_boxed_slice()),
+
+			model: definition.model.as_deref().map(leak),
+			color: definition.color.as_deref().map(leak),
+			iconsprite: definition.iconsprite.as_deref().map(leak),
+			size: definition.size.as_deref().map(leak),
+			decal: definition.decal,
+
+			// Property defaults and flags can't be represented without Rust types, see `DynamicQuakeClasses::definition` instead.
+			properties: &[],
+			// Named inputs and outputs are only supported for Rust classes.
+			inputs: &[],
+			outputs: &[],
+		},
+		spawn_fn: spawn_dynamic_class,
+	}))
+}
+
+fn collect_rust_class_defaults(info: &QuakeClassInfo, defaults: &mut HashMap<String, String>) {
+	for base in info.base {
+		collect_rust_class_defaults(&base.info, defaults);
+	}
+
+	for property in info.properties {
+		let Some(default_value) = property.default_value else { continue };
+		let value = default_value();
+		let value = value.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(&value);
+		defaults.insert(property.name.to_string(), value.to_string());
+	}
+}
+
+/// Parses the source of an FGD file and [registers](register_dynamic_classes) its classes.
+///
+/// If `include_dir` is specified, files included via `@include` are read relative to it and imported first, otherwise they're ignored.
+pub fn import_fgd(type_registry: &mut TypeRegistry, source: &str, include_dir: Option<&Path>) -> anyhow::Result<()> {
+	let file = parse_fgd(source)?;
+
+	for include in &file.includes {
+		let Some(include_dir) = include_dir else {
+			warn!("Ignoring `@include \"{include}\"` in FGD imported from a string, use `import_fgd_file` instead");
+			continue;
+		};
+
+		let path = include_dir.join(include);
+		let source = fs::read_to_string(&path).map_err(|err| anyhow!("reading {}: {err}", path.display()))?;
+		import_fgd(type_registry, &source, path.parent()).map_err(|err| anyhow!("{}: {err}", path.display()))?;
+	}
+
+	register_dynamic_classes(type_registry, file.classes.into_iter().map(DynamicClass::from));
+
+	Ok(())
+}
+
+fn spawn_dynamic_class(view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
+	// This is called for every dynamic class in the hierarchy, the first one spawns everything.
+	if view.world.entity(view.entity).contains::<DynamicClassProperties>() {
+		return Ok(());
+	}
+
+	let type_registry = view.type_registry;
+	let entry = DynamicQuakeClasses::get(type_registry)
+		.and_then(|classes| classes.entry(view.class))
+		.ok_or_else(|| anyhow!("dynamic class `{}` isn't registered", view.class.info.name))?;
+
+	let mut properties = entry.defaults.clone();
+	properties.extend(view.src_entity.properties.iter().map(|(key, value)| (key.clone(), value.clone())));
+
+	for component in &entry.components {
+		insert_dynamic_component(&mut view.world.entity_mut(view.entity), type_registry, component, &properties)
+			.map_err(|err| anyhow!("component `{}`: {err}", component.component))?;
+	}
+
+	let entity = view.world.entity(view.entity);
+	let spawn_transform = !view.class.info.ty.is_base() && !entity.contains::<Transform>();
+	#[cfg(feature = "client")]
+	let spawn_visibility = !view.class.info.ty.is_base() && !entity.contains::<Visibility>();
+
+	view.world.entity_mut(view.entity).insert(DynamicClassProperties {
+		classname: view.class.info.name.to_string(),
+		properties,
+	});
+
+	if spawn_transform {
+		<Transform as QuakeClass>::class_spawn(view)?;
+	}
+	#[cfg(feature = "client")]
+	if spawn_visibility {
+		<Visibility as QuakeClass>::class_spawn(view)?;
+	}
+
+	Ok(())
+}
+
+fn insert_dynamic_component(
+	entity: &mut EntityWorldMut,
+	type_registry: &TypeRegistry,
+	component: &DynamicClassComponent,
+	properties: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+	let registration = type_registry
+		.get_with_type_path(&component.component)
+		.ok_or_else(|| anyhow!("not registered"))?;
+	let reflect_default = registration
+		.data::<ReflectDefault>()
+		.ok_or_else(|| anyhow!("doesn't reflect `Default`"))?;
+	let reflect_component = registration
+		.data::<ReflectComponent>()
+		.ok_or_else(|| anyhow!("doesn't reflect `Component`"))?;
+
+	let mut value = reflect_default.default();
+
+	for (field_path, property) in &component.fields {
+		let Some(input) = properties.get(property) else { continue };
+
+		let field = value
+			.reflect_path_mut(field_path.as_str())
+			.map_err(|err| anyhow!("field `{field_path}`: {err}"))?;
+		let fgd_type = field
+			.get_represented_type_info()
+			.and_then(|info| type_registry.get_type_data::<ReflectFgdType>(info.type_id()))
+			.ok_or_else(|| anyhow!("field `{field_path}`'s type doesn't reflect `FgdType`"))?;
+
+		let parsed = (fgd_type.fgd_parse)(input).map_err(|err| anyhow!("property `{property}`: {err}"))?;
+		field.try_apply(&*parsed).map_err(|err| anyhow!("field `{field_path}`: {err}"))?;
+	}
+
+	reflect_component.insert(entity, value.as_partial_reflect(), type_registry);
+
+	Ok(())
+}
+
+/// The properties of an entity of a dynamic class, with defaults from its class definition filled in for properties the map doesn't set.
+#[derive(Component, Reflect, Debug, Clone, Default)]
+#[reflect(Component, Debug, Clone, Default)]
+pub struct DynamicClassProperties {
+	pub classname: String,
+	pub properties: HashMap<String, String>,
+}
+impl DynamicClassProperties {
+	/// Helper function to try to parse an [`FgdType`] property, like [`QuakeMapEntity::get`].
+	pub fn get<T: FgdType>(&self, key: &str) -> Result<T, QuakeEntityError> {
+		let s = self
+			.properties
+			.get(key)
+			.ok_or_else(|| QuakeEntityError::RequiredPropertyNotFound { property: key.to_string() })?;
+
+		T::fgd_parse(s).map_err(|err| QuakeEntityError::PropertyParseError {
+			property: key.to_string(),
+			value: s.to_string(),
+			required_type: std::any::type_name::<T>(),
+			error: format!("{err}"),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::util::*;
+
+	#[test]
+	fn import_and_spawn() {
+		let mut app = App::new();
+		app.init_resource::<AppTypeRegistry>()
+			.add_plugins(builtin::BasicClassesPlugins)
+			.import_fgd(
+				r#"
+				@BaseClass = breakable [ health(integer) : "Health" : 10 ]
+				@PointClass base(breakable) size(-8 -8 -8, 8 8 8) = item_crate : "A crate"
+				[
+					spawnflags(flags) = [ 1 : "Floating" : 1 ]
+					message(string) : "Message" : "Hello"
+				]
+				"#,
+			);
+
+		let type_registry = app.world().resource::<AppTypeRegistry>().read();
+		assert_eq!(generate_class_map(&type_registry)["item_crate"].info.base[0].info.name, "breakable");
+
+		let fgd = fgd::write_fgd(&type_registry);
+		assert!(fgd.contains("@BaseClass = breakable\n[\n\thealth(integer) : \"Health\" : 10 : \"\"\n]\n"));
+		assert!(fgd.contains("@PointClass base(breakable) size(-8 -8 -8, 8 8 8) = item_crate : \"A crate\"\n"));
+
+		let (world, entity) = spawn_test_entity(&type_registry, &default(), &[("classname", "item_crate"), ("health", "25")]).unwrap();
+
+		let properties = world.entity(entity).get::<DynamicClassProperties>().unwrap();
+		assert_eq!(properties.classname, "item_crate");
+		assert_eq!(properties.get::<u32>("health"), Ok(25));
+		assert_eq!(properties.get::<u32>("spawnflags"), Ok(1));
+		assert_eq!(properties.get::<String>("message").unwrap(), "Hello");
+		assert!(world.entity(entity).contains::<Transform>());
+	}
+
+	#[test]
+	fn reimport() {
+		const FGD: &str = r#"
+			@BaseClass = breakable [ health(integer) : "Health" : 10 ]
+			@PointClass base(breakable) = item_crate : "A crate" []
+		"#;
+
+		let mut app = App::new();
+		app.init_resource::<AppTypeRegistry>().import_fgd(FGD);
+		let crate_class = |app: &App| {
+			let type_registry = app.world().resource::<AppTypeRegistry>().read();
+			DynamicQuakeClasses::get(&type_registry).unwrap().get_class("item_crate").unwrap()
+		};
+		let original = crate_class(&app);
+
+		// Identical classes are reused.
+		app.import_fgd(FGD);
+		assert!(std::ptr::eq(crate_class(&app), original));
+
+		// Classes deriving from a replaced class use the new version.
+		app.import_fgd(r#"@BaseClass = breakable [ health(integer) : "Health" : 20 ]"#);
+		let type_registry = app.world().resource::<AppTypeRegistry>().read();
+		let classes = DynamicQuakeClasses::get(&type_registry).unwrap();
+		assert_eq!(classes.iter().count(), 2);
+		let item_crate = classes.get_class("item_crate").unwrap();
+		assert!(std::ptr::eq(item_crate.info.base[0], classes.get_class("breakable").unwrap()));
+
+		let (world, entity) = spawn_test_entity(&type_registry, &default(), &[("classname", "item_crate")]).unwrap();
+		assert_eq!(world.entity(entity).get::<DynamicClassProperties>().unwrap().get::<u32>("health"), Ok(20));
+		drop(type_registry);
+
+		// Only changing defaults doesn't leak a new class, and going back to a previous version reuses the class leaked for it.
+		assert!(std::ptr::eq(crate_class(&app), original));
+		app.import_fgd(r#"@PointClass base(breakable) = item_crate : "A sturdy crate" []"#);
+		assert!(!std::ptr::eq(crate_class(&app), original));
+		app.import_fgd(FGD);
+		assert!(std::ptr::eq(crate_class(&app), original));
+	}
+
+	#[test]
+	fn reflected_components() {
+		#[derive(Component, Reflect, Debug, Default, PartialEq)]
+		#[reflect(Component, Default)]
+		struct Health {
+			max: u32,
+			regen: f32,
+			tint: Vec3,
+		}
+
+		let class: DynamicClass = TomlMaterialDeserializer
+			.deserialize(
+				br#"
+				name = "item_medkit"
+				ty = "point"
+
+				[[properties]]
+				name = "amount"
+				ty = { value = "integer" }
+				default_value = "10"
+
+				[[properties]]
+				name = "tint"
+				ty = { value = "vector" }
+
+				[[components]]
+				component = "bevy_trenchbroom::class::dynamic::tests::Health"
+				fields = { max = "amount", tint = "tint" }
+				"#,
+			)
+			.unwrap();
+
+		let mut app = App::new();
+		app.init_resource::<AppTypeRegistry>()
+			.add_plugins((QuakeClassPlugin, builtin::BasicClassesPlugins))
+			.register_type::<Health>()
+			.register_dynamic_classes([class]);
+
+		let type_registry = app.world().resource::<AppTypeRegistry>().read();
+		let (world, entity) = spawn_test_entity(&type_registry, &default(), &[("classname", "item_medkit"), ("tint", "1 0.5 0")]).unwrap();
+
+		assert_eq!(
+			world.entity(entity).get::<Health>(),
+			Some(&Health {
+				max: 10,
+				regen: 0.,
+				tint: vec3(1., 0.5, 0.),
+			})
+		);
+	}
+}
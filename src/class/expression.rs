@@ -0,0 +1,703 @@
+//! An evaluator for [TrenchBroom's expression language](https://trenchbroom.github.io/manual/latest/#expression_language),
+//! used by the `model` and `iconsprite` class attributes to choose what an entity looks like based on its properties.
+//!
+//! # Examples
+//! ```
+//! # use bevy::platform::collections::HashMap;
+//! # use bevy_trenchbroom::class::expression::*;
+//! let expression = Expression::parse(r#"{{ spawnflags & 1 -> "models/big.glb", "models/small.glb" }}"#).unwrap();
+//!
+//! let properties = HashMap::from([("spawnflags".to_string(), "3".to_string())]);
+//! assert_eq!(expression.evaluate(&properties).unwrap(), ExpressionValue::String("models/big.glb".into()));
+//! assert_eq!(expression.evaluate(&HashMap::new()).unwrap(), ExpressionValue::String("models/small.glb".into()));
+//! ```
+
+use std::fmt;
+
+use crate::{fgd::FgdType, *};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ExpressionError {
+	#[error("unexpected character {0:?} at {1}")]
+	UnexpectedCharacter(char, usize),
+	#[error("unterminated string starting at {0}")]
+	UnterminatedString(usize),
+	#[error("expected {expected}, found {found}")]
+	UnexpectedToken { expected: &'static str, found: String },
+	#[error("can't convert {value} to {ty}")]
+	Conversion { value: String, ty: &'static str },
+	#[error("can't apply `{op}` to {lhs} and {rhs}")]
+	InvalidOperands { op: &'static str, lhs: String, rhs: String },
+	#[error("can't index {value} with {index}")]
+	InvalidIndex { value: String, index: String },
+}
+
+/// The result of evaluating an [`Expression`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ExpressionValue {
+	/// The value of a variable that doesn't exist, or a switch without a matching case.
+	#[default]
+	Undefined,
+	Null,
+	Boolean(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<ExpressionValue>),
+	Map(HashMap<String, ExpressionValue>),
+}
+impl ExpressionValue {
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			Self::Undefined => "undefined",
+			Self::Null => "null",
+			Self::Boolean(_) => "boolean",
+			Self::Number(_) => "number",
+			Self::String(_) => "string",
+			Self::Array(_) => "array",
+			Self::Map(_) => "map",
+		}
+	}
+
+	/// Converts this value to a boolean, like in a switch case condition.
+	pub fn to_bool(&self) -> bool {
+		match self {
+			Self::Undefined | Self::Null => false,
+			Self::Boolean(value) => *value,
+			Self::Number(value) => *value != 0.,
+			Self::String(value) => !value.is_empty() && value != "false",
+			Self::Array(_) | Self::Map(_) => true,
+		}
+	}
+
+	/// Converts this value to a number. Entity properties are strings, so strings are parsed, undefined variables and empty strings become 0.
+	pub fn to_number(&self) -> Result<f64, ExpressionError> {
+		match self {
+			Self::Undefined | Self::Null => Ok(0.),
+			Self::Boolean(value) => Ok(*value as u8 as f64),
+			Self::Number(value) => Ok(*value),
+			Self::String(value) if value.trim().is_empty() => Ok(0.),
+			Self::String(value) => value.trim().parse().map_err(|_| self.conversion_error("number")),
+			Self::Array(_) | Self::Map(_) => Err(self.conversion_error("number")),
+		}
+	}
+
+	/// Converts this value to a string. Undefined values become empty strings.
+	pub fn to_string_value(&self) -> Result<String, ExpressionError> {
+		match self {
+			Self::Undefined | Self::Null => Ok(String::new()),
+			Self::Boolean(value) => Ok(value.to_string()),
+			Self::Number(value) => Ok(value.to_string()),
+			Self::String(value) => Ok(value.clone()),
+			Self::Array(_) | Self::Map(_) => Err(self.conversion_error("string")),
+		}
+	}
+
+	fn conversion_error(&self, ty: &'static str) -> ExpressionError {
+		ExpressionError::Conversion { value: self.to_string(), ty }
+	}
+}
+impl fmt::Display for ExpressionValue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Undefined => write!(f, "undefined"),
+			Self::Null => write!(f, "null"),
+			Self::Boolean(value) => write!(f, "{value}"),
+			Self::Number(value) => write!(f, "{value}"),
+			Self::String(value) => write!(f, "{value:?}"),
+			Self::Array(values) => write!(f, "[{}]", values.iter().join(", ")),
+			Self::Map(map) => write!(f, "{{ {} }}", map.iter().map(|(key, value)| format!("{key:?}: {value}")).join(", ")),
+		}
+	}
+}
+
+/// What a `model` or `iconsprite` expression evaluated to for a specific entity.
+///
+/// See the [TrenchBroom docs](https://trenchbroom.github.io/manual/latest/#display-models-for-entities) for how these are specified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSpec {
+	pub path: String,
+	pub skin: u32,
+	pub frame: u32,
+	/// (Default: [`Vec3::ONE`])
+	pub scale: Vec3,
+}
+impl ModelSpec {
+	pub fn new(path: impl Into<String>) -> Self {
+		Self {
+			path: path.into(),
+			skin: 0,
+			frame: 0,
+			scale: Vec3::ONE,
+		}
+	}
+
+	/// Reads either a path, or a map with `path`, `skin`, `frame` and `scale` entries.
+	///
+	/// Returns [`None`] if there's no path, such as when no case of a switch expression matched.
+	pub fn from_value(value: &ExpressionValue) -> Result<Option<Self>, ExpressionError> {
+		let map = match value {
+			ExpressionValue::Undefined | ExpressionValue::Null => return Ok(None),
+			ExpressionValue::String(path) if path.is_empty() => return Ok(None),
+			ExpressionValue::String(path) => return Ok(Some(Self::new(path))),
+			ExpressionValue::Map(map) => map,
+			value => return Err(value.conversion_error("model")),
+		};
+
+		let Some(path) = map.get("path").map(ExpressionValue::to_string_value).transpose()? else { return Ok(None) };
+		if path.is_empty() {
+			return Ok(None);
+		}
+		let mut spec = Self::new(path);
+
+		if let Some(skin) = map.get("skin") {
+			spec.skin = skin.to_number()? as u32;
+		}
+		if let Some(frame) = map.get("frame") {
+			spec.frame = frame.to_number()? as u32;
+		}
+		spec.scale = match map.get("scale") {
+			None | Some(ExpressionValue::Undefined | ExpressionValue::Null) => Vec3::ONE,
+			// Vectors are usually entity properties, like `modelscale_vec`.
+			Some(ExpressionValue::String(scale)) if scale.split_whitespace().count() == 3 => {
+				Vec3::fgd_parse(scale).map_err(|_| ExpressionError::Conversion {
+					value: scale.clone(),
+					ty: "vector",
+				})?
+			}
+			Some(ExpressionValue::Array(scale)) if scale.len() == 3 => {
+				Vec3::new(scale[0].to_number()? as f32, scale[1].to_number()? as f32, scale[2].to_number()? as f32)
+			}
+			Some(scale) => Vec3::splat(scale.to_number()? as f32),
+		};
+
+		Ok(Some(spec))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+	Plus,
+	Minus,
+	Not,
+	BitwiseNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+	Multiply,
+	Divide,
+	Modulo,
+	Add,
+	Subtract,
+	ShiftLeft,
+	ShiftRight,
+	Less,
+	LessOrEqual,
+	Greater,
+	GreaterOrEqual,
+	Equal,
+	NotEqual,
+	BitwiseAnd,
+	BitwiseXor,
+	BitwiseOr,
+	And,
+	Or,
+	/// `a..b`, an inclusive range of numbers.
+	Range,
+	/// `condition -> value`, a case of a switch expression.
+	Case,
+}
+impl BinaryOperator {
+	/// Operators from lowest to highest precedence.
+	const PRECEDENCE: &[&[(&str, Self)]] = &[
+		&[("->", Self::Case)],
+		&[("..", Self::Range)],
+		&[("||", Self::Or)],
+		&[("&&", Self::And)],
+		&[("|", Self::BitwiseOr)],
+		&[("^", Self::BitwiseXor)],
+		&[("&", Self::BitwiseAnd)],
+		&[("==", Self::Equal), ("!=", Self::NotEqual)],
+		&[
+			("<=", Self::LessOrEqual),
+			(">=", Self::GreaterOrEqual),
+			("<", Self::Less),
+			(">", Self::Greater),
+		],
+		&[("<<", Self::ShiftLeft), (">>", Self::ShiftRight)],
+		&[("+", Self::Add), ("-", Self::Subtract)],
+		&[("*", Self::Multiply), ("/", Self::Divide), ("%", Self::Modulo)],
+	];
+
+	fn symbol(self) -> &'static str {
+		Self::PRECEDENCE
+			.iter()
+			.flat_map(|level| level.iter())
+			.find(|(_, op)| *op == self)
+			.map(|(symbol, _)| *symbol)
+			.unwrap_or_default()
+	}
+}
+
+/// A parsed TrenchBroom expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+	Literal(ExpressionValue),
+	/// An entity property.
+	Variable(String),
+	Array(Vec<Expression>),
+	Map(Vec<(String, Expression)>),
+	Unary(UnaryOperator, Box<Expression>),
+	Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+	Subscript(Box<Expression>, Vec<Expression>),
+	/// `{{ case, case, ..., default }}`, evaluates to the first case whose condition is true.
+	Switch(Vec<Expression>),
+}
+impl Expression {
+	pub fn parse(input: &str) -> Result<Self, ExpressionError> {
+		let mut parser = Parser {
+			tokens: tokenize(input)?,
+			pos: 0,
+		};
+		let expression = parser.parse_expression(0)?;
+		parser.expect_end()?;
+		Ok(expression)
+	}
+
+	/// Evaluates this expression, using `properties` for variables.
+	pub fn evaluate(&self, properties: &HashMap<String, String>) -> Result<ExpressionValue, ExpressionError> {
+		use ExpressionValue as V;
+
+		Ok(match self {
+			Self::Literal(value) => value.clone(),
+			Self::Variable(name) => properties.get(name).map(|value| V::String(value.clone())).unwrap_or_default(),
+			Self::Array(elements) => V::Array(evaluate_flattening_ranges(elements, properties)?),
+			Self::Map(entries) => V::Map(
+				entries
+					.iter()
+					.map(|(key, value)| Ok((key.clone(), value.evaluate(properties)?)))
+					.collect::<Result<_, ExpressionError>>()?,
+			),
+			Self::Unary(op, operand) => {
+				let operand = operand.evaluate(properties)?;
+				match op {
+					UnaryOperator::Plus => V::Number(operand.to_number()?),
+					UnaryOperator::Minus => V::Number(-operand.to_number()?),
+					UnaryOperator::Not => V::Boolean(!operand.to_bool()),
+					UnaryOperator::BitwiseNot => V::Number(!(operand.to_number()? as i64) as f64),
+				}
+			}
+			Self::Binary(op, lhs, rhs) => match op {
+				// Short-circuiting operators.
+				BinaryOperator::And => V::Boolean(lhs.evaluate(properties)?.to_bool() && rhs.evaluate(properties)?.to_bool()),
+				BinaryOperator::Or => V::Boolean(lhs.evaluate(properties)?.to_bool() || rhs.evaluate(properties)?.to_bool()),
+				BinaryOperator::Case => match lhs.evaluate(properties)?.to_bool() {
+					true => rhs.evaluate(properties)?,
+					false => V::Undefined,
+				},
+				op => evaluate_binary(*op, lhs.evaluate(properties)?, rhs.evaluate(properties)?)?,
+			},
+			Self::Subscript(value, indices) => {
+				let value = value.evaluate(properties)?;
+				let indices = evaluate_flattening_ranges(indices, properties)?;
+
+				match indices.as_slice() {
+					[index] => subscript(&value, index)?,
+					indices => V::Array(indices.iter().map(|index| subscript(&value, index)).collect::<Result<_, _>>()?),
+				}
+			}
+			Self::Switch(cases) => {
+				for case in cases {
+					let value = case.evaluate(properties)?;
+					if value != V::Undefined {
+						return Ok(value);
+					}
+				}
+				V::Undefined
+			}
+		})
+	}
+}
+
+/// Evaluates a list of expressions, expanding any ranges into the list, like TrenchBroom does in array literals and subscripts.
+fn evaluate_flattening_ranges(expressions: &[Expression], properties: &HashMap<String, String>) -> Result<Vec<ExpressionValue>, ExpressionError> {
+	let mut values = Vec::with_capacity(expressions.len());
+	for expression in expressions {
+		match (expression, expression.evaluate(properties)?) {
+			(Expression::Binary(BinaryOperator::Range, ..), ExpressionValue::Array(range)) => values.extend(range),
+			(_, value) => values.push(value),
+		}
+	}
+	Ok(values)
+}
+
+fn evaluate_binary(op: BinaryOperator, lhs: ExpressionValue, rhs: ExpressionValue) -> Result<ExpressionValue, ExpressionError> {
+	use ExpressionValue as V;
+
+	let invalid_operands = || ExpressionError::InvalidOperands {
+		op: op.symbol(),
+		lhs: lhs.to_string(),
+		rhs: rhs.to_string(),
+	};
+	let numbers = || -> Result<(f64, f64), ExpressionError> { Ok((lhs.to_number()?, rhs.to_number()?)) };
+	let integers = || -> Result<(i64, i64), ExpressionError> { numbers().map(|(lhs, rhs)| (lhs as i64, rhs as i64)) };
+
+	Ok(match op {
+		BinaryOperator::Add => match (&lhs, &rhs) {
+			(V::String(lhs), V::String(rhs)) => V::String(format!("{lhs}{rhs}")),
+			(V::Array(lhs), V::Array(rhs)) => V::Array(lhs.iter().chain(rhs).cloned().collect()),
+			(V::Map(lhs), V::Map(rhs)) => V::Map(lhs.iter().chain(rhs).map(|(key, value)| (key.clone(), value.clone())).collect()),
+			(V::Array(_) | V::Map(_), _) | (_, V::Array(_) | V::Map(_)) => return Err(invalid_operands()),
+			_ => numbers().map(|(lhs, rhs)| V::Number(lhs + rhs))?,
+		},
+		BinaryOperator::Subtract => numbers().map(|(lhs, rhs)| V::Number(lhs - rhs))?,
+		BinaryOperator::Multiply => numbers().map(|(lhs, rhs)| V::Number(lhs * rhs))?,
+		BinaryOperator::Divide => numbers().map(|(lhs, rhs)| V::Number(lhs / rhs))?,
+		BinaryOperator::Modulo => numbers().map(|(lhs, rhs)| V::Number(lhs % rhs))?,
+		BinaryOperator::ShiftLeft => integers().map(|(lhs, rhs)| V::Number(lhs.wrapping_shl(rhs as u32) as f64))?,
+		BinaryOperator::ShiftRight => integers().map(|(lhs, rhs)| V::Number(lhs.wrapping_shr(rhs as u32) as f64))?,
+		BinaryOperator::BitwiseAnd => integers().map(|(lhs, rhs)| V::Number((lhs & rhs) as f64))?,
+		BinaryOperator::BitwiseXor => integers().map(|(lhs, rhs)| V::Number((lhs ^ rhs) as f64))?,
+		BinaryOperator::BitwiseOr => integers().map(|(lhs, rhs)| V::Number((lhs | rhs) as f64))?,
+		BinaryOperator::Equal => V::Boolean(values_equal(&lhs, &rhs)),
+		BinaryOperator::NotEqual => V::Boolean(!values_equal(&lhs, &rhs)),
+		BinaryOperator::Less | BinaryOperator::LessOrEqual | BinaryOperator::Greater | BinaryOperator::GreaterOrEqual => {
+			let ordering = match (&lhs, &rhs) {
+				(V::String(lhs), V::String(rhs)) => lhs.cmp(rhs),
+				_ => {
+					let (lhs, rhs) = numbers()?;
+					lhs.partial_cmp(&rhs).ok_or_else(invalid_operands)?
+				}
+			};
+			V::Boolean(match op {
+				BinaryOperator::Less => ordering.is_lt(),
+				BinaryOperator::LessOrEqual => ordering.is_le(),
+				BinaryOperator::Greater => ordering.is_gt(),
+				_ => ordering.is_ge(),
+			})
+		}
+		BinaryOperator::Range => {
+			let (start, end) = integers()?;
+			match start <= end {
+				true => V::Array((start..=end).map(|i| V::Number(i as f64)).collect()),
+				false => V::Array((end..=start).rev().map(|i| V::Number(i as f64)).collect()),
+			}
+		}
+		BinaryOperator::And | BinaryOperator::Or | BinaryOperator::Case => unreachable!("evaluated lazily"),
+	})
+}
+
+/// Compares values, converting strings (such as entity properties) to numbers when compared to a number.
+fn values_equal(lhs: &ExpressionValue, rhs: &ExpressionValue) -> bool {
+	use ExpressionValue as V;
+
+	match (lhs, rhs) {
+		(V::Number(_), _) | (_, V::Number(_)) => match (lhs.to_number(), rhs.to_number()) {
+			(Ok(lhs), Ok(rhs)) => lhs == rhs,
+			_ => false,
+		},
+		(V::Boolean(_), _) | (_, V::Boolean(_)) => lhs.to_bool() == rhs.to_bool(),
+		_ => lhs == rhs,
+	}
+}
+
+fn subscript(value: &ExpressionValue, index: &ExpressionValue) -> Result<ExpressionValue, ExpressionError> {
+	use ExpressionValue as V;
+
+	let invalid_index = || ExpressionError::InvalidIndex {
+		value: value.to_string(),
+		index: index.to_string(),
+	};
+	// Negative indices count from the end.
+	let wrap_index = |len: usize| -> Result<usize, ExpressionError> {
+		let index = index.to_number()? as i64;
+		let index = if index < 0 { len as i64 + index } else { index };
+		usize::try_from(index).ok().filter(|index| *index < len).ok_or_else(invalid_index)
+	};
+
+	Ok(match value {
+		V::Array(values) => values[wrap_index(values.len())?].clone(),
+		V::String(string) => {
+			let chars = string.chars().collect_vec();
+			V::String(chars[wrap_index(chars.len())?].to_string())
+		}
+		V::Map(map) => map.get(&index.to_string_value()?).cloned().unwrap_or_default(),
+		_ => return Err(invalid_index()),
+	})
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	Number(f64),
+	String(String),
+	Name(String),
+	Symbol(&'static str),
+}
+impl fmt::Display for Token {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Number(value) => write!(f, "{value}"),
+			Self::String(value) => write!(f, "{value:?}"),
+			Self::Name(name) => write!(f, "`{name}`"),
+			Self::Symbol(symbol) => write!(f, "`{symbol}`"),
+		}
+	}
+}
+
+/// Symbols, longest first so that multi-character operators take priority.
+const SYMBOLS: &[&str] = &[
+	"->", "..", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "!", "~", "<", ">", "&", "^", "|", "(", ")", "[", "]", "{",
+	"}", ",", ":",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ExpressionError> {
+	let mut tokens = Vec::new();
+	let mut chars = input.char_indices().peekable();
+
+	while let Some(&(start, c)) = chars.peek() {
+		if c.is_whitespace() {
+			chars.next();
+		} else if c == '"' {
+			chars.next();
+			let mut string = String::new();
+			loop {
+				match chars.next() {
+					Some((_, '"')) => break,
+					Some((_, '\\')) => match chars.next() {
+						Some((_, 'n')) => string.push('\n'),
+						Some((_, 't')) => string.push('\t'),
+						Some((_, c)) => string.push(c),
+						None => return Err(ExpressionError::UnterminatedString(start)),
+					},
+					Some((_, c)) => string.push(c),
+					None => return Err(ExpressionError::UnterminatedString(start)),
+				}
+			}
+			tokens.push(Token::String(string));
+		} else if c.is_ascii_digit() {
+			let rest = &input[start..];
+			let mut len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+			// Fractional part, making sure not to eat range operators.
+			if rest[len..].starts_with('.') && rest[len + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+				len += 1 + rest[len + 1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - len - 1);
+			}
+			tokens.push(Token::Number(
+				rest[..len].parse().map_err(|_| ExpressionError::UnexpectedCharacter(c, start))?,
+			));
+			while chars.peek().is_some_and(|(pos, _)| *pos < start + len) {
+				chars.next();
+			}
+		} else if c.is_alphabetic() || c == '_' {
+			let rest = &input[start..];
+			let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+			tokens.push(Token::Name(rest[..len].to_string()));
+			while chars.peek().is_some_and(|(pos, _)| *pos < start + len) {
+				chars.next();
+			}
+		} else {
+			let Some(symbol) = SYMBOLS.iter().find(|symbol| input[start..].starts_with(**symbol)) else {
+				return Err(ExpressionError::UnexpectedCharacter(c, start));
+			};
+			tokens.push(Token::Symbol(symbol));
+			for _ in 0..symbol.len() {
+				chars.next();
+			}
+		}
+	}
+
+	Ok(tokens)
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	pos: usize,
+}
+impl Parser {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.pos)
+	}
+
+	fn peek_symbol(&self, symbol: &str) -> bool {
+		self.peek_symbol_at(0, symbol)
+	}
+
+	fn peek_symbol_at(&self, offset: usize, symbol: &str) -> bool {
+		matches!(self.tokens.get(self.pos + offset), Some(Token::Symbol(s)) if *s == symbol)
+	}
+
+	fn next(&mut self) -> Option<Token> {
+		let token = self.tokens.get(self.pos).cloned();
+		self.pos += 1;
+		token
+	}
+
+	fn unexpected(&self, expected: &'static str) -> ExpressionError {
+		ExpressionError::UnexpectedToken {
+			expected,
+			found: self.peek().map(ToString::to_string).unwrap_or_else(|| "end of expression".to_string()),
+		}
+	}
+
+	fn expect_symbol(&mut self, symbol: &'static str) -> Result<(), ExpressionError> {
+		if !self.peek_symbol(symbol) {
+			return Err(self.unexpected(symbol));
+		}
+		self.pos += 1;
+		Ok(())
+	}
+
+	fn expect_end(&self) -> Result<(), ExpressionError> {
+		match self.peek() {
+			None => Ok(()),
+			Some(_) => Err(self.unexpected("end of expression")),
+		}
+	}
+
+	/// Parses a comma-separated list of expressions until `end`.
+	fn parse_list(&mut self, end: &'static str) -> Result<Vec<Expression>, ExpressionError> {
+		let mut expressions = Vec::new();
+		while !self.peek_symbol(end) {
+			expressions.push(self.parse_expression(0)?);
+			if !self.peek_symbol(",") {
+				break;
+			}
+			self.pos += 1;
+		}
+		self.expect_symbol(end)?;
+		Ok(expressions)
+	}
+
+	/// Parses binary operators of at least precedence level `level`.
+	fn parse_expression(&mut self, level: usize) -> Result<Expression, ExpressionError> {
+		let Some(operators) = BinaryOperator::PRECEDENCE.get(level) else { return self.parse_unary() };
+
+		let mut lhs = self.parse_expression(level + 1)?;
+
+		while let Some((_, op)) = operators.iter().find(|(symbol, _)| self.peek_symbol(symbol)) {
+			self.pos += 1;
+			let rhs = self.parse_expression(level + 1)?;
+			lhs = Expression::Binary(*op, Box::new(lhs), Box::new(rhs));
+		}
+
+		Ok(lhs)
+	}
+
+	fn parse_unary(&mut self) -> Result<Expression, ExpressionError> {
+		let op = match self.peek() {
+			Some(Token::Symbol("+")) => UnaryOperator::Plus,
+			Some(Token::Symbol("-")) => UnaryOperator::Minus,
+			Some(Token::Symbol("!")) => UnaryOperator::Not,
+			Some(Token::Symbol("~")) => UnaryOperator::BitwiseNot,
+			_ => return self.parse_postfix(),
+		};
+		self.pos += 1;
+		Ok(Expression::Unary(op, Box::new(self.parse_unary()?)))
+	}
+
+	fn parse_postfix(&mut self) -> Result<Expression, ExpressionError> {
+		let mut expression = self.parse_term()?;
+
+		while self.peek_symbol("[") {
+			self.pos += 1;
+			expression = Expression::Subscript(Box::new(expression), self.parse_list("]")?);
+		}
+
+		Ok(expression)
+	}
+
+	fn parse_term(&mut self) -> Result<Expression, ExpressionError> {
+		if self.peek_symbol("{") && self.peek_symbol_at(1, "{") {
+			self.pos += 2;
+			let cases = self.parse_list("}")?;
+			self.expect_symbol("}")?;
+			return Ok(Expression::Switch(cases));
+		}
+
+		let unexpected = self.unexpected("a value");
+
+		Ok(match self.next() {
+			Some(Token::Number(value)) => Expression::Literal(ExpressionValue::Number(value)),
+			Some(Token::String(value)) => Expression::Literal(ExpressionValue::String(value)),
+			Some(Token::Name(name)) => match name.as_str() {
+				"true" => Expression::Literal(ExpressionValue::Boolean(true)),
+				"false" => Expression::Literal(ExpressionValue::Boolean(false)),
+				"null" => Expression::Literal(ExpressionValue::Null),
+				_ => Expression::Variable(name),
+			},
+			Some(Token::Symbol("(")) => {
+				let expression = self.parse_expression(0)?;
+				self.expect_symbol(")")?;
+				expression
+			}
+			Some(Token::Symbol("[")) => Expression::Array(self.parse_list("]")?),
+			Some(Token::Symbol("{")) => {
+				let mut entries = Vec::new();
+				while !self.peek_symbol("}") {
+					let key = match self.next() {
+						Some(Token::String(key) | Token::Name(key)) => key,
+						_ => {
+							self.pos -= 1;
+							return Err(self.unexpected("a map key"));
+						}
+					};
+					self.expect_symbol(":")?;
+					entries.push((key, self.parse_expression(0)?));
+					if !self.peek_symbol(",") {
+						break;
+					}
+					self.pos += 1;
+				}
+				self.expect_symbol("}")?;
+				Expression::Map(entries)
+			}
+			_ => return Err(unexpected),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn evaluate(input: &str, properties: &[(&str, &str)]) -> ExpressionValue {
+		let properties = properties.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+		Expression::parse(input).unwrap().evaluate(&properties).unwrap()
+	}
+
+	#[test]
+	fn expression_evaluation() {
+		use ExpressionValue as V;
+
+		assert_eq!(evaluate("1 + 2 * 3 - -1", &[]), V::Number(8.));
+		assert_eq!(evaluate("(1 << 4) | 3 & 2", &[]), V::Number(18.));
+		assert_eq!(evaluate("\"a\" + \"b\" == \"ab\" && !false", &[]), V::Boolean(true));
+		assert_eq!(evaluate("[0..2, 5][-1]", &[]), V::Number(5.));
+		assert_eq!(
+			evaluate("[\"a\", \"b\", \"c\"][1..2]", &[]),
+			V::Array(vec![V::String("b".into()), V::String("c".into())])
+		);
+
+		// Properties are strings, but compare as numbers.
+		assert_eq!(evaluate("health == 100", &[("health", "100")]), V::Boolean(true));
+		assert_eq!(evaluate("missing", &[]), V::Undefined);
+
+		// Stringified Rust tokens put spaces between braces.
+		let model = r#"{ { spawnflags & 2 -> { "path" : "b.mdl", skin : skin + 1 }, "a.mdl" } }"#;
+		assert_eq!(evaluate(model, &[]), V::String("a.mdl".into()));
+		assert_eq!(
+			evaluate(model, &[("spawnflags", "6"), ("skin", "1")]),
+			V::Map(
+				[("path".into(), V::String("b.mdl".into())), ("skin".into(), V::Number(2.))]
+					.into_iter()
+					.collect()
+			)
+		);
+
+		let spec = ModelSpec::from_value(&evaluate(r#"{ "path": "a.mdl", "frame": 2, "scale": s }"#, &[("s", "1 2 3")])).unwrap();
+		assert_eq!(spec.map(|spec| (spec.frame, spec.scale)), Some((2, vec3(1., 2., 3.))));
+
+		assert!(Expression::parse("1 +").is_err());
+		assert!(Expression::parse("\"unterminated").is_err());
+		assert!(Expression::parse("1 2").is_err());
+	}
+}
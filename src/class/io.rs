@@ -0,0 +1,696 @@
+//! Quake-style entity IO, where entities fire their [`Target`]s, [using](Use) every entity with a matching [`Targetable::targetname`].
+//!
+//! Firing follows Quake's `SUB_UseTargets`:
+//! - If the entity has a [`TargetDelay`], firing is postponed by that many seconds.
+//! - If the entity has a [`TargetMessage`], a [`CenterPrint`] event is triggered for the game to display.
+//! - Every entity matching [`KillTarget::killtarget`] is despawned.
+//! - Every entity matching [`Target::target`] receives a [`Use`] event, which classes can observe to implement their behavior.
+//!
+//! # Examples
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_trenchbroom::prelude::*;
+//! use bevy_trenchbroom::class::io::*;
+//!
+//! #[point_class(base(Targetable))]
+//! #[derive(Default)]
+//! struct Lamp {
+//!     on: bool,
+//! }
+//!
+//! fn toggle_lamp(trigger: On<Use>, mut query: Query<&mut Lamp>) {
+//!     let Ok(mut lamp) = query.get_mut(trigger.entity) else { return };
+//!     lamp.on = !lamp.on;
+//! }
+//!
+//! fn press_button(mut commands: Commands, player: Entity, button: Entity) {
+//!     commands.trigger(Fire::new(button).with_activator(player));
+//! }
+//! ```
+//!
+//! # Named inputs and outputs
+//! Classes can also declare Source-style named inputs and outputs with the `inputs(...)` and `outputs(...)` class attributes.
+//!
+//! Each output shows up in-editor as a property of the same name, containing connections formatted as `target,Input,parameter,delay,times_to_fire`,
+//! separated by `;`. Only the target and input are required. Targets are targetnames, or `!self` and `!activator`.
+//! The connections are read into an [`Outputs`] component when spawning, and are sent when triggering [`FireOutput`].
+//!
+//! Inputs generate an enum named after the class (e.g. `DoorInput` for `Door`), with a variant for each input containing its parameter parsed by [`FgdType`](crate::fgd::FgdType).
+//! Receiving an input triggers a [`ClassInput`] event of that enum on the entity. Every entity also accepts the `Use` input, which triggers [`Use`], and `Kill`, which despawns it.
+//! ```
+//! # use bevy::prelude::*;
+//! # use bevy_trenchbroom::prelude::*;
+//! use bevy_trenchbroom::class::io::*;
+//!
+//! #[solid_class(
+//!     base(Targetable),
+//!     inputs(
+//!         /// Opens the door.
+//!         Open,
+//!         SetSpeed(f32),
+//!     ),
+//!     outputs(
+//!         /// Fired when the door starts opening.
+//!         OnOpen,
+//!     ),
+//! )]
+//! #[derive(Default)]
+//! struct Door {
+//!     speed: f32,
+//! }
+//!
+//! fn door_input(trigger: On<ClassInput<DoorInput>>, mut commands: Commands, mut query: Query<&mut Door>) {
+//!     let Ok(mut door) = query.get_mut(trigger.entity) else { return };
+//!     match trigger.input {
+//!         DoorInput::Open => commands.trigger(FireOutput::new(trigger.entity, "OnOpen")),
+//!         DoorInput::SetSpeed(speed) => door.speed = speed,
+//!     }
+//! }
+//! ```
+use bevy::ecs::system::SystemParam;
+
+use super::{
+	QuakeClassOutput, QuakeClassSpawnView, ReflectQuakeClass,
+	builtin::{KillTarget, Target, TargetDelay, TargetMessage, Targetable},
+};
+use crate::*;
+
+pub struct EntityIoPlugin;
+impl Plugin for EntityIoPlugin {
+	fn build(&self, app: &mut App) {
+		#[rustfmt::skip]
+		app
+			.init_resource::<Targetnames>()
+			.register_type::<DelayedFire>()
+			.register_type::<Outputs>()
+			.register_type::<DelayedInput>()
+			.add_observer(Self::add_targetname)
+			.add_observer(Self::remove_targetname)
+			.add_observer(Self::fire)
+			.add_observer(Self::fire_output)
+			.add_systems(Update, (Self::tick_delayed_fires, Self::tick_delayed_inputs))
+		;
+	}
+}
+impl EntityIoPlugin {
+	pub fn add_targetname(trigger: On<Insert, Targetable>, query: Query<&Targetable>, mut targetnames: ResMut<Targetnames>) {
+		let Ok(targetable) = query.get(trigger.event_target()) else { return };
+		if targetable.targetname.is_empty() {
+			return;
+		}
+
+		targetnames
+			.entities
+			.entry(targetable.targetname.0.clone())
+			.or_default()
+			.push(trigger.event_target());
+	}
+
+	pub fn remove_targetname(trigger: On<Discard, Targetable>, query: Query<&Targetable>, mut targetnames: ResMut<Targetnames>) {
+		let Ok(targetable) = query.get(trigger.event_target()) else { return };
+		let Some(entities) = targetnames.entities.get_mut(&targetable.targetname.0) else { return };
+
+		entities.retain(|entity| *entity != trigger.event_target());
+		if entities.is_empty() {
+			targetnames.entities.remove(&targetable.targetname.0);
+		}
+	}
+
+	pub fn fire(trigger: On<Fire>, mut io: EntityIo) {
+		io.fire(trigger.entity, trigger.activator);
+	}
+
+	pub fn tick_delayed_fires(mut commands: Commands, mut query: Query<(Entity, &mut DelayedFire)>, time: Res<Time>, mut io: EntityIo) {
+		for (entity, mut delayed) in &mut query {
+			if !delayed.timer.tick(time.delta()).is_finished() {
+				continue;
+			}
+
+			io.fire_immediately(delayed.source, delayed.activator);
+			commands.entity(entity).despawn();
+		}
+	}
+
+	pub fn fire_output(trigger: On<FireOutput>, mut commands: Commands, mut query: Query<&mut Outputs>, targetnames: Res<Targetnames>) {
+		let Ok(mut outputs) = query.get_mut(trigger.entity) else { return };
+
+		for connection in &mut outputs.0 {
+			if connection.output != trigger.output {
+				continue;
+			}
+			match &mut connection.times_to_fire {
+				Some(0) => continue,
+				Some(times) => *times -= 1,
+				None => {}
+			}
+
+			if connection.delay > 0. {
+				commands.spawn(DelayedInput {
+					target: connection.target.clone(),
+					input: connection.input.clone(),
+					parameter: connection.parameter.clone(),
+					source: trigger.entity,
+					activator: trigger.activator,
+					timer: Timer::from_seconds(connection.delay, TimerMode::Once),
+				});
+				continue;
+			}
+
+			for target in targetnames.resolve(&connection.target, trigger.entity, trigger.activator) {
+				commands.queue(SendInput {
+					target,
+					input: connection.input.clone(),
+					parameter: connection.parameter.clone(),
+					source: trigger.entity,
+					activator: trigger.activator,
+				});
+			}
+		}
+	}
+
+	pub fn tick_delayed_inputs(
+		mut commands: Commands,
+		mut query: Query<(Entity, &mut DelayedInput)>,
+		time: Res<Time>,
+		targetnames: Res<Targetnames>,
+	) {
+		for (entity, mut delayed) in &mut query {
+			if !delayed.timer.tick(time.delta()).is_finished() {
+				continue;
+			}
+
+			for target in targetnames.resolve(&delayed.target, delayed.source, delayed.activator) {
+				commands.queue(SendInput {
+					target,
+					input: delayed.input.clone(),
+					parameter: delayed.parameter.clone(),
+					source: delayed.source,
+					activator: delayed.activator,
+				});
+			}
+			commands.entity(entity).despawn();
+		}
+	}
+}
+
+/// Maps targetnames to the entities with a [`Targetable`] component that has it, kept up to date as [`Targetable`] components are inserted and removed.
+///
+/// NOTE: Targetnames are global rather than scoped to the map an entity was spawned from, so if you have multiple maps loaded at once, they can target each other's entities.
+/// This includes [`PathNode`](crate::class::builtin::PathNode) links, and two instances of the same map will fire each other's targets.
+/// If you need several maps loaded at once, give their targetnames unique prefixes, or despawn the previous map before spawning the next.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Targetnames {
+	entities: HashMap<String, Vec<Entity>>,
+}
+impl Targetnames {
+	/// Returns every entity with a [`Targetable::targetname`] of `targetname`.
+	pub fn get(&self, targetname: &str) -> &[Entity] {
+		self.entities.get(targetname).map(Vec::as_slice).unwrap_or_default()
+	}
+
+	/// Like [`get`](Self::get), but also resolves the special `!self` and `!activator` targets used by [output connections](OutputConnection).
+	pub fn resolve(&self, target: &str, source: Entity, activator: Option<Entity>) -> Vec<Entity> {
+		match target {
+			"!self" => vec![source],
+			"!activator" => activator.into_iter().collect(),
+			_ => self.get(target).to_vec(),
+		}
+	}
+}
+
+/// Fires the targets of [`entity`](Self::entity), see the [module-level documentation](self).
+///
+/// For firing from systems that already have access to it, [`EntityIo`] can be used instead.
+#[derive(EntityEvent, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fire {
+	#[event_target]
+	pub entity: Entity,
+	/// The entity that caused this, such as the player that pressed a button. Passed on to [`Use`] and [`CenterPrint`].
+	pub activator: Option<Entity>,
+}
+impl Fire {
+	pub fn new(entity: Entity) -> Self {
+		Self { entity, activator: None }
+	}
+
+	pub fn with_activator(self, activator: Entity) -> Self {
+		Self {
+			activator: Some(activator),
+			..self
+		}
+	}
+}
+
+/// Triggered on every entity targeted by a fired entity. Observe this to make your classes respond to being targeted.
+#[derive(EntityEvent, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Use {
+	#[event_target]
+	pub entity: Entity,
+	/// The entity whose targets were fired.
+	pub source: Entity,
+	/// See [`Fire::activator`].
+	pub activator: Option<Entity>,
+}
+
+/// Triggered when an entity with a [`TargetMessage`] fires. In Quake, this message would be printed in the center of the activator's screen if it's a player.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct CenterPrint {
+	pub message: String,
+	/// The entity whose targets were fired.
+	pub source: Entity,
+	/// See [`Fire::activator`].
+	pub activator: Option<Entity>,
+}
+
+/// A pending [`Fire`] of an entity with a [`TargetDelay`]. If the source entity is despawned before the delay ends, nothing happens.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct DelayedFire {
+	pub source: Entity,
+	pub activator: Option<Entity>,
+	pub timer: Timer,
+}
+
+/// Fires entities' targets from within a system, see the [module-level documentation](self).
+#[derive(SystemParam)]
+pub struct EntityIo<'w, 's> {
+	commands: Commands<'w, 's>,
+	targetnames: Res<'w, Targetnames>,
+	query: Query<
+		'w,
+		's,
+		(
+			Option<&'static Target>,
+			Option<&'static KillTarget>,
+			Option<&'static TargetDelay>,
+			Option<&'static TargetMessage>,
+		),
+	>,
+}
+impl EntityIo<'_, '_> {
+	/// Fires the targets of `source`, or schedules it to be fired later if it has a [`TargetDelay`].
+	pub fn fire(&mut self, source: Entity, activator: Option<Entity>) {
+		let Ok((_, _, Some(delay), _)) = self.query.get(source) else {
+			self.fire_immediately(source, activator);
+			return;
+		};
+
+		if delay.delay > 0. {
+			self.commands.spawn(DelayedFire {
+				source,
+				activator,
+				timer: Timer::from_seconds(delay.delay, TimerMode::Once),
+			});
+		} else {
+			self.fire_immediately(source, activator);
+		}
+	}
+
+	/// Fires the targets of `source`, ignoring its [`TargetDelay`].
+	pub fn fire_immediately(&mut self, source: Entity, activator: Option<Entity>) {
+		let Ok((target, kill_target, _, message)) = self.query.get(source) else { return };
+		let target = target.map(|target| target.target.0.clone());
+		let kill_target = kill_target.map(|kill_target| kill_target.killtarget.0.clone());
+
+		if let Some(message) = message
+			&& !message.message.is_empty()
+		{
+			self.commands.trigger(CenterPrint {
+				message: message.message.clone(),
+				source,
+				activator,
+			});
+		}
+
+		if let Some(kill_target) = kill_target {
+			self.kill(&kill_target);
+		}
+
+		if let Some(target) = target {
+			self.use_targets(&target, source, activator);
+		}
+	}
+
+	/// Triggers [`Use`] on every entity with a [`Targetable::targetname`] of `targetname`.
+	pub fn use_targets(&mut self, targetname: &str, source: Entity, activator: Option<Entity>) {
+		for &entity in self.targetnames.get(targetname) {
+			self.commands.trigger(Use { entity, source, activator });
+		}
+	}
+
+	/// Despawns every entity with a [`Targetable::targetname`] of `targetname`.
+	pub fn kill(&mut self, targetname: &str) {
+		for &entity in self.targetnames.get(targetname) {
+			self.commands.entity(entity).try_despawn();
+		}
+	}
+}
+
+/// The entities involved in sending an input, passed to [`QuakeClassInput::dispatch`](super::QuakeClassInput::dispatch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputContext {
+	/// The entity receiving the input.
+	pub entity: Entity,
+	/// The entity whose output sent the input.
+	pub source: Entity,
+	/// See [`Fire::activator`].
+	pub activator: Option<Entity>,
+}
+
+/// Triggered on an entity when it receives one of its class' named inputs. `I` is the input enum generated by the class macro, such as `DoorInput` for `Door`.
+#[derive(EntityEvent, Debug, Clone)]
+pub struct ClassInput<I: Send + Sync + 'static> {
+	#[event_target]
+	pub entity: Entity,
+	/// The entity whose output sent the input.
+	pub source: Entity,
+	/// See [`Fire::activator`].
+	pub activator: Option<Entity>,
+	pub input: I,
+}
+impl<I: Send + Sync + 'static> ClassInput<I> {
+	pub fn new(context: InputContext, input: I) -> Self {
+		Self {
+			entity: context.entity,
+			source: context.source,
+			activator: context.activator,
+			input,
+		}
+	}
+}
+
+/// The output connections of an entity, read from the properties of its class' [outputs](super::QuakeClassOutput) when spawning.
+#[derive(Component, Reflect, Debug, Clone, Default, PartialEq)]
+#[reflect(Component, Default)]
+pub struct Outputs(pub Vec<OutputConnection>);
+
+/// A connection from an entity's output to other entities' input, formatted as `target,Input,parameter,delay,times_to_fire`.
+#[derive(Reflect, Debug, Clone, PartialEq)]
+pub struct OutputConnection {
+	/// The name of the output this connection is from.
+	pub output: String,
+	/// The targetname of the entities to send the input to, or `!self` or `!activator`.
+	pub target: String,
+	pub input: String,
+	/// The parameter of the input, an empty string if it doesn't take one.
+	pub parameter: String,
+	/// How many seconds to wait before sending the input.
+	pub delay: f32,
+	/// How many more times this connection can be fired, or [`None`] for unlimited. Written as `-1` in connection strings.
+	pub times_to_fire: Option<u32>,
+}
+impl OutputConnection {
+	/// Parses a single connection string. Like Source, fields can also be separated by the escape character (`0x1B`).
+	pub fn parse(output: &str, s: &str) -> anyhow::Result<Self> {
+		let separator = if s.contains('\u{1B}') { '\u{1B}' } else { ',' };
+		let mut fields = s.split(separator).map(str::trim);
+
+		let mut required = |name: &str| match fields.next() {
+			Some(field) if !field.is_empty() => Ok(field.to_string()),
+			_ => Err(anyhow!("output connection `{s}` is missing its {name}")),
+		};
+		let target = required("target")?;
+		let input = required("input")?;
+
+		let parameter = fields.next().unwrap_or_default().to_string();
+		let delay = match fields.next() {
+			Some(delay) if !delay.is_empty() => delay
+				.parse()
+				.map_err(|err| anyhow!("output connection `{s}` has an invalid delay: {err}"))?,
+			_ => 0.,
+		};
+		let times_to_fire = match fields.next() {
+			Some(times) if !times.is_empty() => {
+				let times: i64 = times
+					.parse()
+					.map_err(|err| anyhow!("output connection `{s}` has an invalid times to fire: {err}"))?;
+				u32::try_from(times).ok()
+			}
+			_ => None,
+		};
+
+		Ok(Self {
+			output: output.to_string(),
+			target,
+			input,
+			parameter,
+			delay,
+			times_to_fire,
+		})
+	}
+}
+
+/// Reads the connections of `outputs` from the entity's properties into its [`Outputs`] component. Used by classes with outputs when spawning.
+pub fn insert_outputs(view: &mut QuakeClassSpawnView, outputs: &[QuakeClassOutput]) -> anyhow::Result<()> {
+	let mut connections = Vec::new();
+
+	for output in outputs {
+		let Some(value) = view.src_entity.properties.get(output.name) else { continue };
+
+		for connection in value.split(';').map(str::trim).filter(|connection| !connection.is_empty()) {
+			connections.push(OutputConnection::parse(output.name, connection)?);
+		}
+	}
+
+	view.world
+		.entity_mut(view.entity)
+		.entry::<Outputs>()
+		.or_default()
+		.get_mut()
+		.0
+		.extend(connections);
+
+	Ok(())
+}
+
+/// Sends the input of each of [`entity`](Self::entity)'s [`Outputs`] connected to [`output`](Self::output).
+#[derive(EntityEvent, Debug, Clone, PartialEq, Eq)]
+pub struct FireOutput {
+	#[event_target]
+	pub entity: Entity,
+	pub output: String,
+	/// See [`Fire::activator`].
+	pub activator: Option<Entity>,
+}
+impl FireOutput {
+	pub fn new(entity: Entity, output: impl Into<String>) -> Self {
+		Self {
+			entity,
+			output: output.into(),
+			activator: None,
+		}
+	}
+
+	pub fn with_activator(self, activator: Entity) -> Self {
+		Self {
+			activator: Some(activator),
+			..self
+		}
+	}
+}
+
+/// An input waiting on its [`OutputConnection::delay`]. The target is resolved when the delay ends.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct DelayedInput {
+	pub target: String,
+	pub input: String,
+	pub parameter: String,
+	pub source: Entity,
+	pub activator: Option<Entity>,
+	pub timer: Timer,
+}
+
+/// Command that sends a named input to an entity, looking it up in the classes of the entity's components.
+///
+/// Errors, such as the entity not having the input or the parameter failing to parse, are logged.
+#[derive(Debug, Clone)]
+pub struct SendInput {
+	pub target: Entity,
+	pub input: String,
+	pub parameter: String,
+	pub source: Entity,
+	pub activator: Option<Entity>,
+}
+impl Command for SendInput {
+	type Out = ();
+
+	fn apply(self, world: &mut World) {
+		let context = InputContext {
+			entity: self.target,
+			source: self.source,
+			activator: self.activator,
+		};
+
+		let input = {
+			let Ok(entity) = world.get_entity(self.target) else { return };
+			let type_registry = world.resource::<AppTypeRegistry>().read();
+
+			entity
+				.archetype()
+				.components()
+				.iter()
+				.filter_map(|component| world.components().get_info(*component)?.type_id())
+				.filter_map(|type_id| type_registry.get_type_data::<ReflectQuakeClass>(type_id))
+				.find_map(|class| class.erased_class.info.find_input(&self.input))
+		};
+
+		match (input, self.input.as_str()) {
+			(Some(input), _) => {
+				let mut commands = world.commands();
+				if let Err(err) = (input.dispatch)(&mut commands, context, &self.parameter) {
+					error!("Sending input `{}` to {}: {err}", self.input, self.target);
+				}
+			}
+			(None, "Use") => world.trigger(Use {
+				entity: self.target,
+				source: self.source,
+				activator: self.activator,
+			}),
+			(None, "Kill") => {
+				world.entity_mut(self.target).despawn();
+			}
+			(None, _) => warn!("{} doesn't have an input named `{}`", self.target, self.input),
+		}
+
+		world.flush();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		class::QuakeClass,
+		util::{kill_target, target, targetable},
+	};
+	use bevy::time::TimeUpdateStrategy;
+	use core::time::Duration;
+
+	#[derive(Resource, Default)]
+	struct Used(Vec<(Entity, Option<Entity>)>);
+
+	#[solid_class(base(Targetable), inputs(Open, SetSpeed(f32)), outputs(OnOpen))]
+	struct Door;
+
+	#[derive(Resource, Default)]
+	struct Received(Vec<String>);
+
+	#[test]
+	fn fire_targets() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin))
+			.init_resource::<Used>()
+			.add_observer(|trigger: On<Use>, mut used: ResMut<Used>| used.0.push((trigger.entity, trigger.activator)));
+
+		let world = app.world_mut();
+		let door = world.spawn(targetable("door")).id();
+		let other_door = world.spawn(targetable("door")).id();
+		let crate_ = world.spawn(targetable("crate")).id();
+		let button = world.spawn((target("door"), kill_target("crate"))).id();
+		let player = world.spawn_empty().id();
+
+		world.trigger(Fire::new(button).with_activator(player));
+		app.update();
+
+		assert_eq!(app.world().resource::<Used>().0, [(door, Some(player)), (other_door, Some(player))]);
+		assert!(app.world().get_entity(crate_).is_err());
+		assert_eq!(app.world().resource::<Targetnames>().get("crate"), []);
+
+		// Renaming should move the entity in the index.
+		app.world_mut().entity_mut(other_door).insert(targetable("gate"));
+		assert_eq!(app.world().resource::<Targetnames>().get("door"), [door]);
+		assert_eq!(app.world().resource::<Targetnames>().get("gate"), [other_door]);
+	}
+
+	#[test]
+	fn parse_connections() {
+		let connection = OutputConnection::parse("OnOpen", "door_2,Close,,0.5").unwrap();
+		assert_eq!(
+			connection,
+			OutputConnection {
+				output: "OnOpen".into(),
+				target: "door_2".into(),
+				input: "Close".into(),
+				parameter: String::new(),
+				delay: 0.5,
+				times_to_fire: None,
+			}
+		);
+
+		let connection = OutputConnection::parse("OnOpen", "lamp\u{1B}SetColor\u{1B}255 0 0\u{1B}0\u{1B}1").unwrap();
+		assert_eq!((connection.parameter.as_str(), connection.times_to_fire), ("255 0 0", Some(1)));
+
+		assert!(OutputConnection::parse("OnOpen", "door_2").is_err());
+		assert!(OutputConnection::parse("OnOpen", "door_2,Close,,soon").is_err());
+	}
+
+	#[test]
+	fn send_inputs() {
+		let mut app = App::new();
+		app.add_plugins((MinimalPlugins, EntityIoPlugin))
+			.register_type::<Door>()
+			.init_resource::<Received>()
+			.init_resource::<Used>()
+			.add_observer(|trigger: On<Use>, mut used: ResMut<Used>| used.0.push((trigger.entity, trigger.activator)))
+			.add_observer(|trigger: On<ClassInput<DoorInput>>, mut received: ResMut<Received>| {
+				received.0.push(match trigger.input {
+					DoorInput::Open => "Open".to_string(),
+					DoorInput::SetSpeed(speed) => format!("SetSpeed({speed})"),
+				});
+			});
+
+		let world = app.world_mut();
+		let door = world.spawn((Door, targetable("door"))).id();
+		let button = world
+			.spawn(Outputs(vec![
+				OutputConnection::parse("OnPressed", "door,SetSpeed,5").unwrap(),
+				OutputConnection::parse("OnPressed", "door,Open,,0,1").unwrap(),
+				OutputConnection::parse("OnPressed", "door,Kill,,10").unwrap(),
+			]))
+			.id();
+
+		world.trigger(FireOutput::new(button, "OnPressed"));
+		world.trigger(FireOutput::new(button, "OnPressed"));
+		world.flush();
+
+		// `Open` can only be fired once.
+		assert_eq!(app.world().resource::<Received>().0, ["SetSpeed(5)", "Open", "SetSpeed(5)"]);
+		assert_eq!(app.world_mut().query::<&DelayedInput>().iter(app.world()).count(), 2);
+		assert!(app.world().get_entity(door).is_ok());
+
+		// Once the delay has passed, the door is killed.
+		app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)));
+		for _ in 0..110 {
+			app.update();
+		}
+		assert!(app.world().get_entity(door).is_err());
+		assert_eq!(app.world_mut().query::<&DelayedInput>().iter(app.world()).count(), 0);
+
+		// `Use` falls back to the `Use` event for entities without such an input, other unknown inputs are ignored.
+		let world = app.world_mut();
+		let lamp = world.spawn(targetable("lamp")).id();
+		let switch = world
+			.spawn(Outputs(vec![
+				OutputConnection::parse("OnPressed", "lamp,Use").unwrap(),
+				OutputConnection::parse("OnPressed", "lamp,Frobnicate").unwrap(),
+			]))
+			.id();
+		world.trigger(FireOutput::new(switch, "OnPressed").with_activator(switch));
+		world.flush();
+		assert_eq!(app.world().resource::<Used>().0, [(lamp, Some(switch))]);
+		assert!(app.world().get_entity(lamp).is_ok());
+
+		// Inputs are listed in the description, and outputs become properties.
+		let definition = crate::fgd::FgdClassDefinition::from_info(&Door::CLASS_INFO);
+		assert_eq!(definition.description.as_deref(), Some("Inputs:\n- Open\n- SetSpeed (float)"));
+		// Line breaks are escaped in the FGD so TrenchBroom reads the whole description.
+		let fgd = definition.to_string();
+		assert!(fgd.contains(r#" : "Inputs:\n- Open\n- SetSpeed (float)""#));
+		assert_eq!(crate::fgd::parse_fgd(&fgd).unwrap().classes[0].description, definition.description);
+		assert_eq!(
+			definition.properties.iter().map(|property| property.name.as_str()).collect_vec(),
+			["OnOpen"]
+		);
+	}
+}
@@ -1,22 +1,64 @@
 pub mod builtin;
+pub mod dynamic;
+pub mod expression;
+pub mod io;
 pub mod scene_hooks;
 
 use core::fmt;
-use std::any::TypeId;
+use std::{any::TypeId, fs, path::Path};
 
 use bevy::{asset::LoadContext, platform::collections::HashSet};
 use bevy_reflect::{FromType, GetTypeRegistration, TypeRegistry};
+use expression::{Expression, ExpressionError, ModelSpec};
 use qmap::QuakeMapEntity;
 
-use crate::{geometry::MapGeometryTexture, util::MapFileType, *};
+use crate::{
+	fgd::{IntBool, IntBoolOverride, ModelPath, ReflectFgdType, Srgb, TargetDestination, TargetSource},
+	geometry::MapGeometryTexture,
+	util::MapFileType,
+	*,
+};
 
 pub struct QuakeClassPlugin;
 impl Plugin for QuakeClassPlugin {
 	fn build(&self, app: &mut App) {
 		#[rustfmt::skip]
 		app
+			.register_type::<dynamic::DynamicClassProperties>()
 			.add_systems(Startup, Self::verify_classes)
 		;
+
+		macro_rules! register_fgd_types {($($ty:ty),* $(,)?) => {
+			$(app.register_type::<$ty>().register_type_data::<$ty, ReflectFgdType>();)*
+		};}
+
+		register_fgd_types!(
+			bool,
+			u8,
+			u16,
+			u32,
+			u64,
+			usize,
+			i8,
+			i16,
+			i32,
+			i64,
+			isize,
+			f32,
+			f64,
+			String,
+			Vec2,
+			Vec3,
+			Vec4,
+			Color,
+			Srgba,
+			Srgb,
+			IntBool,
+			IntBoolOverride,
+			TargetSource,
+			TargetDestination,
+			ModelPath,
+		);
 	}
 }
 impl QuakeClassPlugin {
@@ -45,7 +87,8 @@ impl QuakeClassPlugin {
 	}
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::EnumIs)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::EnumIs, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum QuakeClassType {
 	/// Cannot be spawned in TrenchBroom, works like a base class in any object-oriented language.
 	#[default]
@@ -102,6 +145,27 @@ impl Default for QuakeClassPropertyType {
 	}
 }
 
+/// A named input of a class, which can be sent to entities through [outputs](QuakeClassOutput) or [`SendInput`](io::SendInput).
+#[derive(Debug, Clone, Copy)]
+pub struct QuakeClassInput {
+	pub name: &'static str,
+	/// The type of the input's parameter, if it takes one.
+	pub parameter: Option<QuakeClassPropertyType>,
+	pub description: Option<&'static str>,
+	/// Parses the parameter and triggers the class' [`ClassInput`](io::ClassInput) event on the target entity.
+	pub dispatch: QuakeClassInputFn,
+}
+
+/// See [`QuakeClassInput::dispatch`].
+pub type QuakeClassInputFn = fn(&mut Commands, io::InputContext, &str) -> anyhow::Result<()>;
+
+/// A named output of a class. Connections to other entities' inputs are set through a property of the same name.
+#[derive(Debug, Clone, Copy)]
+pub struct QuakeClassOutput {
+	pub name: &'static str,
+	pub description: Option<&'static str>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChoicesKey {
 	String(&'static str),
@@ -134,6 +198,10 @@ pub struct QuakeClassInfo {
 	pub decal: bool,
 
 	pub properties: &'static [QuakeClassProperty],
+	/// Named inputs other entities' outputs can send to, see [`io`].
+	pub inputs: &'static [QuakeClassInput],
+	/// Named outputs, which are read from properties of the same name, see [`io`].
+	pub outputs: &'static [QuakeClassOutput],
 }
 impl QuakeClassInfo {
 	/// Recursively checks if this class is a subclass of `T`. Does not return `true` if this class *is* `T`.
@@ -143,9 +211,43 @@ impl QuakeClassInfo {
 			.any(|class| class.type_id == TypeId::of::<T>() || class.info.derives_from::<T>())
 	}
 
-	/// Returns the path of the in-editor model of this class.
+	/// Evaluates this class' `model` expression against a map entity's properties.
+	///
+	/// Returns [`None`] if this class doesn't have a model, or the expression didn't produce one for this entity.
+	///
+	/// # Examples
+	/// ```
+	/// # use bevy::prelude::*;
+	/// # use bevy_trenchbroom::prelude::*;
+	/// #[point_class(
+	///     model({{ spawnflags & 1 -> { "path": "models/lit.glb", "skin": 1 }, "models/unlit.glb" }}),
+	/// )]
+	/// struct MyClass;
+	///
+	/// let mut entity = QuakeMapEntity::default();
+	/// assert_eq!(MyClass::CLASS_INFO.resolve_model(&entity).unwrap().unwrap().path, "models/unlit.glb");
+	///
+	/// entity.properties.insert("spawnflags".into(), "1".into());
+	/// let model = MyClass::CLASS_INFO.resolve_model(&entity).unwrap().unwrap();
+	/// assert_eq!((model.path.as_str(), model.skin), ("models/lit.glb", 1));
+	/// ```
+	pub fn resolve_model(&self, entity: &QuakeMapEntity) -> Result<Option<ModelSpec>, ExpressionError> {
+		Self::resolve_model_expression(self.model, entity)
+	}
+
+	/// Evaluates this class' `iconsprite` expression against a map entity's properties. See [`resolve_model`](Self::resolve_model).
+	pub fn resolve_iconsprite(&self, entity: &QuakeMapEntity) -> Result<Option<ModelSpec>, ExpressionError> {
+		Self::resolve_model_expression(self.iconsprite, entity)
+	}
+
+	fn resolve_model_expression(expression: Option<&str>, entity: &QuakeMapEntity) -> Result<Option<ModelSpec>, ExpressionError> {
+		let Some(expression) = expression else { return Ok(None) };
+		ModelSpec::from_value(&Expression::parse(expression)?.evaluate(&entity.properties)?)
+	}
+
+	/// Returns the path of the in-editor model of this class, for an entity with no properties set.
 	///
-	/// TODO: This currently only works for classes with the syntax `#[model("path/to/model")]`, anything more complex will produce `None`.
+	/// Use [`resolve_model`](Self::resolve_model) to get the model for a specific entity.
 	///
 	/// # Examples
 	/// ```
@@ -156,14 +258,23 @@ impl QuakeClassInfo {
 	/// )]
 	/// struct MyClass;
 	///
-	/// assert_eq!(MyClass::CLASS_INFO.model_path(), Some("models/my_class.glb"));
+	/// assert_eq!(MyClass::CLASS_INFO.model_path().as_deref(), Some("models/my_class.glb"));
 	/// ```
-	pub fn model_path(&self) -> Option<&str> {
-		let model = self.model?;
-		if !model.starts_with('"') || !model.ends_with('"') {
-			return None;
-		}
-		Some(model.trim_matches('"'))
+	pub fn model_path(&self) -> Option<String> {
+		self.resolve_model(&default()).ok().flatten().map(|model| model.path)
+	}
+
+	/// Returns the path of the in-editor icon of this class, for an entity with no properties set.
+	pub fn iconsprite_path(&self) -> Option<String> {
+		self.resolve_iconsprite(&default()).ok().flatten().map(|model| model.path)
+	}
+
+	/// Finds an input by name in this class or its base classes.
+	pub fn find_input(&self, name: &str) -> Option<&'static QuakeClassInput> {
+		self.inputs
+			.iter()
+			.find(|input| input.name == name)
+			.or_else(|| self.base.iter().find_map(|base| base.info.find_input(name)))
 	}
 
 	/// If any property in this class or its base classes has this property type, returns `true`, else `false`.
@@ -186,12 +297,20 @@ impl QuakeClassInfo {
 	}
 }
 
-/// Generates a map of classnames to classes from a type registry.
+/// Generates a map of classnames to classes from a type registry, including [dynamic classes](dynamic). Rust classes take priority over dynamic classes with the same classname.
 pub fn generate_class_map(registry: &TypeRegistry) -> HashMap<&'static str, &'static ErasedQuakeClass> {
-	registry
-		.iter_with_data::<ReflectQuakeClass>()
-		.filter(|(_, class)| class.enabled)
-		.map(|(_, class)| (class.erased_class.info.name, class.erased_class))
+	let dynamic_classes = dynamic::DynamicQuakeClasses::get(registry)
+		.into_iter()
+		.flat_map(dynamic::DynamicQuakeClasses::iter);
+
+	dynamic_classes
+		.chain(
+			registry
+				.iter_with_data::<ReflectQuakeClass>()
+				.filter(|(_, class)| class.enabled)
+				.map(|(_, class)| class.erased_class),
+		)
+		.map(|class| (class.info.name, class))
 		.collect()
 }
 
@@ -267,18 +386,27 @@ impl ErasedQuakeClass {
 		}
 	}
 
+	/// Returns `true` if `other` is the same class. Classnames are compared as well as types, as all [dynamic classes](dynamic) share a type.
+	pub fn is(&self, other: &ErasedQuakeClass) -> bool {
+		self.type_id == other.type_id && self.info.name == other.info.name
+	}
+
 	/// Calls [`Self::spawn_fn`] recursively for all base classes. For almost all cases, you should use the [`spawn_quake_entity_into_scene`] function instead of this.
 	pub fn apply_spawn_fn_recursive(&self, view: &mut QuakeClassSpawnView) -> anyhow::Result<()> {
 		self.apply_spawn_fn_recursive_internal(view, &mut default())
 	}
 
-	fn apply_spawn_fn_recursive_internal(&self, view: &mut QuakeClassSpawnView, spawned_classes: &mut HashSet<TypeId>) -> anyhow::Result<()> {
+	fn apply_spawn_fn_recursive_internal(
+		&self,
+		view: &mut QuakeClassSpawnView,
+		spawned_classes: &mut HashSet<(TypeId, &'static str)>,
+	) -> anyhow::Result<()> {
 		for base in self.info.base {
-			if spawned_classes.contains(&base.type_id) {
+			if spawned_classes.contains(&(base.type_id, base.info.name)) {
 				continue;
 			}
 			base.apply_spawn_fn_recursive_internal(view, spawned_classes)?;
-			spawned_classes.insert(base.type_id);
+			spawned_classes.insert((base.type_id, base.info.name));
 		}
 
 		(self.spawn_fn)(view)?;
@@ -317,6 +445,16 @@ pub trait QuakeClassAppExt {
 	fn disable_class<T: QuakeClass>(&mut self) -> &mut Self;
 	/// Registers a class after disabling all other classes with the same classname.
 	fn override_class<T: QuakeClass>(&mut self) -> &mut Self;
+	/// Registers classes defined at runtime, see [`dynamic`] for more info. Classes can only derive from classes registered before them.
+	///
+	/// NOTE: Each distinct version of a class leaks a small allocation, see [`dynamic::register_dynamic_classes`].
+	fn register_dynamic_classes(&mut self, classes: impl IntoIterator<Item = dynamic::DynamicClass>) -> &mut Self;
+	/// Parses the source of an FGD file, registering its classes as [dynamic classes](dynamic). Errors are logged.
+	///
+	/// `@include` directives are ignored, use [`import_fgd_file`](Self::import_fgd_file) to import included files too.
+	fn import_fgd(&mut self, source: &str) -> &mut Self;
+	/// Reads and [imports](Self::import_fgd) an FGD file, as well as the files it includes, relative to it.
+	fn import_fgd_file(&mut self, path: impl AsRef<Path>) -> &mut Self;
 }
 impl QuakeClassAppExt for App {
 	#[track_caller]
@@ -350,6 +488,37 @@ impl QuakeClassAppExt for App {
 		drop(type_registry);
 		self.register_type::<T>()
 	}
+	fn register_dynamic_classes(&mut self, classes: impl IntoIterator<Item = dynamic::DynamicClass>) -> &mut Self {
+		let mut type_registry = self.world().resource::<AppTypeRegistry>().write();
+		dynamic::register_dynamic_classes(&mut type_registry, classes);
+
+		drop(type_registry);
+		self
+	}
+	fn import_fgd(&mut self, source: &str) -> &mut Self {
+		let mut type_registry = self.world().resource::<AppTypeRegistry>().write();
+
+		if let Err(err) = dynamic::import_fgd(&mut type_registry, source, None) {
+			error!("Importing FGD: {err}");
+		}
+
+		drop(type_registry);
+		self
+	}
+	fn import_fgd_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+		let path = path.as_ref();
+		let mut type_registry = self.world().resource::<AppTypeRegistry>().write();
+
+		if let Err(err) = fs::read_to_string(path)
+			.map_err(anyhow::Error::from)
+			.and_then(|source| dynamic::import_fgd(&mut type_registry, &source, path.parent()))
+		{
+			error!("Importing FGD {}: {err}", path.display());
+		}
+
+		drop(type_registry);
+		self
+	}
 }
 
 #[cfg(test)]
@@ -1,4 +1,5 @@
 use super::*;
+use crate::class::expression::ModelSpec;
 use crate::config::SpawnFnOnce;
 use crate::*;
 use bevy::ecs::lifecycle::HookContext;
@@ -174,13 +175,27 @@ impl SceneHooks {
 		self.with(crate::physics::ConvexCollision)
 	}
 
+	/// Like [`convex_collider`](Self::convex_collider), but the collider is a sensor, which triggers [`SensorEntered`](crate::physics::SensorEntered) when other colliders enter it instead of blocking them.
+	#[cfg(feature = "physics-integration")]
+	pub fn sensor_collider(self) -> Self {
+		self.with((crate::physics::ConvexCollision, crate::physics::SensorCollision))
+	}
+
+	/// Like [`convex_collider`](Self::convex_collider), but the collider is kinematic, for entities that move by setting their transform, such as doors.
+	#[cfg(feature = "physics-integration")]
+	pub fn kinematic_collider(self) -> Self {
+		self.with((crate::physics::ConvexCollision, crate::physics::KinematicCollision))
+	}
+
 	#[cfg(all(feature = "bsp", feature = "client"))]
 	pub fn without_lightmaps(self) -> Self {
-		use crate::bsp::lighting::AnimatedLightingHandle;
+		use crate::bsp::lighting::{AnimatedLightingHandle, DeluxemapHandle};
 
 		self.push(move |view| {
 			for mesh_view in view.meshes.iter() {
-				view.world.entity_mut(mesh_view.entity).remove::<AnimatedLightingHandle>();
+				view.world
+					.entity_mut(mesh_view.entity)
+					.remove::<(AnimatedLightingHandle, DeluxemapHandle)>();
 			}
 			Ok(())
 		})
@@ -195,22 +210,12 @@ impl SceneHooks {
 	/// This is the internal function that you should use when creating your own model loading hooks.
 	/// For general use, you should use functions like [`spawn_class_gltf(...)`](Self::spawn_class_gltf) for better ergonomics.
 	///
-	/// TODO: This currently only works for simple paths (e.g. `#[model("path/to/model")]`), more advanced uses of the `model` property won't work.
+	/// The `model` expression is evaluated against each entity's properties, so entities can choose their model based on things like `spawnflags`.
+	/// The model's `scale` is applied to the entity's [`Transform`], and its `skin` and `frame` are applied to Quake models and sprites.
 	pub fn spawn_class_model_internal<T: QuakeClass>(self, label: Option<&'static str>) -> Self {
 		self.push(move |view| {
-			let Some(model_path) = T::CLASS_INFO.model_path() else {
-				anyhow::bail!("`spawn_class_model` called but `model` property missing/invalid!");
-			};
-
-			let mut model_path = AssetPath::from(model_path);
-
-			if let Some(label) = label {
-				model_path = model_path.with_label(label);
-			}
-
-			let model_handle = view.load_context.load(model_path);
-
-			view.world.entity_mut(view.entity).insert(WorldAssetRoot(model_handle));
+			let Some(model) = resolve_class_model::<T>(view, false)? else { return Ok(()) };
+			spawn_model(view, model, label);
 			Ok(())
 		})
 	}
@@ -219,8 +224,6 @@ impl SceneHooks {
 	///
 	/// This function exists in such a way that you can directly use it as a spawn hook for your class, or call it from within an existing spawn hook.
 	///
-	/// TODO: This currently only works for simple paths (e.g. `#[model("path/to/model")]`), more advanced uses of the `model` property won't work.
-	///
 	/// # Examples
 	/// ```
 	/// # use bevy::prelude::*;
@@ -236,9 +239,73 @@ impl SceneHooks {
 		self.spawn_class_model_internal::<T>(Some("Scene0"))
 	}
 
+	/// Spawns the model stored in this class' `model` property as a Quake `.mdl` or Quake 2 `.md2` model.
+	///
+	/// The spawned model has a [`QuakeModelAnimationPlayer`](crate::quake_model::QuakeModelAnimationPlayer) you can use to play its animations.
+	///
+	/// # Examples
+	/// ```ignore
+	/// # use bevy::prelude::*;
+	/// # use bevy_trenchbroom::prelude::*;
+	/// #[point_class(
+	///     model("progs/armor.mdl"),
+	///     size(-16 -16 0, 16 16 56),
+	///     hooks(SceneHooks::new().spawn_class_quake_model::<Self>()),
+	/// )]
+	/// pub struct ItemArmor1;
+	/// ```
+	#[cfg(all(feature = "client", feature = "bsp"))]
+	pub fn spawn_class_quake_model<T: QuakeClass>(self) -> Self {
+		self.spawn_class_model_internal::<T>(Some("Scene"))
+	}
+
+	/// Spawns this class' `model` as a Quake `.spr` or Quake 2 `.sp2` sprite, falling back to its `iconsprite` if it doesn't have one.
+	///
+	/// The spawned sprite has a [`QuakeSpriteBillboard`](crate::quake_model::sprite::QuakeSpriteBillboard), which turns it to face the camera.
+	///
+	/// # Examples
+	/// ```ignore
+	/// # use bevy::prelude::*;
+	/// # use bevy_trenchbroom::prelude::*;
+	/// #[point_class(
+	///     model("progs/flame2.spr"),
+	///     hooks(SceneHooks::new().spawn_class_quake_sprite::<Self>()),
+	/// )]
+	/// pub struct LightFlameSmall;
+	/// ```
+	#[cfg(all(feature = "client", feature = "bsp"))]
+	pub fn spawn_class_quake_sprite<T: QuakeClass>(self) -> Self {
+		self.push(|view| {
+			let Some(sprite) = resolve_class_model::<T>(view, true)? else { return Ok(()) };
+			spawn_model(view, sprite, Some("Scene"));
+			Ok(())
+		})
+	}
+
+	/// Spawns the model stored in this class' `model` property, choosing how to load it based on its file extension.
+	///
+	/// glTF files (`.gltf`, `.glb`) spawn their first scene, Quake models and sprites (`.mdl`, `.md2`, `.spr`, `.sp2`) require the `client` and `bsp` features.
+	/// Useful when the model is chosen per entity, like with the [`ModelProp`](crate::class::builtin::ModelProp) base class.
+	pub fn spawn_class_model<T: QuakeClass>(self) -> Self {
+		self.push(|view| {
+			let Some(model) = resolve_class_model::<T>(view, false)? else { return Ok(()) };
+			let extension = model.path.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+
+			let label = match extension.as_deref() {
+				Some("gltf" | "glb") => "Scene0",
+				#[cfg(all(feature = "client", feature = "bsp"))]
+				Some("mdl" | "md2" | "spr" | "sp2") => "Scene",
+				_ => anyhow::bail!("{}: unsupported model format for \"{}\"", T::CLASS_INFO.name, model.path),
+			};
+
+			spawn_model(view, model, Some(label));
+			Ok(())
+		})
+	}
+
 	/// Scene hook that simply loads the path specified in the model, adding it to the map's asset dependencies.
 	///
-	/// TODO: This currently only works for simple paths (e.g. `#[model("path/to/model")]`), more advanced uses of the `model` property won't work.
+	/// Like [`spawn_class_model_internal`](Self::spawn_class_model_internal), the `model` expression is evaluated for each entity, so only the models actually used are loaded.
 	///
 	/// # Examples
 	/// ```
@@ -252,17 +319,63 @@ impl SceneHooks {
 	/// ```
 	pub fn preload_model<T: QuakeClass>(self) -> Self {
 		self.push(|view| {
-			let Some(model_path) = T::CLASS_INFO.model_path() else {
-				anyhow::bail!("`spawn_class_model` called but `model` property missing/invalid!");
-			};
+			let Some(model) = resolve_class_model::<T>(view, false)? else { return Ok(()) };
 
-			let handle = view.load_context.load_builder().load_untyped(model_path);
+			let handle = view.load_context.load_builder().load_untyped(model.path);
 			view.preload_asset(handle.untyped());
 			Ok(())
 		})
 	}
 }
 
+/// Evaluates `T`'s `model` expression (or `iconsprite` as a fallback if `allow_iconsprite` is `true`) against the properties of the entity being spawned.
+///
+/// Errors if `T` doesn't have a model at all, returns [`None`] if the expression doesn't produce one for this entity.
+fn resolve_class_model<T: QuakeClass>(view: &QuakeClassSpawnView, allow_iconsprite: bool) -> anyhow::Result<Option<ModelSpec>> {
+	let info = T::CLASS_INFO;
+
+	if info.model.is_some() {
+		return info
+			.resolve_model(view.src_entity)
+			.map_err(|err| anyhow!("evaluating `model` expression of {}: {err}", info.name));
+	}
+	if allow_iconsprite && info.iconsprite.is_some() {
+		return info
+			.resolve_iconsprite(view.src_entity)
+			.map_err(|err| anyhow!("evaluating `iconsprite` expression of {}: {err}", info.name));
+	}
+
+	anyhow::bail!("model hook called on {} but `model` property missing!", info.name);
+}
+
+/// Spawns `model` as the scene of the entity being spawned, with the asset label `label`.
+fn spawn_model(view: &mut QuakeClassSpawnView, model: ModelSpec, label: Option<&'static str>) {
+	let mut model_path = AssetPath::from(model.path);
+
+	if let Some(label) = label {
+		model_path = model_path.with_label(label);
+	}
+
+	let model_handle = view.load_context.load(model_path);
+
+	let mut entity = view.world.entity_mut(view.entity);
+	entity.insert(WorldAssetRoot(model_handle));
+
+	if model.scale != Vec3::ONE
+		&& let Some(mut transform) = entity.get_mut::<Transform>()
+	{
+		transform.scale *= model.scale;
+	}
+
+	#[cfg(all(feature = "client", feature = "bsp"))]
+	if model.skin != 0 || model.frame != 0 {
+		entity.insert(crate::quake_model::QuakeModelAppearance {
+			skin: model.skin,
+			frame: model.frame,
+		});
+	}
+}
+
 // TODO: Can't reflect until https://github.com/bevyengine/bevy/pull/18827 lands
 /// Hacky component that stores a preloaded asset in the scene for just long enough for it not to be detected unused and removed.
 #[derive(Component, Reflect, Debug, Clone, Default)]
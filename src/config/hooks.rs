@@ -5,12 +5,14 @@ use std::any::type_name;
 use qbsp::{BspFormat, data::texture::EmbeddedTextureName};
 
 use super::*;
+use crate::{class::ErasedQuakeClass, qmap::QuakeMapEntity};
 
 #[cfg(feature = "bsp")]
 pub type LoadEmbeddedTextureFn = dyn for<'a, 'b> Fn(EmbeddedTextureLoadView<'a, 'b>) -> BoxedFuture<'a, Handle<GenericMaterial>> + Send + Sync;
 pub type LoadLooseTextureFn = dyn for<'a, 'b> Fn(TextureLoadView<'a, 'b>) -> BoxedFuture<'a, Handle<GenericMaterial>> + Send + Sync;
 pub type SpawnFn = dyn Fn(&mut QuakeClassSpawnView) -> anyhow::Result<()> + Send + Sync;
 pub type SpawnFnOnce = dyn FnOnce(&mut QuakeClassSpawnView) -> anyhow::Result<()> + Send + Sync;
+pub type SpawnFilterFn = dyn Fn(SpawnFilterView) -> bool + Send + Sync;
 
 /// Wrapper for storing a stack of dynamic functions. Use [`Hook::set`] to push a new function onto the stack.
 #[derive(Deref)]
@@ -63,6 +65,15 @@ impl<'a, 'b> TextureLoadView<'a, 'b> {
 	}
 }
 
+/// Inputs available when deciding whether to spawn an entity with [`TrenchBroomConfig::spawn_filter`].
+pub struct SpawnFilterView<'a> {
+	pub src_entity: &'a QuakeMapEntity,
+	pub src_entity_idx: usize,
+	pub class: &'a ErasedQuakeClass,
+	pub settings: &'a MapLoadSettings,
+	pub tb_config: &'a TrenchBroomConfig,
+}
+
 #[cfg(feature = "bsp")]
 #[derive(Deref, DerefMut)]
 pub struct EmbeddedTextureLoadView<'a, 'b> {
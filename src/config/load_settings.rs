@@ -0,0 +1,49 @@
+use class::builtin::SpawnFilterFlags;
+use enumflags2::BitFlags;
+
+use super::*;
+
+/// Settings for loading `.map` and `.bsp` files, passed to the [`spawn_filter`](TrenchBroomConfig::spawn_filter) to choose which entities to spawn.
+///
+/// Use [`AssetServer::load_with_settings`] to load a map with settings other than the default.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapLoadSettings {
+	pub skill: Skill,
+	pub game_mode: GameMode,
+}
+impl MapLoadSettings {
+	/// The [`SpawnFilterFlags`] that exclude entities from spawning with these settings.
+	///
+	/// Like Quake, skill levels are ignored in deathmatch.
+	pub fn excluding_flags(&self) -> BitFlags<SpawnFilterFlags> {
+		let skill = match self.skill {
+			Skill::Easy => SpawnFilterFlags::NotInEasy,
+			Skill::Normal => SpawnFilterFlags::NotInNormal,
+			Skill::Hard => SpawnFilterFlags::NotInHard,
+		};
+
+		match self.game_mode {
+			GameMode::Singleplayer => skill | SpawnFilterFlags::NotInSingleplayer,
+			GameMode::Coop => skill | SpawnFilterFlags::NotInCoop,
+			GameMode::Deathmatch => SpawnFilterFlags::NotInDeathmatch.into(),
+		}
+	}
+}
+
+/// The difficulty a map is loaded with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Skill {
+	Easy,
+	#[default]
+	Normal,
+	Hard,
+}
+
+/// The game mode a map is loaded with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GameMode {
+	#[default]
+	Singleplayer,
+	Coop,
+	Deathmatch,
+}
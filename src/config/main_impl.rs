@@ -1,12 +1,13 @@
 use std::path::Path;
 
 use crate::{
-	class::builtin::{read_rotation_from_entity, read_translation_from_entity},
+	class::builtin::{SpawnFilterFlags, read_rotation_from_entity, read_translation_from_entity},
 	fgd::{TargetDestination, TargetSource},
 };
 use bevy::asset::io::AssetSourceId;
 #[cfg(feature = "bsp")]
 use bsp::GENERIC_MATERIAL_PREFIX;
+use enumflags2::BitFlags;
 
 use super::*;
 
@@ -36,6 +37,8 @@ impl TrenchBroomConfig {
 	pub fn default_compute_lightmap_settings() -> ComputeLightmapSettings {
 		ComputeLightmapSettings {
 			special_lighting_color: [75; 3],
+			max_width: 2048,
+			max_height: 2048,
 			..default()
 		}
 	}
@@ -96,6 +99,32 @@ impl TrenchBroomConfig {
 		Ok(())
 	}
 
+	pub fn spawn_filter(mut self, provider: impl FnOnce(Arc<SpawnFilterFn>) -> Arc<SpawnFilterFn>) -> Self {
+		self.spawn_filter.set(provider);
+		self
+	}
+
+	/// Filters out entities whose [`SpawnFilterFlags`] exclude them from the [`MapLoadSettings`] the map is loaded with. `worldspawn` always spawns.
+	///
+	/// Bits of `spawnflags` other than [`SpawnFilterFlags`] are ignored. Entities whose `spawnflags` fail to parse are left out with a warning.
+	pub fn default_spawn_filter(view: SpawnFilterView) -> bool {
+		if view.src_entity.classname() == Ok("worldspawn") {
+			return true;
+		}
+		if !view.src_entity.properties.contains_key("spawnflags") {
+			return true;
+		}
+		let spawnflags = match view.src_entity.get::<u32>("spawnflags") {
+			Ok(spawnflags) => BitFlags::<SpawnFilterFlags>::from_bits_truncate(spawnflags),
+			Err(err) => {
+				warn!("Not spawning entity {} ({}): {err}", view.src_entity_idx, view.class.info.name);
+				return false;
+			}
+		};
+
+		!spawnflags.intersects(view.settings.excluding_flags())
+	}
+
 	pub fn post_scene_spawn_hook(mut self, provider: impl FnOnce(Arc<SpawnFn>) -> Arc<SpawnFn>) -> Self {
 		self.post_scene_spawn_hook.set(provider);
 		self
@@ -207,20 +236,30 @@ impl TrenchBroomConfig {
 
 				if view.tb_config().asset_exists(view.asset_server, &source, &path).await {
 					#[cfg(feature = "client")]
-					let image = view.load_context.load::<Image>(AssetPath::from_path(&path).with_source(source));
+					let image = view.load_context.load::<Image>(AssetPath::from_path(&path).with_source(source.clone()));
 					#[cfg(feature = "client")]
 					let material = (view.tb_config().image_material_loader.material)(image);
 					#[cfg(feature = "client")]
 					let material_handle = material.add_labeled_asset(view.load_context, format!("Material_{}", view.name));
 
-					return view.load_context.add_labeled_asset(
-						format!("GenericMaterial_{}", view.name),
-						GenericMaterial {
-							#[cfg(feature = "client")]
-							handle: material_handle,
-							properties: (view.tb_config().image_material_loader.properties)(),
-						},
-					);
+					#[allow(unused_mut)]
+					let mut generic_material = GenericMaterial {
+						#[cfg(feature = "client")]
+						handle: material_handle,
+						properties: (view.tb_config().image_material_loader.properties)(),
+					};
+
+					// Quake 2 textures store their animation chain in the file.
+					#[cfg(all(feature = "client", feature = "bsp"))]
+					if ext == "wal"
+						&& let Some(animation) = bsp::wal::load_wal_animation(view.load_context, &source, &path, view.tb_config()).await
+					{
+						generic_material.set_property(GenericMaterial::ANIMATION, animation);
+					}
+
+					return view
+						.load_context
+						.add_labeled_asset(format!("GenericMaterial_{}", view.name), generic_material);
 				}
 			}
 
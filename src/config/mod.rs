@@ -1,5 +1,7 @@
 mod hooks;
 pub use hooks::*;
+mod load_settings;
+pub use load_settings::*;
 mod main_impl;
 mod manifest;
 pub use manifest::*;
@@ -68,6 +70,8 @@ pub struct TrenchBroomConfig {
 	/// The supported extensions of your texture files. This is also used for material loading as a fallback. (Default: ["png"])
 	///
 	/// Each one of these adds a filesystem call to check if the file exists when loading loose textures, so try to keep this to what you absolutely need.
+	///
+	/// With the `bsp` feature, this can include Quake 2's `"wal"`.
 	#[default(["png".to_string()].into())]
 	#[builder(into)]
 	pub texture_extensions: Vec<String>,
@@ -102,6 +106,8 @@ pub struct TrenchBroomConfig {
 	#[default(Some("{{ scale == undefined -> %%scale%%, scale }}".to_string()))]
 	#[builder(into)]
 	pub entity_scale_expression: Option<String>,
+	/// The format to write entity definitions in when writing the game config. (Default: [`EntityDefinitionFormat::Fgd`])
+	pub entity_definition_format: EntityDefinitionFormat,
 	/// Whether to set property defaults into an entity on creation, or leave them to use the default value that is defined in entity definitions. It is not recommended to use this.
 	pub entity_set_default_properties: bool,
 
@@ -171,13 +177,26 @@ pub struct TrenchBroomConfig {
 	#[cfg(all(feature = "client", feature = "bsp"))]
 	#[default(IrradianceVolumeMultipliers::SLIGHT_SHADOW)]
 	pub irradiance_volume_multipliers: IrradianceVolumeMultipliers,
+	/// The size in light grid cells of each irradiance volume a BSP's light grid is split into, clamped to fit within [`MAX_IRRADIANCE_VOLUME_TILE_SIZE`](crate::bsp::loader::MAX_IRRADIANCE_VOLUME_TILE_SIZE) with overlap. (Default: 64)
+	#[cfg(all(feature = "client", feature = "bsp"))]
+	#[default(64)]
+	pub irradiance_volume_tile_size: u32,
 
 	/// Whether to ignore map entity spawning errors for not having an entity definition for the map entity in question's classname. (Default: false)
 	pub suppress_invalid_entity_definitions: bool,
 
 	/// Whether to disable bsp lighting (lightmaps and irradiance volumes). This is for rendering backends where these aren't supported like OpenGL.
+	/// If you still want lighting on those backends, see [`cpu_lighting_compositing`](Self::cpu_lighting_compositing).
 	#[cfg(feature = "bsp")]
 	pub no_bsp_lighting: bool,
+	/// If [`Some`], animated lighting is composited on the CPU instead of with compute shaders, for backends without compute support like WebGL2.
+	/// This keeps lighting images in the main world, using more memory. (Default: [`None`])
+	#[cfg(all(feature = "client", feature = "bsp"))]
+	pub cpu_lighting_compositing: Option<crate::bsp::lighting::CpuLightingCompositing>,
+	/// Path to a [`LightingAnimatorsAsset`](crate::bsp::lighting::LightingAnimatorsAsset) (e.g. `"lighting/default.animators.toml"`) applied to the [`LightingAnimators`] resource,
+	/// and hot reloaded if Bevy's `file_watcher` feature is enabled. Maps can override it with the `_lighting_animators` worldspawn key. (Default: [`None`])
+	#[cfg(all(feature = "client", feature = "bsp"))]
+	pub lighting_animators: Option<AssetPath<'static>>,
 
 	#[cfg(feature = "bsp")]
 	#[builder(skip)]
@@ -190,6 +209,7 @@ pub struct TrenchBroomConfig {
 	/// Default frames per second for embedded animated textures.
 	///
 	/// If [`Some`], embedded textures with names starting with `+<0..9>` will become animated, going to the next number in the range, and if it doesn't exist, looping back around to 0.
+	/// This is also used for the animation chains of Quake 2 `.wal` textures.
 	///
 	/// (Default: `Some(5)`)
 	#[cfg(feature = "bsp")]
@@ -235,7 +255,8 @@ pub struct TrenchBroomConfig {
 
 	/// How lightmaps atlas' are computed when loading BSP files.
 	///
-	/// It's worth noting that `wgpu` has a [texture size limit of 2048](https://github.com/gfx-rs/wgpu/discussions/2952), which can be expanded via [`RenderPlugin`](bevy::render::RenderPlugin) if needed.
+	/// `max_width` and `max_height` are the size of each atlas page, when a page fills up, lightmaps are packed into a new one.
+	/// They default to 2048, which is `wgpu`'s [default texture size limit](https://github.com/gfx-rs/wgpu/discussions/2952).
 	///
 	/// NOTE: `special_lighting_color` is set to gray (`75`) by default instead of white (`255`), because otherwise all textures with it look way too bright and washed out, not sure why.
 	///
@@ -258,6 +279,11 @@ pub struct TrenchBroomConfig {
 	#[default(Hook(Arc::new(Self::default_post_scene_spawn_hook)))]
 	pub post_scene_spawn_hook: Hook<SpawnFn>,
 
+	/// Decides whether each entity in a map is spawned, based on the [`MapLoadSettings`] it's loaded with. Runs before any spawn hooks. (Default: [`TrenchBroomConfig::default_spawn_filter`])
+	#[builder(skip)]
+	#[default(Hook(Arc::new(Self::default_spawn_filter)))]
+	pub spawn_filter: Hook<SpawnFilterFn>,
+
 	/// Scene hooks to run on solid classes unless overridden.
 	#[default(SceneHooks::new)]
 	pub default_solid_scene_hooks: fn() -> SceneHooks,
@@ -292,6 +318,12 @@ pub struct TrenchBroomConfig {
 	#[cfg(feature = "bsp")]
 	pub bicubic_lightmap_filtering: bool,
 
+	/// If `true`, deluxemaps (baked light directions) are loaded from a `.lux` file next to the BSP, or its `LIGHTINGDIR` BSPX lump.
+	/// Lightmapped meshes using a [`StandardMaterial`] with a normal map will have it replaced with a [`DeluxemapMaterial`](crate::bsp::lighting::DeluxemapMaterial). (Default: `true`)
+	#[cfg(feature = "bsp")]
+	#[default(true)]
+	pub load_deluxemaps: bool,
+
 	/// Whether brush meshes are kept around in memory after they're sent to the GPU. Default: [`RenderAssetUsages::all`] (kept around)
 	#[default(RenderAssetUsages::all())]
 	pub brush_mesh_asset_usages: RenderAssetUsages,
@@ -304,7 +336,14 @@ pub struct TrenchBroomConfig {
 
 #[cfg(test)]
 mod tests {
+	use bevy::reflect::TypeRegistry;
+
 	use super::*;
+	use crate::{
+		class::builtin::{FuncDoor, TriggerOnce},
+		qmap::QuakeMapEntity,
+		util::spawn_test_entity,
+	};
 
 	#[test]
 	fn coordinate_conversions() {
@@ -313,4 +352,78 @@ mod tests {
 		let input = vec3(20.6, 1.72, 9.0);
 		assert_eq!(config.from_bevy_space(config.to_bevy_space(input)), input);
 	}
+
+	#[test]
+	fn default_spawn_filter() {
+		let config = TrenchBroomConfig::default();
+		let class = class::ErasedQuakeClass::of::<Target>();
+		let spawns_with = |classname: &str, spawnflags: &str, skill, game_mode| {
+			let src_entity = QuakeMapEntity {
+				properties: [
+					("classname".to_string(), classname.to_string()),
+					("spawnflags".to_string(), spawnflags.to_string()),
+				]
+				.into(),
+				..default()
+			};
+			(config.spawn_filter)(SpawnFilterView {
+				src_entity: &src_entity,
+				src_entity_idx: 0,
+				class: &class,
+				settings: &MapLoadSettings { skill, game_mode },
+				tb_config: &config,
+			})
+		};
+		let spawns = |classname: &str, spawnflags: u32, skill, game_mode| spawns_with(classname, &spawnflags.to_string(), skill, game_mode);
+
+		// Not in easy or deathmatch.
+		let spawnflags = 256 | 2048;
+		assert!(!spawns("monster_army", spawnflags, Skill::Easy, GameMode::Singleplayer));
+		assert!(!spawns("monster_army", spawnflags, Skill::Easy, GameMode::Coop));
+		assert!(spawns("monster_army", spawnflags, Skill::Hard, GameMode::Singleplayer));
+		assert!(!spawns("monster_army", spawnflags, Skill::Hard, GameMode::Deathmatch));
+		// Skill is ignored in deathmatch.
+		assert!(spawns("monster_army", 256, Skill::Easy, GameMode::Deathmatch));
+		assert!(spawns("worldspawn", spawnflags, Skill::Easy, GameMode::Singleplayer));
+
+		// Unknown high bits are ignored rather than failing to parse.
+		let spawnflags = (1 << 16) | (1 << 20) | 512;
+		assert!(!spawns("monster_army", spawnflags, Skill::Normal, GameMode::Singleplayer));
+		assert!(spawns("monster_army", spawnflags, Skill::Hard, GameMode::Singleplayer));
+		// Invalid spawnflags don't slip past the filter.
+		assert!(!spawns_with("monster_army", "lots", Skill::Hard, GameMode::Singleplayer));
+	}
+
+	#[test]
+	fn spawn_filter_builtin_classes() {
+		let config = TrenchBroomConfig::default();
+		let mut type_registry = TypeRegistry::new();
+		type_registry.register::<FuncDoor>();
+		type_registry.register::<TriggerOnce>();
+		let class_map = class::generate_class_map(&type_registry);
+
+		// Not in easy or normal.
+		let spawnflags = (256 | 512).to_string();
+		for classname in ["func_door", "trigger_once"] {
+			let properties = [("classname", classname), ("spawnflags", spawnflags.as_str())];
+			let src_entity = QuakeMapEntity {
+				properties: properties.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+				..default()
+			};
+
+			for skill in [Skill::Easy, Skill::Normal, Skill::Hard] {
+				let spawns = (config.spawn_filter)(SpawnFilterView {
+					src_entity: &src_entity,
+					src_entity_idx: 0,
+					class: class_map[classname],
+					settings: &MapLoadSettings { skill, ..default() },
+					tb_config: &config,
+				});
+				assert_eq!(spawns, skill == Skill::Hard, "{classname} on {skill:?}");
+			}
+
+			// The class's own spawnflags have to parse as well, even with bits it doesn't define.
+			spawn_test_entity(&type_registry, &config, &properties).unwrap();
+		}
+	}
 }
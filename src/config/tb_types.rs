@@ -64,6 +64,26 @@ impl MapFileFormat {
 	}
 }
 
+/// The file format entity definitions are written in, see [`TrenchBroomConfig::entity_definition_format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EntityDefinitionFormat {
+	/// The FGD format, originally from Worldcraft/Hammer.
+	#[default]
+	Fgd,
+	/// Radiant's XML-based ENT format. Keeps multi-line descriptions and documents each spawnflag, but has no base classes.
+	Ent,
+}
+
+impl EntityDefinitionFormat {
+	/// The extension of entity definition files in this format.
+	pub fn extension(self) -> &'static str {
+		match self {
+			Self::Fgd => "fgd",
+			Self::Ent => "ent",
+		}
+	}
+}
+
 /// Tag for applying attributes to certain brushes/faces, for example, making a `trigger` material transparent.
 #[derive(Debug, Clone, Default, DefaultBuilder)]
 pub struct TrenchBroomTag {
@@ -2,7 +2,7 @@ use std::{env, fs, io, path::Path};
 
 use bevy_reflect::TypeRegistry;
 
-use crate::fgd::write_fgd;
+use crate::fgd::{write_ent, write_fgd};
 
 use super::*;
 
@@ -208,6 +208,11 @@ impl TrenchBroomConfig {
 		Ok(())
 	}
 
+	/// The name of the entity definitions file written alongside the game config, e.g. `MyGame.fgd`.
+	pub fn entity_definitions_file_name(&self) -> String {
+		format!("{}.{}", self.name, self.entity_definition_format.extension())
+	}
+
 	/// Writes the game configuration into a directory, it is your choice when to do this in your application, and where you want to save the config to.
 	///
 	/// If you have a standard TrenchBroom installation, you can use [`write_game_config_to_default_directory`](Self::write_game_config_to_default_directory) instead to use the default location.
@@ -243,7 +248,7 @@ impl TrenchBroomConfig {
 				"excludes": self.texture_exclusions.clone(),
 			},
 			"entities": {
-				"definitions": [ format!("{}.fgd", self.name) ],
+				"definitions": [ self.entity_definitions_file_name() ],
 				"defaultcolor": format!("{} {} {} {}", self.entity_default_color.x, self.entity_default_color.y, self.entity_default_color.z, self.entity_default_color.w),
 				"scale": "$$scale$$", // Placeholder
 				"setDefaultProperties": self.entity_set_default_properties,
@@ -286,10 +291,14 @@ impl TrenchBroomConfig {
 		fs::write(folder.join("GameConfig.cfg"), buf)?;
 
 		//////////////////////////////////////////////////////////////////////////////////
-		//// FGD
+		//// ENTITY DEFINITIONS
 		//////////////////////////////////////////////////////////////////////////////////
 
-		fs::write(folder.join(format!("{}.fgd", self.name)), write_fgd(type_registry))?;
+		let definitions = match self.entity_definition_format {
+			EntityDefinitionFormat::Fgd => write_fgd(type_registry),
+			EntityDefinitionFormat::Ent => write_ent(type_registry),
+		};
+		fs::write(folder.join(self.entity_definitions_file_name()), definitions)?;
 
 		info!("Successfully wrote TrenchBroom game config to {}", folder.display());
 
@@ -0,0 +1,487 @@
+//! Generates Rust source code from FGD class definitions, see [`fgd_to_rust`].
+use core::fmt::Write;
+
+use bevy::platform::collections::HashSet;
+use class::QuakeClassType;
+
+use super::definition::*;
+use crate::*;
+
+/// Generates Rust source code defining quake classes equivalent to FGD class definitions, for porting existing entity sets.
+///
+/// - Property types are mapped to [`FgdType`](super::FgdType)s, e.g. `integer` to `i32` and `vector` to [`Vec3`]. Unknown types become `String`s.
+/// - `choices` properties become enums deriving `FgdType`, and `flags` properties become [`FgdFlags`](super::FgdFlags) of `enumflags2` enums.
+/// - Descriptions become documentation comments, and defaults are applied both to `#[class(default = ...)]` and the struct's [`Default`] implementation.
+/// - Point and solid classes inherit from [`Transform`] and [`Visibility`] so they can be placed.
+///
+/// The output ends with a plugin registering all generated classes.
+pub fn fgd_to_rust(classes: &[FgdClassDefinition]) -> String {
+	let mut codegen = Codegen::default();
+
+	for class in classes {
+		codegen.class(class);
+	}
+
+	let mut s = String::from("// Generated from an FGD file by bevy_trenchbroom's `fgd_to_rust`.\n\nuse bevy::prelude::*;\n");
+	if codegen.uses_fgd_module {
+		s.push_str("use bevy_trenchbroom::{fgd::*, prelude::*};\n");
+	} else {
+		s.push_str("use bevy_trenchbroom::prelude::*;\n");
+	}
+	if codegen.uses_bitflags {
+		s.push_str("use enumflags2::bitflags;\n");
+	}
+
+	s.push_str(&codegen.items);
+
+	s.push_str("\npub struct FgdClassesPlugin;\nimpl Plugin for FgdClassesPlugin {\n\tfn build(&self, app: &mut App) {\n");
+	s.push_str("\t\t#[rustfmt::skip]\n\t\tapp\n");
+	for ident in &codegen.class_idents {
+		writeln!(s, "\t\t\t.register_type::<{ident}>()").ok();
+	}
+	s.push_str("\t\t;\n\t}\n}\n");
+
+	s
+}
+
+#[derive(Default)]
+struct Codegen {
+	items: String,
+	class_idents: Vec<String>,
+	uses_fgd_module: bool,
+	uses_bitflags: bool,
+}
+impl Codegen {
+	fn class(&mut self, class: &FgdClassDefinition) {
+		let ident = pascal_case(&class.name, "Class");
+
+		let mut options = Vec::new();
+
+		let mut bases = Vec::new();
+		if !class.ty.is_base() {
+			bases.extend(["Transform".to_string(), "Visibility".to_string()]);
+		}
+		bases.extend(class.base.iter().map(|base| pascal_case(base, "Class")));
+		if !bases.is_empty() {
+			options.push(format!("base({})", bases.join(", ")));
+		}
+
+		if snake_case_of_pascal(&ident) != class.name {
+			options.push(format!("classname({:?})", class.name));
+		}
+		for (name, value) in [
+			("model", &class.model),
+			("iconsprite", &class.iconsprite),
+			("color", &class.color),
+			("size", &class.size),
+		] {
+			if let Some(value) = value {
+				options.push(format!("{name}({value})"));
+			}
+		}
+		if class.decal {
+			options.push("decal".to_string());
+		}
+
+		let mut fields = Vec::new();
+		let mut field_idents = HashSet::new();
+		for property in &class.properties {
+			let mut field = self.field(&ident, property);
+			while !field_idents.insert(field.ident.clone()) {
+				field.ident.push('_');
+			}
+			if field.ident != property.name {
+				field.class_options.insert(0, format!("rename = {:?}", property.name));
+			}
+			fields.push(field);
+		}
+
+		let s = &mut self.items;
+		s.push('\n');
+		write_doc(s, "", class.description.as_deref());
+
+		let attribute = match class.ty {
+			QuakeClassType::Base => "base_class",
+			QuakeClassType::Point => "point_class",
+			QuakeClassType::Solid => "solid_class",
+		};
+		if options.is_empty() {
+			writeln!(s, "#[{attribute}]").ok();
+		} else {
+			writeln!(s, "#[{attribute}(").ok();
+			for option in options {
+				writeln!(s, "\t{option},").ok();
+			}
+			writeln!(s, ")]").ok();
+		}
+
+		let manual_default = fields.iter().any(|field| field.default_expr.is_some());
+
+		if fields.is_empty() {
+			writeln!(s, "pub struct {ident};").ok();
+		} else {
+			if !manual_default {
+				writeln!(s, "#[derive(Default)]").ok();
+			}
+			writeln!(s, "pub struct {ident} {{").ok();
+			for field in &fields {
+				write_doc(s, "\t", field.doc.as_deref());
+				if !field.class_options.is_empty() {
+					writeln!(s, "\t#[class({})]", field.class_options.join(", ")).ok();
+				}
+				writeln!(s, "\tpub {}: {},", field.ident, field.ty).ok();
+			}
+			writeln!(s, "}}").ok();
+		}
+
+		if manual_default {
+			writeln!(s, "impl Default for {ident} {{\n\tfn default() -> Self {{\n\t\tSelf {{").ok();
+			for field in &fields {
+				writeln!(s, "\t\t\t{}: {},", field.ident, field.default_expr.as_deref().unwrap_or("default()")).ok();
+			}
+			writeln!(s, "\t\t}}\n\t}}\n}}").ok();
+		}
+
+		self.class_idents.push(ident);
+	}
+
+	fn field(&mut self, class_ident: &str, property: &FgdPropertyDefinition) -> Field {
+		let default = property.default_value_unquoted();
+		let default = default.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+		let mut field = Field {
+			ident: field_ident(&property.name),
+			ty: "String".to_string(),
+			doc: property.description.clone(),
+			class_options: Vec::new(),
+			default_expr: None,
+		};
+
+		if let Some(title) = &property.title
+			&& *title != property.name
+		{
+			field.class_options.push(format!("title = {title:?}"));
+		}
+
+		match &property.ty {
+			FgdPropertyType::Value(ty) => {
+				let ty = value_type(ty);
+				field.ty = ty.to_string();
+				if !matches!(ty, "i32" | "f32" | "Vec3" | "String") {
+					self.uses_fgd_module = true;
+				}
+				field.default_expr = default.and_then(|default| default_expr(ty, default));
+			}
+			FgdPropertyType::Choices(choices) => {
+				let enum_ident = format!("{class_ident}{}", pascal_case(&property.name, "Property"));
+				if let Some(item) = choices_enum(&enum_ident, choices, default) {
+					self.items.push('\n');
+					self.items.push_str(&item);
+					field.ty = enum_ident;
+				} else {
+					// Keys that aren't numbers or identifiers can't be represented by `#[derive(FgdType)]`.
+					field.default_expr = default.and_then(|default| default_expr("String", default));
+				}
+			}
+			FgdPropertyType::Flags(flags) => {
+				let enum_ident = format!("{class_ident}{}", pascal_case(&property.name, "Property"));
+				match flags_enum(&enum_ident, flags) {
+					Some(item) => {
+						self.items.push('\n');
+						self.items.push_str(&item);
+						field.ty = format!("FgdFlags<{enum_ident}>");
+						self.uses_fgd_module = true;
+						self.uses_bitflags = true;
+					}
+					None => field.ty = "u32".to_string(),
+				}
+				// Flags defaults are specified in the enum.
+				return field;
+			}
+		}
+
+		if let Some(default) = default {
+			// Only integers can be without quotes.
+			let literal = if default.parse::<u64>().is_ok() {
+				default.to_string()
+			} else {
+				format!("{default:?}")
+			};
+			field.class_options.push(format!("default = {literal}"));
+		}
+
+		field
+	}
+}
+
+struct Field {
+	ident: String,
+	ty: String,
+	doc: Option<String>,
+	/// Arguments to the `#[class(...)]` attribute.
+	class_options: Vec<String>,
+	/// Expression used in the generated [`Default`] implementation, if it's different from the field type's default.
+	default_expr: Option<String>,
+}
+
+fn value_type(ty: &str) -> &'static str {
+	match ty {
+		"integer" => "i32",
+		"float" => "f32",
+		// FGD booleans are integers, which `bool` doesn't parse.
+		"boolean" | "bool" => "IntBool",
+		"vector" | "origin" => "Vec3",
+		"color255" | "color1" => "Srgb",
+		"target_source" => "TargetSource",
+		"target_destination" => "TargetDestination",
+		"studio" | "sprite" | "model" => "ModelPath",
+		_ => "String",
+	}
+}
+
+/// Returns a Rust expression for a property default of the specified type, or [`None`] if it's the type's default or can't be parsed.
+fn default_expr(ty: &str, default: &str) -> Option<String> {
+	let floats = || -> Option<[f32; 3]> {
+		let values = default.split_whitespace().map(str::parse).collect::<Result<Vec<f32>, _>>().ok()?;
+		values.try_into().ok()
+	};
+
+	match ty {
+		"i32" => default.parse::<i32>().ok().filter(|v| *v != 0).map(|v| v.to_string()),
+		"f32" => default.parse::<f32>().ok().filter(|v| *v != 0.).map(|v| format!("{v:?}")),
+		"IntBool" => default.parse::<i32>().is_ok_and(|v| v > 0).then(|| "IntBool(true)".to_string()),
+		"Vec3" => floats()
+			.filter(|v| *v != [0.; 3])
+			.map(|[x, y, z]| format!("Vec3::new({x:?}, {y:?}, {z:?})")),
+		"Srgb" => floats().map(|color| {
+			let [r, g, b] = if color.iter().any(|channel| *channel > 1.) {
+				color.map(|channel| channel / 255.)
+			} else {
+				color
+			};
+			format!("Srgb::new({r:?}, {g:?}, {b:?})")
+		}),
+		"TargetSource" | "TargetDestination" | "ModelPath" => Some(format!("{ty}({default:?}.to_string())")),
+		_ => Some(format!("{default:?}.to_string()")),
+	}
+}
+
+fn choices_enum(ident: &str, choices: &[(String, String)], default: Option<&str>) -> Option<String> {
+	let keys = choices.iter().map(|(key, _)| key.trim_matches('"')).collect_vec();
+	let default_idx = default.and_then(|default| keys.iter().position(|key| *key == default)).unwrap_or(0);
+
+	let mut s = String::new();
+
+	if keys.iter().all(|key| key.parse::<i32>().is_ok()) {
+		writeln!(
+			s,
+			"#[derive(FgdType, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]\n#[number_key]"
+		)
+		.ok();
+		writeln!(s, "pub enum {ident} {{").ok();
+
+		let mut variant_idents = HashSet::new();
+		for (i, ((_, title), key)) in choices.iter().zip(&keys).enumerate() {
+			let mut variant = pascal_case(title, "");
+			if variant.is_empty() || !variant_idents.insert(variant.clone()) {
+				variant = format!("Value{}", key.replace('-', "Neg"));
+				variant_idents.insert(variant.clone());
+			}
+
+			writeln!(s, "\t/// {title}").ok();
+			if i == default_idx {
+				writeln!(s, "\t#[default]").ok();
+			}
+			writeln!(s, "\t{variant} = {key},").ok();
+		}
+	} else if keys.iter().all(|key| is_identifier(key)) && keys.iter().all_unique() {
+		// The variant names are used as keys.
+		writeln!(
+			s,
+			"#[derive(FgdType, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]\n#[allow(non_camel_case_types)]"
+		)
+		.ok();
+		writeln!(s, "pub enum {ident} {{").ok();
+
+		for (i, ((_, title), key)) in choices.iter().zip(&keys).enumerate() {
+			writeln!(s, "\t/// {title}").ok();
+			if i == default_idx {
+				writeln!(s, "\t#[default]").ok();
+			}
+			writeln!(s, "\t{key},").ok();
+		}
+	} else {
+		return None;
+	}
+
+	writeln!(s, "}}").ok();
+	Some(s)
+}
+
+fn flags_enum(ident: &str, flags: &[FgdFlag]) -> Option<String> {
+	// enumflags2 only supports single-bit flags.
+	let flags = flags.iter().filter(|flag| flag.value.is_power_of_two()).collect_vec();
+	if flags.is_empty() {
+		return None;
+	}
+
+	let mut variant_idents = HashSet::new();
+	let variants = flags
+		.iter()
+		.map(|flag| {
+			let mut variant = pascal_case(&flag.title, "");
+			if variant.is_empty() || !variant_idents.insert(variant.clone()) {
+				variant = format!("Flag{}", flag.value);
+				variant_idents.insert(variant.clone());
+			}
+			variant
+		})
+		.collect_vec();
+
+	let defaults = flags
+		.iter()
+		.zip(&variants)
+		.filter(|(flag, _)| flag.default)
+		.map(|(_, variant)| variant.as_str())
+		.join(" | ");
+
+	let mut s = String::new();
+	if defaults.is_empty() {
+		writeln!(s, "#[bitflags]").ok();
+	} else {
+		writeln!(s, "#[bitflags(default = {defaults})]").ok();
+	}
+	writeln!(
+		s,
+		"#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]\n#[repr(u32)]\npub enum {ident} {{"
+	)
+	.ok();
+
+	for (flag, variant) in flags.iter().zip(&variants) {
+		writeln!(s, "\t/// {}", flag.title).ok();
+		writeln!(s, "\t{variant} = 1 << {},", flag.value.trailing_zeros()).ok();
+	}
+
+	writeln!(s, "}}").ok();
+	Some(s)
+}
+
+fn write_doc(s: &mut String, indent: &str, doc: Option<&str>) {
+	for line in doc.into_iter().flat_map(str::lines) {
+		writeln!(s, "{indent}/// {}", line.trim()).ok();
+	}
+}
+
+/// Converts a classname or title into a PascalCase identifier, prefixing it with `prefix` if it would start with a number.
+fn pascal_case(s: &str, prefix: &str) -> String {
+	let ident: String = s
+		.split(|c: char| !c.is_ascii_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.map(|word| {
+			let mut chars = word.chars();
+			chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars).collect::<String>()
+		})
+		.collect();
+
+	if ident.starts_with(|c: char| c.is_ascii_digit()) {
+		format!("{prefix}{ident}")
+	} else {
+		ident
+	}
+}
+
+/// What the class macros would convert `ident` back into. Only needs to be accurate for identifiers we generate from snake_case classnames.
+fn snake_case_of_pascal(ident: &str) -> String {
+	let mut s = String::new();
+	for c in ident.chars() {
+		if c.is_ascii_uppercase() {
+			if !s.is_empty() {
+				s.push('_');
+			}
+			s.push(c.to_ascii_lowercase());
+		} else {
+			s.push(c);
+		}
+	}
+	s
+}
+
+fn field_ident(property: &str) -> String {
+	let mut ident: String = property
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+		.collect();
+
+	if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+		ident.insert(0, '_');
+	}
+	if RUST_KEYWORDS.contains(&ident.as_str()) {
+		ident.push('_');
+	}
+
+	ident
+}
+
+fn is_identifier(s: &str) -> bool {
+	s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+		&& s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+		&& !RUST_KEYWORDS.contains(&s)
+		&& s != "_"
+}
+
+#[rustfmt::skip]
+const RUST_KEYWORDS: &[&str] = &[
+	"as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for", "gen", "if", "impl", "in",
+	"let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+	"unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fgd::parse_fgd;
+
+	#[test]
+	fn generate_classes() {
+		let file = parse_fgd(
+			r#"
+			@BaseClass = Targetname [ targetname(target_source) : "Name" ]
+			@PointClass base(Targetname) size(-8 -8 -8, 8 8 8) color(255 128 0) = item_crate : "A crate."
+			[
+				health(integer) : "Health" : 10 : "How much damage it takes."
+				type(choices) : "Type" : 1 =
+				[
+					0 : "Wood"
+					1 : "Metal"
+				]
+				spawnflags(flags) =
+				[
+					1 : "Floating" : 0
+					2 : "Hidden" : 1
+				]
+				_tint(color255) : "Tint" : "255 0 0"
+			]
+			@SolidClass = func_wall2 []
+			"#,
+		)
+		.unwrap();
+
+		let source = fgd_to_rust(&file.classes);
+
+		for expected in [
+			"use bevy_trenchbroom::{fgd::*, prelude::*};\nuse enumflags2::bitflags;\n",
+			"#[base_class(\n\tclassname(\"Targetname\"),\n)]\n#[derive(Default)]\npub struct Targetname {\n\t#[class(title = \"Name\")]\n\tpub targetname: TargetSource,\n}\n",
+			"#[derive(FgdType, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]\n#[number_key]\npub enum ItemCrateType {\n\t/// Wood\n\tWood = 0,\n\t/// Metal\n\t#[default]\n\tMetal = 1,\n}\n",
+			"#[bitflags(default = Hidden)]\n#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]\n#[repr(u32)]\npub enum ItemCrateSpawnflags {\n\t/// Floating\n\tFloating = 1 << 0,\n\t/// Hidden\n\tHidden = 1 << 1,\n}\n",
+			"/// A crate.\n#[point_class(\n\tbase(Transform, Visibility, Targetname),\n\tcolor(255 128 0),\n\tsize(-8 -8 -8, 8 8 8),\n)]\npub struct ItemCrate {\n",
+			"\t/// How much damage it takes.\n\t#[class(title = \"Health\", default = 10)]\n\tpub health: i32,\n",
+			"\t#[class(rename = \"type\", title = \"Type\", default = 1)]\n\tpub type_: ItemCrateType,\n",
+			"\tpub spawnflags: FgdFlags<ItemCrateSpawnflags>,\n",
+			"\t\t\thealth: 10,\n\t\t\ttype_: default(),\n\t\t\tspawnflags: default(),\n\t\t\t_tint: Srgb::new(1.0, 0.0, 0.0),\n",
+			"#[solid_class(\n\tbase(Transform, Visibility),\n)]\npub struct FuncWall2;\n",
+			"\t\t\t.register_type::<Targetname>()\n\t\t\t.register_type::<ItemCrate>()\n\t\t\t.register_type::<FuncWall2>()\n",
+		] {
+			assert!(source.contains(expected), "expected:\n{expected}\nin:\n{source}");
+		}
+	}
+}
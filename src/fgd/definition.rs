@@ -0,0 +1,255 @@
+//! Owned versions of [`QuakeClassInfo`], for classes that only exist at runtime, such as ones [imported from FGD files](crate::class::dynamic).
+use core::fmt;
+
+use class::{QuakeClassInfo, QuakeClassPropertyType, QuakeClassType};
+
+use super::FgdType;
+use crate::*;
+
+/// A class definition as it appears in an FGD file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FgdClassDefinition {
+	pub ty: QuakeClassType,
+	pub name: String,
+	pub description: Option<String>,
+	/// Classnames of the classes this class inherits from.
+	pub base: Vec<String>,
+
+	pub model: Option<String>,
+	pub color: Option<String>,
+	pub iconsprite: Option<String>,
+	pub size: Option<String>,
+	pub decal: bool,
+
+	pub properties: Vec<FgdPropertyDefinition>,
+}
+impl FgdClassDefinition {
+	/// Copies a Rust-defined class' info. Flag defaults are read from the property's default value,
+	/// and multi-line flag documentation is split into a title and description.
+	pub fn from_info(info: &QuakeClassInfo) -> Self {
+		Self {
+			ty: info.ty,
+			name: info.name.to_string(),
+			description: Self::description_with_inputs(info),
+			base: info.base.iter().map(|base| base.info.name.to_string()).collect(),
+
+			model: info.model.map(str::to_string),
+			color: info.color.map(str::to_string),
+			iconsprite: info.iconsprite.map(str::to_string),
+			size: info.size.map(str::to_string),
+			decal: info.decal,
+
+			properties: info
+				.properties
+				.iter()
+				.map(|property| {
+					let default_value = property.default_value.map(|f| f());
+
+					FgdPropertyDefinition {
+						ty: match property.ty {
+							QuakeClassPropertyType::Value(ty) => FgdPropertyType::Value(ty.to_string()),
+							QuakeClassPropertyType::Choices(choices) => {
+								FgdPropertyType::Choices(choices.iter().map(|(key, title)| (key.to_string(), title.to_string())).collect())
+							}
+							QuakeClassPropertyType::Flags(new_flags_iter) => {
+								let default = default_value.as_deref().and_then(|s| u32::fgd_parse(s).ok()).unwrap_or(0);
+
+								FgdPropertyType::Flags(
+									new_flags_iter()
+										.enumerate()
+										.map(|(i, (value, docs))| {
+											// The first line of a flag's documentation is its title, the rest is its description.
+											let (title, description) = docs.split_once('\n').unwrap_or((docs, ""));
+											let description = description.trim();
+
+											FgdFlag {
+												value,
+												title: title.trim().to_string(),
+												default: (default >> i) & 1 != 0,
+												description: (!description.is_empty()).then(|| description.to_string()),
+											}
+										})
+										.collect(),
+								)
+							}
+						},
+						name: property.name.to_string(),
+						title: property.title.map(str::to_string),
+						description: property.description.map(str::to_string),
+						default_value,
+					}
+				})
+				.chain(info.outputs.iter().map(|output| {
+					// TrenchBroom doesn't support Source-style outputs, so we write them as regular properties.
+					let format = "Connections formatted as `target,Input,parameter,delay,times_to_fire`, separated by `;`.";
+
+					FgdPropertyDefinition {
+						ty: FgdPropertyType::Value("string".to_string()),
+						name: output.name.to_string(),
+						title: Some(format!("Output {}", output.name)),
+						description: Some(match output.description {
+							Some(description) => format!("{description} {format}"),
+							None => format.to_string(),
+						}),
+						default_value: None,
+					}
+				}))
+				.collect(),
+		}
+	}
+
+	/// Lists a class' inputs after its description, as FGD files can't represent them in a way TrenchBroom understands.
+	fn description_with_inputs(info: &QuakeClassInfo) -> Option<String> {
+		if info.inputs.is_empty() {
+			return info.description.map(str::to_string);
+		}
+
+		let mut description = info.description.map(|description| format!("{description}\n\n")).unwrap_or_default();
+		description.push_str("Inputs:");
+
+		for input in info.inputs {
+			description.push_str("\n- ");
+			description.push_str(input.name);
+			if let Some(QuakeClassPropertyType::Value(ty)) = input.parameter {
+				description.push_str(&format!(" ({ty})"));
+			}
+			if let Some(input_description) = input.description {
+				description.push_str(": ");
+				description.push_str(input_description);
+			}
+		}
+
+		Some(description)
+	}
+}
+impl fmt::Display for FgdClassDefinition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "@{}Class ", self.ty)?;
+
+		if !self.base.is_empty() {
+			write!(f, "base({}) ", self.base.join(", "))?;
+		}
+
+		if let Some(value) = &self.color {
+			write!(f, "color({value}) ")?;
+		}
+		if let Some(value) = &self.iconsprite {
+			write!(f, "iconsprite({value}) ")?;
+		}
+		if let Some(value) = &self.size {
+			write!(f, "size({value}) ")?;
+		}
+		if let Some(value) = &self.model {
+			write!(f, "model({value}) ")?;
+		}
+		if self.decal {
+			write!(f, "decal() ")?;
+		}
+
+		write!(f, "= {}", self.name)?;
+		if let Some(description) = &self.description {
+			write!(f, " : \"{}\"", escape_newlines(description))?;
+		}
+		writeln!(f, "\n[")?;
+
+		for property in &self.properties {
+			write!(f, "{property}")?;
+		}
+
+		writeln!(f, "]")
+	}
+}
+
+/// TrenchBroom can end a string early at a line break, so they're written as `\n` escapes, which [`parse_fgd`](super::parse_fgd) reads back.
+fn escape_newlines(s: &str) -> String {
+	s.replace('\n', "\\n")
+}
+
+/// A property of an [`FgdClassDefinition`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FgdPropertyDefinition {
+	pub ty: FgdPropertyType,
+	pub name: String,
+	pub title: Option<String>,
+	pub description: Option<String>,
+	/// The default value as written in the FGD, including quotes if it has them. Not written for flags, see [`FgdFlag::default`].
+	pub default_value: Option<String>,
+}
+impl FgdPropertyDefinition {
+	/// The value an entity should have for this property if it isn't set in the map, without quotes. Flags are combined into a single integer.
+	pub fn default_value_unquoted(&self) -> Option<String> {
+		if let FgdPropertyType::Flags(flags) = &self.ty {
+			return Some(flags.iter().filter(|flag| flag.default).fold(0, |acc, flag| acc | flag.value).to_string());
+		}
+
+		let value = self.default_value.as_deref()?;
+		Some(value.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(value).to_string())
+	}
+}
+impl fmt::Display for FgdPropertyDefinition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let ty = match &self.ty {
+			FgdPropertyType::Value(ty) => ty.as_str(),
+			FgdPropertyType::Choices(_) => "choices",
+			FgdPropertyType::Flags(flags) => {
+				writeln!(f, "\t{}(flags) =\n\t[", self.name)?;
+				for flag in flags {
+					write!(f, "\t\t{} : \"{}\" : {}", flag.value, flag.title, flag.default as u8)?;
+					if let Some(description) = &flag.description {
+						write!(f, " : \"{}\"", escape_newlines(description))?;
+					}
+					writeln!(f)?;
+				}
+				return writeln!(f, "\t]");
+			}
+		};
+
+		write!(
+			f,
+			"\t{}({ty}) : \"{}\" : {} : \"{}\"",
+			self.name,
+			self.title.as_deref().unwrap_or(&self.name),
+			self.default_value.as_deref().unwrap_or_default(),
+			escape_newlines(self.description.as_deref().unwrap_or_default()),
+		)?;
+
+		if let FgdPropertyType::Choices(choices) = &self.ty {
+			write!(f, " = \n\t[\n")?;
+			for (key, title) in choices {
+				writeln!(f, "\t\t{key} : \"{title}\"")?;
+			}
+			write!(f, "\t]")?;
+		}
+
+		writeln!(f)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FgdPropertyType {
+	/// The type name as written in the FGD, e.g. `string` or `integer`.
+	Value(String),
+	/// Keys as written in the FGD (including quotes if it has them), and their titles.
+	Choices(Vec<(String, String)>),
+	Flags(Vec<FgdFlag>),
+}
+impl Default for FgdPropertyType {
+	fn default() -> Self {
+		Self::Value("string".to_string())
+	}
+}
+
+/// A single flag of a [`FgdPropertyType::Flags`] property.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FgdFlag {
+	pub value: u32,
+	pub title: String,
+	/// Whether this flag is on by default.
+	#[serde(default)]
+	pub default: bool,
+	#[serde(default)]
+	pub description: Option<String>,
+}
@@ -0,0 +1,286 @@
+//! Writes TrenchBroom's XML-based ENT entity definition format, see [`write_ent`].
+//!
+//! Compared to FGD, ENT keeps multi-line descriptions intact and documents each spawnflag individually,
+//! but it has no concept of base classes, so inherited options and properties are flattened into each class.
+use core::fmt::Write;
+
+use bevy_reflect::TypeRegistry;
+use class::QuakeClassType;
+
+use super::{class_definitions, definition::*};
+use crate::*;
+
+/// Writes classes in a type registry to a string for writing `ent` (XML entity definition) files.
+///
+/// Like [`write_fgd`](super::write_fgd), this includes [dynamic classes](crate::class::dynamic) whose classnames aren't taken by a Rust class.
+pub fn write_ent(type_registry: &TypeRegistry) -> String {
+	ent_from_definitions(&class_definitions(type_registry))
+}
+
+fn ent_from_definitions(definitions: &[FgdClassDefinition]) -> String {
+	let classes = definitions
+		.iter()
+		.filter(|class| !class.ty.is_base())
+		.map(|class| flatten(class, definitions))
+		.collect_vec();
+
+	let mut s = String::from("<?xml version=\"1.0\"?>\n<classes>\n");
+
+	// Choices are declared as lists up front, which properties then reference by element name.
+	for class in &classes {
+		for property in &class.properties {
+			let FgdPropertyType::Choices(choices) = &property.ty else { continue };
+
+			writeln!(s, "<list name=\"{}\">", list_name(class, property)).ok();
+			for (key, title) in choices {
+				writeln!(
+					s,
+					"\t<item name=\"{}\" value=\"{}\"/>",
+					escape(title.trim()),
+					escape(key.trim_matches('"'))
+				)
+				.ok();
+			}
+			writeln!(s, "</list>").ok();
+		}
+	}
+
+	for class in &classes {
+		let element = match class.ty {
+			QuakeClassType::Solid => "group",
+			_ => "point",
+		};
+
+		write!(s, "<{element} name=\"{}\"", escape(&class.name)).ok();
+		if let Some(color) = class.color.as_deref().and_then(normalize_color) {
+			write!(s, " color=\"{color}\"").ok();
+		}
+		if let Some(size) = &class.size
+			&& class.ty == QuakeClassType::Point
+		{
+			write!(s, " box=\"{}\"", escape(&size.replace(',', " ").split_whitespace().join(" "))).ok();
+		}
+		if let Some(model) = &class.model
+			&& class.ty == QuakeClassType::Point
+		{
+			write!(s, " model=\"{}\"", escape(model)).ok();
+		}
+		writeln!(s, ">").ok();
+
+		if let Some(description) = &class.description {
+			writeln!(s, "{}", escape(description.trim())).ok();
+		}
+
+		for property in &class.properties {
+			write_property(&mut s, class, property);
+		}
+
+		writeln!(s, "</{element}>").ok();
+	}
+
+	s.push_str("</classes>\n");
+	s
+}
+
+fn write_property(s: &mut String, class: &FgdClassDefinition, property: &FgdPropertyDefinition) {
+	let element = match &property.ty {
+		// ENT flags always refer to spawnflags, other flags properties fall back to plain integers.
+		FgdPropertyType::Flags(flags) if property.name == "spawnflags" => {
+			for flag in flags.iter().filter(|flag| flag.value.is_power_of_two()) {
+				write!(
+					s,
+					"\t<flag key=\"{}\" name=\"{}\" bit=\"{}\"",
+					flag_key(&flag.title),
+					escape(&flag.title),
+					flag.value.trailing_zeros()
+				)
+				.ok();
+				write_description(s, "flag", flag.description.as_deref());
+			}
+			return;
+		}
+		FgdPropertyType::Flags(_) => "integer".to_string(),
+		FgdPropertyType::Choices(_) => list_name(class, property),
+		FgdPropertyType::Value(ty) => match ty.as_str() {
+			"integer" => "integer",
+			"float" => "real",
+			"boolean" | "bool" => "boolean",
+			"target_source" => "targetname",
+			"target_destination" => "target",
+			"color" | "color1" | "color255" => "color",
+			"studio" | "model" => "model",
+			"sound" => "sound",
+			"decal" | "material" | "texture" => "texture",
+			_ => "string",
+		}
+		.to_string(),
+	};
+
+	write!(
+		s,
+		"\t<{element} key=\"{}\" name=\"{}\"",
+		escape(&property.name),
+		escape(property.title.as_deref().unwrap_or(&property.name))
+	)
+	.ok();
+	if let Some(default) = property.default_value_unquoted()
+		&& !default.is_empty()
+	{
+		write!(s, " value=\"{}\"", escape(&default)).ok();
+	}
+	write_description(s, &element, property.description.as_deref());
+}
+
+/// Finishes an element opened by the caller, with `description` as its text.
+fn write_description(s: &mut String, element: &str, description: Option<&str>) {
+	match description.map(str::trim).filter(|description| !description.is_empty()) {
+		Some(description) => writeln!(s, ">{}</{element}>", escape(description)),
+		None => writeln!(s, "/>"),
+	}
+	.ok();
+}
+
+/// Copies `class`, with the options and properties of its base classes merged in.
+/// Properties of the class override inherited properties of the same name, staying in the inherited position.
+fn flatten(class: &FgdClassDefinition, definitions: &[FgdClassDefinition]) -> FgdClassDefinition {
+	let mut flattened = FgdClassDefinition {
+		base: Vec::new(),
+		properties: Vec::new(),
+		..class.clone()
+	};
+
+	for base in class.base.iter().filter_map(|base| definitions.iter().find(|class| &class.name == base)) {
+		let base = flatten(base, definitions);
+
+		flattened.model = flattened.model.or(base.model);
+		flattened.color = flattened.color.or(base.color);
+		flattened.size = flattened.size.or(base.size);
+
+		for property in base.properties {
+			merge_property(&mut flattened.properties, property);
+		}
+	}
+
+	for property in &class.properties {
+		merge_property(&mut flattened.properties, property.clone());
+	}
+
+	flattened
+}
+
+/// Flags are merged like TrenchBroom does with FGD base classes, so classes can add their own spawnflags to inherited ones.
+fn merge_property(properties: &mut Vec<FgdPropertyDefinition>, property: FgdPropertyDefinition) {
+	let Some(existing) = properties.iter_mut().find(|existing| existing.name == property.name) else {
+		properties.push(property);
+		return;
+	};
+
+	if let (FgdPropertyType::Flags(existing_flags), FgdPropertyType::Flags(flags)) = (&mut existing.ty, &property.ty) {
+		let mut merged = existing_flags.clone();
+		for flag in flags {
+			match merged.iter_mut().find(|existing_flag| existing_flag.value == flag.value) {
+				Some(existing_flag) => *existing_flag = flag.clone(),
+				None => merged.push(flag.clone()),
+			}
+		}
+		merged.sort_by_key(|flag| flag.value);
+		*existing = FgdPropertyDefinition {
+			ty: FgdPropertyType::Flags(merged),
+			..property
+		};
+	} else {
+		*existing = property;
+	}
+}
+
+/// The name of the list a `choices` property's element refers to. Lists are global, so this includes the classname.
+fn list_name(class: &FgdClassDefinition, property: &FgdPropertyDefinition) -> String {
+	format!("{}_{}", class.name, property.name)
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+		.collect()
+}
+
+fn flag_key(title: &str) -> String {
+	title
+		.trim()
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+		.collect()
+}
+
+/// ENT colors are `0-1` floats, while FGD classes usually use `0-255`.
+fn normalize_color(color: &str) -> Option<String> {
+	let components = color.split_whitespace().map(str::parse::<f32>).collect::<Result<Vec<f32>, _>>().ok()?;
+
+	if components.iter().any(|component| *component > 1.) {
+		Some(components.iter().map(|component| component / 255.).join(" "))
+	} else {
+		Some(components.iter().join(" "))
+	}
+}
+
+fn escape(s: &str) -> String {
+	let mut escaped = String::with_capacity(s.len());
+	for c in s.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			_ => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fgd::parse_fgd;
+
+	#[test]
+	fn write_classes() {
+		let file = parse_fgd(
+			r#"
+			@BaseClass color(255 0 0) = targetname [ targetname(target_source) : "Name" ]
+
+			@PointClass base(targetname) size(-8 -8 -8, 8 8 8) = item_box : "A box <for testing>"
+			[
+				health(integer) : "Health" : 10 : "How much damage it takes."
+				style(choices) : "Style" : 0 =
+				[
+					0 : "Normal"
+					1 : "Flicker"
+				]
+				spawnflags(flags) =
+				[
+					1 : "Floating" : 0
+					2 : "Hidden" : 1 : "Starts hidden."
+				]
+			]
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(
+			ent_from_definitions(&file.classes),
+			r#"<?xml version="1.0"?>
+<classes>
+<list name="item_box_style">
+	<item name="Normal" value="0"/>
+	<item name="Flicker" value="1"/>
+</list>
+<point name="item_box" color="1 0 0" box="-8 -8 -8 8 8 8">
+A box &lt;for testing&gt;
+	<targetname key="targetname" name="Name"/>
+	<integer key="health" name="Health" value="10">How much damage it takes.</integer>
+	<item_box_style key="style" name="Style" value="0"/>
+	<flag key="FLOATING" name="Floating" bit="0"/>
+	<flag key="HIDDEN" name="Hidden" bit="1">Starts hidden.</flag>
+</point>
+</classes>
+"#
+		);
+	}
+}
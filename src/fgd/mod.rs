@@ -1,35 +1,67 @@
+mod codegen;
+mod definition;
+mod ent;
+mod parse;
+pub use codegen::*;
+pub use definition::*;
+pub use ent::*;
+pub use parse::*;
+
 use core::fmt;
 
 use bevy_reflect::{
-	GetTypeRegistration, TypeRegistry,
+	FromType, GetTypeRegistration, TypeRegistry,
 	enums::{DynamicEnum, DynamicVariant, Enum},
 };
-use class::{ChoicesKey, QuakeClassPropertyType};
+use class::{ChoicesKey, QuakeClassPropertyType, dynamic::DynamicQuakeClasses};
 use enumflags2::{BitFlag, BitFlags};
 
 use crate::*;
 
 /// Writes classes in a type registry to a string for writing `fgd` (entity definition) files.
+///
+/// This includes [dynamic classes](crate::class::dynamic) whose classnames aren't taken by a Rust class.
 pub fn write_fgd(type_registry: &TypeRegistry) -> String {
-	let classes = type_registry
+	use fmt::Write;
+	let mut s = String::new();
+
+	for definition in class_definitions(type_registry) {
+		writeln!(s, "{definition}").ok();
+	}
+
+	s
+}
+
+/// Collects the definitions of every class that should be written to an entity definition file, sorted by classname.
+///
+/// Base classes nothing inherits from are left out, as are classes with unregistered base classes.
+pub(crate) fn class_definitions(type_registry: &TypeRegistry) -> Vec<FgdClassDefinition> {
+	let rust_classes = type_registry
 		.iter_with_data::<ReflectQuakeClass>()
 		.filter(|(_, class)| class.enabled)
 		.map(|(_, class)| class.erased_class)
+		.collect_vec();
+
+	let dynamic_classes = DynamicQuakeClasses::get(type_registry)
+		.into_iter()
+		.flat_map(DynamicQuakeClasses::iter)
+		.filter(|class| rust_classes.iter().all(|rust_class| rust_class.info.name != class.info.name));
+
+	let classes = rust_classes
+		.iter()
+		.copied()
+		.chain(dynamic_classes)
 		.sorted_by(|a, b| a.info.name.cmp(b.info.name))
 		.collect_vec();
 
-	use fmt::Write;
-	let mut s = String::new();
-	macro_rules! write {($($arg:tt)*) => {
-		s.write_fmt(format_args!($($arg)*)).ok()
-	};}
+	let mut definitions = Vec::with_capacity(classes.len());
 
 	'class_loop: for class in &classes {
 		// If this is a base class, and nothing depends on it, we shouldn't write it.
 		if class.info.ty.is_base()
 			&& classes
 				.iter()
-				.all(|checking_class| !checking_class.info.base.iter().any(|base| base.type_id == class.type_id))
+				.all(|checking_class| !checking_class.info.base.iter().any(|base| base.is(class)))
 		{
 			continue;
 		}
@@ -42,77 +74,15 @@ pub fn write_fgd(type_registry: &TypeRegistry) -> String {
 			}
 		}
 
-		write!("@{}Class ", class.info.ty);
-
-		if !class.info.base.is_empty() {
-			write!("base({}) ", class.info.base.iter().map(|base| base.info.name).join(", "));
-		}
-
-		if let Some(value) = class.info.color {
-			write!("color({value}) ");
-		}
-		if let Some(value) = class.info.iconsprite {
-			write!("iconsprite({value}) ");
-		}
-		if let Some(value) = class.info.size {
-			write!("size({value}) ");
-		}
-		if let Some(value) = class.info.model {
-			write!("model({value}) ");
-		}
-		if class.info.decal {
-			write!("decal() ");
-		}
-
-		write!("= {}", class.info.name);
-		if let Some(description) = class.info.description {
-			write!(" : \"{description}\"");
-		}
-		write!("\n[\n");
-
-		for property in class.info.properties {
-			if let QuakeClassPropertyType::Flags(new_flags_iter) = property.ty {
-				let flags_iter = new_flags_iter();
-
-				write!("\t{}(flags) =\n\t[\n", property.name);
-				let default = property.default_value.and_then(|f| u32::fgd_parse(&f()).ok()).unwrap_or(0);
-
-				for (i, (value, title)) in flags_iter.enumerate() {
-					write!("\t\t{value} : \"{title}\" : {}\n", (default >> i) & 1);
-				}
-
-				write!("\t]\n");
-				continue;
-			}
-
-			write!(
-				"\t{}({}) : \"{}\" : {} : \"{}\"",
-				property.name,
-				match property.ty {
-					QuakeClassPropertyType::Value(ty) => ty,
-					QuakeClassPropertyType::Choices(_) => "choices",
-					QuakeClassPropertyType::Flags(_) => unreachable!(),
-				},
-				property.title.unwrap_or(property.name),
-				property.default_value.unwrap_or(String::new)(),
-				property.description.unwrap_or_default(),
-			);
-
-			if let QuakeClassPropertyType::Choices(choices) = property.ty {
-				write!(" = \n\t[\n");
-				for (key, title) in choices {
-					write!("\t\t{key} : \"{title}\"\n");
-				}
-				write!("\t]");
-			}
-
-			write!("\n");
-		}
-
-		write!("]\n\n");
+		definitions.push(
+			match DynamicQuakeClasses::get(type_registry).and_then(|classes| classes.definition(class)) {
+				Some(definition) => definition.clone(),
+				None => FgdClassDefinition::from_info(&class.info),
+			},
+		);
 	}
 
-	s
+	definitions
 }
 
 /// Contains Quake/TrenchBroom-specific parsing and stringification functions.
@@ -135,6 +105,21 @@ pub trait FgdType: Sized {
 	}
 }
 
+/// Reflects [`FgdType::fgd_parse`], allowing entity properties to be parsed into reflected fields, such as components of [dynamic classes](crate::class::dynamic).
+///
+/// Builtin [`FgdType`]s have this registered by [`QuakeClassPlugin`](crate::class::QuakeClassPlugin), use `#[reflect(FgdType)]` to register it for your own.
+#[derive(Clone)]
+pub struct ReflectFgdType {
+	pub fgd_parse: fn(&str) -> anyhow::Result<Box<dyn PartialReflect>>,
+}
+impl<T: FgdType + PartialReflect> FromType<T> for ReflectFgdType {
+	fn from_type() -> Self {
+		Self {
+			fgd_parse: |input| Ok(Box::new(T::fgd_parse(input)?)),
+		}
+	}
+}
+
 impl FgdType for String {
 	const PROPERTY_TYPE: QuakeClassPropertyType = QuakeClassPropertyType::Value("string");
 
@@ -433,6 +418,23 @@ impl FgdType for TargetSource {
 	}
 }
 
+/// Path to a model or sprite, relative to the assets folder. Shows up as a model browser in TrenchBroom.
+///
+/// Use it with a `model` attribute expression to preview the model per entity, like the [`ModelProp`](crate::class::builtin::ModelProp) base class does.
+#[derive(Reflect, Debug, Clone, Default, PartialEq, Hash, Deref, DerefMut, Serialize, Deserialize)]
+pub struct ModelPath(pub String);
+impl FgdType for ModelPath {
+	const PROPERTY_TYPE: QuakeClassPropertyType = QuakeClassPropertyType::Value("studio");
+
+	fn fgd_parse(input: &str) -> anyhow::Result<Self> {
+		String::fgd_parse(input).map(Self)
+	}
+
+	fn fgd_to_string_unquoted(&self) -> String {
+		self.0.fgd_to_string_unquoted()
+	}
+}
+
 // We don't support the more common `bitflags` crate because it doesn't seem to support `derive(Reflect)`,
 // and as far as i know i can't get documentation from each flag.
 
@@ -0,0 +1,387 @@
+//! A parser for FGD files, see [`parse_fgd`].
+use class::QuakeClassType;
+
+use super::definition::*;
+use crate::*;
+
+/// The contents of a parsed FGD file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FgdFile {
+	pub classes: Vec<FgdClassDefinition>,
+	/// Paths of files included via `@include`, in order.
+	pub includes: Vec<String>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("line {line}: {message}")]
+pub struct FgdParseError {
+	pub line: usize,
+	pub message: String,
+}
+
+/// Parses the source of an FGD file into class definitions.
+///
+/// Supports `@BaseClass`, `@PointClass` and `@SolidClass` definitions with any class options (`base`, `model`, `size`, etc.),
+/// and `choices` and `flags` properties. Other directives such as `@mapsize` are skipped, as are Source-style inputs and outputs.
+pub fn parse_fgd(source: &str) -> Result<FgdFile, FgdParseError> {
+	let mut parser = FgdParser { source, pos: 0 };
+	let mut file = FgdFile::default();
+
+	loop {
+		parser.skip_trivia();
+		if parser.at_end() {
+			break;
+		}
+
+		parser.expect('@')?;
+		let directive = parser.ident()?;
+
+		let ty = match directive.to_ascii_lowercase().as_str() {
+			"baseclass" => QuakeClassType::Base,
+			"solidclass" => QuakeClassType::Solid,
+			// Source-specific point class variants.
+			"pointclass" | "npcclass" | "keyframeclass" | "moveclass" | "filterclass" => QuakeClassType::Point,
+			"include" => {
+				file.includes.push(parser.string()?);
+				continue;
+			}
+			_ => {
+				parser.skip_directive();
+				continue;
+			}
+		};
+
+		file.classes.push(parser.class(ty)?);
+	}
+
+	Ok(file)
+}
+
+struct FgdParser<'a> {
+	source: &'a str,
+	pos: usize,
+}
+impl<'a> FgdParser<'a> {
+	fn rest(&self) -> &'a str {
+		&self.source[self.pos..]
+	}
+	fn at_end(&self) -> bool {
+		self.pos >= self.source.len()
+	}
+	fn peek(&self) -> Option<char> {
+		self.rest().chars().next()
+	}
+
+	fn error(&self, message: impl Into<String>) -> FgdParseError {
+		FgdParseError {
+			line: self.source[..self.pos].lines().count().max(1),
+			message: message.into(),
+		}
+	}
+
+	/// Skips whitespace and `//` comments.
+	fn skip_trivia(&mut self) {
+		loop {
+			let trimmed = self.rest().trim_start();
+			self.pos = self.source.len() - trimmed.len();
+
+			if trimmed.starts_with("//") {
+				self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+			} else {
+				break;
+			}
+		}
+	}
+
+	/// Skips trivia, then if the next character is `c`, consumes it and returns `true`.
+	fn eat(&mut self, c: char) -> bool {
+		self.skip_trivia();
+		if self.peek() == Some(c) {
+			self.pos += c.len_utf8();
+			true
+		} else {
+			false
+		}
+	}
+	fn expect(&mut self, c: char) -> Result<(), FgdParseError> {
+		if self.eat(c) {
+			Ok(())
+		} else {
+			Err(self.error(format!("expected `{c}`")))
+		}
+	}
+
+	/// Reads a bare word, such as a classname, property name or number.
+	fn ident(&mut self) -> Result<&'a str, FgdParseError> {
+		self.skip_trivia();
+		let rest = self.rest();
+		let len = rest.find(|c: char| c.is_whitespace() || "()[]:=,\"@".contains(c)).unwrap_or(rest.len());
+
+		if len == 0 {
+			return Err(self.error("expected identifier"));
+		}
+		self.pos += len;
+		Ok(&rest[..len])
+	}
+
+	/// Reads a quoted string without its quotes, turning `\n` escapes into line breaks. Adjacent strings joined with `+` are concatenated.
+	fn string(&mut self) -> Result<String, FgdParseError> {
+		let mut s = String::new();
+		loop {
+			self.expect('"')?;
+			let rest = self.rest();
+			let Some(len) = rest.find('"') else { return Err(self.error("unterminated string")) };
+			s.push_str(&rest[..len].replace("\\n", "\n"));
+			self.pos += len + 1;
+
+			if !self.eat('+') {
+				return Ok(s);
+			}
+		}
+	}
+
+	/// Reads a value, either a quoted string (keeping its quotes) or a bare word. Returns [`None`] if the value is empty.
+	fn raw_value(&mut self) -> Result<Option<String>, FgdParseError> {
+		self.skip_trivia();
+		match self.peek() {
+			Some('"') => Ok(Some(format!("\"{}\"", self.string()?))),
+			Some(c) if c.is_ascii_digit() || "-+.".contains(c) => Ok(Some(self.ident()?.to_string())),
+			_ => Ok(None),
+		}
+	}
+
+	/// Reads everything up to the matching closing parenthesis, assuming the opening one has already been consumed.
+	fn balanced_parens(&mut self) -> Result<&'a str, FgdParseError> {
+		let rest = self.rest();
+		let mut depth = 0;
+		let mut in_string = false;
+
+		for (i, c) in rest.char_indices() {
+			match c {
+				'"' => in_string = !in_string,
+				'(' if !in_string => depth += 1,
+				')' if !in_string && depth == 0 => {
+					self.pos += i + 1;
+					return Ok(rest[..i].trim());
+				}
+				')' if !in_string => depth -= 1,
+				_ => {}
+			}
+		}
+
+		Err(self.error("unclosed `(`"))
+	}
+
+	/// Skips an unsupported directive, up until the next one.
+	fn skip_directive(&mut self) {
+		let rest = self.rest();
+		let mut depth = 0_i32;
+		let mut in_string = false;
+
+		for (i, c) in rest.char_indices() {
+			match c {
+				'"' => in_string = !in_string,
+				'(' | '[' if !in_string => depth += 1,
+				')' | ']' if !in_string => depth -= 1,
+				'@' if !in_string && depth <= 0 => {
+					self.pos += i;
+					return;
+				}
+				_ => {}
+			}
+		}
+
+		self.pos = self.source.len();
+	}
+
+	fn class(&mut self, ty: QuakeClassType) -> Result<FgdClassDefinition, FgdParseError> {
+		let mut class = FgdClassDefinition { ty, ..default() };
+
+		while !self.eat('=') {
+			let option = self.ident()?;
+			let value = if self.eat('(') { self.balanced_parens()? } else { "" };
+
+			match option.to_ascii_lowercase().as_str() {
+				"base" => class.base = value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect(),
+				"model" | "studio" => class.model = Some(value.to_string()),
+				"color" => class.color = Some(value.to_string()),
+				"iconsprite" | "sprite" => class.iconsprite = Some(value.to_string()),
+				"size" => class.size = Some(value.to_string()),
+				"decal" => class.decal = true,
+				// Editor-specific options we don't have anything to do with.
+				_ => {}
+			}
+		}
+
+		class.name = self.ident()?.to_string();
+		if self.eat(':') {
+			class.description = Some(self.string()?);
+		}
+
+		self.expect('[')?;
+		while !self.eat(']') {
+			if let Some(property) = self.property()? {
+				class.properties.push(property);
+			}
+		}
+
+		Ok(class)
+	}
+
+	/// Parses a property, returning [`None`] for ones we don't support, such as Source inputs and outputs.
+	fn property(&mut self) -> Result<Option<FgdPropertyDefinition>, FgdParseError> {
+		let name = self.ident()?;
+
+		self.skip_trivia();
+		if matches!(name, "input" | "output") && self.peek() != Some('(') {
+			self.ident()?;
+			self.expect('(')?;
+			self.balanced_parens()?;
+			if self.eat(':') {
+				self.string()?;
+			}
+			return Ok(None);
+		}
+
+		self.expect('(')?;
+		let ty = self.balanced_parens()?.to_ascii_lowercase();
+
+		self.skip_trivia();
+		if self.rest().starts_with("readonly") || self.rest().starts_with("report") {
+			self.ident()?;
+		}
+
+		let mut property = FgdPropertyDefinition {
+			name: name.to_string(),
+			..default()
+		};
+
+		if self.eat(':') {
+			self.skip_trivia();
+			if self.peek() == Some('"') {
+				property.title = Some(self.string()?);
+			}
+			if self.eat(':') {
+				property.default_value = self.raw_value()?;
+				if self.eat(':') {
+					property.description = Some(self.string()?).filter(|s| !s.is_empty());
+				}
+			}
+		}
+
+		property.ty = match ty.as_str() {
+			"choices" => {
+				self.expect('=')?;
+				FgdPropertyType::Choices(self.list(|parser| {
+					let key = parser.raw_value()?.ok_or_else(|| parser.error("expected choice key"))?;
+					parser.expect(':')?;
+					Ok((key, parser.string()?))
+				})?)
+			}
+			"flags" => {
+				self.expect('=')?;
+				FgdPropertyType::Flags(self.list(|parser| {
+					let value = parser.ident()?;
+					let value = value.parse().map_err(|_| parser.error(format!("invalid flag value `{value}`")))?;
+					parser.expect(':')?;
+					let title = parser.string()?;
+
+					let mut flag = FgdFlag {
+						value,
+						title,
+						default: false,
+						description: None,
+					};
+					if parser.eat(':') {
+						flag.default = parser.raw_value()?.is_some_and(|default| default.trim_matches('"') != "0");
+						if parser.eat(':') {
+							flag.description = Some(parser.string()?);
+						}
+					}
+					Ok(flag)
+				})?)
+			}
+			_ => FgdPropertyType::Value(ty),
+		};
+
+		Ok(Some(property))
+	}
+
+	/// Parses a `[ ... ]` list, calling `entry` for each element.
+	fn list<T>(&mut self, mut entry: impl FnMut(&mut Self) -> Result<T, FgdParseError>) -> Result<Vec<T>, FgdParseError> {
+		self.expect('[')?;
+		let mut entries = Vec::new();
+		while !self.eat(']') {
+			if self.at_end() {
+				return Err(self.error("unclosed `[`"));
+			}
+			entries.push(entry(self)?);
+		}
+		Ok(entries)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_classes() {
+		let file = parse_fgd(
+			r#"
+			// A comment.
+			@mapsize(-4096, 4096)
+			@include "base.fgd"
+
+			@BaseClass color(255 0 0) = targetname [ targetname(target_source) : "Name" ]
+
+			@PointClass base(targetname) size(-8 -8 -8, 8 8 8) model({ "path": "progs/box.mdl" }) = item_box : "A box" + ", in a map"
+			[
+				health(integer) : "Health" : 10 : "How much damage it takes."
+				style(choices) : "Style" : 0 =
+				[
+					0 : "Normal"
+					1 : "Flicker"
+				]
+				spawnflags(flags) =
+				[
+					1 : "Floating" : 0
+					2 : "Hidden" : 1 : "Starts hidden."
+				]
+				message(string) : "Message" : : "Printed when picked up."
+				input Kill(void) : "Removes the box."
+			]
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(file.includes, ["base.fgd"]);
+		let [base, class] = &file.classes[..] else { panic!("{:?}", file.classes) };
+
+		assert_eq!(base.ty, QuakeClassType::Base);
+		assert_eq!(base.color.as_deref(), Some("255 0 0"));
+
+		assert_eq!(class.ty, QuakeClassType::Point);
+		assert_eq!(class.name, "item_box");
+		assert_eq!(class.description.as_deref(), Some("A box, in a map"));
+		assert_eq!(class.base, ["targetname"]);
+		assert_eq!(class.size.as_deref(), Some("-8 -8 -8, 8 8 8"));
+		assert_eq!(class.model.as_deref(), Some(r#"{ "path": "progs/box.mdl" }"#));
+
+		let [health, style, spawnflags, message] = &class.properties[..] else { panic!("{:?}", class.properties) };
+		assert_eq!(health.ty, FgdPropertyType::Value("integer".into()));
+		assert_eq!(health.default_value.as_deref(), Some("10"));
+		assert_eq!(health.description.as_deref(), Some("How much damage it takes."));
+		assert_eq!(
+			style.ty,
+			FgdPropertyType::Choices(vec![("0".into(), "Normal".into()), ("1".into(), "Flicker".into())])
+		);
+		assert_eq!(spawnflags.default_value_unquoted().as_deref(), Some("2"));
+		assert_eq!(message.default_value, None);
+		assert_eq!(message.description.as_deref(), Some("Printed when picked up."));
+
+		// Writing and parsing again should be lossless.
+		let reparsed = parse_fgd(&file.classes.iter().join("\n")).unwrap();
+		assert_eq!(reparsed.classes, file.classes);
+	}
+}
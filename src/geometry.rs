@@ -55,6 +55,8 @@ pub struct MapGeometryTexture {
 	pub material: Handle<GenericMaterial>,
 	#[cfg(all(feature = "client", feature = "bsp"))]
 	pub lightmap: Option<Handle<AnimatedLighting>>,
+	#[cfg(all(feature = "client", feature = "bsp"))]
+	pub deluxemap: Option<Handle<AnimatedLighting>>,
 	/// If the texture should be full-bright
 	#[cfg(feature = "bsp")]
 	pub flags: BspTexFlags,
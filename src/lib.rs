@@ -26,6 +26,8 @@ pub mod physics;
 pub mod physics_avian;
 pub mod prelude;
 pub mod qmap;
+#[cfg(all(feature = "client", feature = "bsp"))]
+pub mod quake_model;
 #[cfg(feature = "client")]
 pub mod special_textures;
 pub mod util;
@@ -46,6 +48,7 @@ use crate::util::{DEFAULT_MISSING_TEXTURE_SIZE, create_missing_texture};
 
 /// Contains all the plugins that makes up bevy_trenchbroom. Most of these you don't want to get rid of or change, but there are a few exceptions.
 /// - If you want to change the [`LightingWorkflow`](class::builtin::LightingWorkflow) you're using, set [`LightingClassesPlugin`](class::builtin::LightingClassesPlugin).
+/// - Likewise, the [`TriggerWorkflow`](class::builtin::TriggerWorkflow) is set with [`TriggerClassesPlugin`](class::builtin::TriggerClassesPlugin).
 /// - [`WriteTrenchBroomConfigOnStartPlugin`](config::WriteTrenchBroomConfigOnStartPlugin) writes the [`TrenchBroomConfig`] on startup, disable if you want to write it out a different time.
 pub struct TrenchBroomPlugins(pub TrenchBroomConfig);
 
@@ -54,6 +57,7 @@ impl PluginGroup for TrenchBroomPlugins {
 		let builder = PluginGroupBuilder::start::<Self>()
 			.add(CorePlugin(self.0))
 			.add(class::QuakeClassPlugin)
+			.add(class::io::EntityIoPlugin)
 			.add_group(class::builtin::BasicClassesPlugins)
 			.add(qmap::QuakeMapPlugin)
 			.add(geometry::GeometryPlugin)
@@ -68,6 +72,9 @@ impl PluginGroup for TrenchBroomPlugins {
 		#[cfg(feature = "bsp")]
 		let builder = builder.add(bsp::BspPlugin);
 
+		#[cfg(all(feature = "client", feature = "bsp"))]
+		let builder = builder.add(quake_model::QuakeModelPlugin);
+
 		#[cfg(all(not(target_family = "wasm"), feature = "client"))]
 		let builder = builder.add(config::WriteTrenchBroomConfigOnStartPlugin);
 
@@ -1,8 +1,10 @@
 use crate::{geometry::LocalSpaceBrushes, *};
 use bevy::platform::collections::HashSet;
+use bevy_mesh::VertexAttributeValues;
 use brush::ConvexHull;
 #[cfg(feature = "bsp")]
 use bsp::BrushHullsAsset;
+use class::builtin::{ModelCollision, ModelProp};
 use geometry::{Brushes, BrushesAsset};
 
 /// Generic physics engine interface. This allows you to support your own physics engine, instead of being forced to use Avian.
@@ -19,6 +21,28 @@ pub trait PhysicsBackend: Send + Sync + 'static {
 	fn compound_collider(colliders: Vec<(Self::Vector, Quat, Self::Collider)>) -> Self::Collider;
 
 	fn insert_static_collider(entity: EntityCommands, collider: Self::Collider);
+	/// Inserts a collider that detects overlapping colliders instead of blocking them. The backend should trigger [`SensorEntered`] on the entity when another collider starts overlapping it.
+	///
+	/// By default no collider is inserted and a warning is logged, as a static collider would turn trigger volumes into walls.
+	fn insert_sensor_collider(entity: EntityCommands, _collider: Self::Collider) {
+		warn_once!(
+			"This physics backend doesn't support sensor colliders, so trigger volumes such as {} won't be touchable. Implement `PhysicsBackend::insert_sensor_collider` to support them",
+			entity.id()
+		);
+	}
+
+	/// Inserts a collider on a body that is moved by setting its transform, such as a door. The backend should trigger [`KinematicTouched`] on the entity when another collider starts touching it.
+	///
+	/// By default no collider is inserted and a warning is logged, as a static collider wouldn't follow the mover.
+	fn insert_kinematic_collider(entity: EntityCommands, _collider: Self::Collider) {
+		warn_once!(
+			"This physics backend doesn't support kinematic colliders, so movers such as {} won't collide. Implement `PhysicsBackend::insert_kinematic_collider` to support them",
+			entity.id()
+		);
+	}
+
+	/// Called when building [`TrenchBroomPhysicsPlugin`], to add anything the backend needs, such as observers triggering [`SensorEntered`] and [`KinematicTouched`].
+	fn build(&self, _app: &mut App) {}
 }
 
 /// Automatically creates convex colliders for entities with [`Brushes`].
@@ -26,11 +50,26 @@ pub trait PhysicsBackend: Send + Sync + 'static {
 #[reflect(Component)]
 pub struct ConvexCollision;
 
+/// Makes the collider created by [`ConvexCollision`] a sensor, which triggers [`SensorEntered`] instead of blocking other colliders.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct SensorCollision;
+
+/// Makes the collider created by [`ConvexCollision`] kinematic, for brush entities that move, such as doors. Touching it triggers [`KinematicTouched`].
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct KinematicCollision;
+
 /// Automatically creates trimesh colliders for entities with [`Mesh3d`].
 #[derive(Component, Reflect, Debug, Clone)]
 #[reflect(Component)]
 pub struct TrimeshCollision;
 
+/// Automatically creates convex hull colliders around the vertices of entities with [`Mesh3d`].
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct ConvexMeshCollision;
+
 enum ConvexPhysicsGeometry<B: PhysicsBackend> {
 	ConvexHull(Vec<B::Vector>),
 	Cuboid { center: B::Vector, half_extents: B::Vector },
@@ -76,11 +115,15 @@ impl<B: PhysicsBackend> Plugin for TrenchBroomPhysicsPlugin<B> {
 
 			// PostUpdate to order right after scenes have been spawned
 			.add_systems(PostUpdate, (
+				Self::add_model_prop_collision,
 				Self::add_convex_colliders,
 				Self::add_trimesh_colliders,
+				Self::add_convex_mesh_colliders,
 				Self::trigger_scene_colliders_ready,
 			).chain())
 		;
+
+		self.backend.build(app);
 	}
 }
 impl<B: PhysicsBackend> TrenchBroomPhysicsPlugin<B> {
@@ -90,13 +133,23 @@ impl<B: PhysicsBackend> TrenchBroomPhysicsPlugin<B> {
 
 	pub fn add_convex_colliders(
 		mut commands: Commands,
-		query: Query<(Entity, Option<&Brushes>, &Transform, Has<LocalSpaceBrushes>), (With<ConvexCollision>, Without<B::Collider>)>,
+		query: Query<
+			(
+				Entity,
+				Option<&Brushes>,
+				&Transform,
+				Has<LocalSpaceBrushes>,
+				Has<SensorCollision>,
+				Has<KinematicCollision>,
+			),
+			(With<ConvexCollision>, Without<B::Collider>),
+		>,
 		brush_lists: Res<Assets<BrushesAsset>>,
 		#[cfg(feature = "bsp")] brush_assets: Res<Assets<BrushHullsAsset>>,
 		mut tests: ResMut<SceneCollidersReadyTests>,
 	) {
 		#[allow(unused)]
-		for (entity, brushes, transform, is_local_space) in &query {
+		for (entity, brushes, transform, is_local_space, is_sensor, is_kinematic) in &query {
 			let Some(brushes) = brushes else {
 				error!(
 					"Entity {entity} has `ConvexCollision`, but no `Brushes`! If you're using Q1 BSPs, you may have forgotten to add the `-wrbrushesonly` flag to qbsp. Removing ConvexCollision component..."
@@ -156,7 +209,13 @@ impl<B: PhysicsBackend> TrenchBroomPhysicsPlugin<B> {
 				continue;
 			}
 
-			B::insert_static_collider(commands.entity(entity), B::compound_collider(colliders));
+			if is_sensor {
+				B::insert_sensor_collider(commands.entity(entity), B::compound_collider(colliders));
+			} else if is_kinematic {
+				B::insert_kinematic_collider(commands.entity(entity), B::compound_collider(colliders));
+			} else {
+				B::insert_static_collider(commands.entity(entity), B::compound_collider(colliders));
+			}
 
 			tests.added_colliders_to_entities.insert(entity);
 		}
@@ -183,6 +242,63 @@ impl<B: PhysicsBackend> TrenchBroomPhysicsPlugin<B> {
 		}
 	}
 
+	pub fn add_convex_mesh_colliders(
+		mut commands: Commands,
+		query: Query<(Entity, &Mesh3d), (With<ConvexMeshCollision>, Without<B::Collider>)>,
+		meshes: Res<Assets<Mesh>>,
+		mut tests: ResMut<SceneCollidersReadyTests>,
+	) {
+		for (entity, mesh3d) in &query {
+			let Some(mesh) = meshes.get(mesh3d.id()) else {
+				continue;
+			};
+
+			let Some(positions) = mesh.attribute(Mesh::ATTRIBUTE_POSITION).and_then(VertexAttributeValues::as_float3) else {
+				error!(
+					"Entity {entity} has ConvexMeshCollision, but its mesh has no positions in a compatible format. Removing ConvexMeshCollision component..."
+				);
+				commands.entity(entity).remove::<ConvexMeshCollision>();
+				continue;
+			};
+
+			let Some(collider) = B::convex_collider(positions.iter().map(|position| B::vec3(Vec3::from_array(*position))).collect()) else {
+				error!("A convex hull collider could not be computed for entity {entity}'s mesh. Removing ConvexMeshCollision component...");
+				commands.entity(entity).remove::<ConvexMeshCollision>();
+				continue;
+			};
+			B::insert_static_collider(commands.entity(entity), collider);
+
+			tests.added_colliders_to_entities.insert(entity);
+		}
+	}
+
+	/// Inserts [`TrimeshCollision`] or [`ConvexMeshCollision`] on meshes spawned under a [`ModelProp`], depending on its [`collision`](ModelProp::collision) property.
+	pub fn add_model_prop_collision(
+		mut commands: Commands,
+		query: Query<Entity, (Added<Mesh3d>, Without<B::Collider>)>,
+		parent_query: Query<&ChildOf>,
+		model_prop_query: Query<&ModelProp>,
+	) {
+		for entity in &query {
+			let Some(model_prop) = parent_query
+				.iter_ancestors(entity)
+				.find_map(|ancestor| model_prop_query.get(ancestor).ok())
+			else {
+				continue;
+			};
+
+			match model_prop.collision {
+				ModelCollision::None => {}
+				ModelCollision::Trimesh => {
+					commands.entity(entity).insert(TrimeshCollision);
+				}
+				ModelCollision::Convex => {
+					commands.entity(entity).insert(ConvexMeshCollision);
+				}
+			}
+		}
+	}
+
 	pub fn trigger_scene_colliders_ready(
 		mut commands: Commands,
 		mut tests: ResMut<SceneCollidersReadyTests>,
@@ -192,7 +308,13 @@ impl<B: PhysicsBackend> TrenchBroomPhysicsPlugin<B> {
 
 		children_query: Query<&Children>,
 		has_collider: Query<(), With<B::Collider>>,
-		still_not_collider_query: Query<(), (Or<(With<ConvexCollision>, With<TrimeshCollision>)>, Without<B::Collider>)>,
+		still_not_collider_query: Query<
+			(),
+			(
+				Or<(With<ConvexCollision>, With<TrimeshCollision>, With<ConvexMeshCollision>)>,
+				Without<B::Collider>,
+			),
+		>,
 	) {
 		let mut scene_roots = HashSet::new();
 
@@ -237,3 +359,85 @@ pub struct SceneCollidersReady {
 	pub scene_root_entity: Entity,
 	pub collider_entities: Vec<Entity>,
 }
+
+/// Triggered on entities with sensor colliders (see [`SensorCollision`]) when another collider starts overlapping them.
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct SensorEntered {
+	#[event_target]
+	pub sensor: Entity,
+	/// The entity that entered the sensor. If the collider is attached to a rigid body, this is the rigid body's entity.
+	pub entity: Entity,
+}
+
+/// Triggered on entities with kinematic colliders (see [`KinematicCollision`]) when another collider starts touching them.
+#[derive(EntityEvent, Debug, Clone, Copy)]
+pub struct KinematicTouched {
+	#[event_target]
+	pub kinematic: Entity,
+	/// The entity that touched the kinematic body. If the collider is attached to a rigid body, this is the rigid body's entity.
+	pub entity: Entity,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Component)]
+	struct TestCollider;
+
+	struct TestBackend;
+	impl PhysicsBackend for TestBackend {
+		type Vector = Vec3;
+		const ZERO: Self::Vector = Vec3::ZERO;
+		fn vec3(v: Vec3) -> Self::Vector {
+			v
+		}
+		fn dvec3(v: DVec3) -> Self::Vector {
+			v.as_vec3()
+		}
+
+		type Collider = TestCollider;
+		fn cuboid_collider(_half_extents: Self::Vector) -> Self::Collider {
+			TestCollider
+		}
+		fn convex_collider(_points: Vec<Self::Vector>) -> Option<Self::Collider> {
+			Some(TestCollider)
+		}
+		fn trimesh_collider(_mesh: &Mesh) -> Option<Self::Collider> {
+			Some(TestCollider)
+		}
+		fn compound_collider(_colliders: Vec<(Self::Vector, Quat, Self::Collider)>) -> Self::Collider {
+			TestCollider
+		}
+
+		fn insert_static_collider(mut entity: EntityCommands, collider: Self::Collider) {
+			entity.insert(collider);
+		}
+	}
+
+	#[test]
+	fn model_prop_collision() {
+		let mut app = App::new();
+		app.add_systems(Update, TrenchBroomPhysicsPlugin::<TestBackend>::add_model_prop_collision);
+
+		let mut spawn_mesh = |collision| {
+			let world = app.world_mut();
+			let prop = world.spawn(ModelProp { collision, ..default() }).id();
+			// Meshes of models are usually nested under the model's own hierarchy.
+			let node = world.spawn(ChildOf(prop)).id();
+			world.spawn((Mesh3d::default(), ChildOf(node))).id()
+		};
+		let none = spawn_mesh(ModelCollision::None);
+		let trimesh = spawn_mesh(ModelCollision::Trimesh);
+		let convex = spawn_mesh(ModelCollision::Convex);
+		app.update();
+
+		let collision = |app: &App, entity: Entity| {
+			let entity = app.world().entity(entity);
+			(entity.contains::<TrimeshCollision>(), entity.contains::<ConvexMeshCollision>())
+		};
+		assert_eq!(collision(&app, none), (false, false));
+		assert_eq!(collision(&app, trimesh), (true, false));
+		assert_eq!(collision(&app, convex), (false, true));
+	}
+}
@@ -1,10 +1,14 @@
 use avian3d::{
 	math::AdjustPrecision,
 	parry::shape::SharedShape,
-	prelude::{Collider, RigidBody},
+	prelude::{Collider, CollisionEventsEnabled, CollisionStart, RigidBody, Sensor},
 };
-use bevy::math::{DVec3, Vec3};
-use bevy_trenchbroom::physics::PhysicsBackend;
+use bevy::{
+	app::App,
+	ecs::{observer::On, query::Has, system::Commands, system::Query},
+	math::{DVec3, Vec3},
+};
+use bevy_trenchbroom::physics::{KinematicTouched, PhysicsBackend, SensorEntered};
 
 /// Integration between the Avian physics engine and bevy_trenchbroom.
 pub struct AvianPhysicsBackend;
@@ -35,4 +39,32 @@ impl PhysicsBackend for AvianPhysicsBackend {
 	fn insert_static_collider(mut entity: bevy::ecs::system::EntityCommands, collider: Self::Collider) {
 		entity.insert(collider).insert_if_new(RigidBody::Static);
 	}
+	fn insert_sensor_collider(mut entity: bevy::ecs::system::EntityCommands, collider: Self::Collider) {
+		entity.insert((collider, Sensor, CollisionEventsEnabled)).insert_if_new(RigidBody::Static);
+	}
+
+	fn insert_kinematic_collider(mut entity: bevy::ecs::system::EntityCommands, collider: Self::Collider) {
+		entity.insert((collider, CollisionEventsEnabled)).insert_if_new(RigidBody::Kinematic);
+	}
+
+	fn build(&self, app: &mut App) {
+		app.add_observer(trigger_touch_events);
+	}
+}
+
+fn trigger_touch_events(trigger: On<CollisionStart>, mut commands: Commands, query: Query<(Has<Sensor>, Option<&RigidBody>)>) {
+	let Ok((is_sensor, body)) = query.get(trigger.collider1) else { return };
+	let entity = trigger.body2.unwrap_or(trigger.collider2);
+
+	if is_sensor {
+		commands.trigger(SensorEntered {
+			sensor: trigger.collider1,
+			entity,
+		});
+	} else if body == Some(&RigidBody::Kinematic) {
+		commands.trigger(KinematicTouched {
+			kinematic: trigger.collider1,
+			entity,
+		});
+	}
 }
@@ -29,11 +29,16 @@ pub use crate::bsp::{
 	lighting::{LightingAnimator, LightingAnimators},
 	loader::IrradianceVolumeMultipliers,
 };
+#[cfg(all(feature = "client", feature = "bsp"))]
+pub use crate::quake_model::{
+	QuakeModel, QuakeModelAnimationPlayer,
+	sprite::{QuakeSprite, QuakeSpriteBillboard},
+};
 pub use crate::{
 	TrenchBroomPlugins, TrenchBroomServer,
 	class::{
 		QuakeClass, QuakeClassAppExt, ReflectQuakeClass,
-		builtin::{KillTarget, Target, Targetable},
+		builtin::{KillTarget, ModelProp, Target, TargetDelay, TargetMessage, Targetable},
 		scene_hooks::*,
 	},
 	config::TrenchBroomConfig,
@@ -0,0 +1,917 @@
+//! The CPU lightmap baker.
+
+use std::{
+	cmp::Reverse,
+	f64::consts::PI,
+	io,
+	path::{Path, PathBuf},
+	ptr,
+};
+
+use brush::{Brush, BrushSurfacePolygon};
+use fgd::{FgdType, IntBool, IntBoolOverride, Srgb};
+use smart_default::SmartDefault;
+
+use super::{trace::TraceWorld, *};
+use crate::{
+	class::builtin::{BspLight, BspLightAttenuation, BspSolidEntity, BspWorldspawn},
+	qmap::{QuakeEntityErrorResultExt, QuakeMapEntities, QuakeMapEntity},
+	util::mangle_to_quat,
+};
+
+/// Settings for [`bake_map_lightmap`].
+#[derive(Debug, Clone, SmartDefault, DefaultBuilder)]
+pub struct LightmapBakeSettings {
+	/// The size of a single luxel in map units. Lower values give sharper shadows, but take longer to bake and use more memory. (Default: 16, like Quake)
+	#[default(16.)]
+	pub luxel_size: f32,
+
+	/// The maximum size of the lightmap atlas. Surfaces too large to fit in it are baked at a lower resolution. (Default: 2048x2048)
+	#[default(uvec2(2048, 2048))]
+	pub max_size: UVec2,
+
+	/// How many luxels of padding to put around each surface in the atlas, to prevent light bleeding between surfaces when filtering. (Default: 1)
+	#[default(1)]
+	pub padding: u32,
+
+	/// How many rays are cast per luxel for dirtmapping (ambient occlusion), if enabled. (Default: 16)
+	#[default(16)]
+	pub dirt_rays: u32,
+
+	/// How many directions `_sunlight2` and `_sunlight3` sky dome lighting is sampled from. (Default: 32)
+	#[default(32)]
+	pub sky_dome_samples: u32,
+
+	/// Names light entities can use as their `style` instead of a number, such as the `names` of a `LightingAnimatorsAsset`.
+	pub style_names: HashMap<String, LightmapStyle>,
+}
+
+/// Bakes the lighting of a `.map` file's entities into a [`MapLightmap`].
+///
+/// Reads the same properties `ericw-tools`' `light` does (see [`BspLight`], [`BspSolidEntity`] and [`BspWorldspawn`]).
+/// Point, spot and sun lights, `_sunlight2`/`_sunlight3` sky domes, light styles, minlight and dirtmapping are supported.
+/// Bounce lighting, surface lights, `_deviance` and texture projection are not.
+///
+/// Sky textures (starting with `sky`), liquids (starting with `*`) and textures in [`TrenchBroomConfig::auto_remove_textures`] aren't lightmapped.
+/// Only worldspawn, `func_group`, `func_detail` and entities with `_shadow` set cast shadows.
+pub fn bake_map_lightmap(entities: &QuakeMapEntities, config: &TrenchBroomConfig, settings: &LightmapBakeSettings) -> anyhow::Result<MapLightmap> {
+	let mut entities = entities.clone();
+	entities.extract_origin_brushes(config);
+	entities.resolve_named_light_styles(&settings.style_names);
+
+	let worldspawn = BakeWorldspawn::read(&entities)?;
+	let (lights, suns) = read_lights(&entities, config, settings, &worldspawn)?;
+	let world = TraceWorld::new(
+		entities
+			.iter()
+			.filter(|entity| casts_shadows(entity))
+			.flat_map(|entity| &entity.brushes)
+			.filter(|brush| !brush.surfaces.iter().all(|surface| !is_solid_texture(&surface.texture, config))),
+	);
+
+	let mut surfaces = Vec::new();
+	let mut surface_entities = Vec::with_capacity(entities.len());
+	for (entity_idx, entity) in entities.iter().enumerate() {
+		surface_entities.push(BakeEntity::read(entity, &worldspawn).map_err(|err| anyhow!("entity {entity_idx}: {err}"))?);
+
+		for (brush_idx, brush) in entity.brushes.iter().enumerate() {
+			for polygon in brush.polygonize() {
+				if polygon.vertices().len() < 3 || !is_lightmapped_texture(&polygon.surface.texture, config) {
+					continue;
+				}
+
+				surfaces.push(BakeSurface::new(
+					MapSurfaceKey::new(entity_idx, brush_idx, brush, &polygon),
+					&polygon,
+					config,
+					settings,
+				));
+			}
+		}
+	}
+	surfaces.sort_by_key(|surface| (surface.key.entity, surface.key.brush, surface.key.surface));
+
+	let size = pack_surfaces(&mut surfaces, settings)?;
+	let pixels = size.element_product() as usize;
+
+	let mut slots: [Vec<Vec3>; 4] = default();
+	let mut styles = vec![LightmapStyle::NONE.0; pixels * 4];
+
+	let ctx = BakeContext {
+		config,
+		settings,
+		worldspawn: &worldspawn,
+		world: &world,
+		lights: &lights,
+		suns: &suns,
+	};
+
+	for surface in &surfaces {
+		let surface_styles = ctx.light_surface(surface, &surface_entities[surface.key.entity as usize]);
+
+		let padded_size = surface.size + settings.padding * 2;
+		for y in 0..padded_size.y {
+			for x in 0..padded_size.x {
+				// Padding repeats the closest edge luxel.
+				let luxel = uvec2(x, y).saturating_sub(UVec2::splat(settings.padding)).min(surface.size - 1);
+				let luxel_idx = (luxel.y * surface.size.x + luxel.x) as usize;
+				let pixel = surface.position + uvec2(x, y);
+				let pixel_idx = (pixel.y * size.x + pixel.x) as usize;
+
+				for (slot_idx, (style, luxels)) in surface_styles.iter().enumerate() {
+					let slot = &mut slots[slot_idx];
+					if slot.is_empty() {
+						slot.resize(pixels, Vec3::ZERO);
+					}
+					slot[pixel_idx] = luxels[luxel_idx];
+					styles[pixel_idx * 4 + slot_idx] = style.0;
+				}
+			}
+		}
+	}
+
+	Ok(MapLightmap {
+		size,
+		surfaces: surfaces.iter().map(|surface| (surface.key, surface.projection(settings))).collect(),
+		slots: slots.map(|slot| {
+			slot.into_iter()
+				.flat_map(|value| value.to_array().map(|channel| worldspawn.luxel_byte(channel)))
+				.collect()
+		}),
+		styles,
+	})
+}
+
+/// Reads the `.map` file at `map_path`, bakes it with [`bake_map_lightmap`], and writes the result next to it with the [`MAP_LIGHTMAP_EXTENSION`] extension.
+///
+/// Returns the path the lightmap was written to.
+pub fn bake_map_lightmap_file(map_path: impl AsRef<Path>, config: &TrenchBroomConfig, settings: &LightmapBakeSettings) -> anyhow::Result<PathBuf> {
+	let map_path = map_path.as_ref();
+
+	let input = std::fs::read(map_path).map_err(|err| anyhow!("Reading {map_path:?}: {err}"))?;
+	let quake_map = quake_map::parse(&mut io::Cursor::new(input))?;
+	let entities = QuakeMapEntities::from_quake_map(quake_map, config);
+
+	let lightmap = bake_map_lightmap(&entities, config, settings)?;
+
+	let path = map_path.with_extension(MAP_LIGHTMAP_EXTENSION);
+	std::fs::write(&path, lightmap.to_bytes()).map_err(|err| anyhow!("Writing {path:?}: {err}"))?;
+
+	Ok(path)
+}
+
+impl MapSurfaceKey {
+	/// Creates the key of a polygon calculated from `brush`. `brush_idx` is after origin brushes have been removed.
+	pub fn new(entity_idx: usize, brush_idx: usize, brush: &Brush, polygon: &BrushSurfacePolygon) -> Self {
+		Self {
+			entity: entity_idx as u32,
+			brush: brush_idx as u32,
+			surface: brush
+				.surfaces
+				.iter()
+				.position(|surface| ptr::eq(surface, polygon.surface))
+				.expect("polygon not calculated from brush") as u32,
+		}
+	}
+}
+
+/// Returns `true` if `texture` is a sky texture, which are unlit, and let in sunlight.
+fn is_sky_texture(texture: &str) -> bool {
+	let name = texture.rsplit('/').next().unwrap_or(texture);
+	name.get(..3).is_some_and(|prefix| prefix.eq_ignore_ascii_case("sky"))
+}
+
+/// Returns `true` if surfaces with `texture` block light.
+fn is_solid_texture(texture: &str, config: &TrenchBroomConfig) -> bool {
+	!texture.starts_with('*') && !config.auto_remove_textures.contains(texture)
+}
+
+fn is_lightmapped_texture(texture: &str, config: &TrenchBroomConfig) -> bool {
+	is_solid_texture(texture, config) && !is_sky_texture(texture)
+}
+
+/// Brush entities that get merged into the world by `qbsp` always cast shadows, others have to opt in.
+fn casts_shadows(entity: &QuakeMapEntity) -> bool {
+	match entity.classname() {
+		Ok("worldspawn" | "func_group") => true,
+		Ok(classname) if classname.starts_with("func_detail") => true,
+		_ => entity.get::<IntBool>("_shadow").is_ok_and(|shadow| shadow.0),
+	}
+}
+
+/// Reads a property, falling back to `default` if it isn't set.
+fn property<T: FgdType>(entity: &QuakeMapEntity, key: &str, default: T) -> anyhow::Result<T> {
+	Ok(entity.get(key).with_default(default)?)
+}
+
+/// Converts a light color to a multiplier, accepting both the 0-255 and 0-1 ranges.
+fn light_color(color: Srgb) -> Vec3 {
+	let color = vec3(color.red, color.green, color.blue);
+	if color.max_element() > 1. { color / 255. } else { color }
+}
+
+/// Resolves an [`IntBoolOverride`], using `default` if it's [`Inherit`](IntBoolOverride::Inherit).
+fn override_or(value: IntBoolOverride, default: bool) -> bool {
+	match value {
+		IntBoolOverride::Enable => true,
+		IntBoolOverride::Inherit => default,
+		IntBoolOverride::Disable => false,
+	}
+}
+
+/// Returns the `i`th of `count` directions evenly spread out in a cone around `axis`, where `cone_cos` is the cosine of the cone's half-angle.
+fn spiral_direction(axis: DVec3, i: u32, count: u32, cone_cos: f64) -> DVec3 {
+	const GOLDEN_ANGLE: f64 = PI * 0.763_932_022_500_210_3; // π(3 - √5)
+
+	let (tangent, bitangent) = axis.any_orthonormal_pair();
+	let z = 1. - (i as f64 + 0.5) / count as f64 * (1. - cone_cos);
+	let radius = (1. - z * z).max(0.).sqrt();
+	let angle = i as f64 * GOLDEN_ANGLE;
+
+	axis * z + tangent * (radius * angle.cos()) + bitangent * (radius * angle.sin())
+}
+
+/// Global lighting settings from the worldspawn.
+struct BakeWorldspawn {
+	dist: f32,
+	range: f32,
+	gamma: f32,
+	maxlight: f32,
+	minlight: f32,
+	minlight_color: Vec3,
+	minlight_dirt: bool,
+	dirt: bool,
+	dirt_settings: DirtSettings,
+	/// In map units.
+	dirt_depth: f64,
+	dirt_angle: f32,
+}
+impl BakeWorldspawn {
+	fn read(entities: &QuakeMapEntities) -> anyhow::Result<Self> {
+		let empty = QuakeMapEntity::default();
+		let worldspawn = entities.worldspawn().unwrap_or(&empty);
+		let defaults = BspWorldspawn::default();
+		let solid_defaults = BspSolidEntity::default();
+
+		let read = || -> anyhow::Result<Self> {
+			let dirt = override_or(property(worldspawn, "_dirt", solid_defaults._dirt)?, false);
+
+			Ok(Self {
+				dist: property(worldspawn, "_dist", defaults._dist)?,
+				range: property(worldspawn, "_range", defaults._range)?,
+				gamma: property(worldspawn, "_gamma", defaults._gamma)?,
+				maxlight: property(worldspawn, "_maxlight", defaults._maxlight)?.unwrap_or(255.),
+				minlight: property(worldspawn, "_minlight", solid_defaults._minlight)?,
+				minlight_color: light_color(property(worldspawn, "_minlight_color", solid_defaults._minlight_color)?),
+				minlight_dirt: override_or(property(worldspawn, "_minlight_dirt", defaults._minlight_dirt)?, dirt),
+				dirt,
+				dirt_settings: DirtSettings {
+					scale: property(worldspawn, "_dirtscale", defaults._dirtscale)?,
+					gain: property(worldspawn, "_dirtgain", defaults._dirtgain)?,
+				},
+				dirt_depth: property(worldspawn, "_dirtdepth", defaults._dirtdepth)? as f64,
+				dirt_angle: property(worldspawn, "_dirtangle", defaults._dirtangle)?,
+			})
+		};
+
+		read().map_err(|err| anyhow!("worldspawn: {err}"))
+	}
+
+	/// Converts a lit value to a final luxel byte.
+	fn luxel_byte(&self, value: f32) -> u8 {
+		let value = (value * self.range).min(self.maxlight).max(0.);
+		((value / 255.).powf(self.gamma.recip()) * 255.).round().clamp(0., 255.) as u8
+	}
+}
+
+/// Lighting settings for surfaces of a specific entity.
+struct BakeEntity {
+	minlight: f32,
+	minlight_color: Vec3,
+	/// If `false`, dirtmapping is disabled on this entity's surfaces.
+	dirt: bool,
+	/// Only receive minlight.
+	lightignore: bool,
+}
+impl BakeEntity {
+	fn read(entity: &QuakeMapEntity, worldspawn: &BakeWorldspawn) -> anyhow::Result<Self> {
+		let defaults = BspSolidEntity::default();
+
+		Ok(Self {
+			minlight: property(entity, "_minlight", worldspawn.minlight)?,
+			minlight_color: property(entity, "_minlight_color", defaults._minlight_color)
+				.map(light_color)
+				.map(|color| {
+					if entity.properties.contains_key("_minlight_color") {
+						color
+					} else {
+						worldspawn.minlight_color
+					}
+				})?,
+			dirt: property(entity, "_dirt", defaults._dirt)? != IntBoolOverride::Disable,
+			lightignore: property(entity, "_lightignore", defaults._lightignore)?.0,
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DirtSettings {
+	scale: f32,
+	gain: f32,
+}
+impl DirtSettings {
+	/// Returns how much light is let through with the specified amount of occlusion.
+	fn attenuate(&self, occlusion: f32) -> f32 {
+		1. - (occlusion * self.scale).clamp(0., 1.).powf(self.gain)
+	}
+}
+
+/// A point or spot light.
+struct BakeLight {
+	/// Bevy space.
+	position: DVec3,
+	light: f32,
+	color: Vec3,
+	style: LightmapStyle,
+	attenuation: BspLightAttenuation,
+	/// Distance scale, from `wait` and worldspawn `_dist`.
+	scale: f32,
+	falloff: Option<f32>,
+	anglescale: f32,
+	spot: Option<BakeSpot>,
+	dirt: Option<DirtSettings>,
+}
+
+struct BakeSpot {
+	/// The direction the spot light is facing in.
+	direction: DVec3,
+	outer_cos: f64,
+	inner_cos: f64,
+}
+
+impl BakeLight {
+	/// Computes the light's value at `distance` map units away, before the angle of incidence and shadowing are taken into account.
+	fn value_at(&self, distance: f32) -> f32 {
+		let scaled_distance = distance * self.scale;
+
+		match self.attenuation {
+			BspLightAttenuation::Linear => match self.falloff {
+				Some(falloff) if falloff > 0. => self.light * (1. - distance / falloff).max(0.),
+				_ => self.light.signum() * (self.light.abs() - scaled_distance).max(0.),
+			},
+			BspLightAttenuation::Reciprocal => self.light * 128. / scaled_distance.max(1.),
+			BspLightAttenuation::ReciprocalSquare => self.light * 128. * 128. / scaled_distance.max(1.).powi(2),
+			BspLightAttenuation::ReciprocalSquareTweaked => self.light * 128. * 128. / (scaled_distance + 128.).powi(2),
+			BspLightAttenuation::None | BspLightAttenuation::LocalMinLight => self.light,
+		}
+	}
+
+	/// How much of the light reaches a point in `direction` (from the light) because of the spot light cone.
+	fn cone_factor(&self, direction: DVec3) -> f64 {
+		let Some(spot) = &self.spot else { return 1. };
+		let cos = spot.direction.dot(direction);
+
+		if cos <= spot.outer_cos {
+			0.
+		} else if cos >= spot.inner_cos {
+			1.
+		} else {
+			(cos - spot.outer_cos) / (spot.inner_cos - spot.outer_cos)
+		}
+	}
+}
+
+/// A directional light coming from the sky. Sky domes are made of many of these.
+#[derive(Clone)]
+struct BakeSun {
+	/// Normalized direction pointing towards the sun.
+	direction: DVec3,
+	light: f32,
+	color: Vec3,
+	style: LightmapStyle,
+	anglescale: f32,
+	dirt: Option<DirtSettings>,
+}
+
+impl BakeSun {
+	/// Splits this sun into `samples` suns spread out over the upper or lower hemisphere, ignoring its direction.
+	fn dome(self, up: bool, samples: u32) -> impl Iterator<Item = Self> {
+		let axis = if up { DVec3::Y } else { DVec3::NEG_Y };
+		let samples = samples.max(1);
+
+		(0..samples).map(move |i| Self {
+			direction: spiral_direction(axis, i, samples, 0.),
+			light: self.light / samples as f32,
+			..self.clone()
+		})
+	}
+}
+
+fn read_lights(
+	entities: &QuakeMapEntities,
+	config: &TrenchBroomConfig,
+	settings: &LightmapBakeSettings,
+	worldspawn: &BakeWorldspawn,
+) -> anyhow::Result<(Vec<BakeLight>, Vec<BakeSun>)> {
+	let mut lights = Vec::new();
+	let mut suns = Vec::new();
+
+	let origin = |entity: &QuakeMapEntity| -> anyhow::Result<DVec3> { Ok(config.to_bevy_space(property(entity, "origin", Vec3::ZERO)?).as_dvec3()) };
+	let dirt = |value: IntBoolOverride, global: bool, settings: DirtSettings| override_or(value, global).then_some(settings);
+
+	// Worldspawn sunlight
+	if let Some(entity) = entities.worldspawn() {
+		let defaults = BspWorldspawn::default();
+		let mut read = || -> anyhow::Result<()> {
+			let anglescale = property(entity, "_anglescale", defaults._anglescale)?;
+
+			for (light_key, mangle_key, color_key) in [
+				("_sunlight", "_sunlight_mangle", "_sunlight_color"),
+				("_sun2", "_sun2_mangle", "_sun2_color"),
+			] {
+				let light = property(entity, light_key, 0.)?;
+				if light == 0. {
+					continue;
+				}
+
+				suns.push(BakeSun {
+					direction: -(mangle_to_quat(property(entity, mangle_key, defaults._sunlight_mangle)?) * Vec3::NEG_Z).as_dvec3(),
+					light,
+					color: light_color(property(entity, color_key, defaults._sunlight_color)?),
+					style: LightmapStyle::NORMAL,
+					anglescale,
+					dirt: dirt(
+						property(entity, "_sunlight_dirt", defaults._sunlight_dirt)?,
+						worldspawn.dirt,
+						worldspawn.dirt_settings,
+					),
+				});
+			}
+
+			let dome_dirt = dirt(
+				property(entity, "_sunlight2_dirt", defaults._sunlight2_dirt)?,
+				worldspawn.dirt,
+				worldspawn.dirt_settings,
+			);
+			for (light_key, color_key, up) in [("_sunlight2", "_sunlight2_color", true), ("_sunlight3", "_sunlight3_color", false)] {
+				let light = property(entity, light_key, 0.)?;
+				if light != 0. {
+					let color = light_color(property(entity, color_key, defaults._sunlight2_color)?);
+					suns.extend(
+						BakeSun {
+							direction: DVec3::ZERO,
+							light,
+							color,
+							style: LightmapStyle::NORMAL,
+							anglescale,
+							dirt: dome_dirt,
+						}
+						.dome(up, settings.sky_dome_samples),
+					);
+				}
+			}
+
+			Ok(())
+		};
+
+		read().map_err(|err| anyhow!("worldspawn: {err}"))?;
+	}
+
+	for (entity_idx, entity) in entities.iter().enumerate() {
+		if !entity.classname().is_ok_and(|classname| classname.starts_with("light")) {
+			continue;
+		}
+
+		let mut read = || -> anyhow::Result<()> {
+			let defaults = BspLight::default();
+
+			if property(entity, "_nostaticlight", defaults._nostaticlight)?.0 || entity.properties.contains_key("_switchableshadow_target") {
+				return Ok(());
+			}
+
+			let position = origin(entity)?;
+			let light = property(entity, "light", defaults.light)?;
+			let color = light_color(property(entity, "_color", defaults._color)?);
+			let style = property(entity, "style", defaults.style)?;
+			let anglescale = property(entity, "_anglescale", defaults._anglescale)?;
+			let light_dirt = property(entity, "_dirt", defaults._dirt)?;
+			let dirt_settings = DirtSettings {
+				scale: property(entity, "_dirtscale", defaults._dirtscale)?.unwrap_or(worldspawn.dirt_settings.scale),
+				gain: property(entity, "_dirtgain", defaults._dirtgain)?.unwrap_or(worldspawn.dirt_settings.gain),
+			};
+
+			let target = match property(entity, "target", defaults.target)? {
+				Some(target) => entities
+					.iter()
+					.find(|other| other.properties.get("targetname") == Some(&target))
+					.map(origin)
+					.transpose()?,
+				None => None,
+			};
+			// The direction the light is facing in, if it has one.
+			let direction = match property(entity, "mangle", defaults.mangle)? {
+				Some(mangle) => Some((mangle_to_quat(mangle) * Vec3::NEG_Z).as_dvec3()),
+				None => target.and_then(|target| (target - position).try_normalize()),
+			};
+
+			let sunlight2 = property(entity, "_sunlight2", defaults._sunlight2)?.0;
+			let sunlight3 = property(entity, "_sunlight3", defaults._sunlight3)?.0;
+			if sunlight2 || sunlight3 {
+				suns.extend(
+					BakeSun {
+						direction: DVec3::ZERO,
+						light,
+						color,
+						style,
+						anglescale,
+						dirt: dirt(light_dirt, worldspawn.dirt, dirt_settings),
+					}
+					.dome(sunlight2, settings.sky_dome_samples),
+				);
+			} else if property(entity, "_sun", defaults._sun)?.0 {
+				suns.push(BakeSun {
+					direction: -direction.unwrap_or(DVec3::NEG_Y),
+					light,
+					color,
+					style,
+					anglescale,
+					dirt: dirt(light_dirt, worldspawn.dirt, dirt_settings),
+				});
+			} else {
+				let angle = property(entity, "angle", defaults.angle)?;
+				let softangle = property(entity, "_softangle", defaults._softangle)?;
+				let outer_cos = (angle as f64 / 2.).to_radians().cos();
+
+				lights.push(BakeLight {
+					position,
+					light,
+					color,
+					style,
+					attenuation: property(entity, "delay", defaults.delay)?,
+					scale: property(entity, "wait", defaults.wait)? * worldspawn.dist,
+					falloff: property(entity, "_falloff", defaults._falloff)?,
+					anglescale,
+					spot: direction.map(|direction| BakeSpot {
+						direction,
+						outer_cos,
+						inner_cos: if softangle > 0. && softangle < angle {
+							(softangle as f64 / 2.).to_radians().cos()
+						} else {
+							outer_cos
+						},
+					}),
+					dirt: dirt(light_dirt, worldspawn.dirt, dirt_settings),
+				});
+			}
+
+			Ok(())
+		};
+
+		read().map_err(|err| anyhow!("light entity {entity_idx}: {err}"))?;
+	}
+
+	// Local minlights don't add light, they're applied after everything else.
+	lights.sort_by_key(|light| matches!(light.attenuation, BspLightAttenuation::LocalMinLight));
+
+	Ok((lights, suns))
+}
+
+/// A lightmapped polygon, and where it is in the atlas.
+struct BakeSurface {
+	key: MapSurfaceKey,
+	normal: DVec3,
+	distance: f64,
+	centroid: DVec3,
+	s: DVec3,
+	t: DVec3,
+	/// The size of a luxel on this surface in Bevy units.
+	luxel_size: f64,
+	/// The surface's bounds on `s` and `t` in luxels.
+	min: IVec2,
+	size: UVec2,
+	/// Top-left corner of the padded surface in the atlas.
+	position: UVec2,
+}
+impl BakeSurface {
+	fn new(key: MapSurfaceKey, polygon: &BrushSurfacePolygon, config: &TrenchBroomConfig, settings: &LightmapBakeSettings) -> Self {
+		let normal = polygon.surface.plane.normal;
+		let up = if normal.y.abs() > 0.9 { DVec3::X } else { DVec3::Y };
+		let s = up.cross(normal).normalize();
+		let t = normal.cross(s);
+
+		let mut luxel_size = settings.luxel_size as f64 / config.scale as f64;
+		let max_size = settings.max_size.saturating_sub(UVec2::splat(settings.padding * 2)).max(UVec2::ONE);
+
+		loop {
+			let (min, max) = polygon
+				.vertices()
+				.iter()
+				.fold((DVec2::INFINITY, DVec2::NEG_INFINITY), |(min, max), vertex| {
+					let projected = dvec2(vertex.dot(s), vertex.dot(t)) / luxel_size;
+					(min.min(projected), max.max(projected))
+				});
+			let min = min.floor();
+			let size = (max.ceil() - min).as_uvec2() + 1;
+
+			if size.cmple(max_size).all() {
+				return Self {
+					key,
+					normal,
+					distance: polygon.surface.plane.distance,
+					centroid: polygon.vertices().iter().sum::<DVec3>() / polygon.vertices().len() as f64,
+					s,
+					t,
+					luxel_size,
+					min: min.as_ivec2(),
+					size,
+					position: UVec2::ZERO,
+				};
+			}
+
+			luxel_size *= 2.;
+		}
+	}
+
+	fn projection(&self, settings: &LightmapBakeSettings) -> MapSurfaceLightmap {
+		MapSurfaceLightmap {
+			s: (self.s / self.luxel_size).as_vec3(),
+			t: (self.t / self.luxel_size).as_vec3(),
+			offset: (self.position + settings.padding).as_vec2() - self.min.as_vec2() + 0.5,
+		}
+	}
+
+	/// The Bevy-space position of a luxel's center on the surface's plane.
+	fn luxel_position(&self, luxel: UVec2) -> DVec3 {
+		let coords = (self.min + luxel.as_ivec2()).as_dvec2() * self.luxel_size;
+		-self.distance * self.normal + self.s * coords.x + self.t * coords.y
+	}
+}
+
+/// Shelf-packs surfaces into an atlas, returning its size.
+fn pack_surfaces(surfaces: &mut [BakeSurface], settings: &LightmapBakeSettings) -> anyhow::Result<UVec2> {
+	let padded_size = |surface: &BakeSurface| surface.size + settings.padding * 2;
+
+	let area = surfaces
+		.iter()
+		.map(|surface| padded_size(surface).as_u64vec2().element_product())
+		.sum::<u64>();
+	let widest = surfaces.iter().map(|surface| padded_size(surface).x).max().unwrap_or(1);
+	let width = ((area as f64).sqrt().ceil() as u32).max(widest).min(settings.max_size.x);
+
+	let mut order = (0..surfaces.len()).collect_vec();
+	order.sort_by_key(|&i| Reverse(padded_size(&surfaces[i]).y));
+
+	let mut cursor = UVec2::ZERO;
+	let mut shelf_height = 0;
+	let mut used_width = 1;
+
+	for i in order {
+		let size = padded_size(&surfaces[i]);
+		if cursor.x + size.x > width {
+			cursor = uvec2(0, cursor.y + shelf_height);
+			shelf_height = 0;
+		}
+
+		surfaces[i].position = cursor;
+		cursor.x += size.x;
+		shelf_height = shelf_height.max(size.y);
+		used_width = used_width.max(cursor.x);
+	}
+
+	let size = uvec2(used_width, (cursor.y + shelf_height).max(1));
+	if size.y > settings.max_size.y {
+		return Err(anyhow!(
+			"lightmap atlas ({size}) doesn't fit in the max size of {}, try increasing the luxel size",
+			settings.max_size
+		));
+	}
+
+	Ok(size)
+}
+
+/// Gets the luxels of `style`, adding it if it doesn't exist yet.
+fn style_luxels(styles: &mut Vec<(LightmapStyle, Vec<Vec3>)>, style: LightmapStyle, luxel_count: usize) -> &mut Vec<Vec3> {
+	let idx = match styles.iter().position(|(other, _)| *other == style) {
+		Some(idx) => idx,
+		None => {
+			styles.push((style, vec![Vec3::ZERO; luxel_count]));
+			styles.len() - 1
+		}
+	};
+	&mut styles[idx].1
+}
+
+struct BakeContext<'a> {
+	config: &'a TrenchBroomConfig,
+	settings: &'a LightmapBakeSettings,
+	worldspawn: &'a BakeWorldspawn,
+	world: &'a TraceWorld<'a>,
+	lights: &'a [BakeLight],
+	suns: &'a [BakeSun],
+}
+impl BakeContext<'_> {
+	/// How far rays are traced to find the sky, in map units.
+	const SKY_DISTANCE: f32 = 65536.;
+	/// How far off of surfaces luxels are sampled, in map units.
+	const SURFACE_OFFSET: f32 = 1.;
+
+	fn map_units(&self, value: f32) -> f64 {
+		(value / self.config.scale) as f64
+	}
+
+	/// Computes the lighting of each luxel of a surface, returning up to 4 styles, with [`LightmapStyle::NORMAL`] first.
+	fn light_surface(&self, surface: &BakeSurface, entity: &BakeEntity) -> Vec<(LightmapStyle, Vec<Vec3>)> {
+		let luxel_count = surface.size.element_product() as usize;
+		let mut styles = vec![(LightmapStyle::NORMAL, vec![Vec3::ZERO; luxel_count])];
+
+		let offset = self.map_units(Self::SURFACE_OFFSET);
+
+		for y in 0..surface.size.y {
+			for x in 0..surface.size.x {
+				let luxel_idx = (y * surface.size.x + x) as usize;
+				let point = self.sample_point(surface, surface.luxel_position(uvec2(x, y)) + surface.normal * offset);
+
+				// Only calculated if a light needs it.
+				let mut occlusion = None;
+				let mut dirt = |settings: Option<DirtSettings>| -> f32 {
+					match settings {
+						Some(settings) if entity.dirt => settings.attenuate(*occlusion.get_or_insert_with(|| self.occlusion(point, surface.normal))),
+						_ => 1.,
+					}
+				};
+
+				if !entity.lightignore {
+					for light in self.lights {
+						let Some(value) = self.light_value(light, point, surface.normal) else { continue };
+						let value = light.color * value * dirt(light.dirt);
+
+						let luxel = &mut style_luxels(&mut styles, light.style, luxel_count)[luxel_idx];
+						if matches!(light.attenuation, BspLightAttenuation::LocalMinLight) {
+							*luxel = luxel.max(value);
+						} else {
+							*luxel += value;
+						}
+					}
+
+					for sun in self.suns {
+						let incidence = surface.normal.dot(sun.direction);
+						if incidence <= 0. || !self.sees_sky(point, sun.direction) {
+							continue;
+						}
+
+						let angle = (1. - sun.anglescale) + sun.anglescale * incidence as f32;
+						style_luxels(&mut styles, sun.style, luxel_count)[luxel_idx] += sun.color * sun.light * angle * dirt(sun.dirt);
+					}
+				}
+
+				let minlight_dirt = self.worldspawn.minlight_dirt.then_some(self.worldspawn.dirt_settings);
+				let minlight = entity.minlight_color * entity.minlight * dirt(minlight_dirt);
+				let luxel = &mut style_luxels(&mut styles, LightmapStyle::NORMAL, luxel_count)[luxel_idx];
+				*luxel = luxel.max(minlight);
+			}
+		}
+
+		// NORMAL is always first, the others are sorted to keep output deterministic.
+		styles[1..].sort_by_key(|(style, _)| style.0);
+		if styles.len() > 4 {
+			warn!(
+				"Lightmap surface {:?} is lit by {} styles, only 4 are supported. Dropping {:?}",
+				surface.key,
+				styles.len(),
+				styles[4..].iter().map(|(style, _)| style.0).collect_vec()
+			);
+			styles.truncate(4);
+		}
+
+		styles
+	}
+
+	/// Luxels on the edge of a surface can end up inside of other brushes, which would make them black. This pulls them towards the center of the surface until they're out.
+	fn sample_point(&self, surface: &BakeSurface, point: DVec3) -> DVec3 {
+		const STEPS: u32 = 4;
+
+		let center = surface.centroid + surface.normal * self.map_units(Self::SURFACE_OFFSET);
+		(0..=STEPS)
+			.map(|step| point.lerp(center, step as f64 / STEPS as f64))
+			.find(|point| !self.world.point_in_solid(*point))
+			.unwrap_or(point)
+	}
+
+	/// Computes how much light a point or spot light casts onto `point`, or [`None`] if it doesn't reach it.
+	fn light_value(&self, light: &BakeLight, point: DVec3, normal: DVec3) -> Option<f32> {
+		let to_light = light.position - point;
+		let distance = to_light.length();
+		let direction = to_light.try_normalize()?;
+
+		let incidence = normal.dot(direction);
+		if incidence <= 0. {
+			return None;
+		}
+
+		let cone = light.cone_factor(-direction);
+		if cone <= 0. {
+			return None;
+		}
+
+		let value = light.value_at(distance as f32 * self.config.scale);
+		if value == 0. {
+			return None;
+		}
+
+		if self.world.occluded(point, direction, distance) {
+			return None;
+		}
+
+		let angle = (1. - light.anglescale) + light.anglescale * incidence as f32;
+		Some(value * angle * cone as f32)
+	}
+
+	/// Returns `true` if a ray from `point` along `direction` hits a sky surface, or escapes the map.
+	fn sees_sky(&self, point: DVec3, direction: DVec3) -> bool {
+		match self.world.trace(point, direction, self.map_units(Self::SKY_DISTANCE)) {
+			Some(hit) => is_sky_texture(&hit.surface.texture),
+			None => true,
+		}
+	}
+
+	/// Computes the ambient occlusion at `point` for dirtmapping, 0 being unoccluded, and 1 being fully occluded.
+	fn occlusion(&self, point: DVec3, normal: DVec3) -> f32 {
+		let depth = self.map_units(self.worldspawn.dirt_depth as f32);
+		let cone_cos = (self.worldspawn.dirt_angle.clamp(1., 90.) as f64).to_radians().cos();
+		let rays = self.settings.dirt_rays.max(1);
+
+		let occlusion = (0..rays)
+			.filter_map(|i| self.world.trace(point, spiral_direction(normal, i, rays, cone_cos), depth))
+			.map(|hit| 1. - hit.distance / depth)
+			.sum::<f64>();
+
+		(occlusion / rays as f64) as f32
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Builds a hollow box room out of 6 brushes, with a light in the middle.
+	fn box_room(light: &str) -> QuakeMapEntities {
+		let wall = |min: [i32; 3], max: [i32; 3]| {
+			let [x0, y0, z0] = min;
+			let [x1, y1, z1] = max;
+			format!(
+				"{{\n( {x0} {y0} {z0} ) ( {x0} {y1} {z0} ) ( {x0} {y0} {z1} ) wall 0 0 0 1 1\n\
+				( {x1} {y0} {z0} ) ( {x1} {y0} {z1} ) ( {x1} {y1} {z0} ) wall 0 0 0 1 1\n\
+				( {x0} {y0} {z0} ) ( {x0} {y0} {z1} ) ( {x1} {y0} {z0} ) wall 0 0 0 1 1\n\
+				( {x0} {y1} {z0} ) ( {x1} {y1} {z0} ) ( {x0} {y1} {z1} ) wall 0 0 0 1 1\n\
+				( {x0} {y0} {z0} ) ( {x1} {y0} {z0} ) ( {x0} {y1} {z0} ) wall 0 0 0 1 1\n\
+				( {x0} {y0} {z1} ) ( {x0} {y1} {z1} ) ( {x1} {y0} {z1} ) wall 0 0 0 1 1\n}}\n"
+			)
+		};
+
+		let brushes = [
+			wall([-144, -144, -144], [144, 144, -128]),
+			wall([-144, -144, 128], [144, 144, 144]),
+			wall([-144, -144, -128], [-128, 144, 128]),
+			wall([128, -144, -128], [144, 144, 128]),
+			wall([-128, -144, -128], [128, -128, 128]),
+			wall([-128, 128, -128], [128, 144, 128]),
+		]
+		.join("");
+
+		let input = format!("{{\n\"classname\" \"worldspawn\"\n{brushes}}}\n{{\n\"classname\" \"light\"\n\"origin\" \"0 0 0\"\n{light}}}\n");
+		let quake_map = quake_map::parse(&mut io::Cursor::new(input)).unwrap();
+		QuakeMapEntities::from_quake_map(quake_map, &TrenchBroomConfig::default())
+	}
+
+	#[test]
+	fn bake_box_room() {
+		let config = TrenchBroomConfig::default();
+		let settings = LightmapBakeSettings::default().luxel_size(32.);
+		let lightmap = bake_map_lightmap(&box_room(""), &config, &settings).unwrap();
+
+		// 6 brushes, 6 surfaces each.
+		assert_eq!(lightmap.surfaces.len(), 36);
+		assert!(!lightmap.slots[0].is_empty());
+		assert!(lightmap.slots[1..].iter().all(Vec::is_empty));
+		assert_eq!(lightmap.styles.len(), lightmap.size.element_product() as usize * 4);
+
+		// The inside of the room is lit, while the outside is dark.
+		let luxel = |brush: u32, surface: u32, point: Vec3| {
+			let (_, projection) = lightmap
+				.surfaces
+				.iter()
+				.find(|(key, _)| key.entity == 0 && key.brush == brush && key.surface == surface)
+				.unwrap();
+			let pixel = projection.project(config.to_bevy_space(point)).as_uvec2();
+			lightmap.slots[0][(pixel.y * lightmap.size.x + pixel.x) as usize * 3]
+		};
+		assert!(luxel(0, 5, vec3(0., 0., -128.)) > 0);
+		assert_eq!(luxel(0, 4, vec3(0., 0., -144.)), 0);
+
+		// Styled lights get their own slot, and can be named.
+		let settings = settings.style_names([("strobe".to_string(), LightmapStyle(5))].into());
+		let lightmap = bake_map_lightmap(&box_room("\"style\" \"strobe\"\n"), &config, &settings).unwrap();
+		assert!(lightmap.slots[1].iter().any(|&channel| channel > 0));
+		assert!(lightmap.styles.chunks_exact(4).any(|styles| styles[..2] == [0, 5]));
+	}
+}
@@ -0,0 +1,276 @@
+//! Baked lighting for `.map` files, computed on the CPU without needing a BSP compiler.
+//!
+//! Bake a map with [`bake_map_lightmap`] or [`bake_map_lightmap_file`] (e.g. headlessly as a build step),
+//! and save the result next to the map with the [`MAP_LIGHTMAP_EXTENSION`] extension. The `.map` loader will pick it up and apply it to the map's meshes.
+
+mod bake;
+mod trace;
+pub use bake::*;
+
+use bevy::{asset::AssetLoader, tasks::ConditionalSendFuture};
+#[cfg(feature = "client")]
+use bevy::{
+	asset::{LoadContext, RenderAssetUsages},
+	render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+#[cfg(feature = "client")]
+use bsp::lighting::{AnimatedLighting, AnimatedLightingType, animated_lighting_usages, new_animated_lighting_output_image};
+#[cfg(feature = "client")]
+use std::mem;
+
+use crate::*;
+
+/// The extension of baked `.map` lightmap files, which are looked for next to the `.map` file with the same name. E.g. `maps/example.map` -> `maps/example.lightmap`.
+pub const MAP_LIGHTMAP_EXTENSION: &str = "lightmap";
+
+const MAGIC: [u8; 4] = *b"TBLM";
+const VERSION: u32 = 1;
+
+/// Identifies a single surface of a brush in a `.map` file, after origin brushes have been removed.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MapSurfaceKey {
+	pub entity: u32,
+	pub brush: u32,
+	pub surface: u32,
+}
+
+/// How a brush surface is projected into a [`MapLightmap`]'s atlas.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MapSurfaceLightmap {
+	pub s: Vec3,
+	pub t: Vec3,
+	/// Added after projection, in pixels.
+	pub offset: Vec2,
+}
+impl MapSurfaceLightmap {
+	/// Projects a Bevy-space vertex of the surface into the atlas, in pixels.
+	#[inline]
+	pub fn project(&self, vertex: Vec3) -> Vec2 {
+		vec2(self.s.dot(vertex), self.t.dot(vertex)) + self.offset
+	}
+}
+
+/// A lightmap atlas baked for a `.map` file.
+#[derive(Asset, Reflect, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MapLightmap {
+	pub size: UVec2,
+	/// Where each lightmapped surface is in the atlas. Surfaces not in here (like sky and liquids) aren't lightmapped.
+	pub surfaces: Vec<(MapSurfaceKey, MapSurfaceLightmap)>,
+	/// sRGB luxels (3 bytes each) for each of the 4 style slots, empty if no surface uses the slot.
+	#[serde(skip)]
+	pub slots: [Vec<u8>; 4],
+	/// The [`LightmapStyle`] of each slot for every luxel (4 bytes each), [`LightmapStyle::NONE`] if the slot is unused.
+	#[serde(skip)]
+	pub styles: Vec<u8>,
+}
+impl MapLightmap {
+	/// Serializes this lightmap into the format [`MapLightmapLoader`] reads.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let header = serde_json::to_vec(self).expect("MapLightmap header is always serializable");
+
+		let mut bytes = Vec::with_capacity(header.len() + self.styles.len() * 4);
+		bytes.extend(MAGIC);
+		bytes.extend(VERSION.to_le_bytes());
+
+		for section in [header.as_slice()]
+			.into_iter()
+			.chain(self.slots.iter().map(Vec::as_slice))
+			.chain([self.styles.as_slice()])
+		{
+			bytes.extend((section.len() as u32).to_le_bytes());
+			bytes.extend(section);
+		}
+
+		bytes
+	}
+
+	/// Parses a lightmap from the output of [`to_bytes`](Self::to_bytes).
+	pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+		let Some(rest) = bytes.strip_prefix(&MAGIC) else { return Err(anyhow!("not a map lightmap file")) };
+
+		fn read_u32(reader: &mut &[u8]) -> anyhow::Result<u32> {
+			let (value, rest) = reader.split_first_chunk::<4>().ok_or_else(|| anyhow!("unexpected end of file"))?;
+			*reader = rest;
+			Ok(u32::from_le_bytes(*value))
+		}
+		fn read_section(reader: &mut &[u8]) -> anyhow::Result<Vec<u8>> {
+			let len = read_u32(reader)? as usize;
+			if reader.len() < len {
+				return Err(anyhow!("unexpected end of file"));
+			}
+			let (section, rest) = reader.split_at(len);
+			*reader = rest;
+			Ok(section.to_vec())
+		}
+
+		let mut reader = rest;
+		let version = read_u32(&mut reader)?;
+		if version != VERSION {
+			return Err(anyhow!("unsupported map lightmap version {version}, expected {VERSION}. Try re-baking"));
+		}
+
+		let mut lightmap: Self = serde_json::from_slice(&read_section(&mut reader)?)?;
+		for slot in &mut lightmap.slots {
+			*slot = read_section(&mut reader)?;
+		}
+		lightmap.styles = read_section(&mut reader)?;
+
+		let pixels = lightmap.size.element_product() as usize;
+		if lightmap.styles.len() != pixels * 4 || lightmap.slots.iter().any(|slot| !slot.is_empty() && slot.len() != pixels * 3) {
+			return Err(anyhow!("luxel data doesn't match lightmap size {}", lightmap.size));
+		}
+
+		Ok(lightmap)
+	}
+}
+
+#[derive(TypePath, Default)]
+pub struct MapLightmapLoader;
+impl AssetLoader for MapLightmapLoader {
+	type Asset = MapLightmap;
+	type Settings = ();
+	type Error = anyhow::Error;
+
+	fn load(
+		&self,
+		reader: &mut dyn bevy::asset::io::Reader,
+		_settings: &Self::Settings,
+		_load_context: &mut bevy::asset::LoadContext,
+	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+			MapLightmap::from_bytes(&bytes)
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&[MAP_LIGHTMAP_EXTENSION]
+	}
+}
+
+/// A [`MapLightmap`] read while loading a `.map`, with its composited lighting added as labeled assets.
+#[cfg(feature = "client")]
+pub(crate) struct LoadedMapLightmap {
+	pub surfaces: HashMap<MapSurfaceKey, MapSurfaceLightmap>,
+	pub size: Vec2,
+	pub animated_lighting: Handle<AnimatedLighting>,
+}
+#[cfg(feature = "client")]
+impl LoadedMapLightmap {
+	/// Computes the lightmap UVs (`UV_1`) of a brush surface's vertices, or [`None`] if the surface isn't lightmapped.
+	pub fn surface_uvs<'a>(&'a self, key: MapSurfaceKey, vertices: &'a [DVec3]) -> Option<impl Iterator<Item = Vec2> + 'a> {
+		let surface = self.surfaces.get(&key)?;
+		Some(vertices.iter().map(move |vertex| surface.project(vertex.as_vec3()) / self.size))
+	}
+}
+
+/// Reads the baked lightmap next to the map being loaded, if there is one.
+#[cfg(feature = "client")]
+pub(crate) async fn load_map_lightmap(load_context: &mut LoadContext<'_>, config: &TrenchBroomConfig) -> anyhow::Result<Option<LoadedMapLightmap>> {
+	if config.no_bsp_lighting {
+		return Ok(None);
+	}
+
+	let path = load_context.path().path().with_extension(MAP_LIGHTMAP_EXTENSION);
+	let Ok(bytes) = load_context.read_asset_bytes(path.clone()).await else { return Ok(None) };
+	let mut lightmap = MapLightmap::from_bytes(&bytes).map_err(|err| anyhow!("Parsing map lightmap {path:?}: {err}"))?;
+
+	let extent = Extent3d {
+		width: lightmap.size.x,
+		height: lightmap.size.y,
+		depth_or_array_layers: 1,
+	};
+
+	let mut i = 0;
+	let input = lightmap.slots.each_mut().map(|slot| {
+		let image = if slot.is_empty() {
+			Image::new_fill(
+				Extent3d::default(),
+				TextureDimension::D2,
+				&[0, 0, 0, 255],
+				TextureFormat::Rgba8UnormSrgb,
+				animated_lighting_usages(config, RenderAssetUsages::RENDER_WORLD),
+			)
+		} else {
+			Image::new(
+				extent,
+				TextureDimension::D2,
+				mem::take(slot).chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+				TextureFormat::Rgba8UnormSrgb,
+				animated_lighting_usages(config, config.bsp_textures_asset_usages),
+			)
+		};
+
+		let handle = load_context.add_labeled_asset(format!("LightmapInput{i}"), image);
+		i += 1;
+		handle
+	});
+
+	let styles = load_context.add_labeled_asset(
+		"LightmapStyles".to_string(),
+		Image::new(
+			extent,
+			TextureDimension::D2,
+			mem::take(&mut lightmap.styles),
+			TextureFormat::Rgba8Uint,
+			animated_lighting_usages(config, RenderAssetUsages::RENDER_WORLD),
+		),
+	);
+
+	let output = load_context.add_labeled_asset(
+		"LightmapOutput".to_string(),
+		new_animated_lighting_output_image(extent, TextureDimension::D2, config),
+	);
+
+	let animated_lighting = load_context.add_labeled_asset(
+		"LightmapAnimator".to_string(),
+		AnimatedLighting {
+			ty: AnimatedLightingType::Lightmap,
+			output,
+			input,
+			styles,
+		},
+	);
+
+	Ok(Some(LoadedMapLightmap {
+		surfaces: lightmap.surfaces.into_iter().collect(),
+		size: lightmap.size.as_vec2(),
+		animated_lighting,
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lightmap_bytes_round_trip() {
+		let lightmap = MapLightmap {
+			size: uvec2(2, 1),
+			surfaces: vec![(
+				MapSurfaceKey {
+					entity: 0,
+					brush: 1,
+					surface: 2,
+				},
+				MapSurfaceLightmap {
+					s: Vec3::X,
+					t: Vec3::Z,
+					offset: vec2(0.5, 0.5),
+				},
+			)],
+			slots: [vec![1, 2, 3, 4, 5, 6], vec![], vec![], vec![]],
+			styles: vec![0, 255, 255, 255, 0, 255, 255, 255],
+		};
+
+		let parsed = MapLightmap::from_bytes(&lightmap.to_bytes()).unwrap();
+		assert_eq!(parsed.size, lightmap.size);
+		assert_eq!(parsed.surfaces, lightmap.surfaces);
+		assert_eq!(parsed.slots, lightmap.slots);
+		assert_eq!(parsed.styles, lightmap.styles);
+
+		assert!(MapLightmap::from_bytes(b"nope").is_err());
+		assert!(MapLightmap::from_bytes(&lightmap.to_bytes()[..20]).is_err());
+	}
+}
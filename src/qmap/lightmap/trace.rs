@@ -0,0 +1,180 @@
+//! Ray tracing against brushes, used by the lightmap baker.
+
+use brush::{Brush, BrushSurface, ConvexHull};
+
+use crate::*;
+
+/// The brushes that cast shadows, with cached bounding boxes.
+pub(super) struct TraceWorld<'a> {
+	occluders: Vec<Occluder<'a>>,
+}
+
+struct Occluder<'a> {
+	brush: &'a Brush,
+	min: DVec3,
+	max: DVec3,
+}
+
+pub(super) struct TraceHit<'a> {
+	pub distance: f64,
+	pub surface: &'a BrushSurface,
+}
+
+impl<'a> TraceWorld<'a> {
+	/// How far outside of a brush's bounding box a ray can be before it's skipped.
+	const BOUNDS_MARGIN: f64 = 0.01;
+
+	pub fn new(brushes: impl IntoIterator<Item = &'a Brush>) -> Self {
+		Self {
+			occluders: brushes
+				.into_iter()
+				.filter_map(|brush| {
+					let (min, max) = brush
+						.calculate_vertices()
+						.fold((DVec3::INFINITY, DVec3::NEG_INFINITY), |(min, max), (vertex, _)| {
+							(min.min(vertex), max.max(vertex))
+						});
+
+					// Invalid brushes don't have any vertices.
+					if !min.is_finite() || !max.is_finite() {
+						return None;
+					}
+
+					Some(Occluder {
+						brush,
+						min: min - Self::BOUNDS_MARGIN,
+						max: max + Self::BOUNDS_MARGIN,
+					})
+				})
+				.collect(),
+		}
+	}
+
+	/// Finds the closest brush surface hit by a ray from `origin` along `direction` (normalized), up to `max_distance`.
+	pub fn trace(&self, origin: DVec3, direction: DVec3, max_distance: f64) -> Option<TraceHit<'a>> {
+		let mut closest: Option<TraceHit> = None;
+
+		for occluder in &self.occluders {
+			let max_distance = closest.as_ref().map(|hit| hit.distance).unwrap_or(max_distance);
+			if !ray_intersects_aabb(origin, direction, max_distance, occluder.min, occluder.max) {
+				continue;
+			}
+
+			if let Some((distance, surface_idx)) = ray_brush_intersection(occluder.brush, origin, direction, max_distance) {
+				closest = Some(TraceHit {
+					distance,
+					surface: &occluder.brush.surfaces[surface_idx],
+				});
+			}
+		}
+
+		closest
+	}
+
+	/// Returns `true` if anything is between `origin` and `max_distance` along `direction` (normalized).
+	pub fn occluded(&self, origin: DVec3, direction: DVec3, max_distance: f64) -> bool {
+		self.occluders.iter().any(|occluder| {
+			ray_intersects_aabb(origin, direction, max_distance, occluder.min, occluder.max)
+				&& ray_brush_intersection(occluder.brush, origin, direction, max_distance).is_some()
+		})
+	}
+
+	/// Returns `true` if `point` is inside any occluding brush.
+	pub fn point_in_solid(&self, point: DVec3) -> bool {
+		self.occluders
+			.iter()
+			.any(|occluder| point.cmpge(occluder.min).all() && point.cmple(occluder.max).all() && occluder.brush.contains_point(point))
+	}
+}
+
+/// Slab test, returns `true` if the ray hits the box before `max_distance`.
+fn ray_intersects_aabb(origin: DVec3, direction: DVec3, max_distance: f64, min: DVec3, max: DVec3) -> bool {
+	let inv_direction = direction.recip();
+	let t1 = (min - origin) * inv_direction;
+	let t2 = (max - origin) * inv_direction;
+
+	let near = t1.min(t2).max_element();
+	let far = t1.max(t2).min_element();
+
+	// NaNs from 0 * infinity (ray exactly on a slab's edge) fail the comparison and fall through to the exact test.
+	!(near > far || far < 0. || near > max_distance)
+}
+
+/// Clips a ray against each plane of `brush`, returning the distance the ray enters at, and the index of the surface it enters through.
+/// If `origin` is inside the brush, it's treated as a hit at distance 0.
+fn ray_brush_intersection(brush: &Brush, origin: DVec3, direction: DVec3, max_distance: f64) -> Option<(f64, usize)> {
+	let mut enter = f64::NEG_INFINITY;
+	let mut enter_surface = 0;
+	let mut exit = f64::INFINITY;
+
+	for (surface_idx, surface) in brush.surfaces.iter().enumerate() {
+		let denom = surface.plane.normal.dot(direction);
+		let side = surface.plane.point_side(origin);
+
+		if denom.abs() < f64::EPSILON {
+			// Parallel, and on the outside of this plane.
+			if side > 0. {
+				return None;
+			}
+			continue;
+		}
+
+		let t = -side / denom;
+		if denom < 0. {
+			if t > enter {
+				enter = t;
+				enter_surface = surface_idx;
+			}
+		} else {
+			exit = exit.min(t);
+		}
+
+		if enter > exit {
+			return None;
+		}
+	}
+
+	if exit < 0. || enter > max_distance {
+		return None;
+	}
+
+	Some((enter.max(0.), enter_surface))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use brush::BrushPlane;
+
+	fn cube(center: DVec3, half_extent: f64) -> Brush {
+		Brush {
+			surfaces: [DVec3::X, DVec3::NEG_X, DVec3::Y, DVec3::NEG_Y, DVec3::Z, DVec3::NEG_Z]
+				.map(|normal| BrushSurface {
+					plane: BrushPlane {
+						normal,
+						distance: -normal.dot(center) - half_extent,
+					},
+					texture: default(),
+					uv: default(),
+				})
+				.into(),
+		}
+	}
+
+	#[test]
+	fn trace_hits_closest() {
+		let brushes = [cube(dvec3(4., 0., 0.), 1.), cube(dvec3(10., 0., 0.), 1.)];
+		let world = TraceWorld::new(&brushes);
+
+		let hit = world.trace(DVec3::ZERO, DVec3::X, 100.).unwrap();
+		assert!((hit.distance - 3.).abs() < 1e-6);
+		assert_eq!(hit.surface.plane.normal, DVec3::NEG_X);
+
+		assert!(world.trace(DVec3::ZERO, DVec3::NEG_X, 100.).is_none());
+		assert!(!world.occluded(DVec3::ZERO, DVec3::X, 2.5));
+		assert!(world.occluded(DVec3::ZERO, DVec3::X, 3.5));
+
+		assert!(world.point_in_solid(dvec3(4., 0.5, 0.)));
+		assert!(!world.point_in_solid(dvec3(7., 0., 0.)));
+	}
+}
@@ -1,14 +1,22 @@
 use std::io;
+#[cfg(all(feature = "lightmap-baker", feature = "client"))]
+use std::ptr;
 
+#[cfg(feature = "bsp")]
+use bevy::platform::collections::HashSet;
 use bevy::{
 	asset::{AssetLoader, AsyncReadExt},
 	platform::collections::hash_map::Entry,
 	tasks::ConditionalSendFuture,
 };
-use brush::{BrushSurfacePolygon, ConvexHull, generate_mesh_from_brush_polygons};
-use config::TextureLoadView;
+use brush::{BrushSurfacePolygon, generate_mesh_from_brush_polygons};
+use config::{MapLoadSettings, SpawnFilterView, TextureLoadView};
 use geometry::{Brushes, BrushesAsset, MapGeometryTexture};
+#[cfg(all(feature = "lightmap-baker", feature = "client"))]
+use lightmap::MapSurfaceKey;
 
+#[cfg(all(feature = "lightmap-baker", feature = "client"))]
+use crate::bsp::lighting::AnimatedLightingHandle;
 use crate::{
 	class::{QuakeClassMeshView, QuakeClassSpawnView, generate_class_map, spawn_quake_entity_into_scene},
 	geometry::BrushGeometry,
@@ -41,13 +49,13 @@ impl QuakeMapLoader {
 }
 impl AssetLoader for QuakeMapLoader {
 	type Asset = QuakeMap;
-	type Settings = ();
+	type Settings = MapLoadSettings;
 	type Error = anyhow::Error;
 
 	fn load(
 		&self,
 		reader: &mut dyn bevy::asset::io::Reader,
-		_settings: &Self::Settings,
+		settings: &Self::Settings,
 		load_context: &mut bevy::asset::LoadContext,
 	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
 		Box::pin(async move {
@@ -62,28 +70,32 @@ impl AssetLoader for QuakeMapLoader {
 
 			let mut world = World::new();
 
-			// Handle origin brushes
-			for map_entity in entities.iter_mut() {
-				let origin_point = map_entity
-					.brushes
-					.iter()
-					.enumerate()
-					.find(|(_, brush)| {
-						brush
-							.surfaces
-							.iter()
-							.all(|surface| self.tb_server.config.origin_textures.contains(&surface.texture))
-					})
-					.map(|(brush_idx, brush)| (brush_idx, self.tb_server.config.from_bevy_space_f64(brush.center()).as_vec3()));
+			entities.extract_origin_brushes(&self.tb_server.config);
 
-				if let Some((origin_brush_idx, origin_point)) = origin_point {
-					map_entity.properties.insert("origin".to_string(), origin_point.fgd_to_string_unquoted());
-					map_entity.brushes.remove(origin_brush_idx);
-				}
+			#[cfg(all(feature = "client", feature = "bsp"))]
+			if let Some(map_animators) =
+				crate::bsp::lighting::prepare_map_lighting_animators(load_context, &self.asset_server, &self.tb_server.config, &mut entities).await?
+			{
+				world.spawn((Name::new("Lighting Animators"), map_animators));
 			}
 
 			let class_map = self.generate_class_map();
 
+			#[cfg(all(feature = "lightmap-baker", feature = "client"))]
+			let map_lightmap = lightmap::load_map_lightmap(load_context, &self.tb_server.config).await?;
+
+			#[cfg(feature = "bsp")]
+			let wad_textures = {
+				let used_textures: HashSet<&str> = entities
+					.iter()
+					.flat_map(|map_entity| &map_entity.brushes)
+					.flat_map(|brush| &brush.surfaces)
+					.map(|surface| surface.texture.as_str())
+					.collect();
+
+				crate::bsp::wad::load_map_wad_textures(used_textures, &self.tb_server, load_context, &self.asset_server, &entities).await?
+			};
+
 			let mut texture_size_cache: TextureSizeCache<&str> = default();
 			let mut material_cache: HashMap<&str, Handle<GenericMaterial>> = default();
 
@@ -97,6 +109,17 @@ impl AssetLoader for QuakeMapLoader {
 					continue;
 				};
 
+				let spawn_filter_view = SpawnFilterView {
+					src_entity: map_entity,
+					src_entity_idx: map_entity_idx,
+					class,
+					settings,
+					tb_config: &self.tb_server.config,
+				};
+				if !(self.tb_server.config.spawn_filter)(spawn_filter_view) {
+					continue;
+				}
+
 				let entity = world.spawn_empty().id();
 
 				let mut meshes = Vec::new();
@@ -115,41 +138,93 @@ impl AssetLoader for QuakeMapLoader {
 
 					meshes.reserve(grouped_polygons.len());
 
+					// Used to find where in the lightmap each polygon is.
+					#[cfg(all(feature = "lightmap-baker", feature = "client"))]
+					let surface_keys: HashMap<usize, MapSurfaceKey> = map_entity
+						.brushes
+						.iter()
+						.enumerate()
+						.flat_map(|(brush_idx, brush)| {
+							brush.surfaces.iter().enumerate().map(move |(surface_idx, surface)| {
+								(
+									ptr::from_ref(surface).addr(),
+									MapSurfaceKey {
+										entity: map_entity_idx as u32,
+										brush: brush_idx as u32,
+										surface: surface_idx as u32,
+									},
+								)
+							})
+						})
+						.collect();
+
 					for (texture, polygons) in grouped_polygons {
 						if self.tb_server.config.auto_remove_textures.contains(texture) {
 							continue;
 						}
 
-						let texture_size = texture_size_cache.entry(texture, load_context, &self.tb_server.config).await;
-
-						// Unrolled into match expression because async
-						let material = match material_cache.entry(texture) {
-							Entry::Occupied(x) => x.into_mut(),
-							Entry::Vacant(x) => x.insert(
-								(self.tb_server.config.load_loose_texture)(TextureLoadView {
-									name: texture,
-									tb_server: &self.tb_server,
-									load_context,
-									asset_server: &self.asset_server,
-									entities: &entities,
-									#[cfg(feature = "client")]
-									alpha_mode: None,
-									#[cfg(feature = "bsp")]
-									embedded_textures: None,
-								})
-								.await,
+						// Textures from WADs are treated like embedded textures.
+						#[cfg(feature = "bsp")]
+						let wad_texture = wad_textures
+							.as_ref()
+							.and_then(|wad_textures| wad_textures.get_ignore_case(texture))
+							.map(|(image, wad_texture)| (image.size(), wad_texture.material.clone()));
+						#[cfg(not(feature = "bsp"))]
+						let wad_texture = None;
+
+						let (texture_size, material) = match wad_texture {
+							Some(wad_texture) => wad_texture,
+							None => (
+								texture_size_cache.entry(texture, load_context, &self.tb_server.config).await,
+								// Unrolled into match expression because async
+								match material_cache.entry(texture) {
+									Entry::Occupied(x) => x.into_mut(),
+									Entry::Vacant(x) => x.insert(
+										(self.tb_server.config.load_loose_texture)(TextureLoadView {
+											name: texture,
+											tb_server: &self.tb_server,
+											load_context,
+											asset_server: &self.asset_server,
+											entities: &entities,
+											#[cfg(feature = "client")]
+											alpha_mode: None,
+											#[cfg(feature = "bsp")]
+											embedded_textures: None,
+										})
+										.await,
+									),
+								}
+								.clone(),
 							),
-						}
-						.clone();
+						};
 
 						let mut mesh = generate_mesh_from_brush_polygons(&polygons, &self.tb_server.config, texture_size);
 
+						// Only use the lightmap if every polygon is in it, surfaces with the same texture are either all lightmapped or none are.
+						#[cfg(all(feature = "lightmap-baker", feature = "client"))]
+						let lightmap = map_lightmap.as_ref().and_then(|map_lightmap| {
+							let uvs = polygons
+								.iter()
+								.map(|polygon| map_lightmap.surface_uvs(surface_keys[&ptr::from_ref(polygon.surface).addr()], polygon.vertices()))
+								.collect::<Option<Vec<_>>>()?;
+
+							mesh.insert_attribute(Mesh::ATTRIBUTE_UV_1, uvs.into_iter().flatten().collect_vec());
+							Some(map_lightmap.animated_lighting.clone())
+						});
+						#[cfg(all(not(feature = "lightmap-baker"), feature = "client", feature = "bsp"))]
+						let lightmap = None;
+
 						if let Ok(origin_point) = map_entity.get::<Vec3>("origin") {
 							mesh = mesh.translated_by(self.tb_server.config.to_bevy_space(-origin_point));
 						}
 
 						let mesh_entity = world.spawn((Name::new(texture.to_string()), Transform::default())).id();
 
+						#[cfg(all(feature = "lightmap-baker", feature = "client"))]
+						if let Some(lightmap) = &lightmap {
+							world.entity_mut(mesh_entity).insert(AnimatedLightingHandle(lightmap.clone()));
+						}
+
 						meshes.push((
 							mesh_entity,
 							mesh,
@@ -157,7 +232,9 @@ impl AssetLoader for QuakeMapLoader {
 								name: Some(texture.to_owned()),
 								material,
 								#[cfg(all(feature = "client", feature = "bsp"))]
-								lightmap: None,
+								lightmap,
+								#[cfg(all(feature = "client", feature = "bsp"))]
+								deluxemap: None,
 								#[cfg(feature = "bsp")]
 								flags: BspTexFlags::Normal,
 							},
@@ -224,6 +301,8 @@ impl AssetLoader for QuakeMapLoader {
 				world: load_context.add_labeled_asset("Scene", WorldAsset::new(world)),
 				meshes: mesh_handles,
 				brush_lists,
+				#[cfg(feature = "bsp")]
+				embedded_textures: wad_textures.map(|wad_textures| wad_textures.finalize(load_context)).unwrap_or_default(),
 				entities,
 			})
 		})
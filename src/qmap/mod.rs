@@ -1,11 +1,17 @@
 use std::any::type_name;
 
-use brush::Brush;
+use brush::{Brush, ConvexHull};
+#[cfg(feature = "bsp")]
+use bsp::BspEmbeddedTexture;
 use fgd::FgdType;
 use geometry::BrushesAsset;
+#[cfg(feature = "bsp")]
+use qbsp::data::texture::EmbeddedTextureName;
 
 use crate::*;
 
+#[cfg(feature = "lightmap-baker")]
+pub mod lightmap;
 pub mod loader;
 
 pub struct QuakeMapPlugin;
@@ -16,6 +22,13 @@ impl Plugin for QuakeMapPlugin {
 			.init_asset::<QuakeMap>()
 			.init_asset_loader::<loader::QuakeMapLoader>()
 		;
+
+		#[cfg(feature = "lightmap-baker")]
+		#[rustfmt::skip]
+		app
+			.init_asset::<lightmap::MapLightmap>()
+			.init_asset_loader::<lightmap::MapLightmapLoader>()
+		;
 	}
 }
 
@@ -26,6 +39,9 @@ pub struct QuakeMap {
 	pub meshes: Vec<Handle<Mesh>>,
 	/// Maps from entity indexes to brush lists.
 	pub brush_lists: HashMap<usize, Handle<BrushesAsset>>,
+	/// Textures loaded from the WADs listed in the worldspawn's `wad` property.
+	#[cfg(feature = "bsp")]
+	pub embedded_textures: HashMap<EmbeddedTextureName, BspEmbeddedTexture>,
 	pub entities: QuakeMapEntities,
 }
 
@@ -60,6 +76,39 @@ impl QuakeMapEntities {
 	pub fn worldspawn(&self) -> Option<&QuakeMapEntity> {
 		self.iter().find(|ent| ent.classname() == Ok("worldspawn"))
 	}
+
+	/// Returns `true` if any light entity (classname starting with `light`, like `ericw-tools`) has a `style` that isn't a number.
+	#[cfg(feature = "bsp")]
+	pub fn has_named_light_styles(&self) -> bool {
+		self.iter().any(|entity| entity.named_light_style().is_some())
+	}
+
+	/// Replaces the `style` of light entities that are names in `names` with the number of their style. Unknown names are left alone.
+	#[cfg(feature = "bsp")]
+	pub fn resolve_named_light_styles(&mut self, names: &HashMap<String, LightmapStyle>) {
+		for entity in self.iter_mut() {
+			let Some(style) = entity.named_light_style().and_then(|name| names.get(name)) else { continue };
+			entity.properties.insert("style".to_string(), style.0.to_string());
+		}
+	}
+
+	/// For each entity with a brush fully textured with one of [`TrenchBroomConfig::origin_textures`],
+	/// removes that brush, and sets the entity's `origin` property to its center.
+	pub(crate) fn extract_origin_brushes(&mut self, config: &TrenchBroomConfig) {
+		for map_entity in self.iter_mut() {
+			let origin_point = map_entity
+				.brushes
+				.iter()
+				.enumerate()
+				.find(|(_, brush)| brush.surfaces.iter().all(|surface| config.origin_textures.contains(&surface.texture)))
+				.map(|(brush_idx, brush)| (brush_idx, config.from_bevy_space_f64(brush.center()).as_vec3()));
+
+			if let Some((origin_brush_idx, origin_point)) = origin_point {
+				map_entity.properties.insert("origin".to_string(), origin_point.fgd_to_string_unquoted());
+				map_entity.brushes.remove(origin_brush_idx);
+			}
+		}
+	}
 }
 
 /// A single entity from a quake map, containing the entities property map, and optionally, brushes.
@@ -102,6 +151,16 @@ impl QuakeMapEntity {
 			error: format!("{err}"),
 		})
 	}
+
+	/// If this is a light entity (classname starting with `light`) with a `style` that isn't a number, returns it.
+	#[cfg(feature = "bsp")]
+	fn named_light_style(&self) -> Option<&str> {
+		if !self.classname().is_ok_and(|classname| classname.starts_with("light")) {
+			return None;
+		}
+		let style = self.properties.get("style")?.trim();
+		style.parse::<i32>().is_err().then_some(style)
+	}
 }
 
 #[derive(Error, Debug, Clone, PartialEq)]
@@ -0,0 +1,323 @@
+//! Quake 2 `.md2` (`IDP2` version 8) models.
+
+use bevy::{
+	asset::{AssetLoader, LoadContext, RenderAssetUsages},
+	render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+	tasks::ConditionalSendFuture,
+};
+use qbsp::{
+	data::util::FixedStr,
+	reader::{BspByteReader, BspParseContext},
+};
+
+use super::*;
+
+const MD2_MAGIC: &[u8; 4] = b"IDP2";
+const MD2_VERSION: i32 = 8;
+
+/// A parsed `.md2` file.
+#[derive(Debug, Clone)]
+pub(crate) struct Md2 {
+	/// Paths to skin images, usually `.pcx` files relative to the game directory.
+	pub skins: Vec<String>,
+	pub geometry: QuakeModelGeometry,
+}
+impl Md2 {
+	pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+		if bytes.get(0..4) != Some(MD2_MAGIC) {
+			return Err(anyhow!("invalid MD2 magic number {:?}", bytes.get(0..4)));
+		}
+
+		let ctx = BspParseContext::default();
+		let mut reader = BspByteReader::new(bytes, &ctx).with_pos(4);
+
+		let version: i32 = reader.read()?;
+		if version != MD2_VERSION {
+			return Err(anyhow!("unsupported MD2 version {version}, expected {MD2_VERSION}"));
+		}
+
+		let [
+			skin_width,
+			skin_height,
+			frame_size,
+			num_skins,
+			num_verts,
+			num_st,
+			num_tris,
+			_num_gl_commands,
+			num_frames,
+			skins_offset,
+			st_offset,
+			tris_offset,
+			frames_offset,
+			_gl_commands_offset,
+			_end_offset,
+		]: [i32; 15] = reader.read()?;
+
+		let mut reader = reader.with_pos(skins_offset as usize);
+		let skins = (0..num_skins)
+			.map(|_| reader.read::<FixedStr<64>>().map(|name| name.as_str().to_string()))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let mut reader = reader.with_pos(st_offset as usize);
+		let texcoords = (0..num_st).map(|_| reader.read::<[i16; 2]>()).collect::<Result<Vec<_>, _>>()?;
+		let skin_size = ivec2(skin_width, skin_height).max(IVec2::ONE).as_vec2();
+
+		let mut geometry = QuakeModelGeometry::default();
+
+		let mut reader = reader.with_pos(tris_offset as usize);
+		for _ in 0..num_tris {
+			let vertices: [u16; 3] = reader.read()?;
+			let st: [u16; 3] = reader.read()?;
+
+			geometry.triangles.push(std::array::from_fn(|i| {
+				let [s, t] = texcoords.get(st[i] as usize).copied().unwrap_or_default();
+				(vertices[i] as u32, vec2(s as f32, t as f32) / skin_size)
+			}));
+		}
+
+		for frame_idx in 0..num_frames {
+			let mut reader = reader.with_pos(frames_offset as usize + frame_idx as usize * frame_size as usize);
+
+			let scale = Vec3::from_array(reader.read()?);
+			let translate = Vec3::from_array(reader.read()?);
+			let name: FixedStr<16> = reader.read()?;
+
+			let positions = (0..num_verts)
+				.map(|_| {
+					let [x, y, z, _normal_index]: [u8; 4] = reader.read()?;
+					Ok(scale * vec3(x as f32, y as f32, z as f32) + translate)
+				})
+				.collect::<anyhow::Result<_>>()
+				.map_err(|err| anyhow!("reading frame {frame_idx}: {err}"))?;
+
+			geometry.frames.push((name.as_str().to_string(), positions));
+		}
+
+		geometry.group_frames_by_name(0..geometry.frames.len() as u32);
+
+		Ok(Self { skins, geometry })
+	}
+}
+
+/// Decodes an 8-bit paletted `.pcx` image, which is what Quake 2 uses for model skins and sprites.
+///
+/// If `cutout` is `true`, palette index 255 is made transparent.
+pub fn decode_pcx(bytes: &[u8], cutout: bool, asset_usages: RenderAssetUsages) -> anyhow::Result<Image> {
+	const HEADER_SIZE: usize = 128;
+	const PALETTE_SIZE: usize = 769;
+
+	if bytes.len() < HEADER_SIZE + PALETTE_SIZE || bytes[0] != 0x0A {
+		return Err(anyhow!("invalid PCX file"));
+	}
+	let [_manufacturer, _version, encoding, bits_per_pixel] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+	let planes = bytes[65];
+	if encoding != 1 || bits_per_pixel != 8 || planes != 1 {
+		return Err(anyhow!(
+			"unsupported PCX format (encoding {encoding}, {bits_per_pixel} bits per pixel, {planes} planes), only 8-bit paletted images are supported"
+		));
+	}
+
+	let read_u16 = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize;
+	let (Some(width), Some(height)) = (read_u16(8).checked_sub(read_u16(4)), read_u16(10).checked_sub(read_u16(6))) else {
+		return Err(anyhow!("invalid PCX file, image bounds are inverted"));
+	};
+	let (width, height) = (width + 1, height + 1);
+	let bytes_per_line = read_u16(66);
+	if bytes_per_line == 0 || width > bytes_per_line {
+		return Err(anyhow!(
+			"invalid PCX file, {bytes_per_line} bytes per line is too few for a width of {width}"
+		));
+	}
+
+	let palette_start = bytes.len() - PALETTE_SIZE;
+	if bytes[palette_start] != 0x0C {
+		return Err(anyhow!("PCX file is missing its 256 color palette"));
+	}
+	let palette = &bytes[palette_start + 1..];
+
+	// Run-length encoded scanlines.
+	let mut indices = Vec::with_capacity(bytes_per_line * height);
+	let mut data = bytes[HEADER_SIZE..palette_start].iter().copied();
+	while indices.len() < bytes_per_line * height {
+		let Some(byte) = data.next() else { break };
+		if byte & 0xC0 == 0xC0 {
+			let value = data.next().unwrap_or_default();
+			indices.extend(std::iter::repeat_n(value, (byte & 0x3F) as usize));
+		} else {
+			indices.push(byte);
+		}
+	}
+	indices.resize(bytes_per_line * height, 0);
+
+	Ok(Image::new(
+		Extent3d {
+			width: width as u32,
+			height: height as u32,
+			..default()
+		},
+		TextureDimension::D2,
+		indices
+			.chunks(bytes_per_line)
+			.flat_map(|line| &line[..width.min(line.len())])
+			.flat_map(|&idx| {
+				if cutout && idx == 255 {
+					return [0; 4];
+				}
+				let idx = idx as usize * 3;
+				[palette[idx], palette[idx + 1], palette[idx + 2], 255]
+			})
+			.collect(),
+		TextureFormat::Rgba8UnormSrgb,
+		asset_usages,
+	))
+}
+
+/// Loads `.md2` files as [`QuakeModel`]s.
+///
+/// Skins are looked up with each of [`TrenchBroomConfig::texture_extensions`] in place of their extension first, falling back to the `.pcx` file itself.
+#[derive(TypePath)]
+pub struct Md2Loader {
+	pub tb_server: TrenchBroomServer,
+	pub asset_server: AssetServer,
+}
+impl FromWorld for Md2Loader {
+	fn from_world(world: &mut World) -> Self {
+		Self {
+			tb_server: world.resource::<TrenchBroomServer>().clone(),
+			asset_server: world.resource::<AssetServer>().clone(),
+		}
+	}
+}
+impl AssetLoader for Md2Loader {
+	type Asset = QuakeModel;
+	type Settings = ();
+	type Error = anyhow::Error;
+
+	fn load(
+		&self,
+		reader: &mut dyn bevy::asset::io::Reader,
+		_settings: &Self::Settings,
+		load_context: &mut LoadContext,
+	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+
+			let md2 = Md2::parse(&bytes)?;
+
+			let mut skins = Vec::with_capacity(md2.skins.len());
+			for (skin_idx, skin) in md2.skins.iter().enumerate() {
+				match load_quake2_image(
+					load_context,
+					&self.asset_server,
+					&self.tb_server.config,
+					skin,
+					format!("Skin{skin_idx}"),
+					false,
+				)
+				.await
+				{
+					Some(image) => skins.push(image),
+					None => error!("Couldn't load skin \"{skin}\" of {:?}", load_context.path()),
+				}
+			}
+
+			Ok(md2.geometry.finish(skins, load_context, &self.tb_server.config))
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["md2"]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn md2_parsing() {
+		const HEADER_SIZE: i32 = 68;
+		// Scale, translate, name, and 3 vertices.
+		const FRAME_SIZE: i32 = 12 + 12 + 16 + 3 * 4;
+
+		let mut bytes = Vec::new();
+		bytes.extend(MD2_MAGIC);
+		bytes.extend(MD2_VERSION.to_le_bytes());
+		let skins_offset = HEADER_SIZE;
+		let st_offset = skins_offset + 64;
+		let tris_offset = st_offset + 3 * 4;
+		let frames_offset = tris_offset + 12;
+		let end_offset = frames_offset + FRAME_SIZE * 2;
+		bytes.extend(
+			[
+				4_i32,
+				2,
+				FRAME_SIZE,
+				1,
+				3,
+				3,
+				1,
+				0,
+				2,
+				skins_offset,
+				st_offset,
+				tris_offset,
+				frames_offset,
+				end_offset,
+				end_offset,
+			]
+			.iter()
+			.flat_map(|v| v.to_le_bytes()),
+		);
+
+		let mut skin = [0; 64];
+		skin[..8].copy_from_slice(b"skin.pcx");
+		bytes.extend(skin);
+		bytes.extend([0_i16, 0, 2, 0, 0, 1].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend([0_u16, 1, 2, 0, 1, 2].iter().flat_map(|v| v.to_le_bytes()));
+
+		for (name, scale) in [(b"run1", 1_f32), (b"run2", 2.)] {
+			bytes.extend([scale, scale, scale, 0., 0., 1.].iter().flat_map(|v| v.to_le_bytes()));
+			let mut frame_name = [0; 16];
+			frame_name[..4].copy_from_slice(name);
+			bytes.extend(frame_name);
+			bytes.extend([[1_u8, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0]].into_iter().flatten());
+		}
+
+		let md2 = Md2::parse(&bytes).unwrap();
+		assert_eq!(md2.skins, ["skin.pcx"]);
+		assert_eq!(md2.geometry.triangles[0][1], (1, vec2(0.5, 0.)));
+		assert_eq!(md2.geometry.frames[1].1[0], vec3(2., 0., 1.));
+		assert_eq!(md2.geometry.clips["run"].frames.len(), 2);
+	}
+
+	#[test]
+	fn pcx_decoding() {
+		let mut bytes = vec![0; 128];
+		bytes[..4].copy_from_slice(&[0x0A, 5, 1, 8]);
+		// 3x1 image.
+		bytes[8] = 2;
+		bytes[65] = 1;
+		bytes[66] = 4;
+		// A run of three 1s, then padding.
+		bytes.extend([0xC3, 1, 0]);
+		bytes.push(0x0C);
+		bytes.extend((0..=255_u8).flat_map(|i| [i, 0, 0]));
+
+		let image = decode_pcx(&bytes, false, RenderAssetUsages::default()).unwrap();
+		assert_eq!(image.width(), 3);
+		assert_eq!(image.pixel_bytes(uvec3(2, 0, 0)).unwrap(), [1, 0, 0, 255]);
+
+		// Malformed headers are errors rather than panics.
+		let mut inverted = bytes.clone();
+		inverted[4] = 3;
+		assert!(decode_pcx(&inverted, false, RenderAssetUsages::default()).is_err());
+		let mut narrow = bytes.clone();
+		narrow[66] = 2;
+		assert!(decode_pcx(&narrow, false, RenderAssetUsages::default()).is_err());
+		narrow[66] = 0;
+		assert!(decode_pcx(&narrow, false, RenderAssetUsages::default()).is_err());
+	}
+}
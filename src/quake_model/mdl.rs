@@ -0,0 +1,255 @@
+//! Quake `.mdl` (`IDPO` version 6) models.
+
+use bevy::{
+	asset::{AssetLoader, LoadContext, RenderAssetUsages},
+	tasks::ConditionalSendFuture,
+};
+use bsp::loader::textures::{load_palette, paletted_image};
+use qbsp::{
+	data::util::FixedStr,
+	reader::{BspByteReader, BspParseContext},
+};
+
+use super::*;
+
+const MDL_MAGIC: &[u8; 4] = b"IDPO";
+const MDL_VERSION: i32 = 6;
+
+/// A parsed `.mdl` file.
+#[derive(Debug, Clone)]
+pub(crate) struct Mdl {
+	pub skin_size: UVec2,
+	/// Palette indices of each skin. Only the first frame of skin groups is kept.
+	pub skins: Vec<Vec<u8>>,
+	pub geometry: QuakeModelGeometry,
+}
+impl Mdl {
+	pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+		if bytes.get(0..4) != Some(MDL_MAGIC) {
+			return Err(anyhow!("invalid MDL magic number {:?}", bytes.get(0..4)));
+		}
+
+		let ctx = BspParseContext::default();
+		let mut reader = BspByteReader::new(bytes, &ctx).with_pos(4);
+
+		let version: i32 = reader.read()?;
+		if version != MDL_VERSION {
+			return Err(anyhow!("unsupported MDL version {version}, expected {MDL_VERSION}"));
+		}
+
+		let scale = Vec3::from_array(reader.read()?);
+		let translate = Vec3::from_array(reader.read()?);
+		let _bounding_radius: f32 = reader.read()?;
+		let _eye_position: [f32; 3] = reader.read()?;
+		let [num_skins, skin_width, skin_height, num_verts, num_tris, num_frames, _sync_type, _flags]: [i32; 8] = reader.read()?;
+		let _size: f32 = reader.read()?;
+
+		let skin_size = uvec2(skin_width.max(0) as u32, skin_height.max(0) as u32);
+		let skin_bytes = skin_size.x as usize * skin_size.y as usize;
+
+		let mut skins = Vec::with_capacity(num_skins.max(0) as usize);
+		for skin_idx in 0..num_skins {
+			let group: i32 = reader.read()?;
+			let num_group_frames = if group == 0 {
+				1
+			} else {
+				let num_group_frames: i32 = reader.read()?;
+				reader.read_bytes(num_group_frames.max(0) as usize * size_of::<f32>())?;
+				num_group_frames.max(0) as usize
+			};
+
+			let data = reader
+				.read_bytes(skin_bytes * num_group_frames)
+				.map_err(|err| anyhow!("reading skin {skin_idx}: {err}"))?;
+			skins.push(data[..skin_bytes.min(data.len())].to_vec());
+		}
+
+		// (on seam, s, t)
+		let texcoords = (0..num_verts).map(|_| reader.read::<[i32; 3]>()).collect::<Result<Vec<_>, _>>()?;
+
+		let mut geometry = QuakeModelGeometry::default();
+
+		for _ in 0..num_tris {
+			let faces_front: i32 = reader.read()?;
+			let vertices: [i32; 3] = reader.read()?;
+
+			geometry.triangles.push(vertices.map(|vertex| {
+				let [on_seam, mut s, t] = texcoords.get(vertex as usize).copied().unwrap_or_default();
+				// Back-facing triangles on the seam use the back half of the skin.
+				if faces_front == 0 && on_seam != 0 {
+					s += skin_width / 2;
+				}
+
+				(vertex as u32, (vec2(s as f32, t as f32) + 0.5) / skin_size.max(UVec2::ONE).as_vec2())
+			}));
+		}
+
+		let read_frame = |reader: &mut BspByteReader| -> anyhow::Result<(String, Vec<Vec3>)> {
+			let _bbox_min: [u8; 4] = reader.read()?;
+			let _bbox_max: [u8; 4] = reader.read()?;
+			let name: FixedStr<16> = reader.read()?;
+
+			let positions = (0..num_verts)
+				.map(|_| {
+					let [x, y, z, _normal_index]: [u8; 4] = reader.read()?;
+					Ok(scale * vec3(x as f32, y as f32, z as f32) + translate)
+				})
+				.collect::<anyhow::Result<_>>()?;
+
+			Ok((name.as_str().to_string(), positions))
+		};
+
+		let mut single_frames = Vec::new();
+
+		for frame_idx in 0..num_frames {
+			let ty: i32 = reader.read()?;
+
+			if ty == 0 {
+				single_frames.push(geometry.frames.len() as u32);
+				geometry
+					.frames
+					.push(read_frame(&mut reader).map_err(|err| anyhow!("reading frame {frame_idx}: {err}"))?);
+				continue;
+			}
+
+			let num_group_frames: i32 = reader.read()?;
+			let _bbox_min: [u8; 4] = reader.read()?;
+			let _bbox_max: [u8; 4] = reader.read()?;
+			let intervals = (0..num_group_frames).map(|_| reader.read::<f32>()).collect::<Result<Vec<_>, _>>()?;
+
+			let mut clip = QuakeModelClip::default();
+			let mut previous_interval = 0.;
+
+			for interval in intervals {
+				clip.frames.push(QuakeModelClipFrame {
+					frame: geometry.frames.len() as u32,
+					// Intervals are the time each frame ends, relative to the start of the group.
+					duration: (interval - previous_interval).max(0.),
+				});
+				previous_interval = interval;
+
+				geometry
+					.frames
+					.push(read_frame(&mut reader).map_err(|err| anyhow!("reading frame group {frame_idx}: {err}"))?);
+			}
+
+			if let Some(first) = clip.frames.first() {
+				let name = geometry.frames[first.frame as usize]
+					.0
+					.trim_end_matches(|c: char| c.is_ascii_digit())
+					.to_string();
+				geometry.clips.insert(name, clip);
+			}
+		}
+
+		geometry.group_frames_by_name(single_frames);
+
+		Ok(Self { skin_size, skins, geometry })
+	}
+}
+
+/// Loads `.mdl` files as [`QuakeModel`]s, using the palette at [`TrenchBroomConfig::texture_pallette`] for skins.
+#[derive(TypePath)]
+pub struct MdlLoader {
+	pub tb_server: TrenchBroomServer,
+}
+impl FromWorld for MdlLoader {
+	fn from_world(world: &mut World) -> Self {
+		Self {
+			tb_server: world.resource::<TrenchBroomServer>().clone(),
+		}
+	}
+}
+impl AssetLoader for MdlLoader {
+	type Asset = QuakeModel;
+	type Settings = ();
+	type Error = anyhow::Error;
+
+	fn load(
+		&self,
+		reader: &mut dyn bevy::asset::io::Reader,
+		_settings: &Self::Settings,
+		load_context: &mut LoadContext,
+	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+
+			let mdl = Mdl::parse(&bytes)?;
+			let config = &self.tb_server.config;
+			let palette = load_palette(load_context, config).await?;
+
+			let skins = mdl
+				.skins
+				.iter()
+				.enumerate()
+				.map(|(skin_idx, data)| {
+					load_context.add_labeled_asset(
+						format!("Skin{skin_idx}"),
+						paletted_image(mdl.skin_size, data, &palette, false, RenderAssetUsages::default()),
+					)
+				})
+				.collect();
+
+			Ok(mdl.geometry.finish(skins, load_context, config))
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["mdl"]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn mdl_parsing() {
+		let mut bytes = Vec::new();
+		bytes.extend(MDL_MAGIC);
+		bytes.extend(MDL_VERSION.to_le_bytes());
+		// Scale, translate, bounding radius, eye position.
+		bytes.extend([2., 2., 2., -1., 0., 0., 0., 0., 0., 0.].iter().flat_map(|v: &f32| v.to_le_bytes()));
+		// 1 skin (2x2), 3 vertices, 1 triangle, 2 frames.
+		bytes.extend([1_i32, 2, 2, 3, 1, 2, 0, 0].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend(0_f32.to_le_bytes());
+
+		bytes.extend(0_i32.to_le_bytes());
+		bytes.extend([0, 1, 2, 3]);
+
+		bytes.extend([0_i32, 0, 0, 1, 0, 0, 0, 0, 1].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend([0_i32, 0, 1, 2].iter().flat_map(|v| v.to_le_bytes()));
+
+		let frame = |name: &[u8], x: u8| {
+			let mut frame = vec![0; 8];
+			let mut frame_name = [0; 16];
+			frame_name[..name.len()].copy_from_slice(name);
+			frame.extend(frame_name);
+			frame.extend([[x, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0]].into_iter().flatten());
+			frame
+		};
+
+		// A single frame, then a group with one frame.
+		bytes.extend(0_i32.to_le_bytes());
+		bytes.extend(frame(b"stand1", 0));
+		bytes.extend([1_i32, 1].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend([0; 8]);
+		bytes.extend(0.25_f32.to_le_bytes());
+		bytes.extend(frame(b"pain1", 3));
+
+		let mdl = Mdl::parse(&bytes).unwrap();
+		assert_eq!(mdl.skin_size, uvec2(2, 2));
+		assert_eq!(mdl.skins, [vec![0, 1, 2, 3]]);
+
+		let geometry = &mdl.geometry;
+		assert_eq!(geometry.triangles.len(), 1);
+		// Back-facing triangle on the seam.
+		assert_eq!(geometry.triangles[0][1], (1, vec2(0.75, 0.25)));
+		assert_eq!(geometry.frames[1].1[0], vec3(5., 0., 0.));
+		assert_eq!(geometry.clips["stand"].frames.len(), 1);
+		assert_eq!(geometry.clips["pain"].frames, [QuakeModelClipFrame { frame: 1, duration: 0.25 }]);
+
+		assert!(Mdl::parse(b"IDP2").is_err());
+	}
+}
@@ -0,0 +1,395 @@
+//! Loaders for Quake's `.mdl` and Quake 2's `.md2` vertex-animated models, and `.spr`/`.sp2` sprites.
+//!
+//! Each frame is baked into its own [`Mesh`], and [`QuakeModelAnimationPlayer`] swaps between them.
+//! Spawn these from point classes with [`SceneHooks::spawn_class_quake_model`](crate::class::SceneHooks::spawn_class_quake_model)
+//! and [`SceneHooks::spawn_class_quake_sprite`](crate::class::SceneHooks::spawn_class_quake_sprite).
+
+pub mod md2;
+pub mod mdl;
+pub mod sprite;
+
+use std::path::PathBuf;
+
+use bevy::{
+	asset::{AssetPath, LoadContext, RenderAssetUsages},
+	mesh::Indices,
+	render::render_resource::PrimitiveTopology,
+	transform::TransformSystems,
+};
+use smart_default::SmartDefault;
+use sprite::*;
+
+use crate::*;
+
+/// Quake 1 and 2 models both animate at 10 frames per second.
+pub const QUAKE_MODEL_FRAME_DURATION: f32 = 0.1;
+
+pub struct QuakeModelPlugin;
+impl Plugin for QuakeModelPlugin {
+	fn build(&self, app: &mut App) {
+		#[rustfmt::skip]
+		app
+			.init_asset::<QuakeModel>()
+			.init_asset::<QuakeModelAnimations>()
+			.init_asset_loader::<mdl::MdlLoader>()
+			.init_asset_loader::<md2::Md2Loader>()
+			.init_asset::<QuakeSprite>()
+			.init_asset::<QuakeSpriteFrames>()
+			.init_asset_loader::<QuakeSpriteLoader>()
+			.add_systems(Update, (Self::apply_quake_model_appearance, Self::animate_quake_models).chain())
+			.add_systems(PostUpdate, Self::update_quake_sprites.before(TransformSystems::Propagate))
+		;
+	}
+}
+impl QuakeModelPlugin {
+	/// Applies [`QuakeModelAppearance`] to Quake models and sprites as they're spawned.
+	pub fn apply_quake_model_appearance(
+		mut model_query: Query<
+			(Entity, &QuakeModelAnimationPlayer, &mut Mesh3d, &mut MeshMaterial3d<StandardMaterial>),
+			Added<QuakeModelAnimationPlayer>,
+		>,
+		mut sprite_query: Query<(Entity, &mut QuakeSpriteBillboard), Added<QuakeSpriteBillboard>>,
+		appearance_query: Query<&QuakeModelAppearance>,
+		parent_query: Query<&ChildOf>,
+		animation_assets: Res<Assets<QuakeModelAnimations>>,
+	) {
+		let find_appearance = |entity: Entity| {
+			std::iter::once(entity)
+				.chain(parent_query.iter_ancestors(entity))
+				.find_map(|entity| appearance_query.get(entity).ok())
+		};
+
+		for (entity, player, mut mesh, mut material) in &mut model_query {
+			let Some(appearance) = find_appearance(entity) else { continue };
+			let Some(animations) = animation_assets.get(&player.animations) else { continue };
+
+			if let Some(frame) = animations.frames.get(appearance.frame as usize) {
+				mesh.0 = frame.clone();
+			}
+			if let Some(skin) = animations.skins.get(appearance.skin as usize) {
+				material.0 = skin.clone();
+			}
+		}
+
+		for (entity, mut billboard) in &mut sprite_query {
+			let Some(appearance) = find_appearance(entity) else { continue };
+			billboard.frame = appearance.frame as usize;
+		}
+	}
+
+	/// Advances [`QuakeModelAnimationPlayer`]s, swapping out their meshes when the frame changes.
+	pub fn animate_quake_models(
+		mut query: Query<(&mut QuakeModelAnimationPlayer, &mut Mesh3d)>,
+		animation_assets: Res<Assets<QuakeModelAnimations>>,
+		time: Res<Time>,
+	) {
+		for (mut player, mut mesh) in &mut query {
+			let Some(animations) = animation_assets.get(&player.animations) else { continue };
+			let Some(clip) = player.clip.as_ref().and_then(|clip| animations.clips.get(clip)) else { continue };
+
+			player.elapsed += time.delta_secs() * player.speed;
+
+			let Some(frame) = clip.frame_at(player.elapsed, player.looping) else { continue };
+			let Some(frame_mesh) = animations.frames.get(frame as usize) else { continue };
+
+			if mesh.0 != *frame_mesh {
+				mesh.0 = frame_mesh.clone();
+			}
+		}
+	}
+}
+
+/// A model loaded from a `.mdl` or `.md2` file.
+#[derive(Asset, Reflect, Debug)]
+pub struct QuakeModel {
+	/// The model on its own, showing the first skin and frame, ready to play animations.
+	pub world: Handle<WorldAsset>,
+	/// One material per skin. Skins can be swapped by changing an entity's [`MeshMaterial3d`].
+	pub skins: Vec<Handle<StandardMaterial>>,
+	pub animations: Handle<QuakeModelAnimations>,
+}
+
+/// Which skin and frame a Quake model or sprite spawned as part of this entity (or its descendants) should show.
+///
+/// Inserted by model scene hooks when the class' `model` expression specifies a `skin` or `frame`.
+#[derive(Component, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[reflect(Component, Default)]
+pub struct QuakeModelAppearance {
+	pub skin: u32,
+	pub frame: u32,
+}
+
+/// Baked frames of a [`QuakeModel`], and the clips they're grouped into.
+#[derive(Asset, Reflect, Debug, Clone, Default)]
+pub struct QuakeModelAnimations {
+	/// A mesh for each frame in the model.
+	pub frames: Vec<Handle<Mesh>>,
+	/// The name of each frame in the model.
+	pub frame_names: Vec<String>,
+	/// Same as [`QuakeModel::skins`], so players can switch skins.
+	pub skins: Vec<Handle<StandardMaterial>>,
+	/// Frames grouped by name with trailing digits removed (e.g. `run1`, `run2`, ... becomes `run`), or by MDL frame group.
+	pub clips: HashMap<String, QuakeModelClip>,
+}
+
+#[derive(Reflect, Debug, Clone, Default, PartialEq)]
+pub struct QuakeModelClip {
+	pub frames: Vec<QuakeModelClipFrame>,
+}
+impl QuakeModelClip {
+	pub fn duration(&self) -> f32 {
+		self.frames.iter().map(|frame| frame.duration).sum()
+	}
+
+	/// Returns the frame index to show `time` seconds into this clip, or [`None`] if the clip is empty.
+	pub fn frame_at(&self, time: f32, looping: bool) -> Option<u32> {
+		let duration = self.duration();
+		let mut time = if looping && duration > 0. { time.rem_euclid(duration) } else { time };
+
+		for frame in &self.frames {
+			if time < frame.duration {
+				return Some(frame.frame);
+			}
+			time -= frame.duration;
+		}
+
+		self.frames.last().map(|frame| frame.frame)
+	}
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub struct QuakeModelClipFrame {
+	/// Index into [`QuakeModelAnimations::frames`].
+	pub frame: u32,
+	/// How long this frame is shown in seconds.
+	pub duration: f32,
+}
+
+/// Plays a [`QuakeModelClip`] by swapping out the entity's [`Mesh3d`] for each frame.
+#[derive(Component, Reflect, Debug, Clone, SmartDefault)]
+#[reflect(Component, Default)]
+pub struct QuakeModelAnimationPlayer {
+	pub animations: Handle<QuakeModelAnimations>,
+	/// The name of the clip to play. If [`None`], the current frame stays as is.
+	pub clip: Option<String>,
+	/// Seconds since the clip started playing.
+	pub elapsed: f32,
+	/// (Default: 1)
+	#[default(1.)]
+	pub speed: f32,
+	/// (Default: true)
+	#[default(true)]
+	pub looping: bool,
+}
+impl QuakeModelAnimationPlayer {
+	/// Starts playing a clip from the beginning.
+	pub fn play(&mut self, clip: impl Into<String>) {
+		self.clip = Some(clip.into());
+		self.elapsed = 0.;
+	}
+}
+
+/// Format-agnostic geometry of a Quake model, in Quake space.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QuakeModelGeometry {
+	/// Each corner is an index into the frame's positions, and a UV.
+	pub triangles: Vec<[(u32, Vec2); 3]>,
+	pub frames: Vec<(String, Vec<Vec3>)>,
+	pub clips: HashMap<String, QuakeModelClip>,
+}
+impl QuakeModelGeometry {
+	/// Groups frames by name with their trailing digits removed, adding them to clips that don't already exist.
+	pub fn group_frames_by_name(&mut self, frames: impl IntoIterator<Item = u32>) {
+		let mut clips: HashMap<String, QuakeModelClip> = default();
+
+		for frame in frames {
+			let name = self.frames[frame as usize].0.trim_end_matches(|c: char| c.is_ascii_digit());
+
+			clips.entry(name.to_string()).or_default().frames.push(QuakeModelClipFrame {
+				frame,
+				duration: QUAKE_MODEL_FRAME_DURATION,
+			});
+		}
+
+		for (name, clip) in clips {
+			self.clips.entry(name).or_insert(clip);
+		}
+	}
+
+	/// Bakes each frame into a mesh, and puts the model in its own scene.
+	pub fn finish(self, skins: Vec<Handle<Image>>, load_context: &mut LoadContext, config: &TrenchBroomConfig) -> QuakeModel {
+		// Vertices are split wherever their UVs differ.
+		let mut corners: Vec<(u32, Vec2)> = Vec::new();
+		let mut corner_indices: HashMap<(u32, [u32; 2]), u32> = default();
+		let mut indices = Vec::with_capacity(self.triangles.len() * 3);
+
+		for triangle in &self.triangles {
+			// Quake models are wound clockwise.
+			for (vertex, uv) in [triangle[0], triangle[2], triangle[1]] {
+				let idx = *corner_indices.entry((vertex, uv.to_array().map(f32::to_bits))).or_insert_with(|| {
+					corners.push((vertex, uv));
+					corners.len() as u32 - 1
+				});
+				indices.push(idx);
+			}
+		}
+
+		let uvs = corners.iter().map(|(_, uv)| uv.to_array()).collect_vec();
+
+		let frames = self
+			.frames
+			.iter()
+			.enumerate()
+			.map(|(frame_idx, (_, positions))| {
+				let positions = positions.iter().map(|position| config.to_bevy_space(*position)).collect_vec();
+
+				// Smooth normals shared across UV seams.
+				let mut normals = vec![Vec3::ZERO; positions.len()];
+				for triangle in &self.triangles {
+					let [a, b, c] = triangle.map(|(vertex, _)| vertex as usize);
+					let (Some(pa), Some(pb), Some(pc)) = (positions.get(a), positions.get(b), positions.get(c)) else { continue };
+					let normal = (pc - pa).cross(pb - pa);
+					for vertex in [a, b, c] {
+						normals[vertex] += normal;
+					}
+				}
+
+				let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, default());
+				mesh.insert_attribute(
+					Mesh::ATTRIBUTE_POSITION,
+					corners
+						.iter()
+						.map(|(vertex, _)| positions.get(*vertex as usize).copied().unwrap_or_default().to_array())
+						.collect_vec(),
+				);
+				mesh.insert_attribute(
+					Mesh::ATTRIBUTE_NORMAL,
+					corners
+						.iter()
+						.map(|(vertex, _)| {
+							normals
+								.get(*vertex as usize)
+								.copied()
+								.unwrap_or_default()
+								.normalize_or(Vec3::Y)
+								.to_array()
+						})
+						.collect_vec(),
+				);
+				mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs.clone());
+				mesh.insert_indices(Indices::U32(indices.clone()));
+
+				load_context.add_labeled_asset(format!("Frame{frame_idx}"), mesh)
+			})
+			.collect_vec();
+
+		let skins = skins
+			.into_iter()
+			.enumerate()
+			.map(|(skin_idx, image)| {
+				load_context.add_labeled_asset(
+					format!("Material{skin_idx}"),
+					StandardMaterial {
+						base_color_texture: Some(image),
+						perceptual_roughness: 1.,
+						..default()
+					},
+				)
+			})
+			.collect_vec();
+
+		let animations = load_context.add_labeled_asset(
+			"Animations".to_string(),
+			QuakeModelAnimations {
+				frames: frames.clone(),
+				frame_names: self.frames.into_iter().map(|(name, _)| name).collect(),
+				skins: skins.clone(),
+				clips: self.clips,
+			},
+		);
+
+		let mut world = World::new();
+		let mut model = world.spawn((
+			Name::new(load_context.path().to_string()),
+			Transform::default(),
+			QuakeModelAnimationPlayer {
+				animations: animations.clone(),
+				..default()
+			},
+		));
+		if let Some(frame) = frames.first() {
+			model.insert(Mesh3d(frame.clone()));
+		}
+		if let Some(skin) = skins.first() {
+			model.insert(MeshMaterial3d(skin.clone()));
+		}
+
+		QuakeModel {
+			world: load_context.add_labeled_asset("Scene".to_string(), WorldAsset::new(world)),
+			skins,
+			animations,
+		}
+	}
+}
+
+/// Loads a Quake 2 image by its path relative to the game directory, usually a `.pcx` file.
+///
+/// Files with the same name and one of [`TrenchBroomConfig::texture_extensions`] take priority, falling back to decoding the `.pcx` file itself into a labeled asset.
+pub(crate) async fn load_quake2_image(
+	load_context: &mut LoadContext<'_>,
+	asset_server: &AssetServer,
+	config: &TrenchBroomConfig,
+	path: &str,
+	label: String,
+	cutout: bool,
+) -> Option<Handle<Image>> {
+	let source = load_context.path().source().clone_owned();
+	let path = PathBuf::from(path.replace('\\', "/"));
+
+	for ext in &config.texture_extensions {
+		if path.extension().is_some_and(|path_ext| path_ext == ext.as_str()) {
+			continue;
+		}
+		let path = path.with_extension(ext);
+		if config.asset_exists(asset_server, &source, &path).await {
+			return Some(load_context.load(AssetPath::from_path(&path).with_source(source)));
+		}
+	}
+
+	let bytes = load_context
+		.read_asset_bytes(AssetPath::from_path(&path).with_source(source))
+		.await
+		.ok()?;
+
+	match md2::decode_pcx(&bytes, cutout, RenderAssetUsages::default()) {
+		Ok(image) => Some(load_context.add_labeled_asset(label, image)),
+		Err(err) => {
+			error!("Decoding {path:?}: {err}");
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn clip_frames() {
+		let mut geometry = QuakeModelGeometry {
+			frames: ["stand1", "stand2", "run1", "run2", "run3"]
+				.into_iter()
+				.map(|name| (name.to_string(), Vec::new()))
+				.collect(),
+			..default()
+		};
+		geometry.group_frames_by_name(0..5);
+
+		let run = &geometry.clips["run"];
+		assert_eq!(run.frames.iter().map(|frame| frame.frame).collect_vec(), [2, 3, 4]);
+		assert_eq!(run.frame_at(0.15, true), Some(3));
+		assert_eq!(run.frame_at(0.35, true), Some(2));
+		assert_eq!(run.frame_at(0.35, false), Some(4));
+		assert_eq!(geometry.clips["stand"].frames.len(), 2);
+	}
+}
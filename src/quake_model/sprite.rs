@@ -0,0 +1,465 @@
+//! Quake `.spr` (`IDSP` version 1) and Quake 2 `.sp2` (`IDS2` version 2) sprites.
+
+use bevy::{
+	asset::{AssetLoader, LoadContext, RenderAssetUsages},
+	render::render_resource::PrimitiveTopology,
+	tasks::ConditionalSendFuture,
+};
+use bsp::loader::textures::{load_palette, paletted_image};
+use qbsp::{
+	data::util::FixedStr,
+	reader::{BspByteReader, BspParseContext},
+};
+
+use super::*;
+
+const SPR_MAGIC: &[u8; 4] = b"IDSP";
+const SPR_VERSION: i32 = 1;
+const SP2_MAGIC: &[u8; 4] = b"IDS2";
+const SP2_VERSION: i32 = 2;
+
+/// How a sprite is rotated to face the camera.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuakeSpriteOrientation {
+	/// Faces the same direction as the camera, but stays upright.
+	ViewParallelUpright,
+	/// Rotates around its vertical axis to face the camera's position.
+	FacingUpright,
+	/// Faces the same direction as the camera.
+	#[default]
+	ViewParallel,
+	/// Doesn't rotate at all, uses the entity's own rotation.
+	Oriented,
+	/// Faces the same direction as the camera, but keeps the entity's roll.
+	ViewParallelOriented,
+}
+impl QuakeSpriteOrientation {
+	pub fn from_spr_type(ty: i32) -> Option<Self> {
+		Some(match ty {
+			0 => Self::ViewParallelUpright,
+			1 => Self::FacingUpright,
+			2 => Self::ViewParallel,
+			3 => Self::Oriented,
+			4 => Self::ViewParallelOriented,
+			_ => return None,
+		})
+	}
+}
+
+/// A sprite loaded from a `.spr` or `.sp2` file.
+#[derive(Asset, Reflect, Debug)]
+pub struct QuakeSprite {
+	/// The sprite on its own, showing its first frame with a [`QuakeSpriteBillboard`].
+	pub world: Handle<WorldAsset>,
+	pub frames: Handle<QuakeSpriteFrames>,
+}
+
+/// The pictures of a [`QuakeSprite`], and the frames they're grouped into.
+#[derive(Asset, Reflect, Debug, Clone, Default)]
+pub struct QuakeSpriteFrames {
+	pub orientation: QuakeSpriteOrientation,
+	/// A quad for each picture in the sprite, offset by the picture's origin.
+	pub meshes: Vec<Handle<Mesh>>,
+	/// A material for each picture in the sprite.
+	pub materials: Vec<Handle<StandardMaterial>>,
+	/// Each frame of the sprite. Frames indexes point into [`meshes`](Self::meshes) and [`materials`](Self::materials).
+	///
+	/// Single pictures are a clip with only one frame, frame groups animate through theirs with their own intervals.
+	pub frames: Vec<QuakeModelClip>,
+}
+
+/// Shows a frame of a [`QuakeSprite`], rotating the entity to face the camera according to the sprite's [`QuakeSpriteOrientation`].
+#[derive(Component, Reflect, Debug, Clone, Default)]
+#[reflect(Component, Default)]
+pub struct QuakeSpriteBillboard {
+	pub frames: Handle<QuakeSpriteFrames>,
+	/// Which of the sprite's frames to show. Frame groups animate on their own, like in Quake.
+	pub frame: usize,
+	/// Overrides the sprite's own orientation if [`Some`].
+	pub orientation: Option<QuakeSpriteOrientation>,
+}
+
+/// A single picture of a sprite, before it's turned into Bevy assets.
+#[derive(Debug, Clone)]
+struct SpritePicture {
+	/// The left and top edges of the picture relative to the sprite's origin, in Quake units.
+	origin: IVec2,
+	size: UVec2,
+	image: SpriteImage,
+}
+
+#[derive(Debug, Clone)]
+enum SpriteImage {
+	/// Palette indices, for `.spr` files.
+	Paletted(Vec<u8>),
+	/// Path to a `.pcx` file, for `.sp2` files.
+	External(String),
+}
+
+/// A parsed `.spr` or `.sp2` file.
+#[derive(Debug, Clone)]
+struct Sprite {
+	orientation: QuakeSpriteOrientation,
+	pictures: Vec<SpritePicture>,
+	frames: Vec<QuakeModelClip>,
+}
+impl Sprite {
+	fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+		match bytes.get(0..4) {
+			Some(magic) if magic == SPR_MAGIC => Self::parse_spr(bytes),
+			Some(magic) if magic == SP2_MAGIC => Self::parse_sp2(bytes),
+			magic => Err(anyhow!("invalid sprite magic number {magic:?}")),
+		}
+	}
+
+	fn parse_spr(bytes: &[u8]) -> anyhow::Result<Self> {
+		let ctx = BspParseContext::default();
+		let mut reader = BspByteReader::new(bytes, &ctx).with_pos(4);
+
+		let version: i32 = reader.read()?;
+		if version != SPR_VERSION {
+			return Err(anyhow!("unsupported SPR version {version}, expected {SPR_VERSION}"));
+		}
+
+		let ty: i32 = reader.read()?;
+		let orientation = QuakeSpriteOrientation::from_spr_type(ty).ok_or_else(|| anyhow!("unknown sprite type {ty}"))?;
+		let _bounding_radius: f32 = reader.read()?;
+		let _max_size: [i32; 2] = reader.read()?;
+		let num_frames: i32 = reader.read()?;
+		let _beam_length: f32 = reader.read()?;
+		let _sync_type: i32 = reader.read()?;
+
+		let read_picture = |reader: &mut BspByteReader| -> anyhow::Result<SpritePicture> {
+			let origin: [i32; 2] = reader.read()?;
+			let size: [u32; 2] = reader.read()?;
+			let data = reader.read_bytes(size[0] as usize * size[1] as usize)?.to_vec();
+
+			Ok(SpritePicture {
+				origin: IVec2::from_array(origin),
+				size: UVec2::from_array(size),
+				image: SpriteImage::Paletted(data),
+			})
+		};
+
+		let mut sprite = Self {
+			orientation,
+			pictures: Vec::new(),
+			frames: Vec::new(),
+		};
+
+		for frame_idx in 0..num_frames {
+			let ty: i32 = reader.read()?;
+
+			if ty == 0 {
+				sprite.frames.push(QuakeModelClip {
+					frames: vec![QuakeModelClipFrame {
+						frame: sprite.pictures.len() as u32,
+						duration: QUAKE_MODEL_FRAME_DURATION,
+					}],
+				});
+				sprite
+					.pictures
+					.push(read_picture(&mut reader).map_err(|err| anyhow!("reading frame {frame_idx}: {err}"))?);
+				continue;
+			}
+
+			let num_group_frames: i32 = reader.read()?;
+			let intervals = (0..num_group_frames).map(|_| reader.read::<f32>()).collect::<Result<Vec<_>, _>>()?;
+
+			let mut clip = QuakeModelClip::default();
+			let mut previous_interval = 0.;
+
+			for interval in intervals {
+				clip.frames.push(QuakeModelClipFrame {
+					frame: sprite.pictures.len() as u32,
+					// Intervals are the time each picture ends, relative to the start of the group.
+					duration: (interval - previous_interval).max(0.),
+				});
+				previous_interval = interval;
+
+				sprite
+					.pictures
+					.push(read_picture(&mut reader).map_err(|err| anyhow!("reading frame group {frame_idx}: {err}"))?);
+			}
+
+			sprite.frames.push(clip);
+		}
+
+		Ok(sprite)
+	}
+
+	fn parse_sp2(bytes: &[u8]) -> anyhow::Result<Self> {
+		let ctx = BspParseContext::default();
+		let mut reader = BspByteReader::new(bytes, &ctx).with_pos(4);
+
+		let version: i32 = reader.read()?;
+		if version != SP2_VERSION {
+			return Err(anyhow!("unsupported SP2 version {version}, expected {SP2_VERSION}"));
+		}
+
+		let num_frames: i32 = reader.read()?;
+
+		let mut sprite = Self {
+			// Quake 2 sprites always face the camera.
+			orientation: QuakeSpriteOrientation::ViewParallel,
+			pictures: Vec::new(),
+			frames: Vec::new(),
+		};
+
+		for frame_idx in 0..num_frames {
+			let [width, height]: [u32; 2] = reader.read()?;
+			// Quake 2 measures origins from the bottom left corner instead.
+			let [origin_x, origin_y]: [i32; 2] = reader.read()?;
+			let name: FixedStr<64> = reader.read().map_err(|err| anyhow!("reading frame {frame_idx}: {err}"))?;
+
+			sprite.frames.push(QuakeModelClip {
+				frames: vec![QuakeModelClipFrame {
+					frame: frame_idx as u32,
+					duration: QUAKE_MODEL_FRAME_DURATION,
+				}],
+			});
+			sprite.pictures.push(SpritePicture {
+				origin: ivec2(-origin_x, height as i32 - origin_y),
+				size: uvec2(width, height),
+				image: SpriteImage::External(name.as_str().to_string()),
+			});
+		}
+
+		Ok(sprite)
+	}
+}
+
+/// Creates the quad of a sprite picture, facing +Z.
+fn sprite_picture_mesh(picture: &SpritePicture, config: &TrenchBroomConfig) -> Mesh {
+	let left = picture.origin.x as f32 / config.scale;
+	let right = (picture.origin.x + picture.size.x as i32) as f32 / config.scale;
+	let top = picture.origin.y as f32 / config.scale;
+	let bottom = (picture.origin.y - picture.size.y as i32) as f32 / config.scale;
+
+	let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, default());
+	mesh.insert_attribute(
+		Mesh::ATTRIBUTE_POSITION,
+		vec![[left, top, 0.], [left, bottom, 0.], [right, bottom, 0.], [right, top, 0.]],
+	);
+	mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; 4]);
+	mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.], [0., 1.], [1., 1.], [1., 0.]]);
+	mesh.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+	mesh
+}
+
+/// Loads `.spr` and `.sp2` files as [`QuakeSprite`]s.
+///
+/// `.spr` pictures use the palette at [`TrenchBroomConfig::texture_pallette`], `.sp2` pictures are looked up like [`Md2Loader`](md2::Md2Loader) skins.
+#[derive(TypePath)]
+pub struct QuakeSpriteLoader {
+	pub tb_server: TrenchBroomServer,
+	pub asset_server: AssetServer,
+}
+impl FromWorld for QuakeSpriteLoader {
+	fn from_world(world: &mut World) -> Self {
+		Self {
+			tb_server: world.resource::<TrenchBroomServer>().clone(),
+			asset_server: world.resource::<AssetServer>().clone(),
+		}
+	}
+}
+impl AssetLoader for QuakeSpriteLoader {
+	type Asset = QuakeSprite;
+	type Settings = ();
+	type Error = anyhow::Error;
+
+	fn load(
+		&self,
+		reader: &mut dyn bevy::asset::io::Reader,
+		_settings: &Self::Settings,
+		load_context: &mut LoadContext,
+	) -> impl ConditionalSendFuture<Output = Result<Self::Asset, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+
+			let sprite = Sprite::parse(&bytes)?;
+			let config = &self.tb_server.config;
+			let palette = load_palette(load_context, config).await?;
+
+			let mut meshes = Vec::with_capacity(sprite.pictures.len());
+			let mut materials = Vec::with_capacity(sprite.pictures.len());
+
+			for (picture_idx, picture) in sprite.pictures.iter().enumerate() {
+				let image = match &picture.image {
+					// Palette index 255 is transparent in sprites.
+					SpriteImage::Paletted(data) => Some(load_context.add_labeled_asset(
+						format!("Image{picture_idx}"),
+						paletted_image(picture.size, data, &palette, true, RenderAssetUsages::default()),
+					)),
+					SpriteImage::External(path) => {
+						let image = load_quake2_image(load_context, &self.asset_server, config, path, format!("Image{picture_idx}"), true).await;
+						if image.is_none() {
+							error!("Couldn't load frame \"{path}\" of {:?}", load_context.path());
+						}
+						image
+					}
+				};
+
+				meshes.push(load_context.add_labeled_asset(format!("Mesh{picture_idx}"), sprite_picture_mesh(picture, config)));
+				materials.push(load_context.add_labeled_asset(
+					format!("Material{picture_idx}"),
+					StandardMaterial {
+						base_color_texture: image,
+						alpha_mode: AlphaMode::Mask(0.5),
+						unlit: true,
+						double_sided: true,
+						cull_mode: None,
+						..default()
+					},
+				));
+			}
+
+			let mut world = World::new();
+			let mut entity = world.spawn((Name::new(load_context.path().to_string()), Transform::default()));
+			if let (Some(mesh), Some(material)) = (meshes.first(), materials.first()) {
+				entity.insert((Mesh3d(mesh.clone()), MeshMaterial3d(material.clone())));
+			}
+
+			let frames = load_context.add_labeled_asset(
+				"Frames".to_string(),
+				QuakeSpriteFrames {
+					orientation: sprite.orientation,
+					meshes,
+					materials,
+					frames: sprite.frames,
+				},
+			);
+			entity.insert(QuakeSpriteBillboard {
+				frames: frames.clone(),
+				..default()
+			});
+
+			Ok(QuakeSprite {
+				world: load_context.add_labeled_asset("Scene".to_string(), WorldAsset::new(world)),
+				frames,
+			})
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["spr", "sp2"]
+	}
+}
+
+impl QuakeModelPlugin {
+	/// Animates [`QuakeSpriteBillboard`]s, and rotates them to face the active camera with the highest order.
+	pub fn update_quake_sprites(
+		mut query: Query<(
+			&QuakeSpriteBillboard,
+			&mut Mesh3d,
+			&mut MeshMaterial3d<StandardMaterial>,
+			&mut Transform,
+			Option<&ChildOf>,
+		)>,
+		parent_query: Query<&GlobalTransform>,
+		camera_query: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+		frames_assets: Res<Assets<QuakeSpriteFrames>>,
+		time: Res<Time>,
+	) {
+		let camera = camera_query
+			.iter()
+			.filter(|(camera, _)| camera.is_active)
+			.max_by_key(|(camera, _)| camera.order)
+			.map(|(_, transform)| transform.compute_transform());
+
+		for (billboard, mut mesh, mut material, mut transform, child_of) in &mut query {
+			let Some(frames) = frames_assets.get(&billboard.frames) else { continue };
+
+			// Like Quake, frame groups are timed globally so all sprites using them stay in sync.
+			if let Some(picture) = frames
+				.frames
+				.get(billboard.frame)
+				.and_then(|clip| clip.frame_at(time.elapsed_secs(), true))
+				.map(|picture| picture as usize)
+			{
+				if let Some(picture_mesh) = frames.meshes.get(picture).filter(|picture_mesh| mesh.0 != **picture_mesh) {
+					mesh.0 = picture_mesh.clone();
+				}
+				if let Some(picture_material) = frames.materials.get(picture).filter(|picture_material| material.0 != **picture_material) {
+					material.0 = picture_material.clone();
+				}
+			}
+
+			let Some(camera) = camera else { continue };
+			let parent_transform = child_of
+				.and_then(|child_of| parent_query.get(child_of.parent()).ok())
+				.copied()
+				.unwrap_or_default();
+			let parent_rotation = parent_transform.rotation();
+
+			// Rotates +Z to face `direction` while staying upright.
+			let upright_facing = |direction: Vec3| {
+				let direction = direction.with_y(0.);
+				(direction.length_squared() > f32::EPSILON).then(|| Transform::default().looking_to(-direction, Vec3::Y).rotation)
+			};
+
+			let rotation = match billboard.orientation.unwrap_or(frames.orientation) {
+				QuakeSpriteOrientation::Oriented => continue,
+				QuakeSpriteOrientation::ViewParallel => Some(camera.rotation),
+				QuakeSpriteOrientation::ViewParallelUpright => upright_facing(camera.back().into()),
+				QuakeSpriteOrientation::FacingUpright => upright_facing(camera.translation - parent_transform.transform_point(transform.translation)),
+				QuakeSpriteOrientation::ViewParallelOriented => {
+					let (_, _, roll) = parent_rotation.to_euler(EulerRot::YXZ);
+					Some(camera.rotation * Quat::from_rotation_z(roll))
+				}
+			};
+
+			if let Some(rotation) = rotation {
+				transform.rotation = parent_rotation.inverse() * rotation;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sprite_parsing() {
+		let mut bytes = Vec::new();
+		bytes.extend(SPR_MAGIC);
+		bytes.extend([SPR_VERSION, 1].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend(0_f32.to_le_bytes());
+		bytes.extend([2_i32, 1, 2].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend(0_f32.to_le_bytes());
+		bytes.extend(0_i32.to_le_bytes());
+
+		let picture = |index: u8| {
+			let mut picture = Vec::new();
+			picture.extend([-1_i32, 1, 2, 1].iter().flat_map(|v| v.to_le_bytes()));
+			picture.extend([index, 255]);
+			picture
+		};
+
+		// A single picture, then a group of two.
+		bytes.extend(0_i32.to_le_bytes());
+		bytes.extend(picture(0));
+		bytes.extend([1_i32, 2].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend([0.1_f32, 0.3].iter().flat_map(|v| v.to_le_bytes()));
+		bytes.extend(picture(1));
+		bytes.extend(picture(2));
+
+		let sprite = Sprite::parse(&bytes).unwrap();
+		assert_eq!(sprite.orientation, QuakeSpriteOrientation::FacingUpright);
+		assert_eq!(sprite.pictures.len(), 3);
+		assert_eq!(sprite.pictures[0].origin, ivec2(-1, 1));
+		assert!(matches!(&sprite.pictures[2].image, SpriteImage::Paletted(data) if data == &[2, 255]));
+
+		let group = &sprite.frames[1];
+		assert_eq!(group.frames.iter().map(|frame| frame.frame).collect_vec(), [1, 2]);
+		assert_eq!(group.frame_at(0.25, true), Some(2));
+		assert_eq!(group.frame_at(0.35, true), Some(1));
+
+		let mesh = sprite_picture_mesh(&sprite.pictures[0], &TrenchBroomConfig::default());
+		let Some(bevy_mesh::VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else { panic!() };
+		let scale = TrenchBroomConfig::default().scale;
+		assert_eq!(positions[2], [1. / scale, 0., 0.]);
+	}
+}
@@ -128,6 +128,66 @@ pub(crate) fn create_test_asset_server() -> AssetServer {
 	)
 }
 
+/// Spawns an entity from a map entity with `properties` through its class's spawn functions, as if it were loaded from a `.map` file.
+#[cfg(test)]
+pub(crate) fn spawn_test_entity(
+	type_registry: &bevy::reflect::TypeRegistry,
+	tb_config: &TrenchBroomConfig,
+	properties: &[(&str, &str)],
+) -> anyhow::Result<(World, Entity)> {
+	let class_map = class::generate_class_map(type_registry);
+	let src_entity = qmap::QuakeMapEntity {
+		properties: properties.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+		..default()
+	};
+	let class = class_map[src_entity.classname()?];
+
+	let asset_server = create_test_asset_server();
+	let mut load_context = create_load_context(&asset_server, "".into(), false, false);
+	let mut world = World::new();
+	let entity = world.spawn_empty().id();
+
+	class.apply_spawn_fn_recursive(&mut class::QuakeClassSpawnView {
+		file_type: MapFileType::Map,
+		tb_config,
+		type_registry,
+		class_map: &class_map,
+		src_entity: &src_entity,
+		src_entity_idx: 0,
+		class,
+		world: &mut world,
+		entity,
+		load_context: &mut load_context,
+		meshes: &mut Vec::new(),
+	})?;
+
+	Ok((world, entity))
+}
+
+/// A [`Targetable`](class::builtin::Targetable) named `targetname`, for tests.
+#[cfg(test)]
+pub(crate) fn targetable(targetname: &str) -> class::builtin::Targetable {
+	class::builtin::Targetable {
+		targetname: fgd::TargetSource(targetname.into()),
+	}
+}
+
+/// A [`Target`](class::builtin::Target) pointing to `target`, for tests.
+#[cfg(test)]
+pub(crate) fn target(target: &str) -> class::builtin::Target {
+	class::builtin::Target {
+		target: fgd::TargetDestination(target.into()),
+	}
+}
+
+/// A [`KillTarget`](class::builtin::KillTarget) pointing to `killtarget`, for tests.
+#[cfg(test)]
+pub(crate) fn kill_target(killtarget: &str) -> class::builtin::KillTarget {
+	class::builtin::KillTarget {
+		killtarget: fgd::TargetDestination(killtarget.into()),
+	}
+}
+
 /// A little bit of code that retrieves and caches image size based on a texture name when loading level geometry.
 #[derive(Debug, Default)]
 pub(crate) struct TextureSizeCache<K> {